@@ -0,0 +1,125 @@
+// Core graph operations at increasing graph sizes. Run with
+//   cargo bench --features bench
+// and compare the reports before and after performance work (indexes, sharding, ...).
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use dokg_memory::bench::{
+    EntityToCreate, KnowledgeGraphState, MissingNodePolicy, NearDuplicatePolicy, RelationToCreate,
+    RelationToDelete,
+};
+use serde_json::json;
+
+const SIZES: &[usize] = &[1_000, 10_000, 100_000];
+const BATCH_SIZE: usize = 100;
+const ENTITY_TYPES: &[&str] = &["person", "project", "company", "document"];
+
+fn entity(i: usize) -> EntityToCreate {
+    EntityToCreate {
+        name: format!("entity-{}", i),
+        entity_type: ENTITY_TYPES[i % ENTITY_TYPES.len()].to_string(),
+        observations: vec![
+            format!("observation {} about topic-{}", i, i % 97),
+            format!("mentions keyword-{}", i % 1_000),
+        ],
+        data: Some(json!({ "rank": i })),
+    }
+}
+
+fn relation(i: usize, size: usize) -> RelationToCreate {
+    RelationToCreate {
+        from: format!("entity-{}", i),
+        to: format!("entity-{}", (i + 1) % size),
+        relation_type: "related_to".to_string(),
+        data: None,
+        confidence: None,
+    }
+}
+
+// A graph of `size` entities chained by one relation each.
+fn graph_of(size: usize) -> KnowledgeGraphState {
+    let mut graph = KnowledgeGraphState::new();
+    graph.create_entities_batch((0..size).map(entity).collect(), NearDuplicatePolicy::Ignore);
+    graph
+        .create_relations_batch(
+            (0..size).map(|i| relation(i, size)).collect(),
+            MissingNodePolicy::Skip,
+        )
+        .expect("relations between existing entities");
+    graph
+}
+
+fn bench_graph_ops(c: &mut Criterion) {
+    for &size in SIZES {
+        let graph = graph_of(size);
+
+        let mut group = c.benchmark_group("search_nodes");
+        group.sample_size(20);
+        group.bench_with_input(BenchmarkId::new("keyword", size), &graph, |b, graph| {
+            b.iter(|| graph.search_nodes("keyword-42", None))
+        });
+        group.bench_with_input(BenchmarkId::new("substring", size), &graph, |b, graph| {
+            b.iter(|| graph.search_nodes("bout topic", None))
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group("create_entities_batch");
+        group.sample_size(10);
+        for (label, policy) in [
+            ("ignore_near_duplicates", NearDuplicatePolicy::Ignore),
+            ("warn_near_duplicates", NearDuplicatePolicy::Warn),
+        ] {
+            group.bench_with_input(BenchmarkId::new(label, size), &graph, |b, graph| {
+                b.iter_batched(
+                    || {
+                        let new_entities = (size..size + BATCH_SIZE).map(entity).collect();
+                        (graph.clone(), new_entities)
+                    },
+                    |(mut graph, new_entities)| graph.create_entities_batch(new_entities, policy),
+                    BatchSize::LargeInput,
+                )
+            });
+        }
+        group.finish();
+
+        let mut group = c.benchmark_group("delete_relations_batch");
+        group.sample_size(10);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &graph, |b, graph| {
+            b.iter_batched(
+                || {
+                    let to_delete: Vec<RelationToDelete> = (0..BATCH_SIZE)
+                        .map(|i| RelationToDelete {
+                            from: format!("entity-{}", i),
+                            to: format!("entity-{}", (i + 1) % size),
+                            relation_type: "related_to".to_string(),
+                        })
+                        .collect();
+                    (graph.clone(), to_delete)
+                },
+                |(mut graph, to_delete)| graph.delete_relations_batch(to_delete),
+                BatchSize::LargeInput,
+            )
+        });
+        group.finish();
+
+        let mut group = c.benchmark_group("serialization");
+        group.sample_size(10);
+        let serialized = serde_json::to_string(&graph).expect("graph serializes");
+        group.bench_with_input(BenchmarkId::new("to_json", size), &graph, |b, graph| {
+            b.iter(|| serde_json::to_string(graph).expect("graph serializes"))
+        });
+        group.bench_with_input(
+            BenchmarkId::new("from_json", size),
+            &serialized,
+            |b, serialized| {
+                b.iter(|| {
+                    serde_json::from_str::<KnowledgeGraphState>(serialized)
+                        .expect("graph deserializes")
+                })
+            },
+        );
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_graph_ops);
+criterion_main!(benches);
@@ -1,8 +1,8 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value as JsonValue};
+use serde_json::json;
 
-const MCP_BASE_URL: &str = "http://localhost:8787/mcp"; // Adjust if your worker runs elsewhere
+const MCP_BASE_URL: &str = "http://localhost:8787/v1/mcp"; // Adjust if your worker runs elsewhere
 
 // --- MCP Generic Request/Response Structs (Client-Side) ---
 #[derive(Serialize)]
@@ -51,15 +51,19 @@ struct McpDeleteEntitiesArgs {
 }
 
 // Struct to parse the JSON string within `ContentBlock.text` for `create_entities` response
-// This should match the structure of `Node` from your `types.rs` or a client-specific version.
+// This should match the structure of `BatchResult` from your `types.rs` or a client-specific version.
 #[derive(Debug, Serialize, Deserialize, Clone)]
-struct ClientNodeResponse {
-    id: String,
-    #[serde(rename = "type")]
-    node_type: String,
-    data: JsonValue,
-    created_at_ms: u64,
-    updated_at_ms: u64,
+struct ClientBatchResult {
+    index: usize,
+    id: Option<String>,
+    status: String,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ClientBatchResponse {
+    results: Vec<ClientBatchResult>,
+    summary: serde_json::Value,
 }
 
 #[tokio::main]
@@ -109,14 +113,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "MCP Pre-Step `delete_entities` raw response: {}",
             delete_response_text
         );
-        // The `delete_entities` tool in mcp.rs currently returns a simple success message,
-        // not a list of deleted IDs in the same way the DO does.
+        // The `delete_entities` tool returns the DO's per-item results (also as structuredContent).
         // We can parse the CallToolResponse to check the text.
         match serde_json::from_str::<CallToolResponse>(&delete_response_text) {
             Ok(parsed_delete_resp) => {
                 if let Some(content) = parsed_delete_resp.content.first() {
                     println!(
-                        "MCP Pre-Step `delete_entities` success message: {}",
+                        "MCP Pre-Step `delete_entities` results: {}",
                         content.text
                     );
                 }
@@ -188,15 +191,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 content_block.text
             );
             // Parse the inner JSON string (which is the actual result from the DO)
-            let created_entities_result: Result<Vec<ClientNodeResponse>, _> =
+            let entity_results: Result<ClientBatchResponse, _> =
                 serde_json::from_str(&content_block.text);
 
-            match created_entities_result {
-                Ok(created_entities) => {
-                    println!(
-                        "Successfully parsed created entities: {:?}",
-                        created_entities
-                    );
+            match entity_results {
+                Ok(entity_results) => {
+                    println!("Successfully parsed entity results: {:?}", entity_results);
+                    let created_entities: Vec<&ClientBatchResult> = entity_results
+                        .results
+                        .iter()
+                        .filter(|r| r.status == "created")
+                        .collect();
                     if created_entities.len() == 2 {
                         println!("SUCCESS: MCP `create_entities` call seems successful and returned 2 entities.");
                     } else {
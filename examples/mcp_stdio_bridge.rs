@@ -0,0 +1,167 @@
+// mcp-memory/examples/mcp_stdio_bridge.rs
+//
+// Speaks MCP over stdio to local clients (e.g. Claude Desktop) and proxies every
+// JSON-RPC message to the worker's `/v1/mcp` endpoint over HTTPS.
+//
+// Configuration (environment variables):
+//   MCP_WORKER_URL  Worker MCP endpoint (default: http://localhost:8787/v1/mcp)
+//   MCP_API_KEY     API key sent as `Authorization: Bearer <key>` (optional when auth is off)
+//
+// Build once, then point the client at the binary:
+//   cargo build --release --example mcp_stdio_bridge
+//
+//   "mcpServers": {
+//     "memory": {
+//       "command": "/path/to/target/release/examples/mcp_stdio_bridge",
+//       "env": {
+//         "MCP_WORKER_URL": "https://<your-worker>.workers.dev/v1/mcp",
+//         "MCP_API_KEY": "<key>"
+//       }
+//     }
+//   }
+//
+// stdout carries only JSON-RPC messages (one per line); diagnostics go to stderr.
+
+use reqwest::Client;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+const DEFAULT_WORKER_URL: &str = "http://localhost:8787/v1/mcp";
+const SESSION_ID_HEADER: &str = "Mcp-Session-Id";
+const INTERNAL_ERROR: i64 = -32603;
+const PARSE_ERROR: i64 = -32700;
+
+struct Bridge {
+    client: Client,
+    worker_url: String,
+    api_key: Option<String>,
+    session_id: Option<String>, // Assigned by the worker on initialize
+}
+
+impl Bridge {
+    // Forwards one message and returns the messages to write back (notifications first).
+    async fn forward(&mut self, message: &Value) -> Result<Vec<Value>, String> {
+        let mut request = self
+            .client
+            .post(&self.worker_url)
+            .header("Accept", "application/json, text/event-stream")
+            .json(message);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        if let Some(session_id) = &self.session_id {
+            request = request.header(SESSION_ID_HEADER, session_id);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| format!("Request to worker failed: {}", e))?;
+        if let Some(session_id) = response
+            .headers()
+            .get(SESSION_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+        {
+            self.session_id = Some(session_id.to_string());
+        }
+        let is_sse = response
+            .headers()
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|ct| ct.starts_with("text/event-stream"));
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read worker response: {}", e))?;
+
+        // Notifications are acknowledged with 202 and no body
+        if body.trim().is_empty() {
+            return if status.is_success() {
+                Ok(Vec::new())
+            } else {
+                Err(format!("Worker responded with HTTP {}", status))
+            };
+        }
+        if is_sse {
+            return parse_sse_messages(&body);
+        }
+        serde_json::from_str(&body)
+            .map(|value| vec![value])
+            .map_err(|_| format!("Worker responded with HTTP {}: {}", status, body.trim()))
+    }
+}
+
+// Extracts the JSON payload of every `data:` event in an SSE body.
+fn parse_sse_messages(body: &str) -> Result<Vec<Value>, String> {
+    let mut messages = Vec::new();
+    for event in body.split("\n\n") {
+        let data: Vec<&str> = event
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(str::trim_start)
+            .collect();
+        if data.is_empty() {
+            continue;
+        }
+        let message = serde_json::from_str(&data.join("\n"))
+            .map_err(|e| format!("Invalid SSE message from worker: {}", e))?;
+        messages.push(message);
+    }
+    Ok(messages)
+}
+
+fn error_response(id: Value, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message }
+    })
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut bridge = Bridge {
+        client: Client::new(),
+        worker_url: std::env::var("MCP_WORKER_URL")
+            .unwrap_or_else(|_| DEFAULT_WORKER_URL.to_string()),
+        api_key: std::env::var("MCP_API_KEY").ok().filter(|k| !k.is_empty()),
+        session_id: None,
+    };
+    eprintln!("mcp_stdio_bridge: proxying stdio to {}", bridge.worker_url);
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let replies = match serde_json::from_str::<Value>(&line) {
+            Err(e) => vec![error_response(
+                Value::Null,
+                PARSE_ERROR,
+                format!("Parse error: {}", e),
+            )],
+            Ok(message) => match bridge.forward(&message).await {
+                Ok(replies) => replies,
+                Err(e) => {
+                    eprintln!("mcp_stdio_bridge: {}", e);
+                    // Only requests (messages with an id) expect an answer
+                    match message.get("id") {
+                        Some(id) if message.get("method").is_some() => {
+                            vec![error_response(id.clone(), INTERNAL_ERROR, e)]
+                        }
+                        _ => Vec::new(),
+                    }
+                }
+            },
+        };
+        for reply in replies {
+            stdout
+                .write_all(format!("{}\n", serde_json::to_string(&reply)?).as_bytes())
+                .await?;
+        }
+        stdout.flush().await?;
+    }
+    Ok(())
+}
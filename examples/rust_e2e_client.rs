@@ -28,7 +28,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::json; // For creating JSON bodies easily
 use serde_json::Value as JsonValue; // For generic data fields
 
-const BASE_URL: &str = "http://localhost:8787/do"; // Adjust if your worker runs elsewhere
+const BASE_URL: &str = "http://localhost:8787/v1/do"; // Adjust if your worker runs elsewhere
 
 // Simplified structs to deserialize responses from the DO
 // We mainly care about the 'id' for subsequent requests.
@@ -77,16 +77,30 @@ struct ClientKnowledgeGraphDataResponse {
     relations: Vec<ClientApiRelation>,
 }
 
-// Generic result type to deserialize {"Ok": T} or {"Err": E}
+// Per-item result returned by every batch endpoint
 #[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "PascalCase")]
-enum ClientResult<T, E> {
-    Ok(T),
-    Err(E),
+struct ClientBatchResult {
+    index: usize,
+    id: Option<String>,
+    status: String, // "created", "updated", "unchanged", "deleted", "already_exists", "not_found", "error"
+    error: Option<String>,
 }
 
-// For batch delete entities/relations responses which return Vec<String>
-// No new struct needed, can deserialize directly to Vec<String>
+// Envelope returned by every batch endpoint
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ClientBatchResponse {
+    results: Vec<ClientBatchResult>,
+    summary: JsonValue,
+}
+
+// IDs of the items in a batch response that ended with the given status
+fn ids_with_status(results: &[ClientBatchResult], status: &str) -> Vec<String> {
+    results
+        .iter()
+        .filter(|r| r.status == status)
+        .filter_map(|r| r.id.clone())
+        .collect()
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -237,8 +251,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         );
         // Decide if this should be a fatal error for the test
     } else {
-        let deleted_ids_pre_step_5: Vec<String> = resp_delete_pre_step_5.json().await?;
-        println!("Pre-Step 5: Successfully called delete for entities. Deleted IDs reported: {:?}", deleted_ids_pre_step_5);
+        let delete_results_pre_step_5: ClientBatchResponse = resp_delete_pre_step_5.json().await?;
+        println!("Pre-Step 5: Successfully called delete for entities. Deleted IDs reported: {:?}", ids_with_status(&delete_results_pre_step_5.results, "deleted"));
     }
 
 
@@ -279,9 +293,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             resp.text().await?
         );
     } else {
-        let created_entities: Vec<NodeResponse> = resp.json().await?;
-        println!("Batch Created Entities: {:?}", created_entities);
-        assert_eq!(created_entities.len(), 3); // Assuming all are new and created
+        let entity_results: ClientBatchResponse = resp.json().await?;
+        println!("Batch Create Entities Results: {:?}", entity_results);
+        assert_eq!(ids_with_status(&entity_results.results, "created").len(), 3); // Assuming all are new and created
     }
     let blog_post_id = "blogpost_123".to_string();
     let tag_rust_id = "tag_rust".to_string();
@@ -314,7 +328,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             resp.text().await?
         );
     } else {
-        let obs_results: Vec<ClientResult<String, String>> = resp.json().await?;
+        let obs_results: ClientBatchResponse = resp.json().await?;
         println!("Add Observations Results: {:?}", obs_results);
         // Add assertions based on expected success/failure if needed
     }
@@ -340,9 +354,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             resp.text().await?
         );
     } else {
-        let created_relations: Vec<EdgeResponse> = resp.json().await?;
-        println!("Batch Created Relations: {:?}", created_relations);
-        assert_eq!(created_relations.len(), 2);
+        let relation_results: ClientBatchResponse = resp.json().await?;
+        println!("Batch Create Relations Results: {:?}", relation_results);
+        assert_eq!(ids_with_status(&relation_results.results, "created").len(), 2);
     }
 
     // --- Step 8: Search Nodes ---
@@ -432,7 +446,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             resp.text().await?
         );
     } else {
-        let delete_obs_results: Vec<ClientResult<String, String>> = resp.json().await?;
+        let delete_obs_results: ClientBatchResponse = resp.json().await?;
         println!("Delete Observations Results: {:?}", delete_obs_results);
     }
 
@@ -458,7 +472,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             resp.text().await?
         );
     } else {
-        let deleted_relation_ids: Vec<String> = resp.json().await?;
+        let delete_relation_results: ClientBatchResponse = resp.json().await?;
+        let deleted_relation_ids = ids_with_status(&delete_relation_results.results, "deleted");
         println!("Deleted Relation IDs: {:?}", deleted_relation_ids);
         // Assert that only one relation was actually deleted, if possible to know its ID
     }
@@ -481,7 +496,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             resp.text().await?
         );
     } else {
-        let deleted_entity_ids: Vec<String> = resp.json().await?;
+        let delete_entity_results: ClientBatchResponse = resp.json().await?;
+        let deleted_entity_ids = ids_with_status(&delete_entity_results.results, "deleted");
         println!("Deleted Entity IDs: {:?}", deleted_entity_ids);
         assert!(deleted_entity_ids.contains(&tag_rust_id));
         assert!(deleted_entity_ids.contains(&tag_async_id));
@@ -0,0 +1,323 @@
+use crate::auth::{AuthError, Caller, Scope};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rsa::pkcs1v15::{Signature, VerifyingKey};
+use rsa::sha2::Sha256;
+use rsa::signature::Verifier;
+use rsa::{BigUint, RsaPublicKey};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use worker::{console_error, Date, Env, Error, Fetch, Url};
+
+// Cloudflare Access: when the worker sits behind an Access application, Access signs a JWT
+// for every request it lets through and sends it in Cf-Access-Jwt-Assertion. The token is
+// checked against the team's public certs, and the identity in it decides the caller's
+// scopes and which graph (DO instance) they work on.
+
+pub const ACCESS_JWT_HEADER: &str = "cf-access-jwt-assertion";
+// Team domain, e.g. "myteam.cloudflareaccess.com"; Access auth is disabled when it's unset
+pub const ACCESS_TEAM_DOMAIN_VAR: &str = "ACCESS_TEAM_DOMAIN";
+// Application Audience (AUD) tag of the Access application
+pub const ACCESS_AUD_VAR: &str = "ACCESS_AUD";
+// Optional JSON object mapping identities to a policy, e.g.
+// {"alice@example.com": {"scopes": ["read", "write", "admin"]},
+//  "*@example.com": {"graph": "user:{identity}"}, "*": {"scopes": ["read"]}}.
+// Keys are an email, "*@domain" or "*", and the most specific one wins. `scopes` defaults to
+// read and write; `graph` names the DO instance, with "{identity}" replaced by the caller's
+// identity, and defaults to the shared graph. Unset, every identity Access admits gets the
+// default policy; set, identities that match no key are refused.
+pub const ACCESS_IDENTITIES_VAR: &str = "ACCESS_IDENTITIES";
+
+const CERTS_TTL_MS: u64 = 60 * 60 * 1000;
+// A token signed with an unknown key triggers a refetch (after a rotation), at most this often
+const CERTS_REFETCH_MS: u64 = 60 * 1000;
+const CLOCK_LEEWAY_SECONDS: u64 = 60;
+
+#[derive(Debug, Clone, Deserialize)]
+struct Jwk {
+    kid: String,
+    n: String,
+    e: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JwkSet {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedCerts {
+    team_domain: String,
+    fetched_at_ms: u64,
+    keys: Vec<Jwk>,
+}
+
+thread_local! {
+    static CERTS: RefCell<Option<CachedCerts>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Deserialize)]
+struct JwtHeader {
+    alg: String,
+    kid: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Audience {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    aud: Audience,
+    exp: u64,
+    nbf: Option<u64>,
+    iss: String,
+    email: Option<String>,
+    common_name: Option<String>, // Set instead of email for service tokens
+    sub: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct IdentityPolicy {
+    #[serde(default = "default_scopes")]
+    scopes: BTreeSet<Scope>,
+    graph: Option<String>,
+}
+
+fn default_scopes() -> BTreeSet<Scope> {
+    BTreeSet::from([Scope::Read, Scope::Write])
+}
+
+impl Default for IdentityPolicy {
+    fn default() -> Self {
+        IdentityPolicy {
+            scopes: default_scopes(),
+            graph: None,
+        }
+    }
+}
+
+pub struct AccessConfig {
+    team_domain: String,
+    audience: String,
+    identities: Option<HashMap<String, IdentityPolicy>>,
+}
+
+// Reads the Access settings; None when the worker isn't set up for Access.
+pub fn config(env: &Env) -> Result<Option<AccessConfig>, AuthError> {
+    let Ok(team_domain) = env.var(ACCESS_TEAM_DOMAIN_VAR) else {
+        return Ok(None);
+    };
+    let team_domain = team_domain.to_string();
+    let team_domain = team_domain
+        .trim()
+        .trim_start_matches("https://")
+        .trim_end_matches('/')
+        .to_string();
+    let audience = env
+        .var(ACCESS_AUD_VAR)
+        .map(|v| v.to_string())
+        .map_err(|_| {
+            AuthError::Misconfigured(format!(
+                "{} is set but {} is not",
+                ACCESS_TEAM_DOMAIN_VAR, ACCESS_AUD_VAR
+            ))
+        })?;
+    let identities = match env.var(ACCESS_IDENTITIES_VAR) {
+        Ok(v) => Some(serde_json::from_str(&v.to_string()).map_err(|e| {
+            AuthError::Misconfigured(format!("{} is not valid: {}", ACCESS_IDENTITIES_VAR, e))
+        })?),
+        Err(_) => None,
+    };
+    Ok(Some(AccessConfig {
+        team_domain,
+        audience,
+        identities,
+    }))
+}
+
+fn decode_part(part: &str) -> Result<Vec<u8>, AuthError> {
+    URL_SAFE_NO_PAD
+        .decode(part)
+        .map_err(|_| AuthError::InvalidAccessToken("malformed token".to_string()))
+}
+
+fn verify_signature(jwk: &Jwk, signing_input: &str, signature: &[u8]) -> bool {
+    let (Ok(n), Ok(e)) = (
+        URL_SAFE_NO_PAD.decode(&jwk.n),
+        URL_SAFE_NO_PAD.decode(&jwk.e),
+    ) else {
+        return false;
+    };
+    let Ok(public_key) = RsaPublicKey::new(BigUint::from_bytes_be(&n), BigUint::from_bytes_be(&e))
+    else {
+        return false;
+    };
+    let Ok(signature) = Signature::try_from(signature) else {
+        return false;
+    };
+    VerifyingKey::<Sha256>::new(public_key)
+        .verify(signing_input.as_bytes(), &signature)
+        .is_ok()
+}
+
+fn check_claims(claims: &Claims, config: &AccessConfig, now_seconds: u64) -> Result<(), AuthError> {
+    let audience_matches = match &claims.aud {
+        Audience::One(aud) => *aud == config.audience,
+        Audience::Many(auds) => auds.contains(&config.audience),
+    };
+    if !audience_matches {
+        return Err(AuthError::InvalidAccessToken(
+            "token is for another application".to_string(),
+        ));
+    }
+    if claims.iss != format!("https://{}", config.team_domain) {
+        return Err(AuthError::InvalidAccessToken(
+            "token was issued by another team".to_string(),
+        ));
+    }
+    if claims.exp + CLOCK_LEEWAY_SECONDS < now_seconds {
+        return Err(AuthError::InvalidAccessToken(
+            "token has expired".to_string(),
+        ));
+    }
+    if claims
+        .nbf
+        .is_some_and(|nbf| nbf > now_seconds + CLOCK_LEEWAY_SECONDS)
+    {
+        return Err(AuthError::InvalidAccessToken(
+            "token is not valid yet".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn fetch_certs(team_domain: &str) -> Result<Vec<Jwk>, AuthError> {
+    let url = format!("https://{}/cdn-cgi/access/certs", team_domain);
+    let result: worker::Result<JwkSet> = async {
+        let mut response = Fetch::Url(Url::parse(&url)?).send().await?;
+        if response.status_code() != 200 {
+            return Err(Error::RustError(format!(
+                "certs endpoint answered {}",
+                response.status_code()
+            )));
+        }
+        response.json().await
+    }
+    .await;
+    result.map(|set| set.keys).map_err(|e| {
+        console_error!("Failed to fetch Access certs from {}: {}", url, e);
+        AuthError::Misconfigured(format!("Access certs are unavailable: {}", e))
+    })
+}
+
+// The team's signing key with the given id, from the cached certs when possible.
+async fn signing_key(
+    team_domain: &str,
+    kid: Option<&str>,
+    now_ms: u64,
+) -> Result<Option<Jwk>, AuthError> {
+    let find = |keys: &[Jwk]| {
+        keys.iter()
+            .find(|key| kid.is_none_or(|kid| key.kid == kid))
+            .cloned()
+    };
+    let cached = CERTS.with_borrow(|certs| {
+        certs
+            .as_ref()
+            .filter(|c| c.team_domain == team_domain)
+            .cloned()
+    });
+    if let Some(cached) = &cached {
+        let age_ms = now_ms.saturating_sub(cached.fetched_at_ms);
+        if age_ms < CERTS_TTL_MS {
+            if let Some(key) = find(&cached.keys) {
+                return Ok(Some(key));
+            }
+            if age_ms < CERTS_REFETCH_MS {
+                return Ok(None);
+            }
+        }
+    }
+    let keys = fetch_certs(team_domain).await?;
+    let key = find(&keys);
+    CERTS.set(Some(CachedCerts {
+        team_domain: team_domain.to_string(),
+        fetched_at_ms: now_ms,
+        keys,
+    }));
+    Ok(key)
+}
+
+// Most specific policy for an identity: exact match, then "*@domain", then "*".
+fn policy_for(config: &AccessConfig, identity: &str) -> Option<IdentityPolicy> {
+    let Some(identities) = &config.identities else {
+        return Some(IdentityPolicy::default());
+    };
+    let lowercase = identity.to_lowercase();
+    let domain_key = lowercase
+        .rsplit_once('@')
+        .map(|(_, domain)| format!("*@{}", domain));
+    identities
+        .iter()
+        .find(|(key, _)| key.to_lowercase() == lowercase)
+        .or_else(|| {
+            let domain_key = domain_key.as_ref()?;
+            identities
+                .iter()
+                .find(|(key, _)| key.to_lowercase() == *domain_key)
+        })
+        .or_else(|| identities.get_key_value("*"))
+        .map(|(_, policy)| policy.clone())
+}
+
+// Verifies an Access token and resolves the caller it identifies.
+pub async fn authenticate(token: &str, config: &AccessConfig) -> Result<Caller, AuthError> {
+    let mut parts = token.trim().split('.');
+    let (Some(header), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(AuthError::InvalidAccessToken("malformed token".to_string()));
+    };
+    let jwt_header: JwtHeader = serde_json::from_slice(&decode_part(header)?)
+        .map_err(|_| AuthError::InvalidAccessToken("malformed token header".to_string()))?;
+    if jwt_header.alg != "RS256" {
+        return Err(AuthError::InvalidAccessToken(format!(
+            "unsupported algorithm '{}'",
+            jwt_header.alg
+        )));
+    }
+    let now_ms = Date::now().as_millis();
+    let key = signing_key(&config.team_domain, jwt_header.kid.as_deref(), now_ms)
+        .await?
+        .ok_or_else(|| AuthError::InvalidAccessToken("unknown signing key".to_string()))?;
+    let signing_input = format!("{}.{}", header, payload);
+    if !verify_signature(&key, &signing_input, &decode_part(signature)?) {
+        return Err(AuthError::InvalidAccessToken("bad signature".to_string()));
+    }
+    let claims: Claims = serde_json::from_slice(&decode_part(payload)?)
+        .map_err(|_| AuthError::InvalidAccessToken("malformed token claims".to_string()))?;
+    check_claims(&claims, config, now_ms / 1000)?;
+
+    let identity = claims
+        .email
+        .or(claims.common_name)
+        .or(claims.sub)
+        .filter(|identity| !identity.is_empty())
+        .ok_or_else(|| AuthError::InvalidAccessToken("token names no identity".to_string()))?;
+    let policy = policy_for(config, &identity).ok_or_else(|| {
+        AuthError::NotPermitted(format!("'{}' has no access to this deployment", identity))
+    })?;
+    let graph = policy
+        .graph
+        .map(|graph| graph.replace("{identity}", &identity.to_lowercase()));
+    Ok(Caller::new(
+        format!("access:{}", identity),
+        policy.scopes,
+        graph,
+    ))
+}
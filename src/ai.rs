@@ -0,0 +1,141 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use worker::{console_error, Env, Result};
+
+// Workers AI binding name, see [ai] in wrangler.toml
+pub const AI_BINDING: &str = "AI";
+// Text model used when the AI_TEXT_MODEL variable isn't set
+pub const DEFAULT_TEXT_MODEL: &str = "@cf/meta/llama-3.1-8b-instruct";
+
+// Longest document POST /graph/ingest accepts
+pub const MAX_INGEST_TEXT_CHARS: usize = 20_000;
+
+pub const EXTRACT_SYSTEM_PROMPT: &str =
+    "You extract a knowledge graph from text. Reply with only a \
+JSON object of the form {\"entities\": [{\"name\": string, \"entityType\": string, \
+\"observations\": [string]}], \"relations\": [{\"from\": string, \"to\": string, \
+\"relationType\": string}]}. Use short proper names for entities, a PascalCase entityType, \
+atomic factual observations, and an UPPER_SNAKE_CASE relationType in active voice. Every \
+relation endpoint must be one of the entities. Only include facts stated in the text.";
+
+pub const SUGGEST_RELATIONS_SYSTEM_PROMPT: &str = "You propose missing relations for a knowledge \
+graph. You are given entities with their observations and the relations that already exist. \
+Reply with only a JSON object of the form {\"suggestions\": [{\"from\": string, \"to\": \
+string, \"relationType\": string, \"confidence\": number between 0 and 1, \"reason\": \
+string}]}. Only use entity names from the list, use an UPPER_SNAKE_CASE relationType in active \
+voice, do not repeat existing relations, and base every suggestion on the observations.";
+
+// Default and maximum number of relation suggestions returned
+pub const DEFAULT_RELATION_SUGGESTIONS: usize = 10;
+pub const MAX_RELATION_SUGGESTIONS: usize = 50;
+
+pub const SUMMARIZE_SYSTEM_PROMPT: &str =
+    "You summarize what a knowledge graph knows about one entity. \
+Reply with only the summary: a few concise sentences that keep every distinct fact from the \
+observations. Do not add facts that are not in the observations.";
+
+// User prompt asking SUMMARIZE_SYSTEM_PROMPT for a summary of `observations`.
+pub fn summary_prompt(name: &str, entity_type: &str, observations: &[String]) -> String {
+    format!(
+        "Entity: {} (type: {})\nObservations:\n- {}",
+        name,
+        entity_type,
+        observations.join("\n- ")
+    )
+}
+
+#[derive(Serialize, Debug)]
+struct ChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+struct ChatInput<'a> {
+    messages: Vec<ChatMessage<'a>>,
+    max_tokens: u32,
+}
+
+#[derive(Deserialize, Debug)]
+struct ChatOutput {
+    response: Option<String>,
+}
+
+// Why an AI call failed; lets routes tell a missing binding (503) from a model error (502).
+#[derive(Debug)]
+pub enum AiError {
+    Unavailable(String),
+    Model(String),
+}
+
+impl std::fmt::Display for AiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AiError::Unavailable(msg) => write!(f, "Workers AI unavailable: {}", msg),
+            AiError::Model(msg) => write!(f, "Workers AI model error: {}", msg),
+        }
+    }
+}
+
+impl AiError {
+    pub fn status(&self) -> u16 {
+        match self {
+            AiError::Unavailable(_) => 503,
+            AiError::Model(_) => 502,
+        }
+    }
+}
+
+pub fn text_model(env: &Env) -> String {
+    env.var("AI_TEXT_MODEL")
+        .map(|v| v.to_string())
+        .unwrap_or_else(|_| DEFAULT_TEXT_MODEL.to_string())
+}
+
+// Runs one system + user prompt through the configured text model and returns its reply.
+pub async fn complete(
+    env: &Env,
+    system: &str,
+    user: &str,
+    max_tokens: u32,
+) -> std::result::Result<String, AiError> {
+    let ai = env.ai(AI_BINDING).map_err(|e| {
+        console_error!("Workers AI binding '{}' unavailable: {}", AI_BINDING, e);
+        AiError::Unavailable(e.to_string())
+    })?;
+    let input = ChatInput {
+        messages: vec![
+            ChatMessage {
+                role: "system",
+                content: system,
+            },
+            ChatMessage {
+                role: "user",
+                content: user,
+            },
+        ],
+        max_tokens,
+    };
+    let output: Result<ChatOutput> = ai.run(text_model(env), input).await;
+    match output {
+        Ok(ChatOutput {
+            response: Some(text),
+        }) => Ok(text.trim().to_string()),
+        Ok(ChatOutput { response: None }) => {
+            Err(AiError::Model("model returned no response".to_string()))
+        }
+        Err(e) => {
+            console_error!("Workers AI call failed: {}", e);
+            Err(AiError::Model(e.to_string()))
+        }
+    }
+}
+
+// Parses the JSON object in a model reply, ignoring any prose or code fences around it.
+pub fn parse_json_reply<T: DeserializeOwned>(reply: &str) -> std::result::Result<T, AiError> {
+    let json = match (reply.find('{'), reply.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &reply[start..=end],
+        _ => return Err(AiError::Model("reply contained no JSON object".to_string())),
+    };
+    serde_json::from_str(json)
+        .map_err(|e| AiError::Model(format!("reply was not in the expected format: {}", e)))
+}
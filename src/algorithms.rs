@@ -0,0 +1,437 @@
+use crate::kg::KnowledgeGraphState;
+use crate::search_index::tokenize;
+use crate::types::{
+    AnchorPath, ApiEntity, ApiRelation, Cycle, CycleReport, Edge, Node, ReachabilityResult,
+    SimilarEntity, TopoOrder,
+};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+// Default number of candidates returned by GET /nodes/{id}/similar
+pub const DEFAULT_SIMILAR_LIMIT: usize = 10;
+
+type Adjacency<'a> = HashMap<&'a str, Vec<&'a Edge>>;
+
+// Outgoing edges per source node, restricted to `relation_types` when given. Lists are sorted
+// by target and type so traversals are deterministic.
+pub fn outgoing_adjacency<'a>(
+    state: &'a KnowledgeGraphState,
+    relation_types: Option<&[String]>,
+) -> Adjacency<'a> {
+    let mut adjacency: Adjacency = HashMap::new();
+    for edge in state.edges.values() {
+        if relation_types.is_some_and(|types| !types.contains(&edge.edge_type)) {
+            continue;
+        }
+        adjacency
+            .entry(edge.source_node_id.as_str())
+            .or_default()
+            .push(edge);
+    }
+    for edges in adjacency.values_mut() {
+        edges.sort_by(|a, b| {
+            (&a.target_node_id, &a.edge_type, &a.id).cmp(&(&b.target_node_id, &b.edge_type, &b.id))
+        });
+    }
+    adjacency
+}
+
+// Breadth-first search for a shortest edge path from `start` to `target`, visiting only nodes
+// accepted by `allowed`. `Some(vec![])` when start == target.
+fn shortest_path<'a>(
+    adjacency: &Adjacency<'a>,
+    start: &'a str,
+    target: &str,
+    max_depth: Option<usize>,
+    allowed: impl Fn(&str) -> bool,
+) -> Option<Vec<&'a Edge>> {
+    // Node -> edge it was first reached through
+    let mut reached_via: HashMap<&str, Option<&Edge>> = HashMap::from([(start, None)]);
+    let mut queue = VecDeque::from([(start, 0usize)]);
+
+    while let Some((node, depth)) = queue.pop_front() {
+        if node == target {
+            break;
+        }
+        if max_depth.is_some_and(|max| depth >= max) {
+            continue;
+        }
+        for edge in adjacency.get(node).into_iter().flatten() {
+            let next = edge.target_node_id.as_str();
+            if allowed(next) && !reached_via.contains_key(next) {
+                reached_via.insert(next, Some(edge));
+                queue.push_back((next, depth + 1));
+            }
+        }
+    }
+
+    reached_via.get(target)?;
+    let mut edges = Vec::new();
+    let mut current = target;
+    while let Some(Some(edge)) = reached_via.get(current) {
+        edges.push(*edge);
+        current = edge.source_node_id.as_str();
+    }
+    edges.reverse();
+    Some(edges)
+}
+
+fn jaccard<T: Eq + std::hash::Hash>(a: &HashSet<T>, b: &HashSet<T>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        0.0
+    } else {
+        a.intersection(b).count() as f64 / union as f64
+    }
+}
+
+fn observation_tokens(node: &Node) -> HashSet<String> {
+    node.data
+        .get("observations")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+        .flat_map(tokenize)
+        .collect()
+}
+
+// Tarjan's algorithm, iterative so deep chains can't overflow the stack.
+fn strongly_connected_components<'a>(adjacency: &Adjacency<'a>) -> Vec<Vec<&'a str>> {
+    let mut roots: Vec<&str> = adjacency.keys().copied().collect();
+    roots.sort();
+
+    let mut index: HashMap<&str, usize> = HashMap::new();
+    let mut lowlink: HashMap<&str, usize> = HashMap::new();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut on_stack: HashSet<&str> = HashSet::new();
+    let mut components = Vec::new();
+
+    for root in roots {
+        if index.contains_key(root) {
+            continue;
+        }
+        // (node, position of the next child to visit); nodes are indexed when first on top
+        let mut call_stack: Vec<(&str, usize)> = vec![(root, 0)];
+        while let Some(&(node, child_pos)) = call_stack.last() {
+            if !index.contains_key(node) {
+                let i = index.len();
+                index.insert(node, i);
+                lowlink.insert(node, i);
+                stack.push(node);
+                on_stack.insert(node);
+            }
+            let children = adjacency.get(node).map(Vec::as_slice).unwrap_or_default();
+            if let Some(edge) = children.get(child_pos) {
+                call_stack.last_mut().unwrap().1 += 1;
+                let child = edge.target_node_id.as_str();
+                if !index.contains_key(child) {
+                    call_stack.push((child, 0));
+                } else if on_stack.contains(child) {
+                    let low = lowlink[node].min(index[child]);
+                    lowlink.insert(node, low);
+                }
+                continue;
+            }
+
+            call_stack.pop();
+            if let Some(&(parent, _)) = call_stack.last() {
+                let low = lowlink[parent].min(lowlink[node]);
+                lowlink.insert(parent, low);
+            }
+            if lowlink[node] == index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    on_stack.remove(member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+impl KnowledgeGraphState {
+    fn edges_to_relations(&self, edges: &[&Edge]) -> Vec<ApiRelation> {
+        edges.iter().map(|e| self.edge_to_api_relation(e)).collect()
+    }
+
+    // Callers check that both endpoints exist.
+    pub fn reachable(
+        &self,
+        from: &str,
+        to: &str,
+        relation_types: Option<&[String]>,
+        max_depth: Option<usize>,
+    ) -> ReachabilityResult {
+        let adjacency = outgoing_adjacency(self, relation_types);
+        match shortest_path(&adjacency, from, to, max_depth, |_| true) {
+            Some(edges) => {
+                let mut path = vec![from.to_string()];
+                path.extend(edges.iter().map(|e| e.target_node_id.clone()));
+                ReachabilityResult {
+                    reachable: true,
+                    path,
+                    relations: self.edges_to_relations(&edges),
+                }
+            }
+            None => ReachabilityResult {
+                reachable: false,
+                path: Vec::new(),
+                relations: Vec::new(),
+            },
+        }
+    }
+
+    // Shortest relation path from `anchor` to each of `entities` it reaches, following
+    // relations in either direction; sorted by length, then name. Callers check that the
+    // anchor exists.
+    pub fn anchor_paths(&self, anchor: &str, entities: &[ApiEntity]) -> Vec<AnchorPath> {
+        let mut adjacency: Adjacency = HashMap::new();
+        for edge in self.edges.values() {
+            adjacency
+                .entry(edge.source_node_id.as_str())
+                .or_default()
+                .push(edge);
+            adjacency
+                .entry(edge.target_node_id.as_str())
+                .or_default()
+                .push(edge);
+        }
+        for edges in adjacency.values_mut() {
+            edges.sort_by(|a, b| (&a.id, &a.edge_type).cmp(&(&b.id, &b.edge_type)));
+        }
+
+        let mut remaining: HashSet<&str> = entities.iter().map(|e| e.name.as_str()).collect();
+        // Node -> edge it was first reached through
+        let mut reached_via: HashMap<&str, Option<&Edge>> = HashMap::from([(anchor, None)]);
+        let mut queue = VecDeque::from([anchor]);
+        while let Some(node) = queue.pop_front() {
+            remaining.remove(node);
+            if remaining.is_empty() {
+                break;
+            }
+            for edge in adjacency.get(node).into_iter().flatten() {
+                let next = if edge.source_node_id == node {
+                    edge.target_node_id.as_str()
+                } else {
+                    edge.source_node_id.as_str()
+                };
+                if !reached_via.contains_key(next) {
+                    reached_via.insert(next, Some(edge));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut paths: Vec<AnchorPath> = entities
+            .iter()
+            .filter(|entity| reached_via.contains_key(entity.name.as_str()))
+            .map(|entity| {
+                let mut path = vec![entity.name.clone()];
+                let mut edges = Vec::new();
+                let mut current = entity.name.as_str();
+                while let Some(Some(edge)) = reached_via.get(current) {
+                    edges.push(*edge);
+                    current = if edge.target_node_id == current {
+                        edge.source_node_id.as_str()
+                    } else {
+                        edge.target_node_id.as_str()
+                    };
+                    path.push(current.to_string());
+                }
+                path.reverse();
+                edges.reverse();
+                AnchorPath {
+                    entity_name: entity.name.clone(),
+                    path,
+                    relations: self.edges_to_relations(&edges),
+                }
+            })
+            .collect();
+        paths.sort_by(|a, b| (a.path.len(), &a.entity_name).cmp(&(b.path.len(), &b.entity_name)));
+        paths
+    }
+
+    // Every strongly connected component of `adjacency` that contains a cycle, each with one
+    // shortest cycle through its alphabetically first entity as a witness.
+    fn cycles_in(&self, adjacency: &Adjacency) -> Vec<Cycle> {
+        let mut cycles: Vec<Cycle> = strongly_connected_components(adjacency)
+            .into_iter()
+            .filter_map(|mut component| {
+                component.sort();
+                let members: HashSet<&str> = component.iter().copied().collect();
+                let start = component[0];
+                // Shortest cycle through `start`: one edge out, then the shortest way back
+                let witness = adjacency
+                    .get(start)
+                    .into_iter()
+                    .flatten()
+                    .filter(|e| members.contains(e.target_node_id.as_str()))
+                    .filter_map(|first| {
+                        let rest = shortest_path(
+                            adjacency,
+                            first.target_node_id.as_str(),
+                            start,
+                            None,
+                            |n| members.contains(n),
+                        )?;
+                        Some(std::iter::once(*first).chain(rest).collect::<Vec<_>>())
+                    })
+                    .min_by_key(|edges| edges.len())?; // None: single node without a self-loop
+
+                let mut path = vec![start.to_string()];
+                path.extend(witness.iter().map(|e| e.target_node_id.clone()));
+                Some(Cycle {
+                    entities: component.iter().map(|n| n.to_string()).collect(),
+                    path,
+                    relations: self.edges_to_relations(&witness),
+                })
+            })
+            .collect();
+        cycles.sort_by(|a, b| a.entities.cmp(&b.entities));
+        cycles
+    }
+
+    pub fn find_cycles(&self, relation_type: Option<&str>) -> CycleReport {
+        let relation_types = relation_type.map(|t| vec![t.to_string()]);
+        let adjacency = outgoing_adjacency(self, relation_types.as_deref());
+        let cycles = self.cycles_in(&adjacency);
+        CycleReport {
+            relation_type: relation_type.map(str::to_string),
+            has_cycles: !cycles.is_empty(),
+            cycles,
+        }
+    }
+
+    // Kahn's algorithm over the entities touched by the selected relations, breaking ties
+    // alphabetically. Fails with one of the cycles when the relations aren't a DAG.
+    pub fn toposort(
+        &self,
+        relation_types: Option<&[String]>,
+        order: TopoOrder,
+    ) -> Result<Vec<String>, Cycle> {
+        let adjacency = outgoing_adjacency(self, relation_types);
+        let mut in_degree: HashMap<&str, usize> = HashMap::new();
+        for (source, edges) in &adjacency {
+            in_degree.entry(source).or_insert(0);
+            for edge in edges {
+                *in_degree.entry(edge.target_node_id.as_str()).or_insert(0) += 1;
+            }
+        }
+
+        let mut ready: BTreeSet<&str> = in_degree
+            .iter()
+            .filter(|(_, degree)| **degree == 0)
+            .map(|(node, _)| *node)
+            .collect();
+        let mut sorted = Vec::with_capacity(in_degree.len());
+        while let Some(node) = ready.pop_first() {
+            sorted.push(node.to_string());
+            for edge in adjacency.get(node).into_iter().flatten() {
+                let target = edge.target_node_id.as_str();
+                let degree = in_degree.get_mut(target).expect("target counted above");
+                *degree -= 1;
+                if *degree == 0 {
+                    ready.insert(target);
+                }
+            }
+        }
+
+        if sorted.len() < in_degree.len() {
+            return Err(self
+                .cycles_in(&adjacency)
+                .into_iter()
+                .next()
+                .expect("an unsortable graph has a cycle"));
+        }
+        if order == TopoOrder::TargetFirst {
+            sorted.reverse();
+        }
+        Ok(sorted)
+    }
+
+    // Neighbors in either direction, excluding the node itself.
+    fn neighbor_ids(&self) -> HashMap<&str, HashSet<&str>> {
+        let mut neighbors: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for edge in self.edges.values() {
+            let (source, target) = (edge.source_node_id.as_str(), edge.target_node_id.as_str());
+            if source != target {
+                neighbors.entry(source).or_default().insert(target);
+                neighbors.entry(target).or_default().insert(source);
+            }
+        }
+        neighbors
+    }
+
+    // Ranks other entities of the same type by neighborhood overlap and shared observation
+    // tokens. Entities with nothing in common are left out. Callers check the node exists.
+    pub fn similar_entities(&self, node_id: &str, limit: usize) -> Vec<SimilarEntity> {
+        let Some(node) = self.nodes.get(node_id) else {
+            return Vec::new();
+        };
+        let neighbors = self.neighbor_ids();
+        let no_neighbors = HashSet::new();
+        let own_neighbors = neighbors.get(node_id).unwrap_or(&no_neighbors);
+        let own_tokens = observation_tokens(node);
+
+        let mut candidates: Vec<SimilarEntity> = self
+            .nodes
+            .values()
+            .filter(|other| other.id != node.id && other.node_type == node.node_type)
+            .filter_map(|other| {
+                let other_neighbors = neighbors.get(other.id.as_str()).unwrap_or(&no_neighbors);
+                let neighbor_similarity = jaccard(own_neighbors, other_neighbors);
+                let observation_similarity = jaccard(&own_tokens, &observation_tokens(other));
+                if neighbor_similarity == 0.0 && observation_similarity == 0.0 {
+                    return None;
+                }
+                let mut shared_neighbors: Vec<String> = own_neighbors
+                    .intersection(other_neighbors)
+                    .map(|n| n.to_string())
+                    .collect();
+                shared_neighbors.sort();
+                Some(SimilarEntity {
+                    name: other.id.clone(),
+                    score: (neighbor_similarity + observation_similarity) / 2.0,
+                    neighbor_similarity,
+                    observation_similarity,
+                    shared_neighbors,
+                })
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        candidates.truncate(limit);
+        candidates
+    }
+
+    // The entities a relation suggestion for `names` may use: the named entities, their
+    // neighbors and their most similar entities, sorted by name.
+    pub fn suggestion_candidates(&self, names: &[String]) -> Vec<String> {
+        let neighbors = self.neighbor_ids();
+        let mut candidates: BTreeSet<String> = BTreeSet::new();
+        for name in names {
+            candidates.insert(name.clone());
+            candidates.extend(
+                neighbors
+                    .get(name.as_str())
+                    .into_iter()
+                    .flatten()
+                    .map(|n| n.to_string()),
+            );
+            candidates.extend(
+                self.similar_entities(name, DEFAULT_SIMILAR_LIMIT)
+                    .into_iter()
+                    .map(|s| s.name),
+            );
+        }
+        candidates.into_iter().collect()
+    }
+}
@@ -0,0 +1,225 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{
+    DegreeBucket, DegreeHub, DegreeReport, GraphStats, GrowthPoint, RelationTypeDegrees, TopEntity,
+    TopMetric, TypePairCount, TypePairReport,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// Graph-shape analytics under /graph/analytics. The degree report tells a useful structure
+// from a hairball: the histogram uses power-of-two buckets (0, 1, 2-3, 4-7, ...) so a few
+// huge hubs don't drown out the long tail, and per relation type the average fan-out and
+// fan-in show which types connect everything to everything.
+//
+// Growth history is one point per UTC day, kept in DO storage apart from the graph state.
+// Each maintenance run overwrites the current day's point, so a day ends up with the counts
+// of its last run.
+
+pub const DEFAULT_TOP_HUBS: usize = 10;
+pub const MAX_TOP_HUBS: usize = 100;
+// A (source type, relation type, target type) combination is flagged as anomalous when its
+// relation type has at least ANOMALY_MIN_RELATIONS relations and the combination carries
+// less than ANOMALY_MAX_SHARE of them
+const ANOMALY_MIN_RELATIONS: usize = 10;
+const ANOMALY_MAX_SHARE: f64 = 0.05;
+// Days of growth history kept; older points are dropped
+pub const MAX_GROWTH_DAYS: usize = 730;
+
+// Bucket bounds for a degree: 0 and 1 get their own, then [2^k, 2^(k+1) - 1].
+fn bucket_bounds(degree: usize) -> (usize, usize) {
+    if degree < 2 {
+        return (degree, degree);
+    }
+    let min = 1 << (usize::BITS - 1 - degree.leading_zeros());
+    (min, min * 2 - 1)
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+// Records `stats` as the point for the day of `now_ms`.
+pub fn record_growth(history: &mut Vec<GrowthPoint>, stats: &GraphStats, now_ms: u64) {
+    let Some(day) = chrono::DateTime::from_timestamp_millis(now_ms as i64)
+        .map(|time| time.format("%Y-%m-%d").to_string())
+    else {
+        return;
+    };
+    let point = GrowthPoint {
+        day,
+        entity_count: stats.entity_count,
+        relation_count: stats.relation_count,
+        observation_count: stats.observation_count,
+    };
+    match history.last_mut() {
+        Some(last) if last.day == point.day => *last = point,
+        _ => history.push(point),
+    }
+    if history.len() > MAX_GROWTH_DAYS {
+        history.drain(..history.len() - MAX_GROWTH_DAYS);
+    }
+}
+
+impl TopMetric {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "degree" => Ok(TopMetric::Degree),
+            "observations" => Ok(TopMetric::Observations),
+            "recency" => Ok(TopMetric::Recency),
+            other => Err(format!(
+                "unknown by '{}' (expected degree, observations or recency)",
+                other
+            )),
+        }
+    }
+}
+
+impl KnowledgeGraphState {
+    // The `limit` entities ranked highest by `by`, ties broken by name. Degree counts the
+    // relations to existing entities, as in the degree report.
+    pub fn top_entities(&self, by: TopMetric, limit: usize) -> Vec<TopEntity> {
+        let mut degrees: HashMap<&str, usize> = HashMap::new();
+        for edge in self.edges.values() {
+            let (source, target) = (edge.source_node_id.as_str(), edge.target_node_id.as_str());
+            if self.nodes.contains_key(source) && self.nodes.contains_key(target) {
+                *degrees.entry(source).or_default() += 1;
+                *degrees.entry(target).or_default() += 1;
+            }
+        }
+        let mut entities: Vec<TopEntity> = self
+            .nodes
+            .values()
+            .map(|node| TopEntity {
+                name: node.id.clone(),
+                entity_type: node.node_type.clone(),
+                degree: degrees.get(node.id.as_str()).copied().unwrap_or(0),
+                observation_count: Self::observations_of(node).len(),
+                updated_at_ms: node.updated_at_ms,
+            })
+            .collect();
+        let key = |entity: &TopEntity| match by {
+            TopMetric::Degree => entity.degree as u64,
+            TopMetric::Observations => entity.observation_count as u64,
+            TopMetric::Recency => entity.updated_at_ms,
+        };
+        entities.sort_by(|a, b| key(b).cmp(&key(a)).then_with(|| a.name.cmp(&b.name)));
+        entities.truncate(limit);
+        entities
+    }
+
+    // Relation counts per (source entityType, relationType, target entityType): the schema the
+    // graph actually follows. Relations to missing entities are left out.
+    pub fn type_pair_report(&self) -> TypePairReport {
+        let mut counts: BTreeMap<(&str, &str, &str), usize> = BTreeMap::new();
+        let mut per_relation_type: HashMap<&str, usize> = HashMap::new();
+        for edge in self.edges.values() {
+            let (Some(source), Some(target)) = (
+                self.nodes.get(&edge.source_node_id),
+                self.nodes.get(&edge.target_node_id),
+            ) else {
+                continue;
+            };
+            *counts
+                .entry((&source.node_type, &edge.edge_type, &target.node_type))
+                .or_default() += 1;
+            *per_relation_type.entry(&edge.edge_type).or_default() += 1;
+        }
+        let mut pairs: Vec<TypePairCount> = counts
+            .into_iter()
+            .map(|((source_type, relation_type, target_type), count)| {
+                let total = per_relation_type[relation_type];
+                let share = ratio(count, total);
+                TypePairCount {
+                    source_type: source_type.to_string(),
+                    relation_type: relation_type.to_string(),
+                    target_type: target_type.to_string(),
+                    count,
+                    share,
+                    anomalous: total >= ANOMALY_MIN_RELATIONS && share < ANOMALY_MAX_SHARE,
+                }
+            })
+            .collect();
+        // Stable, so equal counts keep the (source, relation, target) order
+        pairs.sort_by_key(|pair| std::cmp::Reverse(pair.count));
+        TypePairReport {
+            relation_count: per_relation_type.values().sum(),
+            pairs,
+        }
+    }
+
+    pub fn degree_report(&self, top: usize) -> DegreeReport {
+        // Node id -> (in, out); relations to missing entities are left out
+        let mut degrees: HashMap<&str, (usize, usize)> =
+            self.nodes.keys().map(|id| (id.as_str(), (0, 0))).collect();
+        // Relation type -> (relations, sources, targets)
+        let mut by_type: BTreeMap<&str, (usize, HashSet<&str>, HashSet<&str>)> = BTreeMap::new();
+        let mut relation_count = 0;
+        for edge in self.edges.values() {
+            let (source, target) = (edge.source_node_id.as_str(), edge.target_node_id.as_str());
+            if !degrees.contains_key(source) || !degrees.contains_key(target) {
+                continue;
+            }
+            relation_count += 1;
+            if let Some(d) = degrees.get_mut(source) {
+                d.1 += 1;
+            }
+            if let Some(d) = degrees.get_mut(target) {
+                d.0 += 1;
+            }
+            let entry = by_type.entry(&edge.edge_type).or_default();
+            entry.0 += 1;
+            entry.1.insert(source);
+            entry.2.insert(target);
+        }
+
+        let mut histogram: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+        for (in_degree, out_degree) in degrees.values() {
+            *histogram
+                .entry(bucket_bounds(in_degree + out_degree))
+                .or_default() += 1;
+        }
+
+        let mut hubs: Vec<DegreeHub> = degrees
+            .iter()
+            .filter(|(_, (i, o))| i + o > 0)
+            .map(|(name, (in_degree, out_degree))| DegreeHub {
+                name: name.to_string(),
+                entity_type: self.nodes[*name].node_type.clone(),
+                in_degree: *in_degree,
+                out_degree: *out_degree,
+                degree: in_degree + out_degree,
+            })
+            .collect();
+        hubs.sort_by(|a, b| b.degree.cmp(&a.degree).then_with(|| a.name.cmp(&b.name)));
+        hubs.truncate(top);
+
+        DegreeReport {
+            entity_count: self.nodes.len(),
+            relation_count,
+            average_degree: ratio(2 * relation_count, self.nodes.len()),
+            max_degree: degrees.values().map(|(i, o)| i + o).max().unwrap_or(0),
+            isolated_count: degrees.values().filter(|(i, o)| i + o == 0).count(),
+            histogram: histogram
+                .into_iter()
+                .map(|((min, max), count)| DegreeBucket { min, max, count })
+                .collect(),
+            top_hubs: hubs,
+            relation_types: by_type
+                .into_iter()
+                .map(
+                    |(relation_type, (count, sources, targets))| RelationTypeDegrees {
+                        relation_type: relation_type.to_string(),
+                        relation_count: count,
+                        source_count: sources.len(),
+                        target_count: targets.len(),
+                        avg_out_degree: ratio(count, sources.len()),
+                        avg_in_degree: ratio(count, targets.len()),
+                    },
+                )
+                .collect(),
+        }
+    }
+}
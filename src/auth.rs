@@ -0,0 +1,261 @@
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use worker::{console_warn, Env, Method, Request};
+
+// Secret holding the API keys as a JSON object mapping each key to its scopes, e.g.
+// {"key-a": ["read"], "key-b": ["read", "write", "admin"]}. Auth is disabled when it's unset.
+// Adding "redacted" to a key's scopes makes everything it reads redacted, and keeps it off the
+// read routes that can't be (see redact.rs).
+// Behind Cloudflare Access (see access.rs) requests carrying an Access token are authenticated
+// by it instead, and API keys remain for clients that reach the worker without one. Requests
+// signed with a shared secret (see signing.rs) are authenticated by their signature.
+pub const API_KEYS_SECRET: &str = "API_KEYS";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Redacted, // Not a permission: marks a key whose graph dumps are always redacted
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Redacted => "redacted",
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+}
+
+// Who made the request and the scopes granted to them.
+#[derive(Debug, Clone)]
+pub struct Caller {
+    id: String, // Stable identifier for per-caller limits; never the key itself
+    scopes: BTreeSet<Scope>,
+    graph: Option<String>, // DO instance the caller works on; None for the shared graph
+}
+
+impl Caller {
+    pub fn new(id: String, scopes: BTreeSet<Scope>, graph: Option<String>) -> Self {
+        Caller { id, scopes, graph }
+    }
+
+    pub fn unrestricted(id: String) -> Self {
+        Caller::new(
+            id,
+            BTreeSet::from([Scope::Read, Scope::Write, Scope::Admin]),
+            None,
+        )
+    }
+
+    // Name of the knowledge graph DO instance that serves this caller.
+    pub fn graph_name(&self) -> &str {
+        self.graph.as_deref().unwrap_or(crate::KNOWLEDGE_GRAPH_NAME)
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn scopes(&self) -> impl Iterator<Item = Scope> + '_ {
+        self.scopes.iter().copied()
+    }
+
+    // Scopes are cumulative: admin implies write, and write implies read.
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.iter().any(|granted| *granted >= scope)
+    }
+
+    pub fn is_redacted(&self) -> bool {
+        self.scopes.contains(&Scope::Redacted)
+    }
+}
+
+#[derive(Debug)]
+pub enum AuthError {
+    MissingKey,
+    InvalidKey,
+    MissingAccessToken,
+    InvalidSignature(String),
+    InvalidAccessToken(String),
+    NotPermitted(String),
+    Misconfigured(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingKey => write!(
+                f,
+                "Missing API key: send 'Authorization: Bearer <key>' or 'X-API-Key: <key>'"
+            ),
+            AuthError::InvalidKey => write!(f, "Invalid API key"),
+            AuthError::MissingAccessToken => {
+                write!(f, "Missing Access token: sign in through Cloudflare Access")
+            }
+            AuthError::InvalidAccessToken(msg) => write!(f, "Invalid Access token: {}", msg),
+            AuthError::InvalidSignature(msg) => write!(f, "Invalid request signature: {}", msg),
+            AuthError::NotPermitted(msg) => write!(f, "Forbidden: {}", msg),
+            AuthError::Misconfigured(msg) => write!(f, "API key configuration error: {}", msg),
+        }
+    }
+}
+
+// Lets axum handlers return auth failures directly, as plain text with the matching status.
+impl axum::response::IntoResponse for AuthError {
+    fn into_response(self) -> axum::response::Response {
+        let status = axum::http::StatusCode::from_u16(self.status())
+            .unwrap_or(axum::http::StatusCode::UNAUTHORIZED);
+        (status, self.to_string()).into_response()
+    }
+}
+
+impl AuthError {
+    pub fn status(&self) -> u16 {
+        match self {
+            AuthError::MissingKey
+            | AuthError::InvalidKey
+            | AuthError::MissingAccessToken
+            | AuthError::InvalidAccessToken(_)
+            | AuthError::InvalidSignature(_) => 401,
+            AuthError::NotPermitted(_) => 403,
+            AuthError::Misconfigured(_) => 500,
+        }
+    }
+}
+
+fn presented_key(req: &Request) -> Option<String> {
+    let headers = req.headers();
+    if let Ok(Some(auth)) = headers.get("authorization") {
+        if let Some(token) = auth.strip_prefix("Bearer ") {
+            return Some(token.trim().to_string());
+        }
+    }
+    headers.get("x-api-key").ok().flatten()
+}
+
+// Without API keys, callers are told apart by client address.
+fn anonymous_caller_id(req: &Request) -> String {
+    let ip = req.headers().get("cf-connecting-ip").ok().flatten();
+    format!("ip:{}", ip.as_deref().unwrap_or("unknown"))
+}
+
+// Short hash of the key, so counters and reports can refer to a key without storing it.
+fn key_fingerprint(key: &str) -> String {
+    let digest = format!("{:x}", md5::compute(key.as_bytes()));
+    format!("key:{}", &digest[..12])
+}
+
+// Compares without short-circuiting so response timing doesn't reveal matching prefixes.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+pub async fn authenticate(req: &Request, env: &Env) -> Result<Caller, AuthError> {
+    let access = crate::access::config(env)?;
+    if let Some(access) = &access {
+        if let Some(token) = req
+            .headers()
+            .get(crate::access::ACCESS_JWT_HEADER)
+            .ok()
+            .flatten()
+        {
+            return crate::access::authenticate(&token, access).await;
+        }
+    }
+    let signing_keys = crate::signing::keys(env)?;
+    if req
+        .headers()
+        .has(crate::signing::SIGNATURE_HEADER)
+        .unwrap_or(false)
+    {
+        let Some(signing_keys) = &signing_keys else {
+            return Err(AuthError::InvalidSignature(format!(
+                "{} is not set",
+                crate::signing::HMAC_KEYS_SECRET
+            )));
+        };
+        return crate::signing::authenticate(req, env, signing_keys).await;
+    }
+    let Ok(secret) = env.secret(API_KEYS_SECRET) else {
+        // Behind Access, a request without a token never gets in unauthenticated
+        if access.is_some() {
+            return Err(AuthError::MissingAccessToken);
+        }
+        // Likewise once signing is set up, unsigned requests are refused
+        if signing_keys.is_some() {
+            return Err(AuthError::InvalidSignature(format!(
+                "missing {} header",
+                crate::signing::SIGNATURE_HEADER
+            )));
+        }
+        console_warn!(
+            "{} is not set; API key authentication is disabled",
+            API_KEYS_SECRET
+        );
+        return Ok(Caller::unrestricted(anonymous_caller_id(req)));
+    };
+    let keys: HashMap<String, BTreeSet<Scope>> = serde_json::from_str(&secret.to_string())
+        .map_err(|e| {
+            AuthError::Misconfigured(format!("{} is not valid: {}", API_KEYS_SECRET, e))
+        })?;
+
+    let presented = presented_key(req).ok_or(AuthError::MissingKey)?;
+    // Check every key so the time taken doesn't depend on which one matched
+    let mut matched: Option<&BTreeSet<Scope>> = None;
+    for (key, scopes) in &keys {
+        if constant_time_eq(key.as_bytes(), presented.as_bytes()) {
+            matched = Some(scopes);
+        }
+    }
+    matched
+        .map(|scopes| Caller::new(key_fingerprint(&presented), scopes.clone(), None))
+        .ok_or(AuthError::InvalidKey)
+}
+
+// Set by the worker on every DO request to the authenticated caller's id, which the DO
+// stamps on what it writes. Only the worker sets it: client headers are not passed through.
+pub const ACTOR_HEADER: &str = "x-actor";
+
+// POST routes of the DO that only read the graph.
+const READ_ONLY_POST_ROUTES: &[&str] = &[
+    "/nodes/batch-get",
+    "/graph/search",
+    "/graph/open",
+    "/graph/relations/search",
+    "/graph/observations/search",
+    "/graph/context",
+    "/graph/relations/suggest",
+    "/graph/reachable",
+    "/graph/toposort",
+    "/graph/subgraph",
+    "/graph/import/validate",
+];
+
+// The route a DO path is matched as: the DO percent-decodes each segment before matching, so
+// scopes are worked out on the decoded path, never on the one the client sent.
+pub fn decoded_do_route(path: &str) -> String {
+    percent_decode_str(path).decode_utf8_lossy().into_owned()
+}
+
+// Scope needed to call a DO route directly through /do/*path (decoded path without the /v1
+// prefix, see decoded_do_route).
+pub fn scope_for_do_route(method: &Method, path: &str) -> Scope {
+    if matches!(path, "/graph/clear" | "/graph/repair" | "/graph/erase")
+        || path.starts_with("/graph/admin/")
+    {
+        Scope::Admin
+    } else if *method == Method::Get
+        || *method == Method::Head
+        || (*method == Method::Post && READ_ONLY_POST_ROUTES.contains(&path))
+    {
+        Scope::Read
+    } else {
+        Scope::Write
+    }
+}
@@ -0,0 +1,70 @@
+use crate::kg::KnowledgeGraphState;
+
+// Scheduled summarization. With auto_summarize_min_observations in the graph metadata, each
+// maintenance run picks the entities holding at least that many observations older than
+// auto_summarize_min_age_days (default 30) and has Workers AI summarize those, at most
+// MAX_AUTO_SUMMARIES_PER_RUN entities per run. The summary replaces the old observations and
+// newer ones stay. What was replaced is archived in DO storage (GET
+// /nodes/{id}/archived-observations) rather than in the graph, whose size is the point.
+// Pinned entities are left alone. Observations stored before their timestamps were kept
+// count as old.
+
+// Graph metadata keys: an observation count (>= 2), and an age in days (>= 0)
+pub const AUTO_SUMMARIZE_MIN_OBSERVATIONS_KEY: &str = "auto_summarize_min_observations";
+pub const AUTO_SUMMARIZE_MIN_AGE_DAYS_KEY: &str = "auto_summarize_min_age_days";
+const DEFAULT_MIN_AGE_DAYS: f64 = 30.0;
+
+// Each summary is a Workers AI call made from the alarm, so a run does only a few
+pub const MAX_AUTO_SUMMARIES_PER_RUN: usize = 5;
+// Archived summarizations kept per entity, oldest dropped first
+pub const MAX_ARCHIVES_PER_ENTITY: usize = 20;
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+impl KnowledgeGraphState {
+    fn auto_summarize_min_observations(&self) -> Option<usize> {
+        self.metadata
+            .get(AUTO_SUMMARIZE_MIN_OBSERVATIONS_KEY)
+            .and_then(|v| v.as_u64())
+            .filter(|count| *count >= 2)
+            .map(|count| count as usize)
+    }
+
+    fn auto_summarize_min_age_ms(&self) -> u64 {
+        let days = self
+            .metadata
+            .get(AUTO_SUMMARIZE_MIN_AGE_DAYS_KEY)
+            .and_then(|v| v.as_f64())
+            .filter(|days| *days >= 0.0)
+            .unwrap_or(DEFAULT_MIN_AGE_DAYS);
+        (days * DAY_MS) as u64
+    }
+
+    // Entities due for summarization with their old observations, the most old observations
+    // first; empty unless auto-summarization is configured.
+    pub fn stale_entities(&self, now_ms: u64) -> Vec<(String, Vec<String>)> {
+        let Some(min_observations) = self.auto_summarize_min_observations() else {
+            return Vec::new();
+        };
+        let cutoff_ms = now_ms.saturating_sub(self.auto_summarize_min_age_ms());
+        let mut due: Vec<(String, Vec<String>)> = self
+            .nodes
+            .values()
+            .filter(|node| !node.pinned && !Self::is_placeholder(node))
+            .filter_map(|node| {
+                let old: Vec<String> = Self::observations_of(node)
+                    .into_iter()
+                    .filter(|observation| {
+                        node.observation_added_ms
+                            .get(observation)
+                            .is_none_or(|added_ms| *added_ms < cutoff_ms)
+                    })
+                    .collect();
+                (old.len() >= min_observations).then(|| (node.id.clone(), old))
+            })
+            .collect();
+        due.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+        due.truncate(MAX_AUTO_SUMMARIES_PER_RUN);
+        due
+    }
+}
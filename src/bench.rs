@@ -0,0 +1,33 @@
+// Support for the native benchmarks in benches/ (`cargo bench --features bench`). The worker
+// clock and console only exist inside the Workers runtime, so graph code built with the
+// feature uses these stand-ins instead; the graph types the benchmarks drive are re-exported.
+
+pub use crate::kg::KnowledgeGraphState;
+pub use crate::types::{
+    EntityToCreate, MissingNodePolicy, NearDuplicatePolicy, RelationToCreate, RelationToDelete,
+};
+
+pub struct Date(u64);
+
+impl Date {
+    pub fn now() -> Self {
+        let elapsed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Date(elapsed.as_millis() as u64)
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        self.0
+    }
+}
+
+// Arguments are still type-checked, but nothing is printed
+macro_rules! console_log {
+    ($($t:tt)*) => {{
+        let _ = format_args!($($t)*);
+    }};
+}
+
+pub(crate) use console_log;
+pub(crate) use console_log as console_warn;
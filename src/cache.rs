@@ -0,0 +1,185 @@
+use crate::circuit;
+use crate::{GraphStub, API_V1_PREFIX};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use worker::*;
+
+// Edge caching of DO GET responses with the Cache API.
+//
+// Entries are keyed by the DO URL plus the graph version the DO reports in
+// X-Graph-Version (bumped on every save). The newest version this data center has seen
+// is itself kept in the cache; a successful write moves it forward, so later reads look
+// up new keys and everything cached for older versions is never served again. The Cache
+// API is local to each data center, so entries also expire after CACHE_TTL_SECONDS to
+// bound staleness after writes that went through another location. Every graph (DO
+// instance, see GraphStub) has its own keys and version marker.
+
+pub const GRAPH_VERSION_HEADER: &str = "X-Graph-Version";
+const CACHE_ORIGIN: &str = "https://graph-cache.internal";
+const CACHE_TTL_SECONDS: u64 = 30;
+const VERSION_MARKER_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+// Admin reports and MCP session state are not graph reads and bypass the cache.
+fn is_cacheable_path(path: &str) -> bool {
+    let path = path.strip_prefix(API_V1_PREFIX).unwrap_or(path);
+    !path.starts_with("/graph/admin/") && !path.starts_with("/mcp/")
+}
+
+fn graph_prefix(graph: &str) -> String {
+    format!(
+        "{}/{}",
+        CACHE_ORIGIN,
+        utf8_percent_encode(graph, NON_ALPHANUMERIC)
+    )
+}
+
+fn version_marker_key(graph: &str) -> String {
+    format!("{}/__graph_version", graph_prefix(graph))
+}
+
+// `path_and_query` is the DO URL without its origin, e.g. `/v1/graph/state`.
+fn entry_key(graph: &str, path_and_query: &str, version: u64) -> String {
+    let separator = if path_and_query.contains('?') {
+        '&'
+    } else {
+        '?'
+    };
+    format!(
+        "{}{}{}__graph_version={}",
+        graph_prefix(graph),
+        path_and_query,
+        separator,
+        version
+    )
+}
+
+fn response_version(response: &Response) -> Option<u64> {
+    response
+        .headers()
+        .get(GRAPH_VERSION_HEADER)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse().ok())
+}
+
+async fn current_version(cache: &Cache, graph: &str) -> Option<u64> {
+    let mut marker = cache.get(version_marker_key(graph), false).await.ok()??;
+    marker.text().await.ok()?.parse().ok()
+}
+
+// Moves the version marker forward; an older version never replaces a newer one.
+async fn record_version(cache: &Cache, graph: &str, version: u64) -> Result<()> {
+    if current_version(cache, graph)
+        .await
+        .is_some_and(|v| v >= version)
+    {
+        return Ok(());
+    }
+    let mut headers = Headers::new();
+    headers.set(
+        "cache-control",
+        &format!("max-age={}", VERSION_MARKER_TTL_SECONDS),
+    )?;
+    cache
+        .put(
+            version_marker_key(graph),
+            Response::ok(version.to_string())?.with_headers(headers),
+        )
+        .await
+}
+
+// Strong ETag for a graph version; every write changes it.
+pub fn graph_etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+// If-None-Match uses weak comparison, so W/ prefixes are ignored; lists and `*` are allowed.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let etag = etag.trim_start_matches("W/");
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+pub fn not_modified(etag: &str) -> Result<Response> {
+    let mut headers = Headers::new();
+    headers.set("etag", etag)?;
+    Ok(Response::empty()?.with_status(304).with_headers(headers))
+}
+
+// Fetches a GET route from the DO, answering 304 when `if_none_match` matches the
+// response's ETag. Cached entries keep the DO's ETag, so this works for cache hits too.
+pub async fn get_from_do(
+    stub: &GraphStub,
+    path_and_query: &str,
+    if_none_match: Option<&str>,
+) -> Result<Response> {
+    let response = fetch_cached(stub, path_and_query).await?;
+    if let (Some(condition), Some(etag)) = (if_none_match, response.headers().get("etag")?) {
+        if response.status_code() == 200 && etag_matches(condition, &etag) {
+            return not_modified(&etag);
+        }
+    }
+    Ok(response)
+}
+
+// Answers from the edge cache when the graph hasn't changed since the response was stored.
+async fn fetch_cached(stub: &GraphStub, path_and_query: &str) -> Result<Response> {
+    let do_url = format!("https://durable-object.internal-url{}", path_and_query);
+    let mut req_init = RequestInit::new();
+    req_init.with_method(Method::Get);
+    let do_req = Request::new_with_init(&do_url, &req_init)?;
+    let path = path_and_query.split('?').next().unwrap_or_default();
+    if !is_cacheable_path(path) {
+        return circuit::fetch(stub, do_req).await;
+    }
+
+    let cache = Cache::default();
+    let graph = stub.graph();
+    if let Some(version) = current_version(&cache, graph).await {
+        if let Ok(Some(cached)) = cache
+            .get(entry_key(graph, path_and_query, version), false)
+            .await
+        {
+            return Ok(cached);
+        }
+    }
+
+    let mut response = circuit::fetch(stub, do_req).await?;
+    if response.status_code() != 200 {
+        return Ok(response);
+    }
+    if let Some(version) = response_version(&response) {
+        // Cache failures only cost a future miss, so they never fail the request
+        let stored: Result<()> = async {
+            record_version(&cache, graph, version).await?;
+            // Headers of a fetched response are immutable, so the entry gets a copy
+            let entry = response.cloned()?;
+            let mut headers = entry.headers().clone();
+            headers.set("cache-control", &format!("max-age={}", CACHE_TTL_SECONDS))?;
+            cache
+                .put(
+                    entry_key(graph, path_and_query, version),
+                    entry.with_headers(headers),
+                )
+                .await
+        }
+        .await;
+        if let Err(e) = stored {
+            console_warn!("Failed to cache DO response for {}: {}", path_and_query, e);
+        }
+    }
+    Ok(response)
+}
+
+// Called with the DO's answer to a non-GET request; a successful write purges cached reads.
+pub async fn invalidate_after_write(stub: &GraphStub, response: &Response) {
+    if !(200..300).contains(&response.status_code()) {
+        return;
+    }
+    if let Some(version) = response_version(response) {
+        if let Err(e) = record_version(&Cache::default(), stub.graph(), version).await {
+            console_warn!("Failed to advance cached graph version: {}", e);
+        }
+    }
+}
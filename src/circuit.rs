@@ -0,0 +1,251 @@
+use crate::auth::{self, Scope};
+use crate::replication::REPLICA_PATH_PREFIX;
+use crate::types::{DoErrorBody, DoErrorDetail};
+use crate::{GraphStub, API_V1_PREFIX};
+use futures_util::future::{select, Either};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::time::Duration;
+use worker::{console_warn, Date, Delay, Env, Request, Response, Result, Stub};
+
+// Timeouts and circuit breaking for calls from the worker to the graph DO. A call that
+// doesn't answer within DO_TIMEOUT_MS, or fails outright, counts as a failure; after
+// DO_BREAKER_FAILURES failures in a row the breaker opens and calls fail fast with 503 for
+// DO_BREAKER_COOLDOWN_MS. Then one probe call is let through (half-open): success closes the
+// breaker, failure opens it again. Every graph has its own breakers, so one identity's failing
+// DO doesn't cut off the others; the primary and the read replicas have separate ones, and a
+// replica's 503 already makes readers fall back to the primary.
+//
+// Failed calls that are safe to repeat are retried up to DO_RETRIES times with jittered
+// exponential backoff (from DO_RETRY_BASE_MS), which rides out DO restarts and migrations:
+// reads (GETs and the read-only POST routes) and writes whose client sent an Idempotency-Key,
+// which the DO applies once and answers repeats of from the stored response (see
+// idempotency.rs). Every attempt counts towards the breaker, and retrying stops once it opens.
+//
+// State lives in the isolate, so each isolate trips on its own.
+
+pub const DO_TIMEOUT_MS_VAR: &str = "DO_TIMEOUT_MS";
+pub const DO_BREAKER_FAILURES_VAR: &str = "DO_BREAKER_FAILURES";
+pub const DO_BREAKER_COOLDOWN_MS_VAR: &str = "DO_BREAKER_COOLDOWN_MS";
+pub const DO_RETRIES_VAR: &str = "DO_RETRIES";
+pub const DO_RETRY_BASE_MS_VAR: &str = "DO_RETRY_BASE_MS";
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+const DEFAULT_TIMEOUT_MS: u64 = 10_000;
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOLDOWN_MS: u64 = 30_000;
+const DEFAULT_RETRIES: u32 = 2;
+const DEFAULT_RETRY_BASE_MS: u64 = 100;
+
+#[derive(Debug, Clone, Copy)]
+struct BreakerConfig {
+    timeout_ms: u64,
+    failure_threshold: u32,
+    cooldown_ms: u64,
+    retries: u32,
+    retry_base_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until_ms: u64 },
+    HalfOpen { since_ms: u64 }, // A probe call is in flight
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Target {
+    Primary,
+    Replica,
+}
+
+thread_local! {
+    static CONFIG: Cell<BreakerConfig> = const {
+        Cell::new(BreakerConfig {
+            timeout_ms: DEFAULT_TIMEOUT_MS,
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown_ms: DEFAULT_COOLDOWN_MS,
+            retries: DEFAULT_RETRIES,
+            retry_base_ms: DEFAULT_RETRY_BASE_MS,
+        })
+    };
+    // Keyed by graph name; closed breakers without failures are left out
+    static BREAKERS: RefCell<HashMap<(String, Target), BreakerState>> =
+        RefCell::new(HashMap::new());
+}
+
+fn var_or(env: &Env, name: &str, default: u64) -> u64 {
+    env.var(name)
+        .ok()
+        .and_then(|v| v.to_string().parse::<u64>().ok())
+        .unwrap_or(default)
+}
+
+fn positive_var(env: &Env, name: &str, default: u64) -> u64 {
+    Some(var_or(env, name, default))
+        .filter(|v| *v > 0)
+        .unwrap_or(default)
+}
+
+// Reads the settings from the environment; called once per worker request.
+pub fn configure(env: &Env) {
+    CONFIG.set(BreakerConfig {
+        timeout_ms: positive_var(env, DO_TIMEOUT_MS_VAR, DEFAULT_TIMEOUT_MS),
+        failure_threshold: positive_var(
+            env,
+            DO_BREAKER_FAILURES_VAR,
+            DEFAULT_FAILURE_THRESHOLD as u64,
+        ) as u32,
+        cooldown_ms: positive_var(env, DO_BREAKER_COOLDOWN_MS_VAR, DEFAULT_COOLDOWN_MS),
+        // "0" disables retries
+        retries: var_or(env, DO_RETRIES_VAR, DEFAULT_RETRIES as u64) as u32,
+        retry_base_ms: positive_var(env, DO_RETRY_BASE_MS_VAR, DEFAULT_RETRY_BASE_MS),
+    });
+}
+
+// Which breaker a request belongs to, and its DO route path without the prefixes.
+fn route_of(req: &Request) -> (Target, String) {
+    let path = req.path();
+    let path = path.strip_prefix(API_V1_PREFIX).unwrap_or(&path);
+    match path.strip_prefix(REPLICA_PATH_PREFIX) {
+        Some(rest) if rest.starts_with('/') => (Target::Replica, rest.to_string()),
+        _ => (Target::Primary, path.to_string()),
+    }
+}
+
+fn is_retryable(req: &Request, route: &str) -> Result<bool> {
+    Ok(
+        auth::scope_for_do_route(&req.method(), route) == Scope::Read
+            || req.headers().has(IDEMPOTENCY_KEY_HEADER)?,
+    )
+}
+
+// Full jitter: a random delay of up to base * 2^attempt.
+fn backoff(base_ms: u64, attempt: u32) -> Duration {
+    let cap_ms = base_ms.saturating_mul(1 << attempt.min(10));
+    let mut bytes = [0u8; 8];
+    let random = match getrandom::getrandom(&mut bytes) {
+        Ok(()) => u64::from_le_bytes(bytes),
+        Err(_) => cap_ms / 2,
+    };
+    Duration::from_millis(random % (cap_ms + 1))
+}
+
+fn with_breaker<T>(graph: &str, target: Target, f: impl FnOnce(&mut BreakerState) -> T) -> T {
+    BREAKERS.with_borrow_mut(|breakers| {
+        let key = (graph.to_string(), target);
+        let state = breakers
+            .entry(key.clone())
+            .or_insert(BreakerState::Closed { failures: 0 });
+        let result = f(state);
+        if matches!(*state, BreakerState::Closed { failures: 0 }) {
+            breakers.remove(&key);
+        }
+        result
+    })
+}
+
+// Whether a call may go out now; moves an expired open breaker to half-open. A probe that
+// never reported back (its request was cancelled) is replaced after one timeout.
+fn admit(graph: &str, target: Target, now_ms: u64, config: BreakerConfig) -> bool {
+    with_breaker(graph, target, |state| match *state {
+        BreakerState::Closed { .. } => true,
+        BreakerState::Open { until_ms } if now_ms >= until_ms => {
+            *state = BreakerState::HalfOpen { since_ms: now_ms };
+            true
+        }
+        BreakerState::HalfOpen { since_ms } if now_ms >= since_ms + config.timeout_ms => {
+            *state = BreakerState::HalfOpen { since_ms: now_ms };
+            true
+        }
+        BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => false,
+    })
+}
+
+fn record(graph: &str, target: Target, succeeded: bool, now_ms: u64, config: BreakerConfig) {
+    with_breaker(graph, target, |state| {
+        *state = match (*state, succeeded) {
+            (_, true) => BreakerState::Closed { failures: 0 },
+            (BreakerState::Closed { failures }, false)
+                if failures + 1 < config.failure_threshold =>
+            {
+                BreakerState::Closed {
+                    failures: failures + 1,
+                }
+            }
+            (_, false) => {
+                console_warn!("DO circuit breaker for {:?} of '{}' opened", target, graph);
+                BreakerState::Open {
+                    until_ms: now_ms + config.cooldown_ms,
+                }
+            }
+        };
+    });
+}
+
+fn unavailable(message: String) -> Result<Response> {
+    Response::from_json(&DoErrorBody {
+        error: DoErrorDetail {
+            code: "unavailable".to_string(),
+            message,
+            failures: Vec::new(),
+            details: None,
+        },
+    })
+    .map(|r| r.with_status(503))
+}
+
+async fn call(stub: &Stub, req: Request, timeout_ms: u64) -> std::result::Result<Response, String> {
+    let call = Box::pin(stub.fetch_with_request(req));
+    let timeout = Box::pin(Delay::from(Duration::from_millis(timeout_ms)));
+    match select(call, timeout).await {
+        Either::Left((Ok(response), _)) => Ok(response),
+        Either::Left((Err(e), _)) => Err(format!("Durable Object call failed: {}", e)),
+        Either::Right(_) => Err(format!(
+            "Durable Object did not answer within {} ms",
+            timeout_ms
+        )),
+    }
+}
+
+// Sends a request to the graph DO under the timeout, retries and the breaker. Failures are
+// answered with a 503 rather than an error, so callers handle them like any other DO response.
+pub async fn fetch(stub: &GraphStub, req: Request) -> Result<Response> {
+    let config = CONFIG.get();
+    let (target, route) = route_of(&req);
+    let retries = if is_retryable(&req, &route)? {
+        config.retries
+    } else {
+        0
+    };
+    let mut req = req;
+    let mut attempt = 0;
+    loop {
+        if !admit(stub.graph(), target, Date::now().as_millis(), config) {
+            return unavailable("Durable Object is unavailable (circuit open)".to_string());
+        }
+        // The body can only be sent once, so the next attempt needs its own copy
+        let next_req = if attempt < retries {
+            Some(req.clone()?)
+        } else {
+            None
+        };
+        let outcome = call(stub, req, config.timeout_ms).await;
+        record(
+            stub.graph(),
+            target,
+            outcome.is_ok(),
+            Date::now().as_millis(),
+            config,
+        );
+        match (outcome, next_req) {
+            (Ok(response), _) => return Ok(response),
+            (Err(message), None) => return unavailable(message),
+            (Err(message), Some(next_req)) => {
+                console_warn!("{}; retrying ({} of {})", message, attempt + 1, retries);
+                Delay::from(backoff(config.retry_base_ms, attempt)).await;
+                req = next_req;
+                attempt += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::Edge;
+
+// Relation confidence. A relation may carry a confidence in [0, 1], stamped with the time it
+// was set; re-creating the relation with a confidence sets it again. With a half-life in the
+// graph metadata the confidence fades on read (ApiRelation.confidence is the decayed value),
+// and the maintenance alarm removes relations that faded below the configured floor.
+// Relations without a confidence never fade.
+
+// Graph metadata keys: a half-life in days (> 0), and a floor in [0, 1]
+pub const CONFIDENCE_HALF_LIFE_DAYS_KEY: &str = "relation_confidence_half_life_days";
+pub const CONFIDENCE_FLOOR_KEY: &str = "relation_confidence_floor";
+
+const DAY_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+// `confidence` halved once per `half_life_ms` elapsed.
+pub fn decayed(confidence: f64, elapsed_ms: u64, half_life_ms: Option<f64>) -> f64 {
+    match half_life_ms {
+        Some(half_life_ms) => confidence * 0.5f64.powf(elapsed_ms as f64 / half_life_ms),
+        None => confidence,
+    }
+}
+
+pub fn check_confidence(confidence: Option<f64>, field: String) -> Result<(), String> {
+    match confidence {
+        Some(c) if !(0.0..=1.0).contains(&c) => Err(format!("{} must be between 0 and 1", field)),
+        _ => Ok(()),
+    }
+}
+
+impl KnowledgeGraphState {
+    fn confidence_half_life_ms(&self) -> Option<f64> {
+        self.metadata
+            .get(CONFIDENCE_HALF_LIFE_DAYS_KEY)
+            .and_then(|v| v.as_f64())
+            .filter(|days| *days > 0.0)
+            .map(|days| days * DAY_MS)
+    }
+
+    pub fn confidence_floor(&self) -> Option<f64> {
+        self.metadata
+            .get(CONFIDENCE_FLOOR_KEY)
+            .and_then(|v| v.as_f64())
+            .filter(|floor| (0.0..=1.0).contains(floor))
+    }
+
+    // The edge's confidence as of `now_ms`, or None when it has none.
+    pub fn effective_confidence(&self, edge: &Edge, now_ms: u64) -> Option<f64> {
+        let confidence = edge.confidence?;
+        let set_at_ms = edge.confidence_at_ms.unwrap_or(edge.created_at_ms);
+        Some(decayed(
+            confidence,
+            now_ms.saturating_sub(set_at_ms),
+            self.confidence_half_life_ms(),
+        ))
+    }
+
+    // Sets the confidence of an edge and of its mirror, if it has one.
+    pub fn set_confidence(&mut self, edge_id: &str, confidence: f64, now_ms: u64) {
+        let mirror_id = self.edges.get(edge_id).and_then(|edge| {
+            let mirror_type = self.mirror_type(&edge.edge_type)?;
+            self.find_edge_id(&edge.target_node_id, &edge.source_node_id, mirror_type)
+        });
+        for id in std::iter::once(edge_id.to_string()).chain(mirror_id) {
+            if let Some(edge) = self.edges.get_mut(&id) {
+                edge.confidence = Some(confidence);
+                edge.confidence_at_ms = Some(now_ms);
+                edge.updated_by = self.actor.clone();
+            }
+        }
+    }
+
+    // Removes the relations whose confidence has faded below `floor`; returns their IDs.
+    // Relations of pinned entities are kept unless `force` is set.
+    pub fn prune_faded_relations(&mut self, now_ms: u64, floor: f64, force: bool) -> Vec<String> {
+        let mut faded: Vec<String> = self
+            .edges
+            .values()
+            .filter(|edge| {
+                force
+                    || !(self.is_pinned(&edge.source_node_id)
+                        || self.is_pinned(&edge.target_node_id))
+            })
+            .filter(|edge| {
+                self.effective_confidence(edge, now_ms)
+                    .is_some_and(|confidence| confidence < floor)
+            })
+            .map(|edge| edge.id.clone())
+            .collect();
+        faded.sort();
+        for id in &faded {
+            self.edges.remove(id);
+        }
+        faded
+    }
+}
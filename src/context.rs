@@ -0,0 +1,200 @@
+use crate::kg::KnowledgeGraphState;
+use crate::search_index::{searchable_strings, tokenize};
+use crate::types::{ContextPack, Edge, Node};
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+// Context packs: the recall an agent would otherwise assemble from search_nodes, get_neighbors
+// and open_nodes, in one call. Entities matching the query (the whole query, or failing that
+// any of its words) are expanded `hops` relations out in either direction, ranked (matches
+// first, then by distance, query words hit and degree) and rendered as markdown until the
+// token budget is spent. An observation already listed for another entity and the mirror of
+// a listed relation are left out. Tokens are estimated at four characters each.
+
+pub const DEFAULT_CONTEXT_TOKENS: usize = 2_000;
+pub const MAX_CONTEXT_TOKENS: usize = 32_000;
+pub const DEFAULT_CONTEXT_HOPS: usize = 1;
+pub const MAX_CONTEXT_HOPS: usize = 3;
+
+fn estimate_tokens(chars: usize) -> usize {
+    chars.div_ceil(4)
+}
+
+// Markdown lines kept while they fit in the budget.
+struct Budget {
+    lines: Vec<String>,
+    chars: usize,
+    max_tokens: usize,
+    truncated: bool,
+}
+
+impl Budget {
+    fn push(&mut self, line: String) -> bool {
+        let chars = self.chars + line.chars().count() + 1;
+        if estimate_tokens(chars) > self.max_tokens {
+            self.truncated = true;
+            return false;
+        }
+        self.chars = chars;
+        self.lines.push(line);
+        true
+    }
+}
+
+// How many of the query's words occur in `text`.
+fn term_hits(text: &str, terms: &[String]) -> usize {
+    let text = text.to_lowercase();
+    terms
+        .iter()
+        .filter(|term| text.contains(term.as_str()))
+        .count()
+}
+
+impl KnowledgeGraphState {
+    fn context_seeds(&self, query: &str, terms: &[String]) -> Vec<&Node> {
+        let query_lower = query.to_lowercase();
+        let candidates: Box<dyn Iterator<Item = &Node>> = match self.search_index.candidates(query)
+        {
+            Some(ids) => Box::new(ids.into_iter().filter_map(|id| self.nodes.get(&id))),
+            None => Box::new(self.nodes.values()),
+        };
+        let seeds: Vec<&Node> = candidates
+            .filter(|node| {
+                searchable_strings(node).any(|s| s.to_lowercase().contains(&query_lower))
+            })
+            .collect();
+        if !seeds.is_empty() {
+            return seeds;
+        }
+        self.nodes
+            .values()
+            .filter(|node| searchable_strings(node).any(|s| term_hits(s, terms) > 0))
+            .collect()
+    }
+
+    pub fn build_context(&self, query: &str, max_tokens: usize, hops: usize) -> ContextPack {
+        let query = query.trim();
+        let terms: Vec<String> = tokenize(query)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        // Relations in either direction, sorted so the output is deterministic
+        let mut adjacency: HashMap<&str, Vec<&Edge>> = HashMap::new();
+        for edge in self.edges.values() {
+            adjacency
+                .entry(&edge.source_node_id)
+                .or_default()
+                .push(edge);
+            adjacency
+                .entry(&edge.target_node_id)
+                .or_default()
+                .push(edge);
+        }
+        for edges in adjacency.values_mut() {
+            edges.sort_by(|a, b| {
+                (&a.source_node_id, &a.edge_type, &a.target_node_id).cmp(&(
+                    &b.source_node_id,
+                    &b.edge_type,
+                    &b.target_node_id,
+                ))
+            });
+        }
+
+        let mut distance: HashMap<&str, usize> = self
+            .context_seeds(query, &terms)
+            .into_iter()
+            .map(|node| (node.id.as_str(), 0))
+            .collect();
+        let mut queue: VecDeque<&str> = distance.keys().copied().collect();
+        while let Some(id) = queue.pop_front() {
+            let next = distance[id] + 1;
+            if next > hops {
+                continue;
+            }
+            for edge in adjacency.get(id).into_iter().flatten() {
+                let other = if edge.source_node_id == id {
+                    edge.target_node_id.as_str()
+                } else {
+                    edge.source_node_id.as_str()
+                };
+                if self.nodes.contains_key(other) && !distance.contains_key(other) {
+                    distance.insert(other, next);
+                    queue.push_back(other);
+                }
+            }
+        }
+
+        let mut ranked: Vec<&Node> = distance
+            .keys()
+            .filter_map(|id| self.nodes.get(*id))
+            .filter(|node| !Self::is_placeholder(node))
+            .collect();
+        ranked.sort_by_cached_key(|node| {
+            let hits = searchable_strings(node)
+                .map(|s| term_hits(s, &terms))
+                .max()
+                .unwrap_or(0);
+            let degree = adjacency.get(node.id.as_str()).map_or(0, Vec::len);
+            (
+                distance[node.id.as_str()],
+                Reverse(hits),
+                Reverse(degree),
+                node.id.clone(),
+            )
+        });
+
+        let mut out = Budget {
+            lines: Vec::new(),
+            chars: 0,
+            max_tokens,
+            truncated: false,
+        };
+        out.push(format!("# Context for \"{}\"", query));
+        if ranked.is_empty() {
+            out.push("\nNothing in the graph matches.".to_string());
+        }
+        let mut included: HashSet<&str> = HashSet::new();
+        let mut entities = Vec::new();
+        let mut listed_observations: HashSet<String> = HashSet::new();
+        let mut listed_relations: HashSet<(&str, &str, &str)> = HashSet::new();
+        for node in ranked {
+            if !out.push(format!("\n## {} ({})", node.id, node.node_type)) {
+                continue;
+            }
+            included.insert(&node.id);
+            entities.push(node.id.clone());
+
+            // Observations mentioning the query first, so they survive a tight budget
+            let mut observations = Self::observations_of(node);
+            observations.sort_by_key(|observation| Reverse(term_hits(observation, &terms)));
+            for observation in observations {
+                if listed_observations.insert(observation.to_lowercase()) {
+                    out.push(format!("- {}", observation));
+                }
+            }
+
+            for edge in adjacency.get(node.id.as_str()).into_iter().flatten() {
+                let (from, to) = (edge.source_node_id.as_str(), edge.target_node_id.as_str());
+                if !included.contains(from) || !included.contains(to) {
+                    continue;
+                }
+                if !listed_relations.insert((from, edge.edge_type.as_str(), to)) {
+                    continue;
+                }
+                if let Some(mirror_type) = self.mirror_type(&edge.edge_type) {
+                    listed_relations.insert((to, mirror_type, from));
+                }
+                out.push(format!("- {} -[{}]-> {}", from, edge.edge_type, to));
+            }
+        }
+
+        ContextPack {
+            query: query.to_string(),
+            estimated_tokens: estimate_tokens(out.chars),
+            markdown: out.lines.join("\n"),
+            entities,
+            truncated: out.truncated,
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{NearDuplicate, NearDuplicatePolicy};
+
+// Near-duplicate check for create_entities. A new name whose edit distance to an existing
+// entity's name (ignoring case, divided by the longer name's length) is at most the threshold
+// is reported with that entity, so agents add observations to it instead of creating a second
+// one; with the reject policy the item is skipped. The graph metadata keys below set the
+// defaults; a create_entities request can override the policy.
+
+// Graph metadata keys: "warn" | "reject" | "ignore", and a distance in [0, 1]
+pub const NEAR_DUPLICATE_POLICY_KEY: &str = "near_duplicate_policy";
+pub const NEAR_DUPLICATE_THRESHOLD_KEY: &str = "near_duplicate_threshold";
+pub const DEFAULT_NEAR_DUPLICATE_THRESHOLD: f64 = 0.2;
+
+// Levenshtein distance of `a` and `b` over the length of the longer one, or None as soon as it
+// must exceed `max`.
+pub fn normalized_distance(a: &[char], b: &[char], max: f64) -> Option<f64> {
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return Some(0.0);
+    }
+    let max_edits = (max * longest as f64).floor() as usize;
+    if a.len().abs_diff(b.len()) > max_edits {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        if current.iter().min().is_some_and(|m| *m > max_edits) {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    let edits = previous[b.len()];
+    (edits <= max_edits).then(|| edits as f64 / longest as f64)
+}
+
+impl KnowledgeGraphState {
+    // The request's policy, else the graph's, else warn.
+    pub fn near_duplicate_policy(
+        &self,
+        requested: Option<NearDuplicatePolicy>,
+    ) -> NearDuplicatePolicy {
+        requested
+            .or_else(|| {
+                self.metadata
+                    .get(NEAR_DUPLICATE_POLICY_KEY)
+                    .and_then(|v| serde_json::from_value(v.clone()).ok())
+            })
+            .unwrap_or(NearDuplicatePolicy::Warn)
+    }
+
+    fn near_duplicate_threshold(&self) -> f64 {
+        self.metadata
+            .get(NEAR_DUPLICATE_THRESHOLD_KEY)
+            .and_then(|v| v.as_f64())
+            .filter(|t| (0.0..=1.0).contains(t))
+            .unwrap_or(DEFAULT_NEAR_DUPLICATE_THRESHOLD)
+    }
+
+    // The existing entity closest to `name` within the threshold (ties go to the first name in
+    // sort order). An entity named exactly `name` is not a near duplicate of it.
+    pub fn nearest_duplicate(&self, name: &str) -> Option<NearDuplicate> {
+        let threshold = self.near_duplicate_threshold();
+        let wanted: Vec<char> = name.to_lowercase().chars().collect();
+        self.nodes
+            .keys()
+            .filter(|existing| existing.as_str() != name)
+            .filter_map(|existing| {
+                let candidate: Vec<char> = existing.to_lowercase().chars().collect();
+                normalized_distance(&wanted, &candidate, threshold).map(|distance| NearDuplicate {
+                    name: existing.clone(),
+                    distance,
+                })
+            })
+            .min_by(|a, b| {
+                a.distance
+                    .total_cmp(&b.distance)
+                    .then_with(|| a.name.cmp(&b.name))
+            })
+    }
+}
@@ -692,7 +692,11 @@ impl DurableObject for KnowledgeGraphDO {
                 graph_state.add_node(node.clone());
                 self.save_graph_state(&graph_state).await?;
                 console_log!("[DO FETCH SUCCESS] POST /nodes - Node created: {}", node_id);
-                Response::from_json(&node)
+                let mut response = Response::from_json(&node)?.with_status(201);
+                response
+                    .headers_mut()
+                    .set("location", &format!("/nodes/{}", node_id))?;
+                Ok(response)
             }
             (Method::Get, ["nodes", node_id]) => match graph_state.get_node(node_id) {
                 Some(node) => Response::from_json(node),
@@ -791,7 +795,11 @@ impl DurableObject for KnowledgeGraphDO {
                 };
                 graph_state.add_edge(edge.clone());
                 self.save_graph_state(&graph_state).await?;
-                Response::from_json(&edge)
+                let mut response = Response::from_json(&edge)?.with_status(201);
+                response
+                    .headers_mut()
+                    .set("location", &format!("/edges/{}", edge_id))?;
+                Ok(response)
             }
             (Method::Get, ["edges", edge_id]) => match graph_state.get_edge(edge_id) {
                 Some(edge) => Response::from_json(edge),
@@ -0,0 +1,181 @@
+use crate::kg::{prune_observation_metadata, KnowledgeGraphState};
+use crate::types::{ErasePayload, ErasureReport, Tombstone};
+use regex::Regex;
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+
+// Right-to-erasure support for POST /graph/erase. A subject is given by entity name (matched
+// case-insensitively as a whole word) or by a regex. Erasing removes entities whose name
+// mentions the subject together with their relations, relations whose data mentions it,
+// and matching observations and data values of the remaining entities. A tombstone keeps the
+// subject's pattern so create_entities, create_relations, add_observations and the import
+// preflight refuse to bring it back.
+
+// Compiled tombstone patterns of a graph, built once per batch.
+pub struct ErasedSubjects(Vec<Regex>);
+
+impl ErasedSubjects {
+    pub fn matches(&self, text: &str) -> bool {
+        self.0.iter().any(|pattern| pattern.is_match(text))
+    }
+}
+
+// The regex a payload erases, and the subject recorded for it.
+fn subject_pattern(payload: &ErasePayload) -> Result<(String, String), String> {
+    match (&payload.name, &payload.pattern) {
+        (Some(name), None) if !name.trim().is_empty() => Ok((
+            name.clone(),
+            format!(r"(?i)(?:^|\W){}(?:$|\W)", regex::escape(name.trim())),
+        )),
+        (None, Some(pattern)) if !pattern.is_empty() => {
+            Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?;
+            Ok((pattern.clone(), pattern.clone()))
+        }
+        _ => Err("give exactly one non-empty 'name' or 'pattern'".to_string()),
+    }
+}
+
+// Removes matching strings from a data value; returns whether anything was removed.
+fn scrub_value(value: &mut JsonValue, pattern: &Regex) -> bool {
+    match value {
+        JsonValue::Array(items) => {
+            let before = items.len();
+            items.retain(|item| !item.as_str().is_some_and(|s| pattern.is_match(s)));
+            let mut scrubbed = items.len() != before;
+            for item in items.iter_mut() {
+                scrubbed |= scrub_value(item, pattern);
+            }
+            scrubbed
+        }
+        JsonValue::Object(map) => {
+            let before = map.len();
+            map.retain(|_, item| !item.as_str().is_some_and(|s| pattern.is_match(s)));
+            let mut scrubbed = map.len() != before;
+            for item in map.values_mut() {
+                scrubbed |= scrub_value(item, pattern);
+            }
+            scrubbed
+        }
+        _ => false,
+    }
+}
+
+fn mentions(value: &JsonValue, pattern: &Regex) -> bool {
+    match value {
+        JsonValue::String(s) => pattern.is_match(s),
+        JsonValue::Array(items) => items.iter().any(|item| mentions(item, pattern)),
+        JsonValue::Object(map) => map.values().any(|item| mentions(item, pattern)),
+        _ => false,
+    }
+}
+
+impl KnowledgeGraphState {
+    pub fn erased_subjects(&self) -> ErasedSubjects {
+        ErasedSubjects(
+            self.tombstones
+                .iter()
+                .filter_map(|tombstone| Regex::new(&tombstone.pattern).ok())
+                .collect(),
+        )
+    }
+
+    // With `dry_run`, reports what would be erased without changing anything.
+    pub fn erase(
+        &mut self,
+        payload: &ErasePayload,
+        current_time_ms: u64,
+    ) -> Result<ErasureReport, String> {
+        let (subject, pattern_source) = subject_pattern(payload)?;
+        let pattern = Regex::new(&pattern_source).map_err(|e| e.to_string())?;
+
+        let mut entities_removed: Vec<String> = self
+            .nodes
+            .keys()
+            .filter(|name| pattern.is_match(name))
+            .cloned()
+            .collect();
+        entities_removed.sort();
+        let mut relations_removed: Vec<String> = self
+            .edges
+            .values()
+            .filter(|edge| {
+                entities_removed.contains(&edge.source_node_id)
+                    || entities_removed.contains(&edge.target_node_id)
+                    || edge
+                        .data
+                        .as_ref()
+                        .is_some_and(|data| mentions(data, &pattern))
+            })
+            .map(|edge| edge.id.clone())
+            .collect();
+        relations_removed.sort();
+
+        let mut observations_removed: BTreeMap<String, usize> = BTreeMap::new();
+        let mut data_scrubbed: Vec<String> = Vec::new();
+        for node in self.nodes.values() {
+            if entities_removed.contains(&node.id) {
+                continue;
+            }
+            let matching = Self::observations_of(node)
+                .iter()
+                .filter(|obs| pattern.is_match(obs))
+                .count();
+            if matching > 0 {
+                observations_removed.insert(node.id.clone(), matching);
+            }
+            let mut data = node.data.clone();
+            if let Some(map) = data.as_object_mut() {
+                map.remove("observations");
+            }
+            if mentions(&data, &pattern) {
+                data_scrubbed.push(node.id.clone());
+            }
+        }
+        data_scrubbed.sort();
+
+        let tombstone = Tombstone {
+            subject,
+            pattern: pattern_source,
+            erased_at_ms: current_time_ms,
+        };
+        if !payload.dry_run {
+            for name in &entities_removed {
+                self.delete_node_and_connected_edges(name);
+            }
+            for edge_id in &relations_removed {
+                self.edges.remove(edge_id);
+            }
+            let touched: Vec<&String> = observations_removed
+                .keys()
+                .chain(data_scrubbed.iter())
+                .collect();
+            for node_id in touched {
+                if let Some(node) = self.nodes.get_mut(node_id) {
+                    // Observations are an array of strings, so this covers them as well
+                    scrub_value(&mut node.data, &pattern);
+                    prune_observation_metadata(node);
+                    node.updated_at_ms = current_time_ms;
+                    node.updated_by = self.actor.clone();
+                    node.version += 1;
+                }
+                self.reindex_node(node_id);
+            }
+            if !self
+                .tombstones
+                .iter()
+                .any(|t| t.pattern == tombstone.pattern)
+            {
+                self.tombstones.push(tombstone.clone());
+            }
+        }
+
+        Ok(ErasureReport {
+            dry_run: payload.dry_run,
+            tombstone,
+            entities_removed,
+            relations_removed,
+            observations_removed,
+            data_scrubbed,
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use crate::types::{BatchResponse, DoErrorBody, DoErrorDetail};
+use serde_json::Value as JsonValue;
+use worker::{Response, Result};
+
+// Error contract between the DO and the worker. Routes answer errors however is convenient
+// (Response::error text, a rejected BatchResponse, a conflict struct); the DO's fetch turns
+// every response with status >= 400 into a DoErrorBody so callers such as the MCP layer can
+// tell "not found" from "invalid request" and see which batch items failed.
+
+pub fn code_for_status(status: u16) -> &'static str {
+    match status {
+        400 => "invalid_request",
+        401 => "unauthorized",
+        403 => "forbidden",
+        404 => "not_found",
+        405 => "method_not_allowed",
+        409 => "conflict",
+        413 => "payload_too_large",
+        429 => "rate_limited",
+        501 => "not_implemented",
+        503 => "unavailable",
+        _ if status < 500 => "client_error",
+        _ => "internal_error",
+    }
+}
+
+fn detail_from_body(status: u16, body: &str) -> DoErrorDetail {
+    let mut detail = DoErrorDetail {
+        code: code_for_status(status).to_string(),
+        message: body.trim().to_string(),
+        failures: Vec::new(),
+        details: None,
+    };
+    match serde_json::from_str::<JsonValue>(body) {
+        Ok(value @ JsonValue::Object(_)) => {
+            if let Ok(batch) = serde_json::from_value::<BatchResponse>(value.clone()) {
+                detail.message = format!(
+                    "{} of {} item(s) failed",
+                    batch.summary.failed, batch.summary.total
+                );
+                detail.failures = batch
+                    .results
+                    .into_iter()
+                    .filter(|r| r.error.is_some())
+                    .collect();
+            } else {
+                if let Some(message) = value.get("error").and_then(JsonValue::as_str) {
+                    detail.message = message.to_string();
+                }
+                detail.details = Some(value);
+            }
+        }
+        _ if detail.message.is_empty() => detail.message = detail.code.replace('_', " "),
+        _ => {}
+    }
+    detail
+}
+
+// DO side: rewrites an error response into the structured body, keeping status and headers.
+pub async fn into_structured(mut response: Response) -> Result<Response> {
+    let status = response.status_code();
+    if status < 400 {
+        return Ok(response);
+    }
+    let body = response.text().await?;
+    let mut headers = response.headers().clone();
+    headers.set("content-type", "application/json")?;
+    let body = DoErrorBody {
+        error: detail_from_body(status, &body),
+    };
+    Ok(Response::from_json(&body)?
+        .with_headers(headers)
+        .with_status(status))
+}
+
+// Worker side: reads the error of a DO response; bodies from before the contract (or from
+// anything in between) are still turned into a detail.
+pub async fn read_error(response: &mut Response) -> Result<DoErrorDetail> {
+    let status = response.status_code();
+    let body = response.text().await?;
+    Ok(match serde_json::from_str::<DoErrorBody>(&body) {
+        Ok(parsed) => parsed.error,
+        Err(_) => detail_from_body(status, &body),
+    })
+}
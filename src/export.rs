@@ -0,0 +1,426 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{
+    ApiEntity, ApiRelation, D3Graph, D3Link, D3Node, KnowledgeGraphDataResponse, TimeRange,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+
+// GET /graph/export?format=... renders a filtered, size-capped slice of the graph for
+// embedding elsewhere. Filters work like POST /graph/subgraph, and `focus` narrows that to the
+// entities within `depth` hops of one entity. The cap keeps the closest entities (then the
+// smallest names) so repeated exports of an unchanged graph are identical.
+//
+// GET /graph/state takes a lighter set of the same parameters (StateOptions) to project the
+// full dump: `entity_types`, `relation_types`, `limit` and `include=entities|relations|both`.
+//
+// Both take `since_ms` and `until_ms` (epoch milliseconds, until exclusive) for incremental
+// backups: entities updated and relations created in that window. A relation in the window
+// is kept even when its ends weren't changed in it, as the previous backup has them.
+
+pub const DEFAULT_EXPORT_NODE_LIMIT: usize = 100;
+pub const MAX_EXPORT_NODE_LIMIT: usize = 1000;
+pub const DEFAULT_FOCUS_DEPTH: usize = 1;
+pub const MAX_FOCUS_DEPTH: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Mermaid,
+    D3,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub entity_types: Option<Vec<String>>,
+    pub relation_types: Option<Vec<String>>,
+    pub limit: usize,
+    pub window: Option<TimeRange>,
+    pub focus: Option<String>,   // Entity whose neighborhood is exported
+    pub depth: usize,            // Hops from `focus`, following relations either way
+    pub direction: &'static str, // Mermaid flowchart direction
+}
+
+// Which halves of the graph GET /graph/state returns; the other one comes back empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphInclude {
+    Entities,
+    Relations,
+    Both,
+}
+
+#[derive(Debug, Clone)]
+pub struct StateOptions {
+    pub entity_types: Option<Vec<String>>,
+    pub relation_types: Option<Vec<String>>,
+    pub limit: Option<usize>,
+    pub include: GraphInclude,
+    pub window: Option<TimeRange>,
+}
+
+// Comma-separated list parameter; empty items are ignored.
+fn list_param(params: &HashMap<String, String>, key: &str) -> Option<Vec<String>> {
+    params.get(key).map(|v| {
+        v.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+// `since_ms` and `until_ms`; None when neither is given.
+fn window_param(params: &HashMap<String, String>) -> Result<Option<TimeRange>, String> {
+    let bound = |key: &str| {
+        params
+            .get(key)
+            .map(|v| {
+                v.parse::<u64>()
+                    .map_err(|_| format!("invalid {} '{}'", key, v))
+            })
+            .transpose()
+    };
+    let window = TimeRange {
+        start: bound("since_ms")?,
+        end: bound("until_ms")?,
+    };
+    if let (Some(start), Some(end)) = (window.start, window.end) {
+        if start > end {
+            return Err("since_ms is after until_ms".to_string());
+        }
+    }
+    Ok((window != TimeRange::default()).then_some(window))
+}
+
+impl ExportOptions {
+    pub fn from_query(params: &HashMap<String, String>) -> Result<Self, String> {
+        let format = match params.get("format").map(String::as_str) {
+            None | Some("json") => ExportFormat::Json,
+            Some("mermaid") => ExportFormat::Mermaid,
+            Some("d3") => ExportFormat::D3,
+            Some(other) => {
+                return Err(format!(
+                    "unknown format '{}' (expected json, mermaid or d3)",
+                    other
+                ))
+            }
+        };
+        let limit = match params.get("limit") {
+            Some(v) => v
+                .parse::<usize>()
+                .map_err(|_| format!("invalid limit '{}'", v))?
+                .clamp(1, MAX_EXPORT_NODE_LIMIT),
+            None => DEFAULT_EXPORT_NODE_LIMIT,
+        };
+        let depth = match params.get("depth") {
+            Some(v) => v
+                .parse::<usize>()
+                .map_err(|_| format!("invalid depth '{}'", v))?
+                .min(MAX_FOCUS_DEPTH),
+            None => DEFAULT_FOCUS_DEPTH,
+        };
+        let direction = match params.get("direction").map(String::as_str) {
+            None | Some("TD") => "TD",
+            Some("LR") => "LR",
+            Some(other) => {
+                return Err(format!("unknown direction '{}' (expected TD or LR)", other))
+            }
+        };
+        Ok(ExportOptions {
+            format,
+            entity_types: list_param(params, "entity_types"),
+            relation_types: list_param(params, "relation_types"),
+            limit,
+            window: window_param(params)?,
+            focus: params.get("focus").cloned(),
+            depth,
+            direction,
+        })
+    }
+}
+
+impl StateOptions {
+    pub fn from_query(params: &HashMap<String, String>) -> Result<Self, String> {
+        let limit = match params.get("limit") {
+            Some(v) => Some(
+                v.parse::<usize>()
+                    .map_err(|_| format!("invalid limit '{}'", v))?,
+            ),
+            None => None,
+        };
+        let include = match params.get("include").map(String::as_str) {
+            None | Some("both") => GraphInclude::Both,
+            Some("entities") => GraphInclude::Entities,
+            Some("relations") => GraphInclude::Relations,
+            Some(other) => {
+                return Err(format!(
+                    "unknown include '{}' (expected entities, relations or both)",
+                    other
+                ))
+            }
+        };
+        Ok(StateOptions {
+            entity_types: list_param(params, "entity_types"),
+            relation_types: list_param(params, "relation_types"),
+            limit,
+            include,
+            window: window_param(params)?,
+        })
+    }
+}
+
+// The exported slice of the graph, plus how many entities matched before the cap.
+pub struct ExportSlice {
+    pub entities: Vec<ApiEntity>,
+    pub relations: Vec<ApiRelation>,
+    pub total_entities: usize,
+}
+
+// Hop distance from `focus` to every entity reachable within `depth`, treating relations as
+// undirected.
+fn neighborhood(focus: &str, relations: &[ApiRelation], depth: usize) -> HashMap<String, usize> {
+    let mut adjacent: HashMap<&str, Vec<&str>> = HashMap::new();
+    for r in relations {
+        adjacent.entry(&r.from).or_default().push(&r.to);
+        adjacent.entry(&r.to).or_default().push(&r.from);
+    }
+    let mut distances = HashMap::from([(focus.to_string(), 0)]);
+    let mut queue = VecDeque::from([(focus, 0)]);
+    while let Some((name, distance)) = queue.pop_front() {
+        if distance == depth {
+            continue;
+        }
+        for next in adjacent.get(name).into_iter().flatten() {
+            if !distances.contains_key(*next) {
+                distances.insert(next.to_string(), distance + 1);
+                queue.push_back((next, distance + 1));
+            }
+        }
+    }
+    distances
+}
+
+impl KnowledgeGraphState {
+    // subgraph() narrowed to `window`: entities updated in it, and relations created in it
+    // between entities of the selected types.
+    fn windowed_subgraph(
+        &self,
+        entity_types: Option<&[String]>,
+        relation_types: Option<&[String]>,
+        window: Option<&TimeRange>,
+    ) -> (Vec<ApiEntity>, Vec<ApiRelation>) {
+        let Some(window) = window else {
+            return self.subgraph(entity_types, relation_types);
+        };
+        let is_kept = |id: &String| {
+            self.nodes
+                .get(id)
+                .is_some_and(|n| entity_types.is_none_or(|types| types.contains(&n.node_type)))
+        };
+        let entities = self
+            .nodes
+            .values()
+            .filter(|n| is_kept(&n.id) && window.contains(n.updated_at_ms))
+            .map(|n| self.node_to_api_entity(n))
+            .collect();
+        let relations = self
+            .edges
+            .values()
+            .filter(|e| relation_types.is_none_or(|types| types.contains(&e.edge_type)))
+            .filter(|e| window.contains(e.created_at_ms))
+            .filter(|e| is_kept(&e.source_node_id) && is_kept(&e.target_node_id))
+            .map(|e| self.edge_to_api_relation(e))
+            .collect();
+        (entities, relations)
+    }
+
+    pub fn export_slice(&self, options: &ExportOptions) -> Result<ExportSlice, String> {
+        let (mut entities, relations) = self.windowed_subgraph(
+            options.entity_types.as_deref(),
+            options.relation_types.as_deref(),
+            options.window.as_ref(),
+        );
+        let distances = match &options.focus {
+            Some(focus) => {
+                if !entities.iter().any(|e| &e.name == focus) {
+                    return Err(format!("focus entity '{}' not found", focus));
+                }
+                let distances = neighborhood(focus, &relations, options.depth);
+                entities.retain(|e| distances.contains_key(&e.name));
+                distances
+            }
+            None => HashMap::new(),
+        };
+        let total_entities = entities.len();
+        entities.sort_by(|a, b| {
+            (distances.get(&a.name), &a.name).cmp(&(distances.get(&b.name), &b.name))
+        });
+        entities.truncate(options.limit);
+
+        let kept: HashSet<&str> = entities.iter().map(|e| e.name.as_str()).collect();
+        let mut relations: Vec<ApiRelation> = relations
+            .into_iter()
+            .filter(|r| kept.contains(r.from.as_str()) && kept.contains(r.to.as_str()))
+            .collect();
+        relations.sort_by(|a, b| {
+            (&a.from, &a.to, &a.relation_type).cmp(&(&b.from, &b.to, &b.relation_type))
+        });
+        Ok(ExportSlice {
+            entities,
+            relations,
+            total_entities,
+        })
+    }
+}
+
+impl KnowledgeGraphState {
+    // The full dump narrowed by `options`. Relations are kept when both ends are among the
+    // selected entities; with a limit, the entities with the smallest names are selected.
+    // With a time window, relations only lose an end the limit cut off.
+    pub fn state_view(&self, options: &StateOptions) -> (Vec<ApiEntity>, Vec<ApiRelation>) {
+        if options.entity_types.is_none()
+            && options.relation_types.is_none()
+            && options.limit.is_none()
+            && options.window.is_none()
+        {
+            let (entities, relations) = self.get_full_graph_data();
+            return match options.include {
+                GraphInclude::Both => (entities, relations),
+                GraphInclude::Entities => (entities, Vec::new()),
+                GraphInclude::Relations => (Vec::new(), relations),
+            };
+        }
+        let (mut entities, relations) = self.windowed_subgraph(
+            options.entity_types.as_deref(),
+            options.relation_types.as_deref(),
+            options.window.as_ref(),
+        );
+        let mut relations = match options.limit {
+            Some(limit) if limit < entities.len() && options.window.is_some() => {
+                entities.sort_by(|a, b| a.name.cmp(&b.name));
+                let cut: HashSet<String> = entities.drain(limit..).map(|e| e.name).collect();
+                relations
+                    .into_iter()
+                    .filter(|r| !cut.contains(&r.from) && !cut.contains(&r.to))
+                    .collect()
+            }
+            Some(limit) if limit < entities.len() => {
+                entities.sort_by(|a, b| a.name.cmp(&b.name));
+                entities.truncate(limit);
+                let kept: HashSet<&str> = entities.iter().map(|e| e.name.as_str()).collect();
+                relations
+                    .into_iter()
+                    .filter(|r| kept.contains(r.from.as_str()) && kept.contains(r.to.as_str()))
+                    .collect()
+            }
+            _ => relations,
+        };
+        match options.include {
+            GraphInclude::Both => {}
+            GraphInclude::Entities => relations.clear(),
+            GraphInclude::Relations => entities.clear(),
+        }
+        (entities, relations)
+    }
+}
+
+impl ExportSlice {
+    pub fn into_json(self) -> KnowledgeGraphDataResponse {
+        KnowledgeGraphDataResponse {
+            entities: self.entities,
+            relations: self.relations,
+        }
+    }
+
+    // Force-graph JSON: entity types become numbered groups (listed in `groups`), and the
+    // relations between an ordered pair of entities collapse into one link weighted by count.
+    pub fn to_d3(&self) -> D3Graph {
+        let groups: Vec<String> = self
+            .entities
+            .iter()
+            .map(|e| e.entity_type.clone())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let nodes = self
+            .entities
+            .iter()
+            .map(|e| D3Node {
+                id: e.name.clone(),
+                group: groups.binary_search(&e.entity_type).unwrap_or_default(),
+                entity_type: e.entity_type.clone(),
+            })
+            .collect();
+        let mut weights: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        for r in &self.relations {
+            *weights.entry((&r.from, &r.to)).or_default() += 1;
+        }
+        let links = weights
+            .into_iter()
+            .map(|((source, target), value)| D3Link {
+                source: source.to_string(),
+                target: target.to_string(),
+                value,
+            })
+            .collect();
+        D3Graph {
+            nodes,
+            links,
+            groups,
+        }
+    }
+
+    // A Mermaid flowchart. Entity names can contain anything, so nodes get generated ids
+    // and names only appear in escaped labels.
+    pub fn to_mermaid(&self, direction: &str) -> String {
+        let mut lines = vec![format!("graph {}", direction)];
+        if self.entities.len() < self.total_entities {
+            lines.push(format!(
+                "    %% Showing {} of {} entities",
+                self.entities.len(),
+                self.total_entities
+            ));
+        }
+        let mut node_ids: HashMap<&str, String> = HashMap::new();
+        for (index, entity) in self.entities.iter().enumerate() {
+            let node_id = format!("n{}", index);
+            lines.push(format!(
+                "    {}[\"{}<br/><small>{}</small>\"]",
+                node_id,
+                mermaid_escape(&entity.name),
+                mermaid_escape(&entity.entity_type)
+            ));
+            node_ids.insert(&entity.name, node_id);
+        }
+        for relation in &self.relations {
+            if let (Some(from), Some(to)) = (
+                node_ids.get(relation.from.as_str()),
+                node_ids.get(relation.to.as_str()),
+            ) {
+                lines.push(format!(
+                    "    {} -->|\"{}\"| {}",
+                    from,
+                    mermaid_escape(&relation.relation_type),
+                    to
+                ));
+            }
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+// Mermaid labels take HTML entities; quotes, pipes and angle brackets would end or break them.
+fn mermaid_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("#quot;"),
+            '|' => escaped.push_str("#124;"),
+            '<' => escaped.push_str("#lt;"),
+            '>' => escaped.push_str("#gt;"),
+            '#' => escaped.push_str("#35;"),
+            '\n' | '\r' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
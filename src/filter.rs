@@ -0,0 +1,153 @@
+// Filter expressions over a node's JSON data, e.g. `data.status == "draft" && data.priority > 3`.
+//
+// Grammar: clauses joined by `&&`; each clause is `<path> <op> <literal>` where the path starts
+// with `data` and walks object keys (or array indices) separated by dots, `op` is one of
+// `== != > >= < <=`, and the literal is a quoted string, number, `true`, `false` or `null`.
+use serde_json::Value as JsonValue;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+#[derive(Debug, Clone)]
+struct Clause {
+    path: Vec<String>, // Segments after the leading `data`
+    op: CompareOp,
+    value: JsonValue,
+}
+
+#[derive(Debug, Clone)]
+pub struct DataFilter {
+    clauses: Vec<Clause>,
+}
+
+// Splits on `&&` outside of quoted strings.
+fn split_clauses(expr: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start = 0;
+    let mut chars = expr.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '&') if matches!(chars.peek(), Some((_, '&'))) => {
+                parts.push(&expr[start..i]);
+                chars.next();
+                start = i + 2;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&expr[start..]);
+    parts
+}
+
+fn parse_literal(raw: &str) -> Result<JsonValue, String> {
+    let raw = raw.trim();
+    if raw.len() >= 2 && raw.starts_with('\'') && raw.ends_with('\'') {
+        return Ok(JsonValue::String(raw[1..raw.len() - 1].to_string()));
+    }
+    // Double-quoted strings, numbers, booleans and null are valid JSON literals
+    serde_json::from_str::<JsonValue>(raw)
+        .ok()
+        .filter(|v| !v.is_object() && !v.is_array())
+        .ok_or_else(|| format!("Invalid literal '{}'", raw))
+}
+
+fn parse_clause(raw: &str) -> Result<Clause, String> {
+    // Two-character operators first so `>=` isn't read as `>`
+    const OPS: [(&str, CompareOp); 6] = [
+        ("==", CompareOp::Eq),
+        ("!=", CompareOp::Ne),
+        (">=", CompareOp::Ge),
+        ("<=", CompareOp::Le),
+        (">", CompareOp::Gt),
+        ("<", CompareOp::Lt),
+    ];
+    let (pos, op_str, op) = OPS
+        .iter()
+        .filter_map(|(s, op)| raw.find(s).map(|pos| (pos, *s, *op)))
+        .min_by_key(|(pos, s, _)| (*pos, std::cmp::Reverse(s.len())))
+        .ok_or_else(|| format!("Missing comparison operator in '{}'", raw.trim()))?;
+
+    let path_str = raw[..pos].trim();
+    let mut segments = path_str.split('.');
+    if segments.next() != Some("data") {
+        return Err(format!("Path '{}' must start with 'data'", path_str));
+    }
+    let path: Vec<String> = segments.map(str::to_string).collect();
+    if path.iter().any(|s| s.is_empty()) {
+        return Err(format!("Invalid path '{}'", path_str));
+    }
+
+    Ok(Clause {
+        path,
+        op,
+        value: parse_literal(&raw[pos + op_str.len()..])?,
+    })
+}
+
+fn resolve<'a>(data: &'a JsonValue, path: &[String]) -> Option<&'a JsonValue> {
+    path.iter()
+        .try_fold(data, |current, segment| match current {
+            JsonValue::Object(map) => map.get(segment),
+            JsonValue::Array(arr) => segment.parse::<usize>().ok().and_then(|i| arr.get(i)),
+            _ => None,
+        })
+}
+
+fn values_equal(a: &JsonValue, b: &JsonValue) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y, // 3 == 3.0
+        _ => a == b,
+    }
+}
+
+fn compare(a: &JsonValue, b: &JsonValue) -> Option<std::cmp::Ordering> {
+    match (a, b) {
+        (JsonValue::Number(x), JsonValue::Number(y)) => x.as_f64()?.partial_cmp(&y.as_f64()?),
+        (JsonValue::String(x), JsonValue::String(y)) => Some(x.cmp(y)),
+        _ => None,
+    }
+}
+
+impl Clause {
+    fn matches(&self, data: &JsonValue) -> bool {
+        let actual = resolve(data, &self.path).unwrap_or(&JsonValue::Null);
+        match self.op {
+            CompareOp::Eq => values_equal(actual, &self.value),
+            CompareOp::Ne => !values_equal(actual, &self.value),
+            CompareOp::Gt => compare(actual, &self.value).is_some_and(|o| o.is_gt()),
+            CompareOp::Ge => compare(actual, &self.value).is_some_and(|o| o.is_ge()),
+            CompareOp::Lt => compare(actual, &self.value).is_some_and(|o| o.is_lt()),
+            CompareOp::Le => compare(actual, &self.value).is_some_and(|o| o.is_le()),
+        }
+    }
+}
+
+impl DataFilter {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let clauses = split_clauses(expr)
+            .into_iter()
+            .map(parse_clause)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(DataFilter { clauses })
+    }
+
+    // A missing path compares as `null`, so `data.archived != true` also matches nodes
+    // that have no `archived` field.
+    pub fn matches(&self, data: &JsonValue) -> bool {
+        self.clauses.iter().all(|clause| clause.matches(data))
+    }
+}
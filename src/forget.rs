@@ -0,0 +1,212 @@
+use crate::auth::Scope;
+use crate::kg::KnowledgeGraphState;
+use crate::search_index::searchable_strings;
+use crate::types::{
+    ConfirmationToken, DeleteObservationItem, ForgetPayload, ForgetPlan, ForgetResponse,
+    ForgetScope, Node, PendingForget, TimeRange,
+};
+use regex::Regex;
+use uuid::Uuid;
+
+// POST /graph/forget purges a topic without naming every entity. The filter is a query
+// (case-insensitive substring) and/or a regex pattern, entity types and a time window; with
+// scope "observations" (the default) the matching observations are deleted, with "entities"
+// the matching entities and their relations. Like clear_graph it takes two calls: the first
+// returns what would be forgotten and a token, and repeating the same request with the token
+// deletes it. The plan is worked out again then, so writes made in between count. Pinned
+// entities are left alone unless `force` is set. Unlike erasure, nothing keeps the topic from
+// being written again. The route and the MCP tool need the write scope, and forgetting whole
+// entities the admin scope (see required_scope).
+
+// How long a forget confirmation token stays valid
+const FORGET_TOKEN_TTL_MS: u64 = 5 * 60 * 1000;
+
+// Scope needed for a forget request; the body decides, so it's checked on top of the route's.
+pub fn required_scope(payload: &ForgetPayload) -> Scope {
+    match payload.scope {
+        ForgetScope::Observations => Scope::Write,
+        ForgetScope::Entities => Scope::Admin,
+    }
+}
+
+struct ForgetFilter<'a> {
+    query: Option<String>, // Lowercased
+    pattern: Option<Regex>,
+    entity_types: &'a [String],
+    window: Option<TimeRange>,
+    force: bool,
+}
+
+impl ForgetFilter<'_> {
+    fn from_payload(payload: &ForgetPayload) -> Result<ForgetFilter<'_>, String> {
+        let query = payload
+            .query
+            .as_deref()
+            .map(str::trim)
+            .filter(|query| !query.is_empty())
+            .map(str::to_lowercase);
+        let pattern = match payload.pattern.as_deref().filter(|p| !p.is_empty()) {
+            Some(pattern) => {
+                Some(Regex::new(pattern).map_err(|e| format!("invalid pattern: {}", e))?)
+            }
+            None => None,
+        };
+        let window = TimeRange {
+            start: payload.since_ms,
+            end: payload.until_ms,
+        };
+        if let (Some(start), Some(end)) = (window.start, window.end) {
+            if start > end {
+                return Err("since_ms is after until_ms".to_string());
+            }
+        }
+        let window = (window != TimeRange::default()).then_some(window);
+        if query.is_none()
+            && pattern.is_none()
+            && payload.entity_types.is_empty()
+            && window.is_none()
+        {
+            return Err(
+                "give at least one of query, pattern, entity_types, since_ms or until_ms"
+                    .to_string(),
+            );
+        }
+        Ok(ForgetFilter {
+            query,
+            pattern,
+            entity_types: &payload.entity_types,
+            window,
+            force: payload.force,
+        })
+    }
+
+    fn has_text(&self) -> bool {
+        self.query.is_some() || self.pattern.is_some()
+    }
+
+    fn text_matches(&self, text: &str) -> bool {
+        self.query
+            .as_ref()
+            .is_none_or(|query| text.to_lowercase().contains(query))
+            && self.pattern.as_ref().is_none_or(|p| p.is_match(text))
+    }
+
+    fn in_window(&self, at_ms: u64) -> bool {
+        self.window.is_none_or(|window| window.contains(at_ms))
+    }
+
+    fn covers(&self, node: &Node) -> bool {
+        (self.force || !node.pinned)
+            && (self.entity_types.is_empty() || self.entity_types.contains(&node.node_type))
+    }
+}
+
+impl KnowledgeGraphState {
+    fn forget_plan(&self, filter: &ForgetFilter, scope: ForgetScope) -> ForgetPlan {
+        let mut plan = ForgetPlan::default();
+        let mut nodes: Vec<&Node> = self.nodes.values().filter(|n| filter.covers(n)).collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+        match scope {
+            ForgetScope::Entities => {
+                for node in nodes {
+                    let text_match = !filter.has_text()
+                        || searchable_strings(node).any(|s| filter.text_matches(s));
+                    if text_match && filter.in_window(node.updated_at_ms) {
+                        plan.entities.push(node.id.clone());
+                    }
+                }
+                plan.relations = self
+                    .edges
+                    .values()
+                    .filter(|edge| {
+                        plan.entities.contains(&edge.source_node_id)
+                            || plan.entities.contains(&edge.target_node_id)
+                    })
+                    .count();
+            }
+            ForgetScope::Observations => {
+                for node in nodes {
+                    let observations: Vec<String> = Self::observations_of(node)
+                        .into_iter()
+                        .filter(|observation| filter.text_matches(observation))
+                        .filter(|observation| {
+                            // Observations stored before their timestamps were kept count
+                            // from the entity's creation
+                            let added_ms = node
+                                .observation_added_ms
+                                .get(observation)
+                                .copied()
+                                .unwrap_or(node.created_at_ms);
+                            filter.in_window(added_ms)
+                        })
+                        .collect();
+                    if !observations.is_empty() {
+                        plan.observations.insert(node.id.clone(), observations);
+                    }
+                }
+            }
+        }
+        plan
+    }
+
+    // Without a token: the plan and a token for this exact request. With the current
+    // unexpired token and the same request: deletes what the plan names.
+    pub fn forget(
+        &mut self,
+        mut payload: ForgetPayload,
+        now_ms: u64,
+    ) -> Result<ForgetResponse, String> {
+        let confirm_token = payload.confirm_token.take();
+        let filter = ForgetFilter::from_payload(&payload)?;
+        let plan = self.forget_plan(&filter, payload.scope);
+
+        let Some(token) = confirm_token else {
+            let confirmation = ConfirmationToken {
+                token: Uuid::new_v4().to_string(),
+                expires_at_ms: now_ms + FORGET_TOKEN_TTL_MS,
+            };
+            self.pending_forget = Some(PendingForget {
+                confirmation: confirmation.clone(),
+                request: payload,
+            });
+            return Ok(ForgetResponse::ConfirmationRequired {
+                confirm_token: confirmation.token,
+                expires_at_ms: confirmation.expires_at_ms,
+                plan,
+            });
+        };
+
+        match self.pending_forget.take() {
+            Some(pending)
+                if pending.confirmation.token == token
+                    && pending.confirmation.expires_at_ms >= now_ms =>
+            {
+                if pending.request != payload {
+                    self.pending_forget = Some(pending);
+                    return Err(
+                        "The confirmation token was issued for a different forget request"
+                            .to_string(),
+                    );
+                }
+                self.delete_entities_batch(plan.entities.clone());
+                let deletions = plan
+                    .observations
+                    .iter()
+                    .map(|(name, observations)| DeleteObservationItem {
+                        entity_name: name.clone(),
+                        observations: observations.clone(),
+                        expected_version: None,
+                    })
+                    .collect();
+                self.delete_observations_batch(deletions);
+                Ok(ForgetResponse::Forgotten { plan })
+            }
+            Some(pending) if pending.confirmation.expires_at_ms >= now_ms => {
+                // Keep the outstanding token usable after a mistyped one
+                self.pending_forget = Some(pending);
+                Err("Invalid confirmation token".to_string())
+            }
+            _ => Err("Confirmation token expired or not issued; request a new one".to_string()),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::auth::{self, Scope};
+use crate::types::IdempotentResponse;
+use crate::API_V1_PREFIX;
+use worker::{Method, Request, Response, Result};
+
+// Idempotency-Key support in the DO, which is what makes retrying a write safe (see circuit.rs).
+// The first write with a key stores its response under the key; a repeat of it, e.g. a retry
+// after the first attempt timed out once the DO had applied it, gets the stored response back
+// instead of running again. Keys belong to the caller that sent them and to one route: reusing
+// a key for another request is refused with 422. While the first write is still running its
+// key is marked in progress and repeats get 409. Only successful responses are kept, so a
+// failed write can be retried for real. Entries expire after IDEMPOTENCY_TTL_MS (markers after
+// IN_PROGRESS_TTL_MS, in case the write never finished) and are purged by maintenance.
+
+// Storage key prefix of the stored responses
+pub const IDEMPOTENCY_KEY_PREFIX: &str = "idempotency:";
+pub const IDEMPOTENCY_TTL_MS: u64 = 24 * 60 * 60 * 1000;
+const IN_PROGRESS_TTL_MS: u64 = 60 * 1000;
+// Set on a response replayed from storage
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+// The storage key and the request line of a write carrying an Idempotency-Key; None for
+// reads and for requests without a key.
+pub fn key_for(req: &Request) -> Result<Option<(String, String)>> {
+    let Some(key) = req
+        .headers()
+        .get(crate::circuit::IDEMPOTENCY_KEY_HEADER)?
+        .filter(|key| !key.trim().is_empty())
+    else {
+        return Ok(None);
+    };
+    let path = req.path();
+    let route = path.strip_prefix(API_V1_PREFIX).unwrap_or(&path);
+    let method = req.method();
+    if method == Method::Get
+        || auth::scope_for_do_route(&method, &auth::decoded_do_route(route)) == Scope::Read
+    {
+        return Ok(None);
+    }
+    let actor = req.headers().get(auth::ACTOR_HEADER)?.unwrap_or_default();
+    Ok(Some((
+        format!("{}{}:{}", IDEMPOTENCY_KEY_PREFIX, actor, key.trim()),
+        format!("{} {}", method.as_ref(), route),
+    )))
+}
+
+impl IdempotentResponse {
+    // Stored before the write runs; status 0 marks it as in progress.
+    pub fn in_progress(request: String, now_ms: u64) -> Self {
+        IdempotentResponse {
+            request,
+            status: 0,
+            headers: Vec::new(),
+            body: String::new(),
+            stored_at_ms: now_ms,
+        }
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        self.status == 0
+    }
+
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        let ttl_ms = if self.is_in_progress() {
+            IN_PROGRESS_TTL_MS
+        } else {
+            IDEMPOTENCY_TTL_MS
+        };
+        self.stored_at_ms + ttl_ms < now_ms
+    }
+
+    // What a repeat of the write is answered with.
+    pub fn replay(&self, request: &str) -> Result<Response> {
+        if self.request != request {
+            Response::error(
+                "Idempotency-Key was already used for a different request",
+                422,
+            )
+        } else if self.is_in_progress() {
+            Response::error(
+                "A request with this Idempotency-Key is still in progress",
+                409,
+            )
+        } else {
+            self.to_response(true)
+        }
+    }
+
+    // Reads a successful response to store it; hands back an equivalent one to send.
+    pub async fn capture(
+        request: String,
+        mut response: Response,
+        now_ms: u64,
+    ) -> Result<(IdempotentResponse, Response)> {
+        let status = response.status_code();
+        let headers: Vec<(String, String)> = response.headers().entries().collect();
+        let body = response.text().await?;
+        let stored = IdempotentResponse {
+            request,
+            status,
+            headers,
+            body,
+            stored_at_ms: now_ms,
+        };
+        let response = stored.to_response(false)?;
+        Ok((stored, response))
+    }
+
+    fn to_response(&self, replayed: bool) -> Result<Response> {
+        let mut response = Response::ok(self.body.clone())?.with_status(self.status);
+        let headers = response.headers_mut();
+        for (name, value) in &self.headers {
+            headers.set(name, value)?;
+        }
+        if replayed {
+            headers.set(IDEMPOTENT_REPLAYED_HEADER, "true")?;
+        }
+        Ok(response)
+    }
+}
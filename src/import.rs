@@ -0,0 +1,458 @@
+use crate::kg::KnowledgeGraphState;
+use crate::search_index::aliases;
+use crate::types::{
+    AddObservationItem, BatchResult, BatchStatus, EntityToCreate, ImportIssue, ImportIssueKind,
+    ImportValidationReport, NearDuplicatePolicy,
+};
+use serde_json::Value as JsonValue;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+// Preflight checks for a graph import, run by POST /graph/import/validate without writing
+// anything. The payload has the shape GET /graph/state exports ({entities, relations}), so
+// an export from one graph can be vetted before it is replayed into another through
+// create_entities and create_relations.
+//
+// create_entities with `merge_existing` is the import side of that: an incoming entity that
+// matches an existing one (same name; a name or alias equal ignoring case; or a near-duplicate
+// name of the same type, see dedup.rs) is merged into it instead of duplicated. Its new
+// observations and the data keys the existing entity lacks are added, and a different name
+// becomes an alias. The response maps each requested name to the entity it resolved to, so
+// the relations imported next can be rewritten to point at it.
+
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+pub const MAX_IMPORT_ENTITIES: usize = 10_000;
+pub const MAX_IMPORT_RELATIONS: usize = 50_000;
+// Beyond this many errors/warnings each, the report only keeps counting
+const MAX_REPORTED_ISSUES: usize = 500;
+
+#[derive(Default)]
+struct IssueCollector {
+    errors: Vec<ImportIssue>,
+    warnings: Vec<ImportIssue>,
+    error_count: usize,
+    warning_count: usize,
+}
+
+impl IssueCollector {
+    fn error(&mut self, kind: ImportIssueKind, path: String, message: String) {
+        self.error_count += 1;
+        if self.errors.len() < MAX_REPORTED_ISSUES {
+            self.errors.push(ImportIssue {
+                kind,
+                path,
+                message,
+            });
+        }
+    }
+
+    fn warning(&mut self, kind: ImportIssueKind, path: String, message: String) {
+        self.warning_count += 1;
+        if self.warnings.len() < MAX_REPORTED_ISSUES {
+            self.warnings.push(ImportIssue {
+                kind,
+                path,
+                message,
+            });
+        }
+    }
+
+    // Returns the field as a non-empty string, recording a schema error otherwise.
+    fn required_string<'a>(
+        &mut self,
+        item: &'a JsonValue,
+        field: &str,
+        path: &str,
+    ) -> Option<&'a str> {
+        match item.get(field) {
+            Some(JsonValue::String(s)) if !s.trim().is_empty() => Some(s),
+            Some(JsonValue::String(_)) => {
+                self.error(
+                    ImportIssueKind::Schema,
+                    format!("{}.{}", path, field),
+                    format!("'{}' must not be empty", field),
+                );
+                None
+            }
+            Some(_) => {
+                self.error(
+                    ImportIssueKind::Schema,
+                    format!("{}.{}", path, field),
+                    format!("'{}' must be a string", field),
+                );
+                None
+            }
+            None => {
+                self.error(
+                    ImportIssueKind::Schema,
+                    path.to_string(),
+                    format!("missing required field '{}'", field),
+                );
+                None
+            }
+        }
+    }
+}
+
+// The entities or relations array of the payload; a missing list counts as empty.
+fn payload_list<'a>(
+    payload: &'a JsonValue,
+    field: &str,
+    issues: &mut IssueCollector,
+) -> &'a [JsonValue] {
+    match payload.get(field) {
+        None | Some(JsonValue::Null) => &[],
+        Some(JsonValue::Array(items)) => items,
+        Some(_) => {
+            issues.error(
+                ImportIssueKind::Schema,
+                field.to_string(),
+                format!("'{}' must be an array", field),
+            );
+            &[]
+        }
+    }
+}
+
+impl KnowledgeGraphState {
+    pub fn validate_import(&self, body: &str) -> ImportValidationReport {
+        let mut issues = IssueCollector::default();
+        if body.len() > MAX_IMPORT_BYTES {
+            issues.error(
+                ImportIssueKind::SizeLimit,
+                String::new(),
+                format!(
+                    "payload is {} bytes, over the limit of {} bytes",
+                    body.len(),
+                    MAX_IMPORT_BYTES
+                ),
+            );
+        }
+        let payload: JsonValue = match serde_json::from_str(body) {
+            Ok(payload @ JsonValue::Object(_)) => payload,
+            Ok(_) => {
+                issues.error(
+                    ImportIssueKind::Schema,
+                    String::new(),
+                    "payload must be an object with 'entities' and 'relations' arrays".to_string(),
+                );
+                JsonValue::Null
+            }
+            Err(e) => {
+                issues.error(
+                    ImportIssueKind::Schema,
+                    String::new(),
+                    format!("payload is not valid JSON: {}", e),
+                );
+                JsonValue::Null
+            }
+        };
+
+        let entities = payload_list(&payload, "entities", &mut issues);
+        let relations = payload_list(&payload, "relations", &mut issues);
+        if entities.len() > MAX_IMPORT_ENTITIES {
+            issues.error(
+                ImportIssueKind::SizeLimit,
+                "entities".to_string(),
+                format!(
+                    "{} entities, over the limit of {}",
+                    entities.len(),
+                    MAX_IMPORT_ENTITIES
+                ),
+            );
+        }
+        if relations.len() > MAX_IMPORT_RELATIONS {
+            issues.error(
+                ImportIssueKind::SizeLimit,
+                "relations".to_string(),
+                format!(
+                    "{} relations, over the limit of {}",
+                    relations.len(),
+                    MAX_IMPORT_RELATIONS
+                ),
+            );
+        }
+
+        let erased = self.erased_subjects();
+        // Name -> index of its first occurrence in the payload
+        let mut names: HashMap<&str, usize> = HashMap::new();
+        for (index, entity) in entities.iter().enumerate() {
+            let path = format!("entities[{}]", index);
+            if !entity.is_object() {
+                issues.error(
+                    ImportIssueKind::Schema,
+                    path,
+                    "entity must be an object".to_string(),
+                );
+                continue;
+            }
+            let name = issues.required_string(entity, "name", &path);
+            issues.required_string(entity, "entityType", &path);
+            match entity.get("observations") {
+                None => {}
+                Some(JsonValue::Array(observations)) => {
+                    for (obs_index, obs) in observations.iter().enumerate() {
+                        match obs.as_str() {
+                            None => issues.error(
+                                ImportIssueKind::Schema,
+                                format!("{}.observations[{}]", path, obs_index),
+                                "observation must be a string".to_string(),
+                            ),
+                            Some(obs) if erased.matches(obs) => issues.warning(
+                                ImportIssueKind::Erased,
+                                format!("{}.observations[{}]", path, obs_index),
+                                "observation mentions an erased subject and would be dropped"
+                                    .to_string(),
+                            ),
+                            Some(_) => {}
+                        }
+                    }
+                }
+                Some(_) => issues.error(
+                    ImportIssueKind::Schema,
+                    format!("{}.observations", path),
+                    "'observations' must be an array of strings".to_string(),
+                ),
+            }
+            // create_entities replaces non-object data with an empty object
+            if entity
+                .get("data")
+                .is_some_and(|data| !data.is_null() && !data.is_object())
+            {
+                issues.warning(
+                    ImportIssueKind::Schema,
+                    format!("{}.data", path),
+                    "'data' is not an object and would be discarded".to_string(),
+                );
+            }
+
+            let Some(name) = name else { continue };
+            if erased.matches(name) {
+                issues.error(
+                    ImportIssueKind::Erased,
+                    format!("{}.name", path),
+                    format!("entity '{}' matches an erased subject", name),
+                );
+            }
+            if let Some(first) = names.get(name) {
+                issues.error(
+                    ImportIssueKind::DuplicateName,
+                    path,
+                    format!(
+                        "entity '{}' is already defined at entities[{}]",
+                        name, first
+                    ),
+                );
+                continue;
+            }
+            names.insert(name, index);
+            // Placeholders are filled in by create_entities instead of clashing
+            if self
+                .nodes
+                .get(name)
+                .is_some_and(|node| !Self::is_placeholder(node))
+            {
+                issues.warning(
+                    ImportIssueKind::AlreadyExists,
+                    path,
+                    format!(
+                        "entity '{}' already exists in the graph and would be skipped",
+                        name
+                    ),
+                );
+            }
+        }
+
+        let mut relation_tuples: HashSet<(&str, &str, &str)> = HashSet::new();
+        for (index, relation) in relations.iter().enumerate() {
+            let path = format!("relations[{}]", index);
+            if !relation.is_object() {
+                issues.error(
+                    ImportIssueKind::Schema,
+                    path,
+                    "relation must be an object".to_string(),
+                );
+                continue;
+            }
+            let from = issues.required_string(relation, "from", &path);
+            let to = issues.required_string(relation, "to", &path);
+            let relation_type = issues.required_string(relation, "relationType", &path);
+            for (field, endpoint) in [("from", from), ("to", to)] {
+                let Some(endpoint) = endpoint else { continue };
+                if erased.matches(endpoint) {
+                    issues.error(
+                        ImportIssueKind::Erased,
+                        format!("{}.{}", path, field),
+                        format!("entity '{}' matches an erased subject", endpoint),
+                    );
+                    continue;
+                }
+                if !names.contains_key(endpoint) && !self.nodes.contains_key(endpoint) {
+                    issues.error(
+                        ImportIssueKind::MissingEndpoint,
+                        format!("{}.{}", path, field),
+                        format!(
+                            "entity '{}' is neither in the payload nor in the graph",
+                            endpoint
+                        ),
+                    );
+                }
+            }
+            if let (Some(from), Some(to), Some(relation_type)) = (from, to, relation_type) {
+                if !relation_tuples.insert((from, to, relation_type)) {
+                    issues.warning(
+                        ImportIssueKind::DuplicateRelation,
+                        path,
+                        format!(
+                            "relation {} -[{}]-> {} appears more than once",
+                            from, relation_type, to
+                        ),
+                    );
+                }
+            }
+        }
+
+        ImportValidationReport {
+            valid: issues.error_count == 0,
+            payload_bytes: body.len(),
+            entity_count: entities.len(),
+            relation_count: relations.len(),
+            error_count: issues.error_count,
+            warning_count: issues.warning_count,
+            errors: issues.errors,
+            warnings: issues.warnings,
+        }
+    }
+}
+
+impl KnowledgeGraphState {
+    // The existing entity an imported one stands for, if any. A placeholder of the same name
+    // is left to create_entities, which fills it in.
+    fn import_match(&self, entity: &EntityToCreate) -> Option<String> {
+        if let Some(node) = self.nodes.get(&entity.name) {
+            return (!Self::is_placeholder(node)).then(|| node.id.clone());
+        }
+        if self.erased_subjects().matches(&entity.name) {
+            return None;
+        }
+        let incoming_aliases = entity
+            .data
+            .as_ref()
+            .and_then(|data| data.get("aliases"))
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str());
+        let named: BTreeSet<&str> = std::iter::once(entity.name.as_str())
+            .chain(incoming_aliases)
+            .flat_map(|name| self.search_index.ids_named(name))
+            .filter(|id| {
+                self.nodes
+                    .get(*id)
+                    .is_some_and(|n| !Self::is_placeholder(n))
+            })
+            .collect();
+        match named.len() {
+            1 => return named.first().map(|id| id.to_string()),
+            0 => {}
+            // Ambiguous; better a new entity than a merge into the wrong one
+            _ => return None,
+        }
+        self.nearest_duplicate(&entity.name)
+            .and_then(|duplicate| self.nodes.get(&duplicate.name))
+            .filter(|node| node.node_type == entity.entity_type && !Self::is_placeholder(node))
+            .map(|node| node.id.clone())
+    }
+
+    // Adds what `entity` brings to the existing entity `name`.
+    fn merge_imported_entity(
+        &mut self,
+        name: &str,
+        entity: EntityToCreate,
+        now_ms: u64,
+    ) -> BatchResult {
+        let mut result = self
+            .add_observations_batch(vec![AddObservationItem {
+                entity_name: name.to_string(),
+                contents: entity.observations,
+                expected_version: None,
+            }])
+            .pop()
+            .unwrap_or_else(|| BatchResult::ok(0, name, BatchStatus::Unchanged));
+        let Some(node) = self.nodes.get_mut(name) else {
+            return result;
+        };
+        if !node.data.is_object() {
+            node.data = serde_json::json!({});
+        }
+        let data = node.data.as_object_mut().unwrap(); // Safe
+        let mut data_changed = false;
+        if let Some(JsonValue::Object(incoming)) = entity.data {
+            for (key, value) in incoming {
+                if key != "observations" && key != "aliases" && !data.contains_key(&key) {
+                    data.insert(key, value);
+                    data_changed = true;
+                }
+            }
+        }
+        let known = std::iter::once(node.id.as_str())
+            .chain(aliases(node))
+            .any(|known| known.to_lowercase() == entity.name.to_lowercase());
+        if !known {
+            let data = node.data.as_object_mut().unwrap(); // Safe
+            match data.get_mut("aliases").and_then(|v| v.as_array_mut()) {
+                Some(list) => list.push(JsonValue::String(entity.name.clone())),
+                None => {
+                    data.insert("aliases".to_string(), serde_json::json!([entity.name]));
+                }
+            }
+            data_changed = true;
+        }
+        if data_changed {
+            node.updated_at_ms = now_ms;
+            node.updated_by = self.actor.clone();
+            if result.status == BatchStatus::Unchanged {
+                node.version += 1;
+                result.status = BatchStatus::Updated;
+            }
+            self.reindex_node(name);
+        }
+        if name != entity.name {
+            result.warning = Some(format!(
+                "Merged into existing entity {} instead of creating {}",
+                name, entity.name
+            ));
+        }
+        result
+    }
+
+    // create_entities with `merge_existing`: each entity is merged into its match or created
+    // (through its template with `use_template`). Also returns requested name -> resolved
+    // entity for the entities that were created or merged.
+    pub fn create_or_merge_entities(
+        &mut self,
+        entities: Vec<EntityToCreate>,
+        policy: NearDuplicatePolicy,
+        use_template: bool,
+        now_ms: u64,
+    ) -> (Vec<BatchResult>, BTreeMap<String, String>) {
+        let mut results = Vec::with_capacity(entities.len());
+        let mut resolved_names = BTreeMap::new();
+        for (index, entity) in entities.into_iter().enumerate() {
+            let requested = entity.name.clone();
+            // Later entities can match the ones created before them
+            let mut result = match self.import_match(&entity) {
+                Some(existing) => self.merge_imported_entity(&existing, entity, now_ms),
+                None if use_template => self
+                    .create_entities_from_templates(vec![entity], policy)
+                    .remove(0),
+                None => self.create_entities_batch(vec![entity], policy).remove(0),
+            };
+            result.index = index;
+            if let (None, Some(id)) = (&result.error, &result.id) {
+                resolved_names.insert(requested, id.clone());
+            }
+            results.push(result);
+        }
+        (results, resolved_names)
+    }
+}
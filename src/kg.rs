@@ -1,27 +1,129 @@
+use crate::filter::DataFilter;
+use crate::maintenance::normalized_node_data;
+use crate::migrations::CURRENT_SCHEMA_VERSION;
+use crate::search_index::{aliases, searchable_strings, SearchIndex};
 use crate::types::{
-    AddObservationItem, ApiEntity, ApiRelation, DeleteObservationItem, Edge, EntityToCreate, Node,
-    RelationToCreate, RelationToDelete,
+    AddObservationItem, ApiEntity, ApiRelation, BatchResponse, BatchResult, BatchStatus,
+    ClearGraphResponse, CompletionKind, CompletionResult, ConfirmationToken, DeleteObservationItem,
+    Edge, EdgeDirection, EdgeListQuery, EdgeListResponse, EntitySuggestion, EntityTemplate,
+    EntityToCreate, ExtractedGraph, GraphStats, MissingNodePolicy, NearDuplicatePolicy, Node,
+    NodeEdge, ObservationMatch, PendingForget, RecentEntity, RelationSuggestion, RelationToCreate,
+    RelationToDelete, RelationTypeSpec, SuggestResult, TimeRange, Tombstone,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value as JsonValue};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use uuid::Uuid;
-use worker::Date;
+// Native benchmarks have no JS clock or console, see bench.rs
+#[cfg(feature = "bench")]
+use crate::bench::{console_log, console_warn, Date};
+#[cfg(not(feature = "bench"))]
+use worker::{console_log, console_warn, Date};
+
+// Entity type given to entities auto-created for unknown relation endpoints
+pub const PLACEHOLDER_ENTITY_TYPE: &str = "Unknown";
+// Flag stored in a placeholder entity's data until the entity is created for real
+const PLACEHOLDER_DATA_KEY: &str = "placeholder";
+// Page sizes for GET /edges
+pub const DEFAULT_EDGE_PAGE_SIZE: usize = 100;
+pub const MAX_EDGE_PAGE_SIZE: usize = 1000;
+// Most ids one POST /nodes/batch-get may ask for
+pub const MAX_BATCH_GET_IDS: usize = 1000;
+// Most values a completion request returns (the MCP limit per response)
+pub const MAX_COMPLETION_VALUES: usize = 100;
+// Entities GET /graph/recent returns without a limit, and at most
+pub const DEFAULT_RECENT_LIMIT: usize = 20;
+pub const MAX_RECENT_LIMIT: usize = 100;
+// Queries one POST /graph/search may carry
+pub const MAX_SEARCH_QUERIES: usize = 20;
+pub const DEFAULT_OBSERVATION_SEARCH_LIMIT: usize = 20;
+pub const MAX_OBSERVATION_SEARCH_LIMIT: usize = 100;
+// Observations GET /graph/recent returns per entity without ?observations=
+pub const DEFAULT_RECENT_OBSERVATIONS: usize = 5;
+// Entities GET /graph/suggest returns without a limit
+pub const DEFAULT_SUGGEST_LIMIT: usize = 10;
+// How long a clear_graph confirmation token stays valid
+const CLEAR_TOKEN_TTL_MS: u64 = 5 * 60 * 1000;
+
+// Upper bound on the serialized graph metadata, which is loaded with every request.
+pub const MAX_METADATA_BYTES: usize = 64 * 1024;
+
+// Drops authors and timestamps of observations a node no longer has.
+pub fn prune_observation_metadata(node: &mut Node) {
+    if node.observation_authors.is_empty() && node.observation_added_ms.is_empty() {
+        return;
+    }
+    let observations = KnowledgeGraphState::observations_of(node);
+    node.observation_authors
+        .retain(|observation, _| observations.contains(observation));
+    node.observation_added_ms
+        .retain(|observation, _| observations.contains(observation));
+}
+
+// Applies a JSON merge patch (RFC 7386): objects merge key by key, a null removes the key,
+// and anything else replaces the target.
+pub fn merge_patch(target: &mut JsonValue, patch: &JsonValue) {
+    let JsonValue::Object(patch_map) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    if let JsonValue::Object(target_map) = target {
+        for (key, value) in patch_map {
+            if value.is_null() {
+                target_map.remove(key);
+            } else {
+                merge_patch(
+                    target_map.entry(key.clone()).or_insert(JsonValue::Null),
+                    value,
+                );
+            }
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct KnowledgeGraphState {
     pub nodes: HashMap<String, Node>, // Node ID (which is entity name) -> Node
     pub edges: HashMap<String, Edge>, // Edge ID (UUID) -> Edge
     pub metadata: HashMap<String, JsonValue>, // Arbitrary metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_clear: Option<ConfirmationToken>, // Issued by the first clear_graph call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pending_forget: Option<PendingForget>, // Issued by a forget preview, see forget.rs
+    #[serde(default)]
+    pub schema_version: u32, // Persisted layout version, see migrations.rs
+    #[serde(default)]
+    pub search_index: SearchIndex, // Kept in sync with every node write, see search_index.rs
+    #[serde(default)]
+    pub version: u64, // Bumped on every save, sent to the worker as X-Graph-Version
+    #[serde(default)]
+    pub relation_types: BTreeMap<String, RelationTypeSpec>, // See relation_types.rs
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub entity_templates: BTreeMap<String, EntityTemplate>, // Entity type -> template, see templates.rs
+    #[serde(default)]
+    pub tombstones: Vec<Tombstone>, // Erased subjects, see erasure.rs
+    // Who is making the current request (API key fingerprint or client address), set by the
+    // DO from the worker's x-actor header and stamped as created_by/updated_by. Never stored.
+    #[serde(skip)]
+    pub actor: Option<String>,
 }
 
 impl KnowledgeGraphState {
     pub fn new() -> Self {
-        KnowledgeGraphState::default()
+        KnowledgeGraphState {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            ..Default::default()
+        }
     }
 
-    pub fn add_node(&mut self, node: Node) -> String {
+    pub fn add_node(&mut self, mut node: Node) -> String {
+        node.created_by = node.created_by.or_else(|| self.actor.clone());
+        node.updated_by = node.updated_by.or_else(|| self.actor.clone());
         let node_id = node.id.clone();
+        self.search_index.index_node(&node);
         self.nodes.insert(node_id.clone(), node);
         node_id
     }
@@ -30,7 +132,8 @@ impl KnowledgeGraphState {
         self.nodes.get(node_id)
     }
 
-    pub fn add_edge(&mut self, edge: Edge) -> String {
+    pub fn add_edge(&mut self, mut edge: Edge) -> String {
+        edge.created_by = edge.created_by.or_else(|| self.actor.clone());
         let edge_id = edge.id.clone();
         self.edges.insert(edge_id.clone(), edge);
         edge_id
@@ -40,15 +143,142 @@ impl KnowledgeGraphState {
         self.edges.get(edge_id)
     }
 
+    // Merge-patches an edge's data; a patch that leaves nothing behind clears it to None.
+    pub fn patch_edge_data(&mut self, edge_id: &str, patch: &JsonValue) -> Option<Edge> {
+        // The mirror (see relation_types.rs) carries the same data
+        let mirror_id = self
+            .mirror_edge_id(self.edges.get(edge_id)?)
+            .filter(|mirror_id| mirror_id != edge_id);
+        for id in std::iter::once(edge_id).chain(mirror_id.as_deref()) {
+            if let Some(edge) = self.edges.get_mut(id) {
+                let mut data = edge.data.take().unwrap_or(JsonValue::Null);
+                merge_patch(&mut data, patch);
+                edge.data = (!data.is_null()).then_some(data);
+                edge.updated_by = self.actor.clone();
+            }
+        }
+        self.edges.get(edge_id).cloned()
+    }
+
+    // Replaces the graph metadata, or merge-patches it (null removes a key). Returns whether
+    // anything changed.
+    pub fn update_metadata(&mut self, update: &JsonValue, merge: bool) -> Result<bool, String> {
+        if !update.is_object() {
+            return Err("metadata must be a JSON object".to_string());
+        }
+        let mut metadata = if merge {
+            let mut current = JsonValue::Object(self.metadata.clone().into_iter().collect());
+            merge_patch(&mut current, update);
+            current
+        } else {
+            update.clone()
+        };
+        let size = metadata.to_string().len();
+        if size > MAX_METADATA_BYTES {
+            return Err(format!(
+                "metadata is {} bytes, more than the {} allowed",
+                size, MAX_METADATA_BYTES
+            ));
+        }
+        let metadata: HashMap<String, JsonValue> = match metadata.take() {
+            JsonValue::Object(map) => map.into_iter().collect(),
+            _ => HashMap::new(),
+        };
+        if metadata == self.metadata {
+            return Ok(false);
+        }
+        self.metadata = metadata;
+        Ok(true)
+    }
+
     pub fn remove_edge(&mut self, edge_id: &str) -> Option<Edge> {
         self.edges.remove(edge_id)
     }
 
-    pub fn find_nodes_by_type(&self, node_type: &str) -> Vec<&Node> {
-        self.nodes
+    // Lists nodes, optionally narrowed by exact type, a filter over their data and creation or
+    // update time. With a time range the nodes come oldest first.
+    pub fn filter_nodes(
+        &self,
+        node_type: Option<&str>,
+        data_filter: Option<&DataFilter>,
+        created: Option<&TimeRange>,
+        updated: Option<&TimeRange>,
+    ) -> Vec<&Node> {
+        let mut nodes: Vec<&Node> = self
+            .nodes
             .values()
-            .filter(|n| n.node_type == node_type)
-            .collect()
+            .filter(|n| created.is_none_or(|r| r.contains(n.created_at_ms)))
+            .filter(|n| updated.is_none_or(|r| r.contains(n.updated_at_ms)))
+            .filter(|n| node_type.is_none_or(|t| n.node_type == t))
+            .filter(|n| data_filter.is_none_or(|f| f.matches(&n.data)))
+            .collect();
+        if created.is_some() {
+            nodes.sort_by(|a, b| (a.created_at_ms, &a.id).cmp(&(b.created_at_ms, &b.id)));
+        } else if updated.is_some() {
+            nodes.sort_by(|a, b| (a.updated_at_ms, &a.id).cmp(&(b.updated_at_ms, &b.id)));
+        }
+        nodes
+    }
+
+    // Filters edges and returns one page of them, ordered by creation time then id so that
+    // offsets stay stable between calls.
+    pub fn list_edges(&self, query: &EdgeListQuery) -> EdgeListResponse {
+        let mut matching: Vec<&Edge> = self
+            .edges
+            .values()
+            .filter(|e| {
+                query
+                    .created_between
+                    .as_ref()
+                    .is_none_or(|r| r.contains(e.created_at_ms))
+            })
+            .filter(|e| query.edge_type.as_ref().is_none_or(|t| &e.edge_type == t))
+            .filter(|e| {
+                query
+                    .source_node_id
+                    .as_ref()
+                    .is_none_or(|id| &e.source_node_id == id)
+            })
+            .filter(|e| {
+                query
+                    .target_node_id
+                    .as_ref()
+                    .is_none_or(|id| &e.target_node_id == id)
+            })
+            .filter(|e| query.created_after.is_none_or(|t| e.created_at_ms > t))
+            .collect();
+        matching.sort_by(|a, b| (a.created_at_ms, &a.id).cmp(&(b.created_at_ms, &b.id)));
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_EDGE_PAGE_SIZE)
+            .clamp(1, MAX_EDGE_PAGE_SIZE);
+        let total = matching.len();
+        let edges: Vec<Edge> = matching
+            .into_iter()
+            .skip(query.offset)
+            .take(limit)
+            .cloned()
+            .collect();
+        let end = query.offset + edges.len();
+        EdgeListResponse {
+            edges,
+            total,
+            offset: query.offset,
+            next_offset: (end < total).then_some(end),
+        }
+    }
+
+    // Edges from `source` to `target`, optionally of one type, in creation order.
+    pub fn find_edges(&self, source: &str, target: &str, edge_type: Option<&str>) -> Vec<&Edge> {
+        let mut matching: Vec<&Edge> = self
+            .edges
+            .values()
+            .filter(|e| e.source_node_id == source && e.target_node_id == target)
+            .filter(|e| edge_type.is_none_or(|t| e.edge_type == t))
+            .collect();
+        matching.sort_by(|a, b| (a.created_at_ms, &a.id).cmp(&(b.created_at_ms, &b.id)));
+        matching
     }
 
     pub fn get_edges_for_node(&self, node_id: &str, direction: Option<&str>) -> Vec<&Edge> {
@@ -62,9 +292,45 @@ impl KnowledgeGraphState {
             .collect()
     }
 
+    // Edges touching a node, tagged with their direction relative to it. `direction` is
+    // "outgoing", "incoming" or anything else for both; self-loops are reported once.
+    pub fn node_edges(
+        &self,
+        node_id: &str,
+        direction: Option<&str>,
+        edge_type: Option<&str>,
+    ) -> Vec<NodeEdge> {
+        let want_outgoing = direction != Some("incoming");
+        let want_incoming = direction != Some("outgoing");
+        let mut node_edges: Vec<NodeEdge> = self
+            .edges
+            .values()
+            .filter(|edge| edge_type.is_none_or(|t| edge.edge_type == t))
+            .filter_map(|edge| {
+                let (direction, neighbor_id) = if want_outgoing && edge.source_node_id == node_id {
+                    (EdgeDirection::Outgoing, &edge.target_node_id)
+                } else if want_incoming && edge.target_node_id == node_id {
+                    (EdgeDirection::Incoming, &edge.source_node_id)
+                } else {
+                    return None;
+                };
+                Some(NodeEdge {
+                    direction,
+                    neighbor_id: neighbor_id.clone(),
+                    edge: edge.clone(),
+                })
+            })
+            .collect();
+        node_edges.sort_by(|a, b| {
+            (a.edge.created_at_ms, &a.edge.id).cmp(&(b.edge.created_at_ms, &b.edge.id))
+        });
+        node_edges
+    }
+
     pub fn delete_node_and_connected_edges(&mut self, node_id: &str) -> Option<Node> {
         let node_to_delete = self.nodes.remove(node_id);
         if node_to_delete.is_some() {
+            self.search_index.remove_node(node_id);
             let mut edge_ids_to_remove = Vec::new();
             for (edge_id, edge) in &self.edges {
                 if edge.source_node_id == node_id || edge.target_node_id == node_id {
@@ -91,37 +357,214 @@ impl KnowledgeGraphState {
             }
             if let Some(new_data) = data_opt {
                 node.data = new_data;
+                prune_observation_metadata(node);
             }
             node.updated_at_ms = current_time_ms;
-            Some(node.clone())
+            node.updated_by = self.actor.clone();
+            node.version += 1;
+            let updated = node.clone();
+            self.search_index.index_node(&updated);
+            Some(updated)
         } else {
             None
         }
     }
 
+    // Rewrites the entity type of every node that has `from`; returns how many changed.
+    pub fn rename_node_type(&mut self, from: &str, to: &str) -> usize {
+        let current_time_ms = Date::now().as_millis();
+        let mut renamed = 0;
+        for node in self.nodes.values_mut().filter(|n| n.node_type == from) {
+            node.node_type = to.to_string();
+            node.updated_at_ms = current_time_ms;
+            node.updated_by = self.actor.clone();
+            node.version += 1;
+            // The type is one of the indexed strings
+            self.search_index.index_node(node);
+            renamed += 1;
+        }
+        renamed
+    }
+
+    // Rewrites the relation type of every edge that has `from`; returns how many changed.
+    pub fn rename_edge_type(&mut self, from: &str, to: &str) -> usize {
+        let mut renamed = 0;
+        for edge in self.edges.values_mut().filter(|e| e.edge_type == from) {
+            edge.edge_type = to.to_string();
+            renamed += 1;
+        }
+        renamed
+    }
+
+    // String observations of a node, in stored order.
+    pub fn observations_of(node: &Node) -> Vec<String> {
+        node.data
+            .get("observations")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect()
+    }
+
+    // Stores a generated summary under data["summary"]. With `replace`, the observations that
+    // were summarized are swapped for the summary; any added since are kept after it.
+    pub fn apply_summary(
+        &mut self,
+        node_id: &str,
+        summary: &str,
+        summarized: &[String],
+        model: &str,
+        replace: bool,
+    ) -> Option<Node> {
+        let current_time_ms = Date::now().as_millis();
+        let node = self.nodes.get_mut(node_id)?;
+        if let Some(normalized) = normalized_node_data(&node.data) {
+            node.data = normalized;
+        }
+        let map = node.data.as_object_mut()?;
+        map.insert(
+            "summary".to_string(),
+            json!({
+                "text": summary,
+                "model": model,
+                "generated_at_ms": current_time_ms,
+                "observation_count": summarized.len(),
+            }),
+        );
+        if replace {
+            let remaining = map
+                .get("observations")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter(|v| {
+                    !v.as_str()
+                        .is_some_and(|s| summarized.iter().any(|o| o == s))
+                })
+                .cloned();
+            let observations: Vec<JsonValue> =
+                std::iter::once(json!(summary)).chain(remaining).collect();
+            map.insert("observations".to_string(), JsonValue::Array(observations));
+            if let Some(actor) = &self.actor {
+                node.observation_authors
+                    .insert(summary.to_string(), actor.clone());
+            }
+            node.observation_added_ms
+                .insert(summary.to_string(), current_time_ms);
+            prune_observation_metadata(node);
+        }
+        node.updated_at_ms = current_time_ms;
+        node.updated_by = self.actor.clone();
+        node.version += 1;
+        let updated = node.clone();
+        self.search_index.index_node(&updated);
+        Some(updated)
+    }
+
+    pub fn is_placeholder(node: &Node) -> bool {
+        node.data
+            .get(PLACEHOLDER_DATA_KEY)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    // Creates a minimal entity standing in for a relation endpoint that doesn't exist yet.
+    fn create_placeholder_node(&mut self, name: &str, current_time_ms: u64) {
+        let placeholder = Node {
+            id: name.to_string(),
+            node_type: PLACEHOLDER_ENTITY_TYPE.to_string(),
+            data: json!({ "observations": [], PLACEHOLDER_DATA_KEY: true }),
+            created_at_ms: current_time_ms,
+            updated_at_ms: current_time_ms,
+            created_by: self.actor.clone(),
+            updated_by: self.actor.clone(),
+            observation_authors: BTreeMap::new(),
+            observation_added_ms: BTreeMap::new(),
+            version: 1,
+            pinned: false,
+        };
+        self.search_index.index_node(&placeholder);
+        self.nodes.insert(name.to_string(), placeholder);
+    }
+
     // --- Batch/Query API Methods ---
 
     pub fn create_entities_batch(
         &mut self,
         entities_to_create: Vec<EntityToCreate>,
-    ) -> Result<Vec<Node>, String> {
-        worker::console_log!(
+        on_near_duplicate: NearDuplicatePolicy,
+    ) -> Vec<BatchResult> {
+        console_log!(
             "create_entities_batch called with {} entities to create.",
             entities_to_create.len()
         );
-        let mut created_nodes = Vec::new();
+        let mut results = Vec::new();
         let current_time_ms = Date::now().as_millis();
+        let erased = self.erased_subjects();
 
-        for entity_spec in entities_to_create {
+        for (index, mut entity_spec) in entities_to_create.into_iter().enumerate() {
             let node_id = entity_spec.name.clone();
-            worker::console_log!("Processing entity_spec for ID: {}", node_id);
+            console_log!("Processing entity_spec for ID: {}", node_id);
 
-            if self.nodes.contains_key(&node_id) {
-                worker::console_log!("Entity with ID: {} already exists. Skipping.", node_id);
-                // Skip if entity with this name (ID) already exists
+            if erased.matches(&node_id) {
+                results.push(BatchResult::failed(
+                    index,
+                    Some(node_id),
+                    BatchStatus::Skipped,
+                    "Entity name matches an erased subject",
+                ));
+                continue;
+            }
+            entity_spec.observations.retain(|obs| !erased.matches(obs));
+
+            // A placeholder left by create_relations is filled in rather than reported as existing.
+            let replaced_placeholder = self.nodes.get(&node_id).filter(|n| Self::is_placeholder(n));
+            let created_at_ms = replaced_placeholder
+                .map(|n| n.created_at_ms)
+                .unwrap_or(current_time_ms);
+            let created_by = match replaced_placeholder {
+                Some(placeholder) => placeholder.created_by.clone(),
+                None => self.actor.clone(),
+            };
+            let version = replaced_placeholder.map_or(1, |n| n.version + 1);
+            let pinned = replaced_placeholder.is_some_and(|n| n.pinned);
+
+            if replaced_placeholder.is_none() && self.nodes.contains_key(&node_id) {
+                console_log!("Entity with ID: {} already exists.", node_id);
+                results.push(BatchResult::failed(
+                    index,
+                    Some(node_id.clone()),
+                    BatchStatus::AlreadyExists,
+                    format!("Entity with name {} already exists", node_id),
+                ));
                 continue;
             }
 
+            let near_duplicate = match on_near_duplicate {
+                _ if replaced_placeholder.is_some() => None,
+                NearDuplicatePolicy::Ignore => None,
+                NearDuplicatePolicy::Warn | NearDuplicatePolicy::Reject => {
+                    self.nearest_duplicate(&node_id)
+                }
+            };
+            if let Some(duplicate) = &near_duplicate {
+                if on_near_duplicate == NearDuplicatePolicy::Reject {
+                    let mut result = BatchResult::failed(
+                        index,
+                        Some(node_id.clone()),
+                        BatchStatus::Skipped,
+                        format!(
+                            "Entity name {} is very close to existing entity {}; add observations to it instead",
+                            node_id, duplicate.name
+                        ),
+                    );
+                    result.near_duplicate = near_duplicate;
+                    results.push(result);
+                    continue;
+                }
+            }
+
             let mut node_data = entity_spec.data.unwrap_or_else(|| json!({}));
 
             // Ensure node_data is an object to store observations
@@ -129,7 +572,7 @@ impl KnowledgeGraphState {
                 // If entity_spec.data was provided but not an object, this is a problem.
                 // We'll overwrite it to store observations, or you could error out.
                 // For simplicity, we create a new object, potentially losing original non-object data.
-                worker::console_warn!(
+                console_warn!(
                     "Data for entity '{}' was not an object and will be overwritten to store observations.",
                     node_id
                 );
@@ -145,56 +588,192 @@ impl KnowledgeGraphState {
                 node_data = json!({ "observations": entity_spec.observations });
             }
 
+            let observation_authors = match &self.actor {
+                Some(actor) => entity_spec
+                    .observations
+                    .iter()
+                    .map(|obs| (obs.clone(), actor.clone()))
+                    .collect(),
+                None => BTreeMap::new(),
+            };
+            let observation_added_ms = entity_spec
+                .observations
+                .iter()
+                .map(|obs| (obs.clone(), current_time_ms))
+                .collect();
             let new_node = Node {
                 id: node_id.clone(),
                 node_type: entity_spec.entity_type,
                 data: node_data,
-                created_at_ms: current_time_ms,
+                created_at_ms,
                 updated_at_ms: current_time_ms,
+                created_by,
+                updated_by: self.actor.clone(),
+                observation_authors,
+                observation_added_ms,
+                version,
+                pinned,
             };
-            self.nodes.insert(node_id.clone(), new_node.clone());
-            created_nodes.push(new_node);
-            worker::console_log!("Successfully created and added node with ID: {}", node_id);
+            self.search_index.index_node(&new_node);
+            self.nodes.insert(node_id.clone(), new_node);
+            console_log!("Successfully created and added node with ID: {}", node_id);
+            let mut result = BatchResult::ok(index, node_id, BatchStatus::Created);
+            if let Some(duplicate) = near_duplicate {
+                result.warning = Some(format!(
+                    "Very close to existing entity {}; consider add_observations on it instead",
+                    duplicate.name
+                ));
+                result.near_duplicate = Some(duplicate);
+            }
+            results.push(result);
         }
-        worker::console_log!(
+        console_log!(
             "create_entities_batch finished. {} nodes created.",
-            created_nodes.len()
+            results
+                .iter()
+                .filter(|r| r.status == BatchStatus::Created)
+                .count()
         );
-        Ok(created_nodes)
+        results
+    }
+
+    // Writes an extraction through the batch operations. Entities that already exist get the
+    // extracted observations added instead. With `MissingNodePolicy::Error` only the relations
+    // are rejected; entities and observations are still written.
+    pub fn apply_extracted_graph(
+        &mut self,
+        extracted: &ExtractedGraph,
+        on_missing_node: MissingNodePolicy,
+    ) -> (BatchResponse, BatchResponse, BatchResponse) {
+        let policy = self.near_duplicate_policy(None);
+        let entity_results = self.create_entities_batch(extracted.entities.clone(), policy);
+        let observations_to_add: Vec<AddObservationItem> = entity_results
+            .iter()
+            .zip(&extracted.entities)
+            .filter(|(result, entity)| {
+                result.status == BatchStatus::AlreadyExists && !entity.observations.is_empty()
+            })
+            .map(|(_, entity)| AddObservationItem {
+                entity_name: entity.name.clone(),
+                contents: entity.observations.clone(),
+                expected_version: None,
+            })
+            .collect();
+        let observation_results = self.add_observations_batch(observations_to_add);
+        let relation_results = self
+            .create_relations_batch(extracted.relations.clone(), on_missing_node)
+            .unwrap_or_else(|rejected| rejected);
+
+        (
+            BatchResponse::new(entity_results),
+            BatchResponse::new(observation_results),
+            BatchResponse::new(relation_results).with_on_missing_node(on_missing_node),
+        )
+    }
+
+    // Describes the first missing endpoint of a relation, if any.
+    fn missing_endpoint_error(&self, rel_data: &RelationToCreate) -> Option<String> {
+        if !self.nodes.contains_key(&rel_data.from) {
+            Some(format!(
+                "Source node with name {} not found for relation",
+                rel_data.from
+            ))
+        } else if !self.nodes.contains_key(&rel_data.to) {
+            Some(format!(
+                "Target node with name {} not found for relation",
+                rel_data.to
+            ))
+        } else {
+            None
+        }
     }
 
+    // With `MissingNodePolicy::Error`, a relation with a missing endpoint rejects the whole batch:
+    // nothing is written and `Err` carries the per-item results explaining why.
     pub fn create_relations_batch(
         &mut self,
         relations_to_create: Vec<RelationToCreate>,
-    ) -> Result<Vec<Edge>, String> {
-        let mut created_edges = Vec::new();
+        on_missing_node: MissingNodePolicy,
+    ) -> Result<Vec<BatchResult>, Vec<BatchResult>> {
+        if on_missing_node == MissingNodePolicy::Error {
+            let missing: Vec<Option<String>> = relations_to_create
+                .iter()
+                .map(|rel_data| self.missing_endpoint_error(rel_data))
+                .collect();
+            if missing.iter().any(Option::is_some) {
+                let results = missing
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, error)| match error {
+                        Some(e) => BatchResult::failed(index, None, BatchStatus::NotFound, e),
+                        None => BatchResult::failed(
+                            index,
+                            None,
+                            BatchStatus::Skipped,
+                            "Not applied: batch rejected because of missing nodes",
+                        ),
+                    })
+                    .collect();
+                return Err(results);
+            }
+        }
+
+        let mut results = Vec::new();
         let current_time_ms = Date::now().as_millis();
+        let erased = self.erased_subjects();
 
-        for rel_data in relations_to_create {
-            // Check if source and target nodes exist
-            if !self.nodes.contains_key(&rel_data.from) {
-                return Err(format!(
-                    "Source node with name {} not found for relation",
-                    rel_data.from
+        for (index, rel_data) in relations_to_create.into_iter().enumerate() {
+            if erased.matches(&rel_data.from) || erased.matches(&rel_data.to) {
+                results.push(BatchResult::failed(
+                    index,
+                    None,
+                    BatchStatus::Skipped,
+                    "Relation endpoint matches an erased subject",
                 ));
+                continue;
             }
-            if !self.nodes.contains_key(&rel_data.to) {
-                return Err(format!(
-                    "Target node with name {} not found for relation",
-                    rel_data.to
-                ));
+            let mut placeholders_created = Vec::new();
+            if on_missing_node == MissingNodePolicy::CreatePlaceholder {
+                for name in [&rel_data.from, &rel_data.to] {
+                    if !self.nodes.contains_key(name) {
+                        self.create_placeholder_node(name, current_time_ms);
+                        placeholders_created.push(name.clone());
+                    }
+                }
+            }
+
+            // Check if source and target nodes exist
+            if let Some(e) = self.missing_endpoint_error(&rel_data) {
+                results.push(BatchResult::failed(index, None, BatchStatus::Skipped, e));
+                continue;
             }
 
             // Check if this exact relation already exists (by from, to, and type)
             // This is O(N) for N edges. If performance is critical for many edges, consider indexing.
-            let exists = self.edges.values().any(|edge| {
-                edge.source_node_id == rel_data.from
-                    && edge.target_node_id == rel_data.to
-                    && edge.edge_type == rel_data.relation_type
-            });
+            let existing_edge_id = self
+                .edges
+                .values()
+                .find(|edge| {
+                    edge.source_node_id == rel_data.from
+                        && edge.target_node_id == rel_data.to
+                        && edge.edge_type == rel_data.relation_type
+                })
+                .map(|edge| edge.id.clone());
 
-            if exists {
-                // Skip creating if it already exists, mirroring TS behavior.
+            if let Some(edge_id) = existing_edge_id {
+                // Restating a relation with a confidence refreshes it
+                if let Some(confidence) = rel_data.confidence {
+                    self.set_confidence(&edge_id, confidence, current_time_ms);
+                    results.push(BatchResult::ok(index, edge_id, BatchStatus::Updated));
+                    continue;
+                }
+                // Don't create a duplicate, mirroring TS behavior, but report the existing edge.
+                results.push(BatchResult::failed(
+                    index,
+                    Some(edge_id),
+                    BatchStatus::AlreadyExists,
+                    "Relation already exists",
+                ));
                 continue;
             }
 
@@ -208,22 +787,32 @@ impl KnowledgeGraphState {
                 created_at_ms: current_time_ms,
                 // updated_at_ms for edges is not in the original Edge struct, add if needed.
                 // For now, keeping Edge struct as is.
+                created_by: self.actor.clone(),
+                updated_by: None,
+                confidence: rel_data.confidence,
+                confidence_at_ms: rel_data.confidence.map(|_| current_time_ms),
             };
-            self.edges.insert(edge_id, new_edge.clone());
-            created_edges.push(new_edge);
+            self.edges.insert(edge_id.clone(), new_edge);
+            let mirrored_edge_id = self.ensure_mirror_edge(&edge_id, current_time_ms);
+            let mut result = BatchResult::ok(index, edge_id, BatchStatus::Created);
+            result.placeholders_created = placeholders_created;
+            result.mirrored_edge_id = mirrored_edge_id;
+            results.push(result);
         }
-        Ok(created_edges)
+        Ok(results)
     }
 
-    // Returns a Vec of Results, each indicating success (with entity name) or failure (with error message)
     pub fn add_observations_batch(
         &mut self,
         observations_to_add: Vec<AddObservationItem>,
-    ) -> Vec<Result<String, String>> {
+    ) -> Vec<BatchResult> {
         let mut results = Vec::new();
         let current_time_ms = Date::now().as_millis();
+        let erased = self.erased_subjects();
 
-        for item in observations_to_add {
+        for (index, mut item) in observations_to_add.into_iter().enumerate() {
+            // Observations mentioning an erased subject are dropped
+            item.contents.retain(|content| !erased.matches(content));
             match self.nodes.get_mut(&item.entity_name) {
                 Some(node) => {
                     // The problematic block that caused diagnostic errors has been removed.
@@ -253,66 +842,132 @@ impl KnowledgeGraphState {
                         let content_val = serde_json::json!(content_str);
                         if !obs_vec.iter().any(|v| v == &content_val) {
                             obs_vec.push(content_val);
+                            node.observation_added_ms
+                                .insert(content_str.clone(), current_time_ms);
+                            if let Some(actor) = &self.actor {
+                                node.observation_authors.insert(content_str, actor.clone());
+                            }
                             actually_added_count += 1;
                         }
                     }
 
                     if actually_added_count > 0 {
                         node.updated_at_ms = current_time_ms;
-                        results.push(Ok(format!(
-                            "Added {} new observation(s) to entity {}",
-                            actually_added_count, item.entity_name
-                        )));
+                        node.updated_by = self.actor.clone();
+                        node.version += 1;
+                        self.reindex_node(&item.entity_name);
+                        results.push(BatchResult::ok(
+                            index,
+                            item.entity_name,
+                            BatchStatus::Updated,
+                        ));
                     } else {
-                        results.push(Ok(format!(
-                            "No new observations added to entity {} (all existed or empty input)",
-                            item.entity_name
-                        )));
+                        // All observations already existed or the input was empty
+                        results.push(BatchResult::ok(
+                            index,
+                            item.entity_name,
+                            BatchStatus::Unchanged,
+                        ));
                     }
                 }
                 None => {
-                    results.push(Err(format!(
-                        "Entity with name {} not found",
-                        item.entity_name
-                    )));
+                    results.push(BatchResult::failed(
+                        index,
+                        Some(item.entity_name.clone()),
+                        BatchStatus::NotFound,
+                        format!("Entity with name {} not found", item.entity_name),
+                    ));
                 }
             }
         }
         results
     }
 
-    // Returns list of IDs of entities that were successfully deleted.
-    pub fn delete_entities_batch(
-        &mut self,
-        entity_names: Vec<String>,
-    ) -> Result<Vec<String>, String> {
-        let mut deleted_ids = Vec::new();
-        for name in entity_names {
-            if self.nodes.contains_key(&name) {
-                self.delete_node_and_connected_edges(&name);
-                deleted_ids.push(name);
+    // Why a write that expected the node at `expected` must be refused, if it must.
+    pub fn version_conflict(&self, node_id: &str, expected: Option<u64>) -> Option<String> {
+        let (node, expected) = (self.nodes.get(node_id)?, expected?);
+        (node.version != expected).then(|| {
+            format!(
+                "Entity {} is at version {}, not the expected {}",
+                node_id, node.version, expected
+            )
+        })
+    }
+
+    // Optimistic locking for the batch writes: when any item's expected version is stale, the
+    // whole batch is refused (Err) with one result per item and nothing is written.
+    pub fn check_versions<'a>(
+        &self,
+        expectations: impl IntoIterator<Item = (&'a str, Option<u64>)>,
+    ) -> Result<(), Vec<BatchResult>> {
+        let mut conflicted = false;
+        let results: Vec<BatchResult> = expectations
+            .into_iter()
+            .enumerate()
+            .map(
+                |(index, (node_id, expected))| match self.version_conflict(node_id, expected) {
+                    Some(e) => {
+                        conflicted = true;
+                        BatchResult::failed(
+                            index,
+                            Some(node_id.to_string()),
+                            BatchStatus::Conflict,
+                            e,
+                        )
+                    }
+                    None => BatchResult::failed(
+                        index,
+                        Some(node_id.to_string()),
+                        BatchStatus::Skipped,
+                        "Not applied: another item of the batch has a version conflict",
+                    ),
+                },
+            )
+            .collect();
+        if conflicted {
+            Err(results)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn delete_entities_batch(&mut self, entity_names: Vec<String>) -> Vec<BatchResult> {
+        let mut results = Vec::new();
+        for (index, name) in entity_names.into_iter().enumerate() {
+            if self.delete_node_and_connected_edges(&name).is_some() {
+                results.push(BatchResult::ok(index, name, BatchStatus::Deleted));
+            } else {
+                results.push(BatchResult::failed(
+                    index,
+                    Some(name.clone()),
+                    BatchStatus::NotFound,
+                    format!("Entity with name {} not found", name),
+                ));
             }
-            // If not found, we silently ignore, similar to TS version.
         }
-        Ok(deleted_ids)
+        results
     }
 
-    // Returns Vec of Results for each deletion attempt.
     pub fn delete_observations_batch(
         &mut self,
         deletions: Vec<DeleteObservationItem>,
-    ) -> Vec<Result<String, String>> {
+    ) -> Vec<BatchResult> {
         let mut results = Vec::new();
         let current_time_ms = Date::now().as_millis();
 
-        for item in deletions {
+        for (index, item) in deletions.into_iter().enumerate() {
             match self.nodes.get_mut(&item.entity_name) {
                 Some(node) => {
                     if !node.data.is_object() {
-                        results.push(Err(format!(
-                            "Entity {} data is not an object, cannot delete observations.",
-                            item.entity_name
-                        )));
+                        results.push(BatchResult::failed(
+                            index,
+                            Some(item.entity_name.clone()),
+                            BatchStatus::Error,
+                            format!(
+                                "Entity {} data is not an object, cannot delete observations.",
+                                item.entity_name
+                            ),
+                        ));
                         continue;
                     }
                     let node_data_map = node.data.as_object_mut().unwrap();
@@ -332,64 +987,192 @@ impl KnowledgeGraphState {
                         }
                     } else {
                         // No "observations" field or not an array, so nothing to delete.
-                        results.push(Ok(format!("No observations found or field is not an array for entity {}, nothing deleted.", item.entity_name)));
+                        results.push(BatchResult::ok(
+                            index,
+                            item.entity_name,
+                            BatchStatus::Unchanged,
+                        ));
                         continue;
                     }
 
                     if obs_modified {
                         node.updated_at_ms = current_time_ms;
-                        results.push(Ok(format!(
-                            "Observations processed for entity {}",
-                            item.entity_name
-                        )));
+                        node.updated_by = self.actor.clone();
+                        node.version += 1;
+                        prune_observation_metadata(node);
+                        self.reindex_node(&item.entity_name);
+                        results.push(BatchResult::ok(
+                            index,
+                            item.entity_name,
+                            BatchStatus::Updated,
+                        ));
                     } else {
-                        results.push(Ok(format!(
-                            "No matching observations deleted for entity {}",
-                            item.entity_name
-                        )));
+                        // No matching observations were found to delete
+                        results.push(BatchResult::ok(
+                            index,
+                            item.entity_name,
+                            BatchStatus::Unchanged,
+                        ));
                     }
                 }
                 None => {
-                    results.push(Err(format!(
-                        "Entity with name {} not found",
-                        item.entity_name
-                    )));
+                    results.push(BatchResult::failed(
+                        index,
+                        Some(item.entity_name.clone()),
+                        BatchStatus::NotFound,
+                        format!("Entity with name {} not found", item.entity_name),
+                    ));
                 }
             }
         }
         results
     }
 
-    // Returns list of IDs of relations that were successfully deleted.
     pub fn delete_relations_batch(
         &mut self,
         relations_to_delete: Vec<RelationToDelete>,
-    ) -> Result<Vec<String>, String> {
-        let mut deleted_edge_ids = Vec::new();
-        let mut edge_ids_to_actually_remove: HashSet<String> = HashSet::new();
+    ) -> Vec<BatchResult> {
+        let mut results = Vec::new();
 
-        for rel_spec in relations_to_delete {
+        for (index, rel_spec) in relations_to_delete.into_iter().enumerate() {
             // Find edge IDs matching the spec. There might be multiple if data differs but we don't check data for deletion.
-            for (edge_id, edge) in &self.edges {
-                if edge.source_node_id == rel_spec.from
-                    && edge.target_node_id == rel_spec.to
-                    && edge.edge_type == rel_spec.relation_type
-                {
-                    edge_ids_to_actually_remove.insert(edge_id.clone());
+            let mut matching_edge_ids: Vec<String> = self
+                .edges
+                .values()
+                .filter(|edge| {
+                    edge.source_node_id == rel_spec.from
+                        && edge.target_node_id == rel_spec.to
+                        && edge.edge_type == rel_spec.relation_type
+                })
+                .map(|edge| edge.id.clone())
+                .collect();
+            matching_edge_ids.sort();
+
+            let mut mirrors_deleted = Vec::new();
+            for edge_id in &matching_edge_ids {
+                if let Some(deleted) = self.edges.remove(edge_id) {
+                    mirrors_deleted.extend(self.remove_mirror_edge(&deleted));
+                }
+            }
+
+            match matching_edge_ids.first() {
+                Some(edge_id) => {
+                    let mut result = BatchResult::ok(index, edge_id, BatchStatus::Deleted);
+                    result.mirrored_edge_id = mirrors_deleted.first().cloned();
+                    result.edges_deleted = matching_edge_ids;
+                    result.mirrors_deleted = mirrors_deleted;
+                    results.push(result)
                 }
+                None => results.push(BatchResult::failed(
+                    index,
+                    None,
+                    BatchStatus::NotFound,
+                    format!(
+                        "Relation {} -[{}]-> {} not found",
+                        rel_spec.from, rel_spec.relation_type, rel_spec.to
+                    ),
+                )),
             }
         }
+        results
+    }
+
+    // Two-step reset: without a token, issues one; with the current unexpired token, removes
+    // every node and edge except pinned nodes and the edges between them (all of them when
+    // forced). Graph metadata is kept.
+    pub fn clear_graph(
+        &mut self,
+        confirm_token: Option<&str>,
+        force: bool,
+    ) -> Result<ClearGraphResponse, String> {
+        let current_time_ms = Date::now().as_millis();
+        let Some(token) = confirm_token else {
+            let pending = ConfirmationToken {
+                token: Uuid::new_v4().to_string(),
+                expires_at_ms: current_time_ms + CLEAR_TOKEN_TTL_MS,
+            };
+            self.pending_clear = Some(pending.clone());
+            return Ok(ClearGraphResponse::ConfirmationRequired {
+                confirm_token: pending.token,
+                expires_at_ms: pending.expires_at_ms,
+            });
+        };
 
-        for edge_id in edge_ids_to_actually_remove {
-            if self.edges.remove(&edge_id).is_some() {
-                deleted_edge_ids.push(edge_id);
+        match self.pending_clear.take() {
+            Some(pending) if pending.token == token && pending.expires_at_ms >= current_time_ms => {
+                let (entity_count, relation_count) = (self.nodes.len(), self.edges.len());
+                self.nodes.retain(|_, node| node.pinned && !force);
+                let nodes = &self.nodes;
+                self.edges.retain(|_, edge| {
+                    nodes.contains_key(&edge.source_node_id)
+                        && nodes.contains_key(&edge.target_node_id)
+                });
+                self.rebuild_search_index();
+                Ok(ClearGraphResponse::Cleared {
+                    deleted_entities: entity_count - self.nodes.len(),
+                    deleted_relations: relation_count - self.edges.len(),
+                    kept_pinned: self.nodes.len(),
+                })
             }
+            Some(pending) if pending.expires_at_ms >= current_time_ms => {
+                // Keep the outstanding token usable after a mistyped one
+                self.pending_clear = Some(pending);
+                Err("Invalid confirmation token".to_string())
+            }
+            _ => Err("Confirmation token expired or not issued; request a new one".to_string()),
+        }
+    }
+
+    pub fn stats(&self) -> GraphStats {
+        let observation_count = self
+            .nodes
+            .values()
+            .filter_map(|n| n.data.get("observations").and_then(|v| v.as_array()))
+            .map(|arr| arr.len())
+            .sum();
+        let entity_types: HashSet<&str> =
+            self.nodes.values().map(|n| n.node_type.as_str()).collect();
+        let relation_types: HashSet<&str> =
+            self.edges.values().map(|e| e.edge_type.as_str()).collect();
+        GraphStats {
+            entity_count: self.nodes.len(),
+            relation_count: self.edges.len(),
+            observation_count,
+            entity_type_count: entity_types.len(),
+            relation_type_count: relation_types.len(),
+            schema_version: self.schema_version,
         }
-        Ok(deleted_edge_ids)
+    }
+
+    // The most recently updated entities with their last `observations` observations, no placeholders.
+    pub fn recent_entities(&self, limit: usize, observations: usize) -> Vec<RecentEntity> {
+        let mut nodes: Vec<&Node> = self
+            .nodes
+            .values()
+            .filter(|node| !Self::is_placeholder(node))
+            .collect();
+        nodes.sort_by(|a, b| (b.updated_at_ms, &b.id).cmp(&(a.updated_at_ms, &a.id)));
+        nodes
+            .into_iter()
+            .take(limit)
+            .map(|node| {
+                let mut entity = self.node_to_api_entity(node);
+                let observation_count = entity.observations.len();
+                entity
+                    .observations
+                    .drain(..observation_count.saturating_sub(observations));
+                RecentEntity {
+                    entity,
+                    observation_count,
+                    created_at_ms: node.created_at_ms,
+                    updated_at_ms: node.updated_at_ms,
+                }
+            })
+            .collect()
     }
 
     // Helper to convert Node to ApiEntity (matching types.rs ApiEntity)
-    fn node_to_api_entity(&self, node: &Node) -> ApiEntity {
+    pub fn node_to_api_entity(&self, node: &Node) -> ApiEntity {
         let observations = node
             .data
             .get("observations")
@@ -419,16 +1202,25 @@ impl KnowledgeGraphState {
             entity_type: node.node_type.clone(),
             observations,
             data: final_other_data,
+            version: node.version,
+            created_by: node.created_by.clone(),
+            updated_by: node.updated_by.clone(),
+            pinned: node.pinned,
         }
     }
 
     // Helper to convert Edge to ApiRelation (matching types.rs ApiRelation)
-    fn edge_to_api_relation(&self, edge: &Edge) -> ApiRelation {
+    pub fn edge_to_api_relation(&self, edge: &Edge) -> ApiRelation {
         ApiRelation {
             from: edge.source_node_id.clone(),
             to: edge.target_node_id.clone(),
             relation_type: edge.edge_type.clone(),
             data: edge.data.clone(),
+            created_by: edge.created_by.clone(),
+            updated_by: edge.updated_by.clone(),
+            confidence: edge
+                .confidence
+                .and_then(|_| self.effective_confidence(edge, Date::now().as_millis())),
         }
     }
 
@@ -448,31 +1240,29 @@ impl KnowledgeGraphState {
 
     // Basic search: matches query against node ID (name), type, and observations.
     // Returns graph data (entities and their interconnecting relations).
-    pub fn search_nodes(&self, query: &str) -> (Vec<ApiEntity>, Vec<ApiRelation>) {
+    pub fn search_nodes(
+        &self,
+        query: &str,
+        data_filter: Option<&DataFilter>,
+    ) -> (Vec<ApiEntity>, Vec<ApiRelation>) {
         let query_lower = query.to_lowercase();
         let mut matching_nodes_set = HashSet::new();
 
-        for node in self.nodes.values() {
-            if node.id.to_lowercase().contains(&query_lower)
-                || node.node_type.to_lowercase().contains(&query_lower)
-            {
-                matching_nodes_set.insert(node.id.clone());
+        // The index narrows the scan to nodes whose tokens cover the query; queries without
+        // alphanumeric tokens (e.g. "" or "-") fall back to checking every node.
+        let candidates: Box<dyn Iterator<Item = &Node>> = match self.search_index.candidates(query)
+        {
+            Some(ids) => Box::new(ids.into_iter().filter_map(|id| self.nodes.get(&id))),
+            None => Box::new(self.nodes.values()),
+        };
+
+        for node in candidates {
+            if data_filter.is_some_and(|f| !f.matches(&node.data)) {
                 continue;
             }
-
-            if let Some(observations_val) = node.data.get("observations") {
-                if let Some(observations_arr) = observations_val.as_array() {
-                    for obs_val in observations_arr {
-                        if let Some(obs_str) = obs_val.as_str() {
-                            if obs_str.to_lowercase().contains(&query_lower) {
-                                matching_nodes_set.insert(node.id.clone());
-                                break; // Found a match in observations for this node
-                            }
-                        }
-                    }
-                }
+            if searchable_strings(node).any(|s| s.to_lowercase().contains(&query_lower)) {
+                matching_nodes_set.insert(node.id.clone());
             }
-            // Optionally, search in other parts of node.data if it's structured and known.
         }
 
         let filtered_entities: Vec<ApiEntity> = matching_nodes_set
@@ -494,6 +1284,231 @@ impl KnowledgeGraphState {
         (filtered_entities, filtered_relations)
     }
 
+    // Observations containing the query (case-insensitive), each with its entity, newest first.
+    // Observations without a recorded timestamp come last.
+    pub fn search_observations(&self, query: &str, limit: usize) -> Vec<ObservationMatch> {
+        let query_lower = query.to_lowercase();
+        let candidates: Box<dyn Iterator<Item = &Node>> = match self.search_index.candidates(query)
+        {
+            Some(ids) => Box::new(ids.into_iter().filter_map(|id| self.nodes.get(&id))),
+            None => Box::new(self.nodes.values()),
+        };
+
+        let mut matches: Vec<ObservationMatch> = candidates
+            .flat_map(|node| {
+                Self::observations_of(node)
+                    .into_iter()
+                    .filter(|observation| observation.to_lowercase().contains(&query_lower))
+                    .map(|observation| ObservationMatch {
+                        entity_name: node.id.clone(),
+                        entity_type: node.node_type.clone(),
+                        added_at_ms: node.observation_added_ms.get(&observation).copied(),
+                        added_by: node.observation_authors.get(&observation).cloned(),
+                        observation,
+                    })
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.added_at_ms
+                .cmp(&a.added_at_ms)
+                .then_with(|| a.entity_name.cmp(&b.entity_name))
+        });
+        matches.truncate(limit);
+        matches
+    }
+
+    // Finds relations whose type or any scalar value in their data contains the query
+    // (case-insensitive), together with the entities at both ends.
+    pub fn search_relations(&self, query: &str) -> (Vec<ApiEntity>, Vec<ApiRelation>) {
+        let query_lower = query.to_lowercase();
+        let matching_edges: Vec<&Edge> = self
+            .edges
+            .values()
+            .filter(|edge| {
+                edge.edge_type.to_lowercase().contains(&query_lower)
+                    || edge
+                        .data
+                        .as_ref()
+                        .is_some_and(|data| json_contains_text(data, &query_lower))
+            })
+            .collect();
+
+        let endpoint_ids: HashSet<&String> = matching_edges
+            .iter()
+            .flat_map(|edge| [&edge.source_node_id, &edge.target_node_id])
+            .collect();
+        let entities = endpoint_ids
+            .into_iter()
+            .filter_map(|id| self.nodes.get(id))
+            .map(|n| self.node_to_api_entity(n))
+            .collect();
+        let relations = matching_edges
+            .into_iter()
+            .map(|e| self.edge_to_api_relation(e))
+            .collect();
+
+        (entities, relations)
+    }
+
+    // The subgraph induced by entities of the given types: relations of the given types whose
+    // endpoints are both kept.
+    pub fn subgraph(
+        &self,
+        entity_types: Option<&[String]>,
+        relation_types: Option<&[String]>,
+    ) -> (Vec<ApiEntity>, Vec<ApiRelation>) {
+        let kept_nodes: HashSet<&String> = self
+            .nodes
+            .values()
+            .filter(|n| entity_types.is_none_or(|types| types.contains(&n.node_type)))
+            .map(|n| &n.id)
+            .collect();
+
+        let entities = kept_nodes
+            .iter()
+            .filter_map(|id| self.nodes.get(*id))
+            .map(|n| self.node_to_api_entity(n))
+            .collect();
+        let relations = self
+            .edges
+            .values()
+            .filter(|e| relation_types.is_none_or(|types| types.contains(&e.edge_type)))
+            .filter(|e| {
+                kept_nodes.contains(&e.source_node_id) && kept_nodes.contains(&e.target_node_id)
+            })
+            .map(|e| self.edge_to_api_relation(e))
+            .collect();
+
+        (entities, relations)
+    }
+
+    // Keeps suggestions between distinct existing entities that don't duplicate a relation
+    // (or an earlier suggestion), clamps confidences to [0, 1] and ranks them.
+    pub fn filter_relation_suggestions(
+        &self,
+        suggestions: Vec<RelationSuggestion>,
+        limit: usize,
+    ) -> Vec<RelationSuggestion> {
+        let mut seen: HashSet<(String, String, String)> = self
+            .edges
+            .values()
+            .map(|e| {
+                (
+                    e.source_node_id.clone(),
+                    e.target_node_id.clone(),
+                    e.edge_type.clone(),
+                )
+            })
+            .collect();
+        let mut kept: Vec<RelationSuggestion> = suggestions
+            .into_iter()
+            .filter(|s| s.from != s.to && !s.relation_type.trim().is_empty())
+            .filter(|s| self.nodes.contains_key(&s.from) && self.nodes.contains_key(&s.to))
+            .filter(|s| seen.insert((s.from.clone(), s.to.clone(), s.relation_type.clone())))
+            .map(|mut s| {
+                s.confidence = if s.confidence.is_finite() {
+                    s.confidence.clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                s
+            })
+            .collect();
+        kept.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+        kept.truncate(limit);
+        kept
+    }
+
+    // Values of the given kind starting with `prefix` (case-insensitive), sorted and capped
+    // at `limit`. Entities also match by alias, but always complete to their name.
+    pub fn complete(&self, kind: CompletionKind, prefix: &str, limit: usize) -> CompletionResult {
+        let prefix_lower = prefix.to_lowercase();
+        let candidates: BTreeSet<&str> = match kind {
+            CompletionKind::Entity => {
+                let names: BTreeSet<&str> = self
+                    .search_index
+                    .names_with_prefix(prefix)
+                    .map(|(_, id)| id)
+                    .collect();
+                return CompletionResult {
+                    total: names.len(),
+                    has_more: names.len() > limit,
+                    values: names.into_iter().take(limit).map(str::to_string).collect(),
+                };
+            }
+            CompletionKind::EntityType => {
+                self.nodes.values().map(|n| n.node_type.as_str()).collect()
+            }
+            CompletionKind::RelationType => {
+                self.edges.values().map(|e| e.edge_type.as_str()).collect()
+            }
+        };
+        let matching: Vec<&str> = candidates
+            .into_iter()
+            .filter(|value| value.to_lowercase().starts_with(&prefix_lower))
+            .collect();
+        CompletionResult {
+            total: matching.len(),
+            has_more: matching.len() > limit,
+            values: matching
+                .into_iter()
+                .take(limit)
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    // Entities whose name or one of whose aliases starts with `prefix` (case-insensitive),
+    // in order of the matched string, each listed once.
+    pub fn suggest(&self, prefix: &str, limit: usize) -> SuggestResult {
+        let prefix_lower = prefix.to_lowercase();
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut suggestions = Vec::new();
+        let mut has_more = false;
+        for (key, id) in self.search_index.names_with_prefix(prefix) {
+            if !seen.insert(id) {
+                continue;
+            }
+            if suggestions.len() == limit {
+                has_more = true;
+                break;
+            }
+            let Some(node) = self.nodes.get(id) else {
+                continue;
+            };
+            let alias = if node.id.to_lowercase().starts_with(&prefix_lower) {
+                None
+            } else {
+                aliases(node)
+                    .find(|alias| alias.to_lowercase() == key)
+                    .map(str::to_string)
+            };
+            suggestions.push(EntitySuggestion {
+                name: node.id.clone(),
+                entity_type: node.node_type.clone(),
+                alias,
+            });
+        }
+        SuggestResult {
+            suggestions,
+            has_more,
+        }
+    }
+
+    // Full nodes for the given ids in request order, each once, plus the ids that don't exist.
+    pub fn nodes_by_ids(&self, ids: &[String]) -> (Vec<Node>, Vec<String>) {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut nodes = Vec::new();
+        let mut missing = Vec::new();
+        for id in ids.iter().filter(|id| seen.insert(id.as_str())) {
+            match self.nodes.get(id) {
+                Some(node) => nodes.push(node.clone()),
+                None => missing.push(id.clone()),
+            }
+        }
+        (nodes, missing)
+    }
+
     // Get specific nodes by name (ID) and their interconnecting relations.
     pub fn open_nodes(&self, names: &[String]) -> (Vec<ApiEntity>, Vec<ApiRelation>) {
         let names_set: HashSet<&String> = names.iter().collect();
@@ -521,3 +1536,15 @@ impl KnowledgeGraphState {
         (filtered_entities, filtered_relations)
     }
 }
+
+// Whether any string, number or boolean inside `value` contains `query_lower` once lowercased.
+fn json_contains_text(value: &JsonValue, query_lower: &str) -> bool {
+    match value {
+        JsonValue::String(s) => s.to_lowercase().contains(query_lower),
+        JsonValue::Number(n) => n.to_string().contains(query_lower),
+        JsonValue::Bool(b) => b.to_string().contains(query_lower),
+        JsonValue::Array(arr) => arr.iter().any(|v| json_contains_text(v, query_lower)),
+        JsonValue::Object(map) => map.values().any(|v| json_contains_text(v, query_lower)),
+        JsonValue::Null => false,
+    }
+}
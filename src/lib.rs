@@ -1,171 +1,709 @@
+use axum::extract::{FromRequest, RawPathParams, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse};
+use axum::routing::{any, get, post};
+use axum::Json;
+use tower_service::Service;
 use worker::*;
 
 // Declare the new modules
+mod access;
+mod ai;
+mod algorithms;
+mod analytics;
+mod auth;
+mod auto_summary;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod cache;
+mod circuit;
+mod confidence;
+mod context;
+mod dedup;
+mod erasure;
+mod errors;
+mod export;
+mod filter;
+mod forget;
+mod idempotency;
+mod import;
 mod kg;
+mod lock;
+mod maintenance;
 mod mcp;
+mod metering;
+mod migrations;
+mod names;
+mod negative_cache;
+mod pinning;
+mod rate_limit;
+mod redact;
+mod relation_types;
+mod remember;
+mod replication;
+mod scheduled_export;
+mod search_index;
+mod signing;
+mod streaming;
+mod templates;
+mod time_range;
 mod types;
+mod validation;
 mod worker_do;
 
+// Prefix of the current (v1) REST API, for both worker and DO routes
+pub const API_V1_PREFIX: &str = "/v1";
+
 // Re-export KnowledgeGraphDO from the `worker_do` module
 // and can be recognized by wrangler for Durable Object bindings.
+pub use metering::MeteringDO;
+pub use rate_limit::RateLimiterDO;
 pub use worker_do::KnowledgeGraphDO;
 
+// Name of the DO instance of the default, shared graph. API keys and signed requests use it;
+// Access identities may be routed to a graph of their own instead (see Caller::graph_name).
+pub const KNOWLEDGE_GRAPH_NAME: &str = "default_knowledge_graph";
+
+// A knowledge graph DO stub with the name of the graph it serves; a read replica carries the
+// name of the graph it mirrors. The edge cache is scoped by graph (see cache.rs).
+pub struct GraphStub {
+    stub: Stub,
+    graph: String,
+}
+
+impl GraphStub {
+    pub fn new(stub: Stub, graph: &str) -> Self {
+        GraphStub {
+            stub,
+            graph: graph.to_string(),
+        }
+    }
+
+    pub fn graph(&self) -> &str {
+        &self.graph
+    }
+}
+
+impl std::ops::Deref for GraphStub {
+    type Target = Stub;
+
+    fn deref(&self) -> &Stub {
+        &self.stub
+    }
+}
+
+// Resolves the stub of a knowledge graph DO instance by name.
+fn graph_stub(env: &Env, do_id_name: &str) -> Result<GraphStub> {
+    let durable_object_binding_name = "KNOWLEDGE_GRAPH_DO";
+    let namespace = env
+        .durable_object(durable_object_binding_name)
+        .map_err(|e| {
+            console_error!(
+                "Failed to get Durable Object namespace '{}': {}",
+                durable_object_binding_name,
+                e
+            );
+            e
+        })?;
+    let id = namespace.id_from_name(do_id_name).map_err(|e| {
+        console_error!(
+            "Failed to get Durable Object ID from name '{}': {}",
+            do_id_name,
+            e
+        );
+        e
+    })?;
+    let stub = id.get_stub().map_err(|e| {
+        console_error!("Failed to get Durable Object stub for ID '{}': {}", id, e);
+        e
+    })?;
+    Ok(GraphStub::new(stub, do_id_name))
+}
+
 #[event(start)]
 pub fn start() {
     // Initialize the panic hook for better error messages.
     console_error_panic_hook::set_once();
 }
 
-#[event(fetch)]
-pub async fn main(req: Request, env: Env, _ctx: Context) -> Result<Response> {
-    let mut router = Router::new();
-
-    router = router
-        .get_async("/", |_req, _ctx| async move {
-            Response::ok(
-                "mcp-memory worker is running. Use /do/... for direct DO interaction or /mcp/... for MCP.",
-            )
-        })
-        .on_async("/do/*path", |worker_req, route_ctx| async move {
-            // Existing logic for /do/*path to forward to Durable Object
-            let env = route_ctx.env.clone();
-            let durable_object_binding_name = "KNOWLEDGE_GRAPH_DO";
-
-            let namespace = match env.durable_object(durable_object_binding_name) {
-                Ok(ns) => ns,
-                Err(e) => {
-                    console_error!("Failed to get Durable Object namespace '{}': {}", durable_object_binding_name, e);
-                    return Response::error(format!("Error getting DO namespace: {}", e), 500);
-                }
-            };
-
-            let do_id_name = "default_knowledge_graph"; // Consider making this configurable or dynamic
-            let id = match namespace.id_from_name(do_id_name) {
-                Ok(i) => i,
-                Err(e) => {
-                    console_error!(
-                        "Failed to get Durable Object ID from name '{}' for namespace '{}': {}",
-                        do_id_name, durable_object_binding_name, e
-                    );
-                    return Response::error(format!("Error getting DO ID from name: {}", e), 500);
-                }
-            };
+// The routes are an axum Router; handlers still work with worker's Request and Response, which
+// these adapters convert from and to axum's types. The Env is the router state.
+struct WorkerRequest(Request);
 
-            let stub = match id.get_stub() {
-                Ok(s) => s,
-                Err(e) => {
-                    console_error!("Failed to get Durable Object stub for ID '{}': {}", id, e);
-                    return Response::error(format!("Error getting DO stub: {}", e), 500);
-                }
-            };
-
-            let path_param = match route_ctx.param("path") {
-                Some(p) => p.to_string(),
-                None => String::new(), // Or handle as an error
-            };
-
-            let mut internal_path_for_do = format!("/{}", path_param);
-            if let Ok(url_obj) = worker_req.url() {
-                if let Some(query_str) = url_obj.query() {
-                    if !query_str.is_empty() {
-                        internal_path_for_do.push('?');
-                        internal_path_for_do.push_str(query_str);
-                    }
-                }
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequest<S> for WorkerRequest {
+    type Rejection = (StatusCode, String);
+
+    async fn from_request(
+        req: axum::extract::Request,
+        _state: &S,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        Request::try_from(req)
+            .map(WorkerRequest)
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+    }
+}
+
+struct WorkerResponse(Result<Response>);
+
+impl From<Result<Response>> for WorkerResponse {
+    fn from(result: Result<Response>) -> Self {
+        WorkerResponse(result)
+    }
+}
+
+impl IntoResponse for WorkerResponse {
+    fn into_response(self) -> axum::response::Response {
+        match self.0 {
+            Ok(response) => response.into(),
+            Err(e) => {
+                console_error!("Request failed: {}", e);
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
             }
+        }
+    }
+}
+
+// Forwards /do/*path (and /v1/do/*path) to the Durable Object, keeping the API version prefix.
+#[worker::send]
+async fn forward_to_do(
+    State(env): State<Env>,
+    params: RawPathParams,
+    WorkerRequest(worker_req): WorkerRequest,
+) -> std::result::Result<WorkerResponse, auth::AuthError> {
+    let caller = auth::authenticate(&worker_req, &env).await?;
+    // Not percent-decoded, so encoded ids reach the DO as the client sent them
+    let path_param = params
+        .iter()
+        .find(|(name, _)| *name == "path")
+        .map(|(_, value)| value.to_string())
+        .unwrap_or_default();
+    let response = rate_limit::enforce(
+        &env,
+        &caller,
+        |message| Response::error(message, 429),
+        forward_to_do_as(worker_req, env.clone(), path_param, &caller),
+    )
+    .await;
+    Ok(response.into())
+}
+
+async fn forward_to_do_as(
+    worker_req: Request,
+    env: Env,
+    path_param: String,
+    caller: &auth::Caller,
+) -> Result<Response> {
+    let durable_object_binding_name = "KNOWLEDGE_GRAPH_DO";
+
+    let namespace = match env.durable_object(durable_object_binding_name) {
+        Ok(ns) => ns,
+        Err(e) => {
+            console_error!(
+                "Failed to get Durable Object namespace '{}': {}",
+                durable_object_binding_name,
+                e
+            );
+            return Response::error(format!("Error getting DO namespace: {}", e), 500);
+        }
+    };
+
+    // Access identities may be routed to a graph of their own, see access.rs
+    let do_id_name = caller.graph_name();
+    let id = match namespace.id_from_name(do_id_name) {
+        Ok(i) => i,
+        Err(e) => {
+            console_error!(
+                "Failed to get Durable Object ID from name '{}' for namespace '{}': {}",
+                do_id_name,
+                durable_object_binding_name,
+                e
+            );
+            return Response::error(format!("Error getting DO ID from name: {}", e), 500);
+        }
+    };
 
-            let full_do_url = format!("https://durable-object.internal-url{}", internal_path_for_do);
-            let mut do_req_init = RequestInit::new();
-            do_req_init.with_method(worker_req.method());
+    let stub = match id.get_stub() {
+        Ok(s) => GraphStub::new(s, do_id_name),
+        Err(e) => {
+            console_error!("Failed to get Durable Object stub for ID '{}': {}", id, e);
+            return Response::error(format!("Error getting DO stub: {}", e), 500);
+        }
+    };
+
+    // Every check below looks at the route the DO will match, i.e. the decoded path
+    let do_route = auth::decoded_do_route(&format!("/{}", path_param));
+
+    // The version prefix goes before /do; the DO would strip a nested one (/do/v1/...) and
+    // run a different route than the one checked below
+    if do_route == "/v1" || do_route.starts_with("/v1/") {
+        return Response::error("Not Found", 404);
+    }
+
+    // Replica addressing and snapshot delivery are internal to the DOs
+    if do_route.starts_with("/internal/") || do_route.starts_with("/replica/") {
+        return Response::error("Not Found", 404);
+    }
+
+    let required_scope = auth::scope_for_do_route(&worker_req.method(), &do_route);
+    if !caller.has_scope(required_scope) {
+        return Response::error(
+            format!(
+                "Forbidden: this route requires the '{}' scope",
+                required_scope.as_str()
+            ),
+            403,
+        );
+    }
+    if caller.is_redacted()
+        && required_scope == auth::Scope::Read
+        && !redact::redacts_route(&worker_req.method(), &do_route)
+    {
+        return Response::error(
+            "Forbidden: this route is not available to redacted keys",
+            403,
+        );
+    }
 
-            if let Some(content_type) = worker_req.headers().get("content-type")? {
-                let mut do_headers = Headers::new();
-                do_headers.set("content-type", &content_type)?;
-                do_req_init.with_headers(do_headers);
+    let method = worker_req.method();
+    let version_prefix = if worker_req.path().starts_with(API_V1_PREFIX) {
+        API_V1_PREFIX
+    } else {
+        ""
+    };
+    let mut query_suffix = String::new();
+    if let Ok(url_obj) = worker_req.url() {
+        if let Some(query_str) = url_obj.query() {
+            if !query_str.is_empty() {
+                query_suffix.push('?');
+                query_suffix.push_str(query_str);
             }
+        }
+    }
+    // Restricted keys can't opt out of redaction; the DO reads the last `redact` it is given
+    if caller.is_redacted() {
+        query_suffix.push(if query_suffix.is_empty() { '?' } else { '&' });
+        query_suffix.push_str("redact=true");
+    }
+    let internal_path_for_do = format!("{}/{}{}", version_prefix, path_param, query_suffix);
 
-            let method = worker_req.method();
-            if method == Method::Post || method == Method::Put || method == Method::Patch {
-                if let Ok(mut cloned_req) = worker_req.clone()  { // Ensure cloning is successful and make the clone mutable
-                    let body_bytes = cloned_req.bytes().await?;
-                    do_req_init.with_body(Some(body_bytes.into()));
-                } else {
-                     return Response::error("Failed to clone request for body forwarding", 500);
-                }
+    // Read-only requests go to the nearest read replica when there is one, see replication.rs
+    let replica = if required_scope == auth::Scope::Read {
+        replication::nearest_replica(&env, &worker_req, do_id_name)
+    } else {
+        None
+    };
+    let replica_path = format!(
+        "{}{}/{}{}",
+        version_prefix,
+        replication::REPLICA_PATH_PREFIX,
+        path_param,
+        query_suffix
+    );
+
+    // Usage is metered per resource, e.g. "POST /graph"; ids in deeper segments are left out
+    let operation = format!(
+        "{} /{}",
+        method.as_ref(),
+        path_param.split('/').next().unwrap_or_default()
+    );
+
+    // Reads are served through the edge cache, see cache.rs
+    if method == Method::Get {
+        let if_none_match = worker_req.headers().get("if-none-match")?;
+        let mut replica_response = None;
+        if let Some(replica) = &replica {
+            let response =
+                cache::get_from_do(replica, &replica_path, if_none_match.as_deref()).await?;
+            // A replica that hasn't been synchronized yet answers 503
+            if response.status_code() != 503 {
+                replica_response = Some(response);
             }
+        }
+        let response = match replica_response {
+            Some(response) => response,
+            None => {
+                cache::get_from_do(&stub, &internal_path_for_do, if_none_match.as_deref()).await?
+            }
+        };
+        metering::record(&env, caller, &operation, 0, 0).await;
+        return Ok(response);
+    }
 
-            let do_req = Request::new_with_init(&full_do_url, &do_req_init)?;
-            stub.fetch_with_request(do_req).await
-        });
+    let full_do_url = format!(
+        "https://durable-object.internal-url{}",
+        internal_path_for_do
+    );
+    let mut do_req_init = RequestInit::new();
+    do_req_init.with_method(worker_req.method());
 
-    // Conditionally add MCP routes if "mcp" feature is enabled
+    // If-None-Match lets the DO answer conditional searches with 304; Idempotency-Key marks a
+    // write as safe to retry (see circuit.rs)
+    let mut do_headers = Headers::new();
+    for name in [
+        "content-type",
+        "if-none-match",
+        circuit::IDEMPOTENCY_KEY_HEADER,
+    ] {
+        if let Some(value) = worker_req.headers().get(name)? {
+            do_headers.set(name, &value)?;
+        }
+    }
+    do_headers.set(auth::ACTOR_HEADER, caller.id())?;
+    do_req_init.with_headers(do_headers);
 
-    {
-        router = router
-            .get_async("/mcp/tools", |_req, _ctx| async move {
-                mcp::list_tools_handler().await
-            })
-            .post_async("/mcp/tool/call", |worker_req, route_ctx| async move {
-                // Removed mut from worker_req
-                // MCP tool calls need access to the DO stub
-                let env = route_ctx.env.clone();
-                let durable_object_binding_name = "KNOWLEDGE_GRAPH_DO";
-
-                let namespace = match env.durable_object(durable_object_binding_name) {
-                    Ok(ns) => ns,
-                    Err(e) => {
-                        console_error!(
-                            "MCP: Failed to get DO namespace '{}': {}",
-                            durable_object_binding_name,
-                            e
+    let mut bytes_written = 0;
+    if method == Method::Post || method == Method::Put || method == Method::Patch {
+        if let Ok(mut cloned_req) = worker_req.clone() {
+            // Ensure cloning is successful and make the clone mutable
+            let body_bytes = cloned_req.bytes().await?;
+            // Forgetting whole entities takes the admin scope; a body the DO can't parse is
+            // refused there
+            if method == Method::Post && do_route == "/graph/forget" {
+                if let Ok(payload) = serde_json::from_slice::<types::ForgetPayload>(&body_bytes) {
+                    let scope = forget::required_scope(&payload);
+                    if !caller.has_scope(scope) {
+                        return Response::error(
+                            format!(
+                                "Forbidden: forgetting entities requires the '{}' scope",
+                                scope.as_str()
+                            ),
+                            403,
                         );
-                        // Return an MCP-formatted error
-                        let err_resp = serde_json::json!({
-                            "error": {
-                                "code": "NamespaceError",
-                                "message": format!("Error getting DO namespace: {}", e)
-                            }
-                        });
-                        return Response::from_json(&err_resp).map(|r| r.with_status(500));
                     }
-                };
-
-                let do_id_name = "default_knowledge_graph";
-                let id = match namespace.id_from_name(do_id_name) {
-                    Ok(i) => i,
-                    Err(e) => {
-                        console_error!(
-                            "MCP: Failed to get DO ID from name '{}' for namespace '{}': {}",
-                            do_id_name,
-                            durable_object_binding_name,
-                            e
-                        );
-                        let err_resp = serde_json::json!({
-                            "error": {
-                                "code": "DurableObjectIdError",
-                                "message": format!("Error getting DO ID from name: {}", e)
-                            }
-                        });
-                        return Response::from_json(&err_resp).map(|r| r.with_status(500));
-                    }
-                };
-
-                let stub = match id.get_stub() {
-                    Ok(s) => s,
-                    Err(e) => {
-                        console_error!("MCP: Failed to get DO stub for ID '{}': {}", id, e);
-                        let err_resp = serde_json::json!({
-                            "error": {
-                                "code": "StubError",
-                                "message": format!("Error getting DO stub: {}", e)
-                            }
-                        });
-                        return Response::from_json(&err_resp).map(|r| r.with_status(500));
-                    }
-                };
-                mcp::call_tool_handler(worker_req, stub).await
+                }
+            }
+            if required_scope != auth::Scope::Read {
+                bytes_written = body_bytes.len() as u64;
+            }
+            do_req_init.with_body(Some(body_bytes.into()));
+        } else {
+            return Response::error("Failed to clone request for body forwarding", 500);
+        }
+    }
+
+    let mut replica_response = None;
+    if let Some(replica) = &replica {
+        let replica_req = Request::new_with_init(
+            &format!("https://durable-object.internal-url{}", replica_path),
+            &do_req_init,
+        )?;
+        let response = circuit::fetch(replica, replica_req).await?;
+        if response.status_code() != 503 {
+            replica_response = Some(response);
+        }
+    }
+    let response = match replica_response {
+        Some(response) => response,
+        None => {
+            let do_req = Request::new_with_init(&full_do_url, &do_req_init)?;
+            circuit::fetch(&stub, do_req).await?
+        }
+    };
+    cache::invalidate_after_write(&stub, &response).await;
+    metering::record(
+        &env,
+        caller,
+        &operation,
+        metering::entities_created(&response),
+        bytes_written,
+    )
+    .await;
+    // The DO's Location is a path of its own; clients reach it under /do (and /v1)
+    match response.headers().get("location")? {
+        Some(location) if location.starts_with('/') => {
+            let mut headers = response.headers().clone();
+            headers.set("location", &format!("{}/do{}", version_prefix, location))?;
+            Ok(response.with_headers(headers))
+        }
+        _ => Ok(response),
+    }
+}
+
+// Authenticates a REST MCP request, answering failures in the legacy error format.
+async fn authenticate_mcp_rest(
+    req: &Request,
+    env: &Env,
+) -> std::result::Result<auth::Caller, Response> {
+    auth::authenticate(req, env).await.map_err(|e| {
+        mcp::mcp_error_response("Unauthorized", &e.to_string()).with_status(e.status())
+    })
+}
+
+fn mcp_rate_limited(message: &str) -> Result<Response> {
+    Ok(mcp::mcp_error_response("RateLimited", message))
+}
+
+#[worker::send]
+async fn mcp_list_tools(
+    State(env): State<Env>,
+    WorkerRequest(req): WorkerRequest,
+) -> WorkerResponse {
+    match authenticate_mcp_rest(&req, &env).await {
+        Ok(caller) => rate_limit::enforce(
+            &env,
+            &caller,
+            mcp_rate_limited,
+            mcp::list_tools_handler(&caller),
+        )
+        .await
+        .into(),
+        Err(resp) => Ok(resp).into(),
+    }
+}
+
+#[worker::send]
+async fn mcp_call_tool(
+    State(env): State<Env>,
+    WorkerRequest(worker_req): WorkerRequest,
+) -> WorkerResponse {
+    let caller = match authenticate_mcp_rest(&worker_req, &env).await {
+        Ok(caller) => caller,
+        Err(resp) => return Ok(resp).into(),
+    };
+    rate_limit::enforce(
+        &env,
+        &caller,
+        mcp_rate_limited,
+        mcp_call_tool_as(worker_req, env.clone(), &caller),
+    )
+    .await
+    .into()
+}
+
+async fn mcp_call_tool_as(
+    worker_req: Request,
+    env: Env,
+    caller: &auth::Caller,
+) -> Result<Response> {
+    // MCP tool calls need access to the DO stub
+    let durable_object_binding_name = "KNOWLEDGE_GRAPH_DO";
+
+    let namespace = match env.durable_object(durable_object_binding_name) {
+        Ok(ns) => ns,
+        Err(e) => {
+            console_error!(
+                "MCP: Failed to get DO namespace '{}': {}",
+                durable_object_binding_name,
+                e
+            );
+            // Return an MCP-formatted error
+            let err_resp = serde_json::json!({
+                "error": {
+                    "code": "NamespaceError",
+                    "message": format!("Error getting DO namespace: {}", e)
+                }
+            });
+            return Response::from_json(&err_resp).map(|r| r.with_status(500));
+        }
+    };
+
+    let do_id_name = caller.graph_name();
+    let id = match namespace.id_from_name(do_id_name) {
+        Ok(i) => i,
+        Err(e) => {
+            console_error!(
+                "MCP: Failed to get DO ID from name '{}' for namespace '{}': {}",
+                do_id_name,
+                durable_object_binding_name,
+                e
+            );
+            let err_resp = serde_json::json!({
+                "error": {
+                    "code": "DurableObjectIdError",
+                    "message": format!("Error getting DO ID from name: {}", e)
+                }
+            });
+            return Response::from_json(&err_resp).map(|r| r.with_status(500));
+        }
+    };
+
+    let stub = match id.get_stub() {
+        Ok(s) => GraphStub::new(s, do_id_name),
+        Err(e) => {
+            console_error!("MCP: Failed to get DO stub for ID '{}': {}", id, e);
+            let err_resp = serde_json::json!({
+                "error": {
+                    "code": "StubError",
+                    "message": format!("Error getting DO stub: {}", e)
+                }
             });
+            return Response::from_json(&err_resp).map(|r| r.with_status(500));
+        }
+    };
+    mcp::call_tool_handler(worker_req, stub, caller, &env).await
+}
+
+#[worker::send]
+async fn mcp_jsonrpc(
+    State(env): State<Env>,
+    WorkerRequest(worker_req): WorkerRequest,
+) -> WorkerResponse {
+    mcp_jsonrpc_as(worker_req, env).await.into()
+}
+
+async fn mcp_jsonrpc_as(worker_req: Request, env: Env) -> Result<Response> {
+    // MCP JSON-RPC transport; failures are reported as JSON-RPC errors
+    let caller = match auth::authenticate(&worker_req, &env).await {
+        Ok(caller) => caller,
+        Err(e) => {
+            return Response::from_json(&mcp::JsonRpcResponse::failure(
+                serde_json::Value::Null,
+                mcp::error_codes::UNAUTHORIZED,
+                e.to_string(),
+                None,
+            ))
+            .map(|r| r.with_status(e.status()))
+        }
+    };
+    let stub = match graph_stub(&env, caller.graph_name()) {
+        Ok(s) => s,
+        Err(e) => {
+            return Response::from_json(&mcp::JsonRpcResponse::failure(
+                serde_json::Value::Null,
+                mcp::error_codes::DO_UNAVAILABLE,
+                format!("Knowledge graph unavailable: {}", e),
+                None,
+            ))
+        }
+    };
+    rate_limit::enforce(
+        &env,
+        &caller,
+        |message| {
+            Response::from_json(&mcp::JsonRpcResponse::failure(
+                serde_json::Value::Null,
+                mcp::error_codes::RATE_LIMITED,
+                message.to_string(),
+                None,
+            ))
+        },
+        mcp::jsonrpc_handler(worker_req, stub, &caller, &env),
+    )
+    .await
+}
+
+// Time-bucketed usage per caller (query: from, to, granularity=hour|day, caller); admin only.
+#[worker::send]
+async fn admin_usage(
+    State(env): State<Env>,
+    WorkerRequest(req): WorkerRequest,
+) -> std::result::Result<WorkerResponse, auth::AuthError> {
+    let caller = auth::authenticate(&req, &env).await?;
+    if !caller.has_scope(auth::Scope::Admin) {
+        return Ok(Response::error("Forbidden: this route requires the 'admin' scope", 403).into());
+    }
+    let query = req.url().map(|url| url.query().map(str::to_string));
+    Ok(match query {
+        Ok(query) => metering::usage_report(&env, query.as_deref()).await.into(),
+        Err(e) => Err(e).into(),
+    })
+}
+
+// Reports the caller's identity, scopes and rate limit window without counting a request.
+#[worker::send]
+async fn quota(
+    State(env): State<Env>,
+    WorkerRequest(req): WorkerRequest,
+) -> std::result::Result<Json<types::QuotaResponse>, auth::AuthError> {
+    let caller = auth::authenticate(&req, &env).await?;
+    Ok(Json(types::QuotaResponse {
+        caller: caller.id().to_string(),
+        scopes: caller.scopes().map(|s| s.as_str().to_string()).collect(),
+        rate_limit: rate_limit::status(&env, &caller).await,
+    }))
+}
+
+// Static single-page graph browser; it calls /v1/do/* with the API key the user enters, so
+// serving the page itself needs no authentication.
+const UI_HTML: &str = include_str!("ui.html");
+
+async fn ui() -> Html<&'static str> {
+    Html(UI_HTML)
+}
+
+async fn index() -> &'static str {
+    "mcp-memory worker is running. Use /v1/do/... for direct DO interaction or /v1/mcp/... for MCP."
+}
+
+// One log line per request with its status and duration.
+async fn log_request(req: axum::extract::Request, next: Next) -> axum::response::Response {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let started_ms = Date::now().as_millis();
+    let response = next.run(req).await;
+    console_log!(
+        "{} {} -> {} ({} ms)",
+        method,
+        path,
+        response.status().as_u16(),
+        Date::now().as_millis().saturating_sub(started_ms)
+    );
+    response
+}
+
+// Comma-separated origins allowed to call the API from browsers, or "*"; unset sends no CORS
+// headers, so only same-origin pages (like /ui) can use it.
+const CORS_ALLOWED_ORIGINS_VAR: &str = "CORS_ALLOWED_ORIGINS";
+
+fn cors_layer(env: &Env) -> Option<tower_http::cors::CorsLayer> {
+    use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+    let origins = env.var(CORS_ALLOWED_ORIGINS_VAR).ok()?.to_string();
+    let allow_origin = if origins.trim() == "*" {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(
+            origins
+                .split(',')
+                .filter_map(|origin| origin.trim().parse().ok()),
+        )
+    };
+    Some(
+        CorsLayer::new()
+            .allow_origin(allow_origin)
+            .allow_methods(Any)
+            .allow_headers(Any)
+            .expose_headers(Any),
+    )
+}
+
+fn router(env: Env) -> axum::Router {
+    let mut router = axum::Router::new()
+        .route("/", get(index))
+        .route("/ui", get(ui));
+    // Every route is served under /v1; the unprefixed paths are kept as aliases for existing clients.
+    for prefix in [API_V1_PREFIX, ""] {
+        router = router
+            .route(&format!("{}/quota", prefix), get(quota))
+            .route(&format!("{}/admin/usage", prefix), get(admin_usage))
+            .route(&format!("{}/do/*path", prefix), any(forward_to_do))
+            .route(&format!("{}/mcp/tools", prefix), get(mcp_list_tools))
+            .route(&format!("{}/mcp/tool/call", prefix), post(mcp_call_tool))
+            .route(&format!("{}/mcp", prefix), post(mcp_jsonrpc));
     }
+    // Shared middleware; layers added last run first
+    if let Some(cors) = cors_layer(&env) {
+        router = router.layer(cors);
+    }
+    router
+        .layer(middleware::from_fn(log_request))
+        .with_state(env)
+}
+
+#[event(fetch)]
+pub async fn main(
+    req: HttpRequest,
+    env: Env,
+    _ctx: Context,
+) -> Result<axum::http::Response<axum::body::Body>> {
+    circuit::configure(&env);
+    Ok(router(env).call(req).await?)
+}
 
-    router.run(req, env).await
+// Cron trigger (see wrangler.toml): periodic exports, see scheduled_export.rs
+#[event(scheduled)]
+pub async fn scheduled(event: ScheduledEvent, env: Env, _ctx: ScheduleContext) {
+    console_log!("Scheduled export triggered by '{}'", event.cron());
+    scheduled_export::run(&env, event.schedule() as u64).await;
 }
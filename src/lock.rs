@@ -0,0 +1,49 @@
+use crate::types::{GraphLock, LockPayload, LockStatus};
+
+// Maintenance lock for POST /graph/admin/lock and /unlock. While it holds, the DO refuses
+// write routes with 503 and a Retry-After header, so writes can't interleave with a long
+// export, migration or repair; admin routes and reads still go through. The lock expires on
+// its own after its TTL, so an operator who never unlocks can't wedge the graph for good.
+
+pub const DEFAULT_LOCK_TTL_SECONDS: u64 = 15 * 60;
+pub const MAX_LOCK_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+impl GraphLock {
+    pub fn new(payload: LockPayload, now_ms: u64) -> Result<GraphLock, String> {
+        let ttl_seconds = payload.ttl_seconds.unwrap_or(DEFAULT_LOCK_TTL_SECONDS);
+        if ttl_seconds == 0 || ttl_seconds > MAX_LOCK_TTL_SECONDS {
+            return Err(format!(
+                "ttl_seconds must be between 1 and {}",
+                MAX_LOCK_TTL_SECONDS
+            ));
+        }
+        Ok(GraphLock {
+            reason: payload.reason.filter(|r| !r.trim().is_empty()),
+            locked_at_ms: now_ms,
+            expires_at_ms: now_ms + ttl_seconds * 1000,
+        })
+    }
+
+    pub fn is_active(&self, now_ms: u64) -> bool {
+        now_ms < self.expires_at_ms
+    }
+
+    // Seconds until the lock expires, rounded up; what a refused writer is told to wait.
+    pub fn retry_after_seconds(&self, now_ms: u64) -> u64 {
+        self.expires_at_ms
+            .saturating_sub(now_ms)
+            .div_ceil(1000)
+            .max(1)
+    }
+}
+
+impl LockStatus {
+    // An expired lock reads as unlocked.
+    pub fn of(lock: Option<GraphLock>, now_ms: u64) -> LockStatus {
+        let lock = lock.filter(|l| l.is_active(now_ms));
+        LockStatus {
+            locked: lock.is_some(),
+            lock,
+        }
+    }
+}
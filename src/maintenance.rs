@@ -0,0 +1,252 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{
+    CompactionReport, DanglingEdge, DuplicateEdgeGroup, MaintenanceReport, RepairReport,
+    ValidationReport,
+};
+use serde_json::{json, Map, Value as JsonValue};
+use std::collections::BTreeMap;
+
+const REPAIR_FIXES: &[&str] = &["orphan_edges", "malformed_observations"];
+
+// Converts a stored observation value to its string form; `None` drops it.
+fn observation_to_string(value: &JsonValue) -> Option<String> {
+    match value {
+        JsonValue::String(s) => Some(s.clone()),
+        JsonValue::Null => None,
+        other => Some(other.to_string()),
+    }
+}
+
+// Returns the normalized form of a node's data, or `None` if it is already well-formed:
+// data must be an object, and "observations" (when present) a deduplicated array of strings.
+pub fn normalized_node_data(data: &JsonValue) -> Option<JsonValue> {
+    let mut map = match data {
+        JsonValue::Object(map) => map.clone(),
+        JsonValue::Null => {
+            let mut map = Map::new();
+            map.insert("observations".to_string(), json!([]));
+            return Some(JsonValue::Object(map));
+        }
+        // Keep scalar/array data around instead of discarding it
+        other => {
+            let mut map = Map::new();
+            map.insert("value".to_string(), other.clone());
+            map.insert("observations".to_string(), json!([]));
+            return Some(JsonValue::Object(map));
+        }
+    };
+
+    let observations = match map.get("observations") {
+        None => return None,
+        Some(JsonValue::Array(arr)) => arr.clone(),
+        Some(JsonValue::String(s)) => vec![JsonValue::String(s.clone())],
+        Some(_) => Vec::new(),
+    };
+    let mut normalized: Vec<String> = Vec::new();
+    for obs in &observations {
+        if let Some(s) = observation_to_string(obs) {
+            if !normalized.contains(&s) {
+                normalized.push(s);
+            }
+        }
+    }
+    let normalized_value = json!(normalized);
+    if map.get("observations") == Some(&normalized_value) {
+        return None;
+    }
+    map.insert("observations".to_string(), normalized_value);
+    Some(JsonValue::Object(map))
+}
+
+// Observations are optional, but when present they must be an array of strings.
+fn has_malformed_observations(data: &JsonValue) -> bool {
+    match data.get("observations") {
+        None => false,
+        Some(JsonValue::Array(arr)) => arr.iter().any(|obs| !obs.is_string()),
+        Some(_) => true,
+    }
+}
+
+impl KnowledgeGraphState {
+    pub fn orphaned_edge_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self
+            .edges
+            .values()
+            .filter(|edge| {
+                !self.nodes.contains_key(&edge.source_node_id)
+                    || !self.nodes.contains_key(&edge.target_node_id)
+            })
+            .map(|edge| edge.id.clone())
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    // Checks the invariants that compact() and the write paths maintain, without fixing anything.
+    pub fn validate(&self) -> ValidationReport {
+        let mut dangling_edges: Vec<DanglingEdge> = self
+            .edges
+            .values()
+            .filter_map(|edge| {
+                let missing_source = !self.nodes.contains_key(&edge.source_node_id);
+                let missing_target = !self.nodes.contains_key(&edge.target_node_id);
+                (missing_source || missing_target).then(|| DanglingEdge {
+                    edge_id: edge.id.clone(),
+                    missing_source,
+                    missing_target,
+                })
+            })
+            .collect();
+        dangling_edges.sort_by(|a, b| a.edge_id.cmp(&b.edge_id));
+
+        let mut non_object_data = Vec::new();
+        let mut malformed_observations = Vec::new();
+        for node in self.nodes.values() {
+            if !node.data.is_object() {
+                non_object_data.push(node.id.clone());
+            } else if has_malformed_observations(&node.data) {
+                malformed_observations.push(node.id.clone());
+            }
+        }
+        non_object_data.sort();
+        malformed_observations.sort();
+
+        let mut tuples: BTreeMap<(&str, &str, &str), Vec<String>> = BTreeMap::new();
+        for edge in self.edges.values() {
+            tuples
+                .entry((&edge.source_node_id, &edge.target_node_id, &edge.edge_type))
+                .or_default()
+                .push(edge.id.clone());
+        }
+        let duplicate_edges: Vec<DuplicateEdgeGroup> = tuples
+            .into_iter()
+            .filter(|(_, edge_ids)| edge_ids.len() > 1)
+            .map(|((source, target, edge_type), mut edge_ids)| {
+                edge_ids.sort();
+                DuplicateEdgeGroup {
+                    source_node_id: source.to_string(),
+                    target_node_id: target.to_string(),
+                    edge_type: edge_type.to_string(),
+                    edge_ids,
+                }
+            })
+            .collect();
+
+        ValidationReport {
+            valid: dangling_edges.is_empty()
+                && non_object_data.is_empty()
+                && malformed_observations.is_empty()
+                && duplicate_edges.is_empty(),
+            nodes_checked: self.nodes.len(),
+            edges_checked: self.edges.len(),
+            dangling_edges,
+            non_object_data,
+            malformed_observations,
+            duplicate_edges,
+        }
+    }
+
+    pub fn remove_orphaned_edges(&mut self) -> Vec<String> {
+        let ids = self.orphaned_edge_ids();
+        for id in &ids {
+            self.edges.remove(id);
+        }
+        ids
+    }
+
+    pub fn normalize_node_data(&mut self) -> Vec<String> {
+        let mut normalized_ids = Vec::new();
+        for node in self.nodes.values_mut() {
+            if let Some(data) = normalized_node_data(&node.data) {
+                node.data = data;
+                normalized_ids.push(node.id.clone());
+            }
+        }
+        normalized_ids.sort();
+        normalized_ids
+    }
+
+    // Repairs state left behind by older or buggy writes. Timestamps are left untouched since
+    // the content as seen by clients doesn't change meaningfully.
+    pub fn compact(&mut self) -> CompactionReport {
+        let report = CompactionReport {
+            orphaned_edges_removed: self.remove_orphaned_edges(),
+            nodes_normalized: self.normalize_node_data(),
+        };
+        // Normalization may change observations, and a rebuild also repairs any index drift
+        self.rebuild_search_index();
+        report
+    }
+
+    // Applies the named fixes from POST /graph/repair?fix=...; `None` applies all of them.
+    pub fn repair(&mut self, fixes: Option<&[&str]>) -> Result<RepairReport, String> {
+        if let Some(unknown) = fixes
+            .unwrap_or_default()
+            .iter()
+            .find(|fix| !REPAIR_FIXES.contains(fix))
+        {
+            return Err(format!(
+                "Unknown fix '{}' (expected one of: {})",
+                unknown,
+                REPAIR_FIXES.join(", ")
+            ));
+        }
+        let requested = |fix: &str| fixes.is_none_or(|fixes| fixes.contains(&fix));
+        let mut report = RepairReport::default();
+        if requested("orphan_edges") {
+            report.orphan_edges_removed = Some(self.remove_orphaned_edges());
+        }
+        if requested("malformed_observations") {
+            let normalized = self.normalize_node_data();
+            if !normalized.is_empty() {
+                self.rebuild_search_index();
+            }
+            report.nodes_normalized = Some(normalized);
+        }
+        Ok(report)
+    }
+
+    // Drops clear_graph and forget confirmation tokens that can no longer be redeemed.
+    pub fn expire_pending_clear(&mut self, now_ms: u64) -> bool {
+        let mut expired = false;
+        if self
+            .pending_clear
+            .as_ref()
+            .is_some_and(|pending| pending.expires_at_ms < now_ms)
+        {
+            self.pending_clear = None;
+            expired = true;
+        }
+        if self
+            .pending_forget
+            .as_ref()
+            .is_some_and(|pending| pending.confirmation.expires_at_ms < now_ms)
+        {
+            self.pending_forget = None;
+            expired = true;
+        }
+        expired
+    }
+
+    // Graph-side part of the periodic maintenance run; the DO fills in storage housekeeping
+    // (sessions_purged), summaries (entities_summarized) and scheduling (next_run_at_ms).
+    pub fn housekeeping(&mut self, now_ms: u64) -> MaintenanceReport {
+        let compaction = self.compact();
+        let clear_token_expired = self.expire_pending_clear(now_ms);
+        let faded_relations_removed = match self.confidence_floor() {
+            Some(floor) => self.prune_faded_relations(now_ms, floor, false),
+            None => Vec::new(),
+        };
+        MaintenanceReport {
+            ran_at_ms: now_ms,
+            compaction,
+            clear_token_expired,
+            sessions_purged: 0,
+            idempotency_keys_purged: 0,
+            faded_relations_removed,
+            entities_summarized: Vec::new(),
+            stats: self.stats(),
+            next_run_at_ms: 0,
+        }
+    }
+}
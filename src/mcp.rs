@@ -1,42 +1,83 @@
+use crate::auth::{self, Caller, Scope};
+use crate::cache;
+use crate::circuit;
+use crate::errors;
+use crate::forget;
+use crate::metering;
+use crate::replication::{self, REPLICA_PATH_PREFIX};
 use crate::types::{
-    AddObservationItem,
-    AddObservationsPayload,
-    CreateEntitiesPayload,
-    CreateRelationsPayload,
-    DeleteEntitiesPayload,
-    DeleteObservationItem,
-    DeleteObservationsPayload,
-    DeleteRelationsPayload,
-    Edge as DoEdge, // For deserializing DO responses if needed for create_*
-    EntityToCreate,
-    KnowledgeGraphDataResponse,
-    Node as DoNode,
-    OpenNodesQuery,
-    RelationToCreate,
-    RelationToDelete,
-    SearchNodesQuery,
+    AddObservationsPayload, AnchoredSearchResponse, BatchResponse, BatchStatus, BuildContextQuery,
+    ClearGraphPayload, ClearGraphResponse, CompletionKind, CompletionResult, ContextPack,
+    CreateEntitiesPayload, CreateRelationsPayload, DeleteEntitiesPayload,
+    DeleteObservationsPayload, DeleteRelationsPayload, DoErrorDetail, EntitySummary, ForgetPayload,
+    ForgetResponse, GroupedSearchResponse, KnowledgeGraphDataResponse, Node, NodeEdge,
+    OpenNodesQuery, PinEntitiesPayload, RecentEntitiesResponse, RememberPayload, RememberResponse,
+    SearchNodesQuery, SearchObservationsQuery, SearchObservationsResponse, SearchRelationsQuery,
+    SessionLogLevel, SuggestRelationsPayload, SuggestRelationsResponse, SummarizePayload,
 };
-use serde::{Deserialize, Serialize};
+use crate::validation::{self, Validate};
+use crate::{GraphStub, API_V1_PREFIX};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
-use worker::{Headers, Method, Request as WorkerRequest, RequestInit, Response, Result, Stub};
+use worker::{Env, Headers, Method, Request as WorkerRequest, RequestInit, Response, Result};
 
 // --- MCP Request/Response Structures ---
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct ToolInputSchema {
-    #[serde(rename = "type")]
-    pub schema_type: String,
-    pub properties: Value,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub required: Option<Vec<String>>,
-}
-
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ToolDefinition {
     pub name: String,
     pub description: String,
     #[serde(rename = "inputSchema")]
     pub input_schema: Value, // Using Value for flexibility with complex schemas
+    #[serde(rename = "outputSchema")]
+    pub output_schema: Value, // Shape of the result's structuredContent
+    pub annotations: ToolAnnotations,
+}
+
+// Behavior hints for hosts (e.g. whether to ask for confirmation). Hints are advisory only.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolAnnotations {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_only_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub destructive_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idempotent_hint: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub open_world_hint: Option<bool>,
+}
+
+impl ToolAnnotations {
+    // Only reads the graph
+    fn read_only() -> Self {
+        ToolAnnotations {
+            read_only_hint: Some(true),
+            open_world_hint: Some(false),
+            ..Default::default()
+        }
+    }
+
+    // Adds to the graph without removing or overwriting anything
+    fn additive(idempotent: bool) -> Self {
+        ToolAnnotations {
+            read_only_hint: Some(false),
+            destructive_hint: Some(false),
+            idempotent_hint: Some(idempotent),
+            open_world_hint: Some(false),
+        }
+    }
+
+    // May delete or overwrite existing data
+    fn destructive(idempotent: bool) -> Self {
+        ToolAnnotations {
+            read_only_hint: Some(false),
+            destructive_hint: Some(true),
+            idempotent_hint: Some(idempotent),
+            open_world_hint: Some(false),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -61,6 +102,8 @@ pub struct ContentBlock {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct CallToolResponse {
     pub content: Vec<ContentBlock>,
+    #[serde(rename = "structuredContent", skip_serializing_if = "Option::is_none")]
+    pub structured_content: Option<Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -77,7 +120,7 @@ pub struct McpErrorResponse {
     pub error: McpError,
 }
 
-fn mcp_error_response(code: &str, message: &str) -> Response {
+pub fn mcp_error_response(code: &str, message: &str) -> Response {
     Response::from_json(&McpErrorResponse {
         error: McpError {
             code: code.to_string(),
@@ -92,80 +135,111 @@ fn mcp_error_response(code: &str, message: &str) -> Response {
 // --- Argument Structs for MCP Tool Calls (matching TS version schemas) ---
 
 #[derive(Deserialize, Debug)]
-struct McpEntityToCreate {
-    name: String,
-    #[serde(rename = "entityType")]
-    entity_type: String,
+struct McpSearchNodesArgs {
+    #[serde(default)]
+    query: Option<String>,
     #[serde(default)]
-    observations: Vec<String>,
+    queries: Vec<String>,
+    #[serde(default)]
+    data_filter: Option<String>,
+    #[serde(default)]
+    anchor: Option<String>,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpCreateEntitiesArgs {
-    entities: Vec<McpEntityToCreate>,
+struct McpSearchRelationsArgs {
+    query: String,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpRelationToCreate {
-    from: String,
-    to: String,
-    #[serde(rename = "relationType")]
-    relation_type: String,
+struct McpBuildContextArgs {
+    query: String,
+    #[serde(default)]
+    max_tokens: Option<usize>,
+    #[serde(default)]
+    hops: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpCreateRelationsArgs {
-    relations: Vec<McpRelationToCreate>,
+struct McpSearchObservationsArgs {
+    query: String,
+    #[serde(default)]
+    limit: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpAddObservationItemArgs {
-    #[serde(rename = "entityName")]
-    entity_name: String,
-    contents: Vec<String>,
+struct McpGetNeighborsArgs {
+    name: String,
+    #[serde(default)]
+    direction: Option<String>,
+    #[serde(default)]
+    relation_type: Option<String>,
+    #[serde(default)]
+    include_edges: bool,
 }
-#[derive(Deserialize, Debug)]
-struct McpAddObservationsArgs {
-    observations: Vec<McpAddObservationItemArgs>,
+
+#[derive(Serialize, Debug)]
+struct NeighborsResult {
+    name: String,
+    neighbors: Vec<Node>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    edges: Option<Vec<NodeEdge>>,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpDeleteEntitiesArgs {
-    #[serde(rename = "entityNames")]
-    entity_names: Vec<String>,
+struct McpSummarizeEntityArgs {
+    name: String,
+    #[serde(default)]
+    replace_observations: bool,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpDeleteObservationItemArgs {
-    #[serde(rename = "entityName")]
-    entity_name: String,
-    observations: Vec<String>,
+struct McpSuggestRelationsArgs {
+    names: Vec<String>,
+    #[serde(default)]
+    max_suggestions: Option<usize>,
 }
+
 #[derive(Deserialize, Debug)]
-struct McpDeleteObservationsArgs {
-    deletions: Vec<McpDeleteObservationItemArgs>,
+struct McpOpenNodesArgs {
+    names: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpDeleteRelationItemArgs {
-    from: String,
-    to: String,
-    #[serde(rename = "relationType")]
-    relation_type: String,
+struct McpReadGraphArgs {
+    #[serde(default)]
+    redact: bool,
+    #[serde(default)]
+    entity_types: Option<Vec<String>>,
+    #[serde(default)]
+    relation_types: Option<Vec<String>>,
+    #[serde(default)]
+    since_ms: Option<u64>,
+    #[serde(default)]
+    until_ms: Option<u64>,
 }
+
 #[derive(Deserialize, Debug)]
-struct McpDeleteRelationsArgs {
-    relations: Vec<McpDeleteRelationItemArgs>,
+struct McpRecentMemoriesArgs {
+    #[serde(default)]
+    limit: Option<usize>,
+    #[serde(default)]
+    observations: Option<usize>,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpSearchNodesArgs {
-    query: String,
+struct McpClearGraphArgs {
+    #[serde(default)]
+    confirm_token: Option<String>,
+    #[serde(default)]
+    force: bool,
 }
 
 #[derive(Deserialize, Debug)]
-struct McpOpenNodesArgs {
-    names: Vec<String>,
+struct McpSetGraphMetadataArgs {
+    metadata: Value,
+    #[serde(default)]
+    replace: bool,
 }
 
 // --- Tool Schemas (as string literals) ---
@@ -180,11 +254,15 @@ mod schemas {
                     "properties": {
                         "name": { "type": "string", "description": "The name of the entity" },
                         "entityType": { "type": "string", "description": "The type of the entity" },
-                        "observations": { "type": "array", "items": { "type": "string" }, "description": "An array of observation contents associated with the entity" }
+                        "observations": { "type": "array", "items": { "type": "string" }, "description": "An array of observation contents associated with the entity" },
+                        "data": { "type": "object", "description": "Structured data stored with the entity" }
                     },
-                    "required": ["name", "entityType", "observations"]
+                    "required": ["name", "entityType"]
                 }
-            }
+            },
+            "on_near_duplicate": { "type": "string", "enum": ["warn", "reject", "ignore"], "description": "What to do with a name very close to an existing entity's: create it with a warning, skip it, or not check. Defaults to the graph's near_duplicate_policy metadata, else warn" },
+            "use_template": { "type": "boolean", "description": "Apply the template registered for each entity's type: add its default observations and relations, and refuse entities missing its required data fields (default false)" },
+            "merge_existing": { "type": "boolean", "description": "For imports: merge each entity into an existing one with the same name, a matching name or alias ignoring case, or a very close name of the same type, instead of creating a duplicate. The result's resolved_names maps each name to the entity it ended up as, to rewrite the relations imported next (default false)" }
         },
         "required": ["entities"]
     }"#;
@@ -199,11 +277,14 @@ mod schemas {
                     "properties": {
                         "from": { "type": "string", "description": "The name of the entity where the relation starts" },
                         "to": { "type": "string", "description": "The name of the entity where the relation ends" },
-                        "relationType": { "type": "string", "description": "The type of the relation" }
+                        "relationType": { "type": "string", "description": "The type of the relation" },
+                        "data": { "type": "object", "description": "Structured data stored with the relation" },
+                        "confidence": { "type": "number", "minimum": 0, "maximum": 1, "description": "How sure you are of the relation; it fades if the graph sets a half-life. Restating an existing relation with a confidence refreshes it" }
                     },
                     "required": ["from", "to", "relationType"]
                 }
-            }
+            },
+            "on_missing_node": { "type": "string", "enum": ["skip", "error", "create_placeholder"], "description": "What to do when a relation's from/to entity doesn't exist: skip that relation (default), reject the whole batch, or create a placeholder entity of type Unknown" }
         },
         "required": ["relations"]
     }"#;
@@ -217,7 +298,8 @@ mod schemas {
                     "type": "object",
                     "properties": {
                         "entityName": { "type": "string", "description": "The name of the entity to add the observations to" },
-                        "contents": { "type": "array", "items": { "type": "string" }, "description": "An array of observation contents to add" }
+                        "contents": { "type": "array", "items": { "type": "string" }, "description": "An array of observation contents to add" },
+                        "expected_version": { "type": "integer", "minimum": 0, "description": "Refuse the whole call if the entity's version (from read_graph) has changed since" }
                     },
                     "required": ["entityName", "contents"]
                 }
@@ -226,10 +308,77 @@ mod schemas {
         "required": ["observations"]
     }"#;
 
+    pub const REMEMBER_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "entities": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string", "description": "The name of the entity" },
+                        "entityType": { "type": "string", "description": "The type of the entity" },
+                        "observations": { "type": "array", "items": { "type": "string" }, "description": "Observations about the entity; added to it if it already exists" },
+                        "data": { "type": "object", "description": "Structured data stored with the entity" }
+                    },
+                    "required": ["name", "entityType"]
+                }
+            },
+            "relations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "from": { "type": "string", "description": "The name of the entity where the relation starts" },
+                        "to": { "type": "string", "description": "The name of the entity where the relation ends" },
+                        "relationType": { "type": "string", "description": "The type of the relation" },
+                        "data": { "type": "object", "description": "Structured data stored with the relation" },
+                        "confidence": { "type": "number", "minimum": 0, "maximum": 1, "description": "How sure you are of the relation" }
+                    },
+                    "required": ["from", "to", "relationType"]
+                }
+            },
+            "observations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "entityName": { "type": "string", "description": "The name of the entity to add the observations to" },
+                        "contents": { "type": "array", "items": { "type": "string" }, "description": "An array of observation contents to add" },
+                        "expected_version": { "type": "integer", "minimum": 0, "description": "Refuse the whole call if the entity's version has changed since" }
+                    },
+                    "required": ["entityName", "contents"]
+                }
+            },
+            "on_near_duplicate": { "type": "string", "enum": ["warn", "reject", "ignore"], "description": "As for create_entities" },
+            "on_missing_node": { "type": "string", "enum": ["skip", "error", "create_placeholder"], "description": "What to do when a relation's from/to entity doesn't exist after the entities are created. Defaults to error, which fails the whole call" }
+        }
+    }"#;
+
+    pub const REMEMBER_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "entities": { "type": "object", "description": "Batch result of the entities, as from create_entities" },
+            "relations": { "type": "object", "description": "Batch result of the relations, as from create_relations" },
+            "observations": { "type": "object", "description": "Batch result of the observations, as from add_observations" }
+        },
+        "required": ["entities", "relations", "observations"]
+    }"#;
+
     pub const DELETE_ENTITIES_SCHEMA: &str = r#"{
         "type": "object",
         "properties": {
-            "entityNames": { "type": "array", "items": { "type": "string" }, "description": "An array of entity names to delete" }
+            "entityNames": { "type": "array", "items": { "type": "string" }, "description": "An array of entity names to delete" },
+            "expected_versions": { "type": "object", "additionalProperties": { "type": "integer", "minimum": 0 }, "description": "Entity name -> version it must still be at; the whole call is refused otherwise" }
+        },
+        "required": ["entityNames"]
+    }"#;
+
+    pub const PIN_ENTITIES_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "entityNames": { "type": "array", "items": { "type": "string" }, "description": "An array of entity names to pin" },
+            "pinned": { "type": "boolean", "description": "Pass false to unpin (default true)" }
         },
         "required": ["entityNames"]
     }"#;
@@ -243,7 +392,8 @@ mod schemas {
                     "type": "object",
                     "properties": {
                         "entityName": { "type": "string", "description": "The name of the entity containing the observations" },
-                        "observations": { "type": "array", "items": { "type": "string" }, "description": "An array of observations to delete" }
+                        "observations": { "type": "array", "items": { "type": "string" }, "description": "An array of observations to delete" },
+                        "expected_version": { "type": "integer", "minimum": 0, "description": "Refuse the whole call if the entity's version has changed since" }
                     },
                     "required": ["entityName", "observations"]
                 }
@@ -272,377 +422,1855 @@ mod schemas {
         "required": ["relations"]
     }"#;
 
-    pub const READ_GRAPH_SCHEMA: &str = r#"{"type": "object", "properties": {}}"#;
+    pub const READ_GRAPH_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "redact": { "type": "boolean", "description": "Mask emails, phone numbers and sensitive data fields in the result" },
+            "entity_types": { "type": "array", "items": { "type": "string" }, "description": "Only return entities of these types, and the relations between them" },
+            "relation_types": { "type": "array", "items": { "type": "string" }, "description": "Only return relations of these types" },
+            "since_ms": { "type": "integer", "minimum": 0, "description": "Only return entities updated and relations created at or after this time (epoch milliseconds)" },
+            "until_ms": { "type": "integer", "minimum": 0, "description": "Only return entities updated and relations created before this time (epoch milliseconds)" }
+        }
+    }"#;
 
     pub const SEARCH_NODES_SCHEMA: &str = r#"{
         "type": "object",
         "properties": {
-            "query": { "type": "string", "description": "The search query to match against entity names, types, and observation content" }
+            "query": { "type": "string", "description": "The search query to match against entity names, types, and observation content" },
+            "queries": { "type": "array", "items": { "type": "string" }, "maxItems": 20, "description": "Several search queries to run at once instead of query; results come back grouped per query" },
+            "data_filter": { "type": "string", "description": "Optional filter over each entity's data, e.g. `data.status == \"draft\" && data.priority > 3`. Clauses are joined with &&; operators are == != > >= < <=" },
+            "anchor": { "type": "string", "description": "Optional entity, e.g. the current user; each result then comes with its shortest relation path from this entity in anchor_paths" }
+        }
+    }"#;
+
+    pub const SEARCH_RELATIONS_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "query": { "type": "string", "description": "The search query to match against relation types and the values stored in relation data" }
         },
         "required": ["query"]
     }"#;
 
-    pub const OPEN_NODES_SCHEMA: &str = r#"{
+    pub const SEARCH_OBSERVATIONS_SCHEMA: &str = r#"{
         "type": "object",
         "properties": {
-            "names": { "type": "array", "items": { "type": "string" }, "description": "An array of entity names to retrieve" }
+            "query": { "type": "string", "description": "Text to look for in observations (case-insensitive)" },
+            "limit": { "type": "integer", "minimum": 0, "maximum": 100, "description": "How many observations to return (default: 20)" }
         },
-        "required": ["names"]
+        "required": ["query"]
     }"#;
-}
 
-// --- MCP Handlers ---
-
-pub async fn list_tools_handler() -> Result<Response> {
-    let tools = vec![
-        ToolDefinition {
-            name: "create_entities".to_string(),
-            description: "Create multiple new entities in the knowledge graph".to_string(),
-            input_schema: serde_json::from_str(schemas::CREATE_ENTITIES_SCHEMA).unwrap(),
-        },
-        ToolDefinition {
-            name: "create_relations".to_string(),
-            description: "Create multiple new relations between entities in the knowledge graph. Relations should be in active voice".to_string(),
-            input_schema: serde_json::from_str(schemas::CREATE_RELATIONS_SCHEMA).unwrap(),
-        },
-        ToolDefinition {
-            name: "add_observations".to_string(),
-            description: "Add new observations to existing entities in the knowledge graph".to_string(),
-            input_schema: serde_json::from_str(schemas::ADD_OBSERVATIONS_SCHEMA).unwrap(),
-        },
-        ToolDefinition {
-            name: "delete_entities".to_string(),
-            description: "Delete multiple entities and their associated relations from the knowledge graph".to_string(),
-            input_schema: serde_json::from_str(schemas::DELETE_ENTITIES_SCHEMA).unwrap(),
-        },
-        ToolDefinition {
-            name: "delete_observations".to_string(),
-            description: "Delete specific observations from entities in the knowledge graph".to_string(),
-            input_schema: serde_json::from_str(schemas::DELETE_OBSERVATIONS_SCHEMA).unwrap(),
-        },
-        ToolDefinition {
-            name: "delete_relations".to_string(),
-            description: "Delete multiple relations from the knowledge graph".to_string(),
-            input_schema: serde_json::from_str(schemas::DELETE_RELATIONS_SCHEMA).unwrap(),
+    pub const SEARCH_OBSERVATIONS_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "observations": {
+                "type": "array",
+                "description": "Matching observations, newest first",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "entityName": { "type": "string" },
+                        "entityType": { "type": "string" },
+                        "observation": { "type": "string" },
+                        "added_at_ms": { "type": "integer", "description": "When the observation was added; absent for observations stored before this was recorded" },
+                        "added_by": { "type": "string", "description": "Actor that added the observation" }
+                    },
+                    "required": ["entityName", "entityType", "observation"]
+                }
+            }
         },
-        ToolDefinition {
-            name: "read_graph".to_string(),
-            description: "Read the entire knowledge graph".to_string(),
-            input_schema: serde_json::from_str(schemas::READ_GRAPH_SCHEMA).unwrap(),
+        "required": ["observations"]
+    }"#;
+
+    pub const BUILD_CONTEXT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "query": { "type": "string", "description": "Topic or question to gather context for" },
+            "max_tokens": { "type": "integer", "minimum": 1, "maximum": 32000, "description": "Token budget of the returned context (default: 2000)" },
+            "hops": { "type": "integer", "minimum": 0, "maximum": 3, "description": "How many relations to follow out from the matching entities (default: 1)" }
         },
-        ToolDefinition {
-            name: "search_nodes".to_string(),
-            description: "Search for nodes in the knowledge graph based on a query".to_string(),
-            input_schema: serde_json::from_str(schemas::SEARCH_NODES_SCHEMA).unwrap(),
+        "required": ["query"]
+    }"#;
+
+    pub const BUILD_CONTEXT_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "query": { "type": "string" },
+            "markdown": { "type": "string", "description": "The context block" },
+            "estimated_tokens": { "type": "integer" },
+            "entities": { "type": "array", "items": { "type": "string" }, "description": "Entities included, most relevant first" },
+            "truncated": { "type": "boolean", "description": "The budget ran out before everything relevant was listed" }
         },
-        ToolDefinition {
-            name: "open_nodes".to_string(),
-            description: "Open specific nodes in the knowledge graph by their names".to_string(),
-            input_schema: serde_json::from_str(schemas::OPEN_NODES_SCHEMA).unwrap(),
+        "required": ["query", "markdown", "estimated_tokens", "entities", "truncated"]
+    }"#;
+
+    pub const GET_NEIGHBORS_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "name": { "type": "string", "description": "The name of the entity whose neighbors to return" },
+            "direction": { "type": "string", "enum": ["outgoing", "incoming", "both"], "description": "Which relations to follow (default: both)" },
+            "relation_type": { "type": "string", "description": "Only follow relations of this type" },
+            "include_edges": { "type": "boolean", "description": "Also return the connecting relations with their direction, type and data (default: false)" }
         },
-    ];
-    Response::from_json(&ListToolsResponse { tools })
-}
+        "required": ["name"]
+    }"#;
 
-async fn call_do_post(stub: &Stub, path: &str, body_value: Value) -> Result<Response> {
-    let mut req_init = RequestInit::new();
-    req_init.with_method(Method::Post);
-    let mut headers = Headers::new();
-    headers.set("Content-Type", "application/json")?;
-    req_init.with_headers(headers);
-    req_init.with_body(Some(serde_json::to_vec(&body_value)?.into()));
+    pub const SUMMARIZE_ENTITY_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "name": { "type": "string", "description": "The name of the entity to summarize" },
+            "replace_observations": { "type": "boolean", "description": "Replace the summarized observations with the summary (default: false, the summary is only stored alongside them)" }
+        },
+        "required": ["name"]
+    }"#;
 
-    let do_url = format!("https://durable-object.internal-url{}", path);
-    let do_req = WorkerRequest::new_with_init(&do_url, &req_init)?;
-    stub.fetch_with_request(do_req).await
-}
+    pub const SUGGEST_RELATIONS_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "names": { "type": "array", "items": { "type": "string" }, "description": "The entities to propose new relations for" },
+            "max_suggestions": { "type": "integer", "minimum": 1, "maximum": 50, "description": "Maximum number of suggestions (default: 10)" }
+        },
+        "required": ["names"]
+    }"#;
 
-async fn call_do_get(stub: &Stub, path: &str) -> Result<Response> {
-    let mut req_init = RequestInit::new();
-    req_init.with_method(Method::Get);
-    let do_url = format!("https://durable-object.internal-url{}", path);
-    let do_req = WorkerRequest::new_with_init(&do_url, &req_init)?;
-    stub.fetch_with_request(do_req).await
-}
+    // --- Output schemas (structuredContent of each tool's result) ---
 
-fn format_do_response_as_mcp_content<T: Serialize>(
-    do_response_data: &T,
-) -> Result<CallToolResponse> {
-    let text = serde_json::to_string_pretty(do_response_data)
-        .map_err(|e| worker::Error::RustError(format!("Serialization error: {}", e)))?;
-    Ok(CallToolResponse {
-        content: vec![ContentBlock {
-            block_type: "text".to_string(),
-            text,
-        }],
-    })
-}
+    pub const BATCH_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "results": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "index": { "type": "integer", "description": "Position of the item in the request" },
+                        "id": { "type": ["string", "null"], "description": "Entity name, or relation ID" },
+                        "status": { "type": "string", "enum": ["created", "updated", "unchanged", "deleted", "already_exists", "skipped", "not_found", "conflict", "error"] },
+                        "error": { "type": "string" },
+                        "placeholders_created": { "type": "array", "items": { "type": "string" } },
+                        "mirrored_edge_id": { "type": "string", "description": "Mirror of the relation created or removed with it" },
+                        "edges_deleted": { "type": "array", "items": { "type": "string" }, "description": "Every relation ID removed for this item" },
+                        "mirrors_deleted": { "type": "array", "items": { "type": "string" }, "description": "Every mirror relation ID removed with them" },
+                        "near_duplicate": {
+                            "type": "object",
+                            "description": "Existing entity whose name is very close to the new one",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "distance": { "type": "number", "description": "Normalized edit distance, 0 to 1" }
+                            },
+                            "required": ["name", "distance"]
+                        },
+                        "warning": { "type": "string" }
+                    },
+                    "required": ["index", "id", "status"]
+                }
+            },
+            "summary": {
+                "type": "object",
+                "properties": {
+                    "total": { "type": "integer" },
+                    "succeeded": { "type": "integer" },
+                    "failed": { "type": "integer" },
+                    "by_status": { "type": "object", "additionalProperties": { "type": "integer" } }
+                },
+                "required": ["total", "succeeded", "failed", "by_status"]
+            },
+            "on_missing_node": { "type": "string", "enum": ["skip", "error", "create_placeholder"] }
+        },
+        "required": ["results", "summary"]
+    }"#;
 
-fn format_simple_mcp_success_message(message: &str) -> Result<CallToolResponse> {
-    Ok(CallToolResponse {
-        content: vec![ContentBlock {
-            block_type: "text".to_string(),
-            text: message.to_string(),
-        }],
-    })
-}
+    pub const GRAPH_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "entities": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "entityType": { "type": "string" },
+                        "observations": { "type": "array", "items": { "type": "string" } },
+                        "data": {},
+                        "version": { "type": "integer" },
+                        "created_by": { "type": "string" },
+                        "updated_by": { "type": "string" }
+                    },
+                    "required": ["name", "entityType", "observations"]
+                }
+            },
+            "relations": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "from": { "type": "string" },
+                        "to": { "type": "string" },
+                        "relationType": { "type": "string" },
+                        "data": {},
+                        "created_by": { "type": "string" },
+                        "updated_by": { "type": "string" },
+                        "confidence": { "type": "number", "description": "Current confidence, after any decay" }
+                    },
+                    "required": ["from", "to", "relationType"]
+                }
+            }
+        },
+        "required": ["entities", "relations"]
+    }"#;
 
-pub async fn call_tool_handler(mut req: WorkerRequest, stub: Stub) -> Result<Response> {
-    let params: CallToolRequestParams = match req.json().await {
-        Ok(p) => p,
-        Err(e) => {
-            return Ok(mcp_error_response(
-                "ParseError",
-                &format!("Failed to parse request: {}", e),
-            ))
+    // GRAPH_OUTPUT_SCHEMA for a single query; `results` holds one such graph per query when
+    // several were passed
+    pub const SEARCH_NODES_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "entities": { "type": "array", "items": { "type": "object" } },
+            "relations": { "type": "array", "items": { "type": "object" } },
+            "anchor_paths": { "type": "array", "items": { "type": "object" } },
+            "results": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string" },
+                        "entities": { "type": "array", "items": { "type": "object" } },
+                        "relations": { "type": "array", "items": { "type": "object" } },
+                        "anchor_paths": { "type": "array", "items": { "type": "object" } }
+                    },
+                    "required": ["query", "entities", "relations"]
+                }
+            }
+        }
+    }"#;
+
+    pub const NEIGHBORS_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "neighbors": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "type": { "type": "string" },
+                        "data": {},
+                        "created_at_ms": { "type": "integer" },
+                        "updated_at_ms": { "type": "integer" }
+                    },
+                    "required": ["id", "type"]
+                }
+            },
+            "edges": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "direction": { "type": "string", "enum": ["outgoing", "incoming"] },
+                        "neighbor_id": { "type": "string" },
+                        "id": { "type": "string" },
+                        "type": { "type": "string" },
+                        "source_node_id": { "type": "string" },
+                        "target_node_id": { "type": "string" },
+                        "data": {},
+                        "created_at_ms": { "type": "integer" }
+                    },
+                    "required": ["direction", "neighbor_id", "id", "type"]
+                }
+            }
+        },
+        "required": ["name", "neighbors"]
+    }"#;
+
+    pub const RECENT_MEMORIES_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "limit": { "type": "integer", "minimum": 0, "maximum": 100, "description": "How many entities to return (default: 20)" },
+            "observations": { "type": "integer", "minimum": 0, "description": "How many of each entity's latest observations to include (default: 5)" }
+        }
+    }"#;
+
+    pub const RECENT_MEMORIES_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "entities": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "entityType": { "type": "string" },
+                        "observations": { "type": "array", "items": { "type": "string" }, "description": "The latest observations, oldest first" },
+                        "observation_count": { "type": "integer", "description": "All observations of the entity" },
+                        "data": {},
+                        "version": { "type": "integer" },
+                        "created_by": { "type": "string" },
+                        "updated_by": { "type": "string" },
+                        "created_at_ms": { "type": "integer" },
+                        "updated_at_ms": { "type": "integer" }
+                    },
+                    "required": ["name", "entityType", "observations", "observation_count", "created_at_ms", "updated_at_ms"]
+                }
+            }
+        },
+        "required": ["entities"]
+    }"#;
+
+    pub const SUMMARY_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "name": { "type": "string" },
+            "summary": { "type": "string" },
+            "model": { "type": "string" },
+            "summarized_observations": { "type": "integer" },
+            "replaced_observations": { "type": "boolean" }
+        },
+        "required": ["name", "summary", "model", "summarized_observations", "replaced_observations"]
+    }"#;
+
+    pub const SUGGEST_RELATIONS_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "model": { "type": "string" },
+            "suggestions": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "properties": {
+                        "from": { "type": "string" },
+                        "to": { "type": "string" },
+                        "relationType": { "type": "string" },
+                        "confidence": { "type": "number", "minimum": 0, "maximum": 1 },
+                        "reason": { "type": "string" }
+                    },
+                    "required": ["from", "to", "relationType", "confidence"]
+                }
+            }
+        },
+        "required": ["model", "suggestions"]
+    }"#;
+
+    pub const CLEAR_GRAPH_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "status": { "type": "string", "enum": ["confirmation_required", "cleared"] },
+            "confirm_token": { "type": "string" },
+            "expires_at_ms": { "type": "integer" },
+            "deleted_entities": { "type": "integer" },
+            "deleted_relations": { "type": "integer" },
+            "kept_pinned": { "type": "integer" }
+        },
+        "required": ["status"]
+    }"#;
+
+    pub const FORGET_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "query": { "type": "string", "description": "Forget what contains this text (case-insensitive)" },
+            "pattern": { "type": "string", "description": "Forget what matches this regular expression" },
+            "entity_types": { "type": "array", "items": { "type": "string" }, "description": "Only entities of these types" },
+            "since_ms": { "type": "integer", "minimum": 0, "description": "Only observations added (or entities updated) at or after this time, in epoch milliseconds" },
+            "until_ms": { "type": "integer", "minimum": 0, "description": "Only observations added (or entities updated) before this time, in epoch milliseconds" },
+            "scope": { "type": "string", "enum": ["observations", "entities"], "description": "Delete the matching observations (default), or the matching entities with their relations" },
+            "force": { "type": "boolean", "description": "Also forget pinned entities, which are left alone otherwise (default false)" },
+            "confirm_token": { "type": "string", "description": "Token from a previous forget call with the same arguments. Omit it to preview what would be forgotten; pass it back to delete it" }
         }
+    }"#;
+
+    pub const FORGET_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "status": { "type": "string", "enum": ["confirmation_required", "forgotten"] },
+            "confirm_token": { "type": "string" },
+            "expires_at_ms": { "type": "integer" },
+            "plan": {
+                "type": "object",
+                "properties": {
+                    "entities": { "type": "array", "items": { "type": "string" } },
+                    "relations": { "type": "integer" },
+                    "observations": { "type": "object", "additionalProperties": { "type": "array", "items": { "type": "string" } } }
+                },
+                "required": ["entities", "relations", "observations"]
+            }
+        },
+        "required": ["status", "plan"]
+    }"#;
+
+    pub const CLEAR_GRAPH_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "confirm_token": { "type": "string", "description": "Token returned by a previous clear_graph call. Omit it to get a token; pass it back to actually delete everything" },
+            "force": { "type": "boolean", "description": "Also delete pinned entities, which are kept otherwise (default false)" }
+        }
+    }"#;
+
+    pub const SET_GRAPH_METADATA_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "metadata": { "type": "object", "description": "Graph-level settings, e.g. description, owner or default policies; case_insensitive_names: true makes entity names resolve ignoring case. near_duplicate_policy (warn, reject or ignore) and near_duplicate_threshold (0 to 1, default 0.2) control the create_entities near-duplicate check. relation_confidence_half_life_days makes relation confidence fade, and relation_confidence_floor has maintenance remove relations that faded below it. auto_summarize_min_observations (at least 2) has maintenance replace an entity's observations older than auto_summarize_min_age_days (default 30) with an AI summary once it holds that many; the originals are archived. Merged into the existing metadata; a null value removes a key" },
+            "replace": { "type": "boolean", "description": "Replace the whole metadata instead of merging (default false)" }
+        },
+        "required": ["metadata"]
+    }"#;
+
+    pub const GRAPH_METADATA_OUTPUT_SCHEMA: &str = r#"{
+        "type": "object",
+        "description": "The graph metadata after the update"
+    }"#;
+
+    pub const OPEN_NODES_SCHEMA: &str = r#"{
+        "type": "object",
+        "properties": {
+            "names": { "type": "array", "items": { "type": "string" }, "description": "An array of entity names to retrieve" }
+        },
+        "required": ["names"]
+    }"#;
+}
+
+// --- MCP Handlers ---
+
+pub fn tool_definitions() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition {
+            name: "create_entities".to_string(),
+            description: "Create multiple new entities in the knowledge graph. A name very close to an existing entity's is reported with that entity; prefer add_observations on it over creating a near duplicate".to_string(),
+            input_schema: serde_json::from_str(schemas::CREATE_ENTITIES_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BATCH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::additive(true),
+        },
+        ToolDefinition {
+            name: "create_relations".to_string(),
+            description: "Create multiple new relations between entities in the knowledge graph. Relations should be in active voice".to_string(),
+            input_schema: serde_json::from_str(schemas::CREATE_RELATIONS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BATCH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::additive(true),
+        },
+        ToolDefinition {
+            name: "add_observations".to_string(),
+            description: "Add new observations to existing entities in the knowledge graph".to_string(),
+            input_schema: serde_json::from_str(schemas::ADD_OBSERVATIONS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BATCH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::additive(true),
+        },
+        ToolDefinition {
+            name: "remember".to_string(),
+            description: "Create entities, relations between them and observations in one call that either fully succeeds or writes nothing, instead of create_entities, create_relations and add_observations one after another".to_string(),
+            input_schema: serde_json::from_str(schemas::REMEMBER_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::REMEMBER_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::additive(true),
+        },
+        ToolDefinition {
+            name: "delete_entities".to_string(),
+            description: "Delete multiple entities and their associated relations from the knowledge graph".to_string(),
+            input_schema: serde_json::from_str(schemas::DELETE_ENTITIES_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BATCH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(true),
+        },
+        ToolDefinition {
+            name: "pin_entities".to_string(),
+            description: "Pin entities holding foundational facts so graph clears and cleanup jobs keep them, or unpin them with pinned: false".to_string(),
+            input_schema: serde_json::from_str(schemas::PIN_ENTITIES_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BATCH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(true),
+        },
+        ToolDefinition {
+            name: "delete_observations".to_string(),
+            description: "Delete specific observations from entities in the knowledge graph".to_string(),
+            input_schema: serde_json::from_str(schemas::DELETE_OBSERVATIONS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BATCH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(true),
+        },
+        ToolDefinition {
+            name: "delete_relations".to_string(),
+            description: "Delete multiple relations from the knowledge graph".to_string(),
+            input_schema: serde_json::from_str(schemas::DELETE_RELATIONS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BATCH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(true),
+        },
+        ToolDefinition {
+            name: "read_graph".to_string(),
+            description: "Read the entire knowledge graph, or only the entities and relations of the given types".to_string(),
+            input_schema: serde_json::from_str(schemas::READ_GRAPH_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::GRAPH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "search_nodes".to_string(),
+            description: "Search for nodes in the knowledge graph based on a query, or on several queries at once".to_string(),
+            input_schema: serde_json::from_str(schemas::SEARCH_NODES_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::SEARCH_NODES_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "search_relations".to_string(),
+            description: "Search for relations by type or data, returning them with the entities they connect".to_string(),
+            input_schema: serde_json::from_str(schemas::SEARCH_RELATIONS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::GRAPH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "search_observations".to_string(),
+            description: "Search observations and return each matching observation with its entity, when it was added and by whom, rather than whole entities".to_string(),
+            input_schema: serde_json::from_str(schemas::SEARCH_OBSERVATIONS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::SEARCH_OBSERVATIONS_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "build_context".to_string(),
+            description: "Gather what the graph knows about a topic as one compact markdown block within a token budget: matching entities, their neighbors, observations and relations, most relevant first".to_string(),
+            input_schema: serde_json::from_str(schemas::BUILD_CONTEXT_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::BUILD_CONTEXT_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "get_neighbors".to_string(),
+            description: "Get the entities directly related to an entity, optionally with the connecting relations".to_string(),
+            input_schema: serde_json::from_str(schemas::GET_NEIGHBORS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::NEIGHBORS_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "summarize_entity".to_string(),
+            description: "Summarize an entity's observations with an LLM, storing the summary on the entity and optionally replacing the observations with it".to_string(),
+            input_schema: serde_json::from_str(schemas::SUMMARIZE_ENTITY_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::SUMMARY_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(false),
+        },
+        ToolDefinition {
+            name: "suggest_relations".to_string(),
+            description: "Propose plausible new relations for the given entities based on their observations, each with a confidence. Nothing is written; confirm suggestions with create_relations".to_string(),
+            input_schema: serde_json::from_str(schemas::SUGGEST_RELATIONS_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::SUGGEST_RELATIONS_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "open_nodes".to_string(),
+            description: "Open specific nodes in the knowledge graph by their names".to_string(),
+            input_schema: serde_json::from_str(schemas::OPEN_NODES_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::GRAPH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "recent_memories".to_string(),
+            description: "List the most recently created or updated entities with their latest observations, newest first. Use it to recall what was learned in recent sessions".to_string(),
+            input_schema: serde_json::from_str(schemas::RECENT_MEMORIES_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::RECENT_MEMORIES_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::read_only(),
+        },
+        ToolDefinition {
+            name: "forget".to_string(),
+            description: "Forget a topic: delete the observations (or, with the admin scope, whole entities) matching a query, pattern, entity types and/or time range, without listing names. Requires two calls: the first previews what would be deleted and returns a confirm_token, the second (same arguments plus the token) deletes it".to_string(),
+            input_schema: serde_json::from_str(schemas::FORGET_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::FORGET_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(false),
+        },
+        ToolDefinition {
+            name: "clear_graph".to_string(),
+            description: "Delete ALL entities and relations from the knowledge graph, except pinned entities unless force is set. Requires two calls: the first returns a confirm_token, the second (with that token) performs the wipe".to_string(),
+            input_schema: serde_json::from_str(schemas::CLEAR_GRAPH_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::CLEAR_GRAPH_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(false),
+        },
+        ToolDefinition {
+            name: "set_graph_metadata".to_string(),
+            description: "Set graph-level metadata such as the graph's description, owner or default policies".to_string(),
+            input_schema: serde_json::from_str(schemas::SET_GRAPH_METADATA_SCHEMA).unwrap(),
+            output_schema: serde_json::from_str(schemas::GRAPH_METADATA_OUTPUT_SCHEMA).unwrap(),
+            annotations: ToolAnnotations::destructive(true),
+        },
+    ]
+}
+
+// Scope a caller needs to see and call each tool; `None` for unknown tools.
+pub fn tool_scope(tool_name: &str) -> Option<Scope> {
+    match tool_name {
+        "read_graph"
+        | "search_nodes"
+        | "search_relations"
+        | "search_observations"
+        | "build_context"
+        | "get_neighbors"
+        | "suggest_relations"
+        | "open_nodes"
+        | "recent_memories" => Some(Scope::Read),
+        "create_entities"
+        | "create_relations"
+        | "add_observations"
+        | "remember"
+        | "delete_entities"
+        | "pin_entities"
+        | "delete_observations"
+        | "delete_relations"
+        | "forget"
+        | "summarize_entity"
+        | "set_graph_metadata" => Some(Scope::Write),
+        "clear_graph" => Some(Scope::Admin),
+        _ => None,
+    }
+}
+
+// Size of a write tool's arguments, metered as bytes written.
+fn tool_bytes_written(tool_name: &str, arguments: &Value) -> u64 {
+    match tool_scope(tool_name) {
+        Some(Scope::Write | Scope::Admin) => arguments.to_string().len() as u64,
+        _ => 0,
+    }
+}
+
+// Entities a tool call added: created entities, plus placeholders made for relations.
+fn tool_entities_created(
+    tool_name: &str,
+    result: &std::result::Result<CallToolResponse, ToolError>,
+) -> u64 {
+    let Some(batch) = result
+        .as_ref()
+        .ok()
+        .and_then(|r| r.structured_content.clone())
+        .and_then(|content| serde_json::from_value::<BatchResponse>(content).ok())
+    else {
+        return 0;
     };
+    match tool_name {
+        "create_entities" => batch
+            .results
+            .iter()
+            .filter(|r| r.status == BatchStatus::Created)
+            .count() as u64,
+        "create_relations" => batch
+            .results
+            .iter()
+            .map(|r| r.placeholders_created.len() as u64)
+            .sum(),
+        _ => 0,
+    }
+}
 
-    let tool_name = params.name.as_str();
-    let args = params.arguments;
+// The tools visible to a caller.
+fn tools_for(caller: &Caller) -> Vec<ToolDefinition> {
+    tool_definitions()
+        .into_iter()
+        .filter(|tool| authorize_tool(&tool.name, caller).is_ok())
+        .collect()
+}
 
-    let mcp_response_result: Result<CallToolResponse> = match tool_name {
-        "create_entities" => {
-            let mcp_args: McpCreateEntitiesArgs = serde_json::from_value(args)?;
-            let do_payload = CreateEntitiesPayload {
-                entities: mcp_args
-                    .entities
-                    .into_iter()
-                    .map(|e| EntityToCreate {
-                        name: e.name,
-                        entity_type: e.entity_type,
-                        observations: e.observations,
-                        data: None, // MCP TS version doesn't have data for entities
-                    })
-                    .collect(),
-            };
-            let mut do_resp =
-                call_do_post(&stub, "/graph/entities", serde_json::to_value(do_payload)?).await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
+fn authorize_tool(tool_name: &str, caller: &Caller) -> std::result::Result<(), ToolError> {
+    match tool_scope(tool_name) {
+        None => Err(ToolError::UnknownTool(tool_name.to_string())),
+        Some(scope) if !caller.has_scope(scope) => Err(ToolError::Forbidden(format!(
+            "tool '{}' requires the '{}' scope",
+            tool_name,
+            scope.as_str()
+        ))),
+        // The model's suggestions quote names and reasons that can't be masked
+        Some(_) if caller.is_redacted() && tool_name == "suggest_relations" => {
+            Err(ToolError::Forbidden(format!(
+                "tool '{}' is not available to redacted keys",
+                tool_name
+            )))
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+pub async fn list_tools_handler(caller: &Caller) -> Result<Response> {
+    Response::from_json(&ListToolsResponse {
+        tools: tools_for(caller),
+    })
+}
+
+async fn call_do_post(stub: &GraphStub, path: &str, body_value: Value) -> Result<Response> {
+    call_do_with_body(stub, Method::Post, path, body_value, None).await
+}
+
+// Write tools name the caller, so the DO can record who wrote what.
+async fn call_do_write(
+    stub: &GraphStub,
+    caller: &Caller,
+    path: &str,
+    body_value: Value,
+) -> Result<Response> {
+    call_do_with_body(stub, Method::Post, path, body_value, Some(caller)).await
+}
+
+async fn call_do_with_body(
+    stub: &GraphStub,
+    method: Method,
+    path: &str,
+    body_value: Value,
+    caller: Option<&Caller>,
+) -> Result<Response> {
+    let mut req_init = RequestInit::new();
+    req_init.with_method(method);
+    let mut headers = Headers::new();
+    headers.set("Content-Type", "application/json")?;
+    if let Some(caller) = caller {
+        headers.set(auth::ACTOR_HEADER, caller.id())?;
+    }
+    req_init.with_headers(headers);
+    req_init.with_body(Some(serde_json::to_vec(&body_value)?.into()));
+
+    let do_url = format!(
+        "https://durable-object.internal-url{}{}",
+        API_V1_PREFIX, path
+    );
+    let do_req = WorkerRequest::new_with_init(&do_url, &req_init)?;
+    let response = circuit::fetch(stub, do_req).await?;
+    cache::invalidate_after_write(stub, &response).await;
+    Ok(response)
+}
+
+async fn call_do_get(stub: &GraphStub, path: &str) -> Result<Response> {
+    cache::get_from_do(stub, &format!("{}{}", API_V1_PREFIX, path), None).await
+}
+
+// read_graph and search_nodes may be served by the nearest read replica; one that hasn't
+// been synchronized yet answers 503 and the primary is asked instead.
+async fn call_do_read(
+    stub: &GraphStub,
+    replica: Option<&GraphStub>,
+    path: &str,
+    body_value: Option<Value>,
+) -> Result<Response> {
+    if let Some(replica) = replica {
+        let replica_path = format!("{}{}", REPLICA_PATH_PREFIX, path);
+        let response = match &body_value {
+            Some(body) => call_do_post(replica, &replica_path, body.clone()).await?,
+            None => call_do_get(replica, &replica_path).await?,
+        };
+        if response.status_code() != 503 {
+            return Ok(response);
+        }
+    }
+    match body_value {
+        Some(body) => call_do_post(stub, path, body).await,
+        None => call_do_get(stub, path).await,
+    }
+}
+
+// Percent-encodes a path segment or query value for a DO URL.
+fn encode_component(value: &str) -> String {
+    utf8_percent_encode(value, NON_ALPHANUMERIC).to_string()
+}
+
+// Tool results are returned both as pretty-printed text (for older clients) and as
+// structuredContent matching the tool's outputSchema.
+fn format_do_response_as_mcp_content<T: Serialize>(
+    do_response_data: &T,
+) -> std::result::Result<CallToolResponse, ToolError> {
+    let structured = serde_json::to_value(do_response_data)
+        .map_err(|e| ToolError::Internal(format!("Serialization error: {}", e)))?;
+    let text = serde_json::to_string_pretty(&structured)
+        .map_err(|e| ToolError::Internal(format!("Serialization error: {}", e)))?;
+    Ok(CallToolResponse {
+        content: vec![ContentBlock {
+            block_type: "text".to_string(),
+            text,
+        }],
+        structured_content: Some(structured),
+    })
+}
+
+// --- Tool Execution ---
+
+// Why a tool call failed; each transport maps these to its own error representation.
+#[derive(Debug)]
+pub enum ToolError {
+    UnknownTool(String),
+    InvalidParams(String),
+    Forbidden(String),
+    DoError { status: u16, error: DoErrorDetail },
+    Internal(String),
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::UnknownTool(name) => write!(f, "Unknown tool: {}", name),
+            ToolError::InvalidParams(msg) => write!(f, "Invalid arguments: {}", msg),
+            ToolError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            ToolError::DoError { status, error } => {
+                write!(f, "DO Error: {} {} - {}", status, error.code, error.message)
             }
-            let created_nodes: Vec<DoNode> = do_resp.json().await?;
-            format_do_response_as_mcp_content(&created_nodes)
+            ToolError::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl From<worker::Error> for ToolError {
+    fn from(e: worker::Error) -> Self {
+        ToolError::Internal(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for ToolError {
+    fn from(e: serde_json::Error) -> Self {
+        ToolError::Internal(e.to_string())
+    }
+}
+
+fn parse_args<T: DeserializeOwned>(args: Value) -> std::result::Result<T, ToolError> {
+    serde_json::from_value(args).map_err(|e| ToolError::InvalidParams(e.to_string()))
+}
+
+// Batch write payloads go through the same validation as the DO routes.
+fn parse_payload<T: DeserializeOwned + Validate>(args: Value) -> std::result::Result<T, ToolError> {
+    validation::from_value(args).map_err(ToolError::InvalidParams)
+}
+
+// Turns a non-2xx DO response into a ToolError carrying the DO's status and structured error.
+async fn ensure_do_success(do_resp: &mut Response) -> std::result::Result<(), ToolError> {
+    if !(200..300).contains(&do_resp.status_code()) {
+        return Err(ToolError::DoError {
+            status: do_resp.status_code(),
+            error: errors::read_error(do_resp).await?,
+        });
+    }
+    Ok(())
+}
+
+async fn execute_tool(
+    tool_name: &str,
+    args: Value,
+    stub: &GraphStub,
+    replica: Option<&GraphStub>,
+    caller: &Caller,
+) -> std::result::Result<CallToolResponse, ToolError> {
+    match tool_name {
+        "create_entities" => {
+            let do_payload: CreateEntitiesPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
+                "/graph/entities",
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let results: BatchResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&results)
         }
         "create_relations" => {
-            let mcp_args: McpCreateRelationsArgs = serde_json::from_value(args)?;
-            let do_payload = CreateRelationsPayload {
-                relations: mcp_args
-                    .relations
-                    .into_iter()
-                    .map(|r| RelationToCreate {
-                        from: r.from,
-                        to: r.to,
-                        relation_type: r.relation_type,
-                        data: None, // MCP TS version doesn't have data for relations
-                    })
-                    .collect(),
-            };
-            let mut do_resp =
-                call_do_post(&stub, "/graph/relations", serde_json::to_value(do_payload)?).await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
-            }
-            let created_edges: Vec<DoEdge> = do_resp.json().await?;
-            format_do_response_as_mcp_content(&created_edges)
+            let do_payload: CreateRelationsPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
+                "/graph/relations",
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let results: BatchResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&results)
         }
         "add_observations" => {
-            let mcp_args: McpAddObservationsArgs = serde_json::from_value(args)?;
-            let do_payload = AddObservationsPayload {
-                observations: mcp_args
-                    .observations
-                    .into_iter()
-                    .map(|o| AddObservationItem {
-                        entity_name: o.entity_name,
-                        contents: o.contents,
-                    })
-                    .collect(),
-            };
-            let mut do_resp = call_do_post(
-                &stub,
+            let do_payload: AddObservationsPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
                 "/graph/observations/add",
                 serde_json::to_value(do_payload)?,
             )
             .await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
-            }
-            // DO returns Vec<Result<String,String>>
-            let results: Value = do_resp.json().await?; // Keep as Value for direct stringification
+            ensure_do_success(&mut do_resp).await?;
+            let results: BatchResponse = do_resp.json().await?;
             format_do_response_as_mcp_content(&results)
         }
+        "remember" => {
+            let do_payload: RememberPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
+                "/graph/remember",
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let remembered: RememberResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&remembered)
+        }
         "delete_entities" => {
-            let mcp_args: McpDeleteEntitiesArgs = serde_json::from_value(args)?;
-            let do_payload = DeleteEntitiesPayload {
-                entity_names: mcp_args.entity_names,
-            };
-            let mut do_resp = call_do_post(
-                &stub,
+            let do_payload: DeleteEntitiesPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
                 "/graph/entities/delete",
                 serde_json::to_value(do_payload)?,
             )
             .await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
-            }
-            // TS version returns generic success. Do not parse do_resp.json().
-            format_simple_mcp_success_message("Entities deleted successfully")
+            ensure_do_success(&mut do_resp).await?;
+            let results: BatchResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&results)
+        }
+        "pin_entities" => {
+            let do_payload: PinEntitiesPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
+                "/graph/entities/pin",
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let results: BatchResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&results)
         }
         "delete_observations" => {
-            let mcp_args: McpDeleteObservationsArgs = serde_json::from_value(args)?;
-            let do_payload = DeleteObservationsPayload {
-                deletions: mcp_args
-                    .deletions
-                    .into_iter()
-                    .map(|d| DeleteObservationItem {
-                        entity_name: d.entity_name,
-                        observations: d.observations,
-                    })
-                    .collect(),
-            };
-            let mut do_resp = call_do_post(
-                &stub,
+            let do_payload: DeleteObservationsPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
                 "/graph/observations/delete",
                 serde_json::to_value(do_payload)?,
             )
             .await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
-            }
-            format_simple_mcp_success_message("Observations deleted successfully")
+            ensure_do_success(&mut do_resp).await?;
+            let results: BatchResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&results)
         }
         "delete_relations" => {
-            let mcp_args: McpDeleteRelationsArgs = serde_json::from_value(args)?;
-            let do_payload = DeleteRelationsPayload {
-                relations: mcp_args
-                    .relations
-                    .into_iter()
-                    .map(|r| RelationToDelete {
-                        from: r.from,
-                        to: r.to,
-                        relation_type: r.relation_type,
-                    })
-                    .collect(),
-            };
-            let mut do_resp = call_do_post(
-                &stub,
+            let do_payload: DeleteRelationsPayload = parse_payload(args)?;
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
                 "/graph/relations/delete",
                 serde_json::to_value(do_payload)?,
             )
             .await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
-            }
-            format_simple_mcp_success_message("Relations deleted successfully")
+            ensure_do_success(&mut do_resp).await?;
+            let results: BatchResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&results)
         }
         "read_graph" => {
-            let mut do_resp = call_do_get(&stub, "/graph/state").await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
+            let mcp_args: McpReadGraphArgs = parse_args(args)?;
+            let mut query = Vec::new();
+            if caller.is_redacted() || mcp_args.redact {
+                query.push("redact=true".to_string());
+            }
+            for (key, types) in [
+                ("entity_types", &mcp_args.entity_types),
+                ("relation_types", &mcp_args.relation_types),
+            ] {
+                // An empty list means no filter, as when it is left out
+                if let Some(types) = types.as_ref().filter(|types| !types.is_empty()) {
+                    let types: Vec<String> = types.iter().map(|t| encode_component(t)).collect();
+                    query.push(format!("{}={}", key, types.join(",")));
+                }
+            }
+            for (key, bound) in [
+                ("since_ms", mcp_args.since_ms),
+                ("until_ms", mcp_args.until_ms),
+            ] {
+                if let Some(bound) = bound {
+                    query.push(format!("{}={}", key, bound));
+                }
             }
+            let path = if query.is_empty() {
+                "/graph/state".to_string()
+            } else {
+                format!("/graph/state?{}", query.join("&"))
+            };
+            let mut do_resp = call_do_read(stub, replica, &path, None).await?;
+            ensure_do_success(&mut do_resp).await?;
             let graph_data: KnowledgeGraphDataResponse = do_resp.json().await?;
             format_do_response_as_mcp_content(&graph_data)
         }
         "search_nodes" => {
-            let mcp_args: McpSearchNodesArgs = serde_json::from_value(args)?;
+            let mcp_args: McpSearchNodesArgs = parse_args(args)?;
+            let grouped = !mcp_args.queries.is_empty();
+            let anchored = mcp_args.anchor.is_some();
             let do_payload = SearchNodesQuery {
                 query: mcp_args.query,
+                queries: mcp_args.queries,
+                data_filter: mcp_args.data_filter,
+                anchor: mcp_args.anchor,
+            };
+            let path = if caller.is_redacted() {
+                "/graph/search?redact=true"
+            } else {
+                "/graph/search"
             };
             let mut do_resp =
-                call_do_post(&stub, "/graph/search", serde_json::to_value(do_payload)?).await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
+                call_do_read(stub, replica, path, Some(serde_json::to_value(do_payload)?)).await?;
+            ensure_do_success(&mut do_resp).await?;
+            if grouped {
+                let search_results: GroupedSearchResponse = do_resp.json().await?;
+                format_do_response_as_mcp_content(&search_results)
+            } else if anchored {
+                let search_results: AnchoredSearchResponse = do_resp.json().await?;
+                format_do_response_as_mcp_content(&search_results)
+            } else {
+                let search_results: KnowledgeGraphDataResponse = do_resp.json().await?;
+                format_do_response_as_mcp_content(&search_results)
             }
+        }
+        "search_relations" => {
+            let mcp_args: McpSearchRelationsArgs = parse_args(args)?;
+            let do_payload = SearchRelationsQuery {
+                query: mcp_args.query,
+            };
+            let path = if caller.is_redacted() {
+                "/graph/relations/search?redact=true"
+            } else {
+                "/graph/relations/search"
+            };
+            let mut do_resp = call_do_post(stub, path, serde_json::to_value(do_payload)?).await?;
+            ensure_do_success(&mut do_resp).await?;
             let search_results: KnowledgeGraphDataResponse = do_resp.json().await?;
             format_do_response_as_mcp_content(&search_results)
         }
+        "build_context" => {
+            let mcp_args: McpBuildContextArgs = parse_args(args)?;
+            let do_payload = BuildContextQuery {
+                query: mcp_args.query,
+                max_tokens: mcp_args.max_tokens,
+                hops: mcp_args.hops,
+            };
+            let path = if caller.is_redacted() {
+                "/graph/context?redact=true"
+            } else {
+                "/graph/context"
+            };
+            let mut do_resp =
+                call_do_read(stub, replica, path, Some(serde_json::to_value(do_payload)?)).await?;
+            ensure_do_success(&mut do_resp).await?;
+            let pack: ContextPack = do_resp.json().await?;
+            // The text content is the markdown itself, ready to paste into a prompt
+            let mut response = format_do_response_as_mcp_content(&pack)?;
+            response.content[0].text = pack.markdown;
+            Ok(response)
+        }
+        "search_observations" => {
+            let mcp_args: McpSearchObservationsArgs = parse_args(args)?;
+            let do_payload = SearchObservationsQuery {
+                query: mcp_args.query,
+                limit: mcp_args.limit,
+            };
+            let path = if caller.is_redacted() {
+                "/graph/observations/search?redact=true"
+            } else {
+                "/graph/observations/search"
+            };
+            let mut do_resp =
+                call_do_read(stub, replica, path, Some(serde_json::to_value(do_payload)?)).await?;
+            ensure_do_success(&mut do_resp).await?;
+            let search_results: SearchObservationsResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&search_results)
+        }
+        "get_neighbors" => {
+            let mcp_args: McpGetNeighborsArgs = parse_args(args)?;
+            let mut query = Vec::new();
+            if let Some(direction) = &mcp_args.direction {
+                query.push(format!("direction={}", encode_component(direction)));
+            }
+            if let Some(relation_type) = &mcp_args.relation_type {
+                query.push(format!("edge_type={}", encode_component(relation_type)));
+            }
+            if caller.is_redacted() {
+                query.push("redact=true".to_string());
+            }
+            let node_path = format!("/nodes/{}", encode_component(&mcp_args.name));
+            let query = query.join("&");
+
+            let mut do_resp =
+                call_do_get(stub, &format!("{}/related?{}", node_path, query)).await?;
+            ensure_do_success(&mut do_resp).await?;
+            let neighbors: Vec<Node> = do_resp.json().await?;
+
+            let edges = if mcp_args.include_edges {
+                let mut do_resp =
+                    call_do_get(stub, &format!("{}/edges?{}", node_path, query)).await?;
+                ensure_do_success(&mut do_resp).await?;
+                Some(do_resp.json::<Vec<NodeEdge>>().await?)
+            } else {
+                None
+            };
+            format_do_response_as_mcp_content(&NeighborsResult {
+                name: mcp_args.name,
+                neighbors,
+                edges,
+            })
+        }
+        "summarize_entity" => {
+            let mcp_args: McpSummarizeEntityArgs = parse_args(args)?;
+            let do_payload = SummarizePayload {
+                replace_observations: mcp_args.replace_observations,
+            };
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
+                &format!("/nodes/{}/summarize", encode_component(&mcp_args.name)),
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let summary: EntitySummary = do_resp.json().await?;
+            format_do_response_as_mcp_content(&summary)
+        }
+        "suggest_relations" => {
+            let mcp_args: McpSuggestRelationsArgs = parse_args(args)?;
+            let do_payload = SuggestRelationsPayload {
+                names: mcp_args.names,
+                max_suggestions: mcp_args.max_suggestions,
+            };
+            let mut do_resp = call_do_post(
+                stub,
+                "/graph/relations/suggest",
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let suggestions: SuggestRelationsResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&suggestions)
+        }
         "open_nodes" => {
-            let mcp_args: McpOpenNodesArgs = serde_json::from_value(args)?;
+            let mcp_args: McpOpenNodesArgs = parse_args(args)?;
             let do_payload = OpenNodesQuery {
                 names: mcp_args.names,
             };
-            let mut do_resp =
-                call_do_post(&stub, "/graph/open", serde_json::to_value(do_payload)?).await?;
-            if do_resp.status_code() != 200 {
-                return Ok(mcp_error_response(
-                    "DOError",
-                    &format!(
-                        "DO Error: {} - {}",
-                        do_resp.status_code(),
-                        do_resp.text().await?
-                    ),
-                ));
-            }
+            let path = if caller.is_redacted() {
+                "/graph/open?redact=true"
+            } else {
+                "/graph/open"
+            };
+            let mut do_resp = call_do_post(stub, path, serde_json::to_value(do_payload)?).await?;
+            ensure_do_success(&mut do_resp).await?;
             let open_results: KnowledgeGraphDataResponse = do_resp.json().await?;
             format_do_response_as_mcp_content(&open_results)
         }
-        _ => Err(worker::Error::RustError(format!(
-            "Unknown tool: {}",
-            tool_name
-        ))),
+        "recent_memories" => {
+            let mcp_args: McpRecentMemoriesArgs = parse_args(args)?;
+            let mut query = Vec::new();
+            if let Some(limit) = mcp_args.limit {
+                query.push(format!("limit={}", limit));
+            }
+            if let Some(observations) = mcp_args.observations {
+                query.push(format!("observations={}", observations));
+            }
+            if caller.is_redacted() {
+                query.push("redact=true".to_string());
+            }
+            let path = format!("/graph/recent?{}", query.join("&"));
+            let mut do_resp = call_do_read(stub, replica, &path, None).await?;
+            ensure_do_success(&mut do_resp).await?;
+            let recent: RecentEntitiesResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&recent)
+        }
+        "forget" => {
+            let do_payload: ForgetPayload = parse_args(args)?;
+            let scope = forget::required_scope(&do_payload);
+            if !caller.has_scope(scope) {
+                return Err(ToolError::Forbidden(format!(
+                    "forgetting entities requires the '{}' scope",
+                    scope.as_str()
+                )));
+            }
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
+                "/graph/forget",
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let forget_result: ForgetResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&forget_result)
+        }
+        "clear_graph" => {
+            let mcp_args: McpClearGraphArgs = parse_args(args)?;
+            let do_payload = ClearGraphPayload {
+                confirm_token: mcp_args.confirm_token,
+                force: mcp_args.force,
+            };
+            let mut do_resp = call_do_write(
+                stub,
+                caller,
+                "/graph/clear",
+                serde_json::to_value(do_payload)?,
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let clear_result: ClearGraphResponse = do_resp.json().await?;
+            format_do_response_as_mcp_content(&clear_result)
+        }
+        "set_graph_metadata" => {
+            let mcp_args: McpSetGraphMetadataArgs = parse_args(args)?;
+            let method = if mcp_args.replace {
+                Method::Put
+            } else {
+                Method::Patch
+            };
+            let mut do_resp = call_do_with_body(
+                stub,
+                method,
+                "/graph/metadata",
+                mcp_args.metadata,
+                Some(caller),
+            )
+            .await?;
+            ensure_do_success(&mut do_resp).await?;
+            let metadata: Value = do_resp.json().await?;
+            format_do_response_as_mcp_content(&metadata)
+        }
+        _ => Err(ToolError::UnknownTool(tool_name.to_string())),
+    }
+}
+
+// Legacy REST endpoint (POST /mcp/tool/call): string error codes with a matching HTTP status.
+pub async fn call_tool_handler(
+    mut req: WorkerRequest,
+    stub: GraphStub,
+    caller: &Caller,
+    env: &Env,
+) -> Result<Response> {
+    let replica = replication::nearest_replica(env, &req, stub.graph());
+    let params: CallToolRequestParams = match req.json().await {
+        Ok(p) => p,
+        Err(e) => {
+            return Ok(mcp_error_response(
+                "ParseError",
+                &format!("Failed to parse request: {}", e),
+            ))
+        }
     };
 
-    match mcp_response_result {
+    let tool_name = params.name.as_str();
+    let bytes_written = tool_bytes_written(tool_name, &params.arguments);
+    let result = match authorize_tool(tool_name, caller) {
+        Ok(()) => execute_tool(tool_name, params.arguments, &stub, replica.as_ref(), caller).await,
+        Err(e) => Err(e),
+    };
+    metering::record(
+        env,
+        caller,
+        tool_name,
+        tool_entities_created(tool_name, &result),
+        bytes_written,
+    )
+    .await;
+    match result {
         Ok(call_response) => Response::from_json(&call_response),
-        Err(e) => Ok(mcp_error_response(
-            "ToolExecutionError",
-            &format!("Error executing tool '{}': {}", tool_name, e),
-        )),
+        Err(e) => {
+            let (code, status) = match &e {
+                ToolError::UnknownTool(_) => ("UnknownTool", 404),
+                ToolError::InvalidParams(_) => ("InvalidParams", 400),
+                ToolError::Forbidden(_) => ("Forbidden", 403),
+                ToolError::DoError { status, .. } if *status < 500 => ("DOError", *status),
+                ToolError::DoError { .. } => ("DOError", 502),
+                ToolError::Internal(_) => ("ToolExecutionError", 500),
+            };
+            let message = format!("Error executing tool '{}': {}", tool_name, e);
+            let data = match e {
+                ToolError::DoError { status, error } => Some(do_error_data(status, error)),
+                _ => None,
+            };
+            Response::from_json(&McpErrorResponse {
+                error: McpError {
+                    code: code.to_string(),
+                    message,
+                    data,
+                },
+            })
+            .map(|r| r.with_status(status))
+        }
+    }
+}
+
+// --- JSON-RPC Transport (POST /mcp) ---
+
+pub const MCP_PROTOCOL_VERSION: &str = "2025-06-18";
+
+// Standard JSON-RPC 2.0 error codes
+pub mod error_codes {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    pub const INTERNAL_ERROR: i64 = -32603;
+    // Application codes (JSON-RPC reserves -32000..-32099 for server errors)
+    pub const DO_ERROR: i64 = -32000; // The Durable Object rejected or failed the operation
+    pub const DO_UNAVAILABLE: i64 = -32001; // The Durable Object could not be reached
+    pub const FORBIDDEN: i64 = -32002; // The API key lacks the scope the tool requires
+    pub const UNAUTHORIZED: i64 = -32003; // No valid API key was presented
+    pub const RATE_LIMITED: i64 = -32004; // The caller exceeded its request rate limit
+    pub const RESOURCE_NOT_FOUND: i64 = -32005; // resources/read found nothing at the URI
+}
+
+#[derive(Deserialize, Debug)]
+struct JsonRpcRequest {
+    #[serde(default)]
+    jsonrpc: String,
+    id: Option<Value>, // Absent for notifications
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JsonRpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct JsonRpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    fn success(id: Value, result: Value) -> Self {
+        JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn failure(id: Value, code: i64, message: impl Into<String>, data: Option<Value>) -> Self {
+        JsonRpcResponse {
+            jsonrpc: "2.0".to_string(),
+            id,
+            result: None,
+            error: Some(JsonRpcError {
+                code,
+                message: message.into(),
+                data,
+            }),
+        }
+    }
+}
+
+// Error data for a DO failure: its status plus the DO's code, message and failed items.
+fn do_error_data(status: u16, error: DoErrorDetail) -> Value {
+    let mut data = serde_json::to_value(error).unwrap_or_default();
+    if let Some(map) = data.as_object_mut() {
+        map.insert("status".to_string(), Value::from(status));
+    }
+    data
+}
+
+impl From<ToolError> for JsonRpcError {
+    fn from(e: ToolError) -> Self {
+        let message = e.to_string();
+        match e {
+            ToolError::UnknownTool(name) => JsonRpcError {
+                code: error_codes::METHOD_NOT_FOUND,
+                message,
+                data: Some(serde_json::json!({ "tool": name })),
+            },
+            ToolError::InvalidParams(_) => JsonRpcError {
+                code: error_codes::INVALID_PARAMS,
+                message,
+                data: None,
+            },
+            ToolError::Forbidden(_) => JsonRpcError {
+                code: error_codes::FORBIDDEN,
+                message,
+                data: None,
+            },
+            ToolError::DoError { status, error } => JsonRpcError {
+                // A timed-out DO or an open circuit breaker, see circuit.rs
+                code: if status == 503 {
+                    error_codes::DO_UNAVAILABLE
+                } else {
+                    error_codes::DO_ERROR
+                },
+                message,
+                data: Some(do_error_data(status, error)),
+            },
+            ToolError::Internal(_) => JsonRpcError {
+                code: error_codes::INTERNAL_ERROR,
+                message,
+                data: None,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct CompleteRequestParams {
+    // `ref` (the prompt or resource being filled in) is not needed: the argument name alone
+    // decides what gets completed, which also covers tool arguments.
+    argument: CompletionArgument,
+}
+
+#[derive(Deserialize, Debug)]
+struct CompletionArgument {
+    name: String,
+    #[serde(default)]
+    value: String,
+}
+
+// Which graph values an argument name refers to, across all tool schemas.
+fn completion_kind_for_argument(argument_name: &str) -> Option<CompletionKind> {
+    match argument_name {
+        "name" | "names" | "from" | "to" | "entityName" | "entityNames" | "entity_name"
+        | "source_node_id" | "target_node_id" => Some(CompletionKind::Entity),
+        "entityType" | "entity_type" | "entity_types" => Some(CompletionKind::EntityType),
+        "relationType" | "relation_type" | "relation_types" => Some(CompletionKind::RelationType),
+        _ => None,
+    }
+}
+
+async fn complete_argument(
+    argument: &CompletionArgument,
+    stub: &GraphStub,
+) -> std::result::Result<CompletionResult, ToolError> {
+    let kind = match completion_kind_for_argument(&argument.name) {
+        Some(CompletionKind::Entity) => "entity",
+        Some(CompletionKind::EntityType) => "entity_type",
+        Some(CompletionKind::RelationType) => "relation_type",
+        None => return Ok(CompletionResult::default()),
+    };
+    let mut do_resp = call_do_get(
+        stub,
+        &format!(
+            "/graph/complete?kind={}&prefix={}",
+            kind,
+            encode_component(&argument.value)
+        ),
+    )
+    .await?;
+    ensure_do_success(&mut do_resp).await?;
+    Ok(do_resp.json().await?)
+}
+
+// --- Resources ---
+
+// Resource templates let hosts read an entity or a search by URI without a tool call:
+// memory://entity/{name} is open_nodes for one entity and memory://search/{query} is
+// search_nodes. Variables are percent-encoded; {name} completes like the tools' `name`.
+const ENTITY_RESOURCE_PREFIX: &str = "memory://entity/";
+const SEARCH_RESOURCE_PREFIX: &str = "memory://search/";
+
+#[derive(Deserialize, Debug)]
+struct ReadResourceParams {
+    uri: String,
+}
+
+fn resource_templates() -> Value {
+    serde_json::json!({
+        "resourceTemplates": [
+            {
+                "uriTemplate": format!("{}{{name}}", ENTITY_RESOURCE_PREFIX),
+                "name": "entity",
+                "description": "An entity and its observations, by name",
+                "mimeType": "application/json"
+            },
+            {
+                "uriTemplate": format!("{}{{query}}", SEARCH_RESOURCE_PREFIX),
+                "name": "search",
+                "description": "Entities matching a search query, with the relations between them",
+                "mimeType": "application/json"
+            }
+        ]
+    })
+}
+
+fn resource_variable(value: &str) -> std::result::Result<String, ToolError> {
+    percent_decode_str(value)
+        .decode_utf8()
+        .map(|v| v.into_owned())
+        .map_err(|e| ToolError::InvalidParams(format!("invalid resource URI: {}", e)))
+}
+
+async fn read_resource(
+    uri: &str,
+    stub: &GraphStub,
+    replica: Option<&GraphStub>,
+    caller: &Caller,
+) -> std::result::Result<Value, JsonRpcError> {
+    let (path, body) = if let Some(name) = uri.strip_prefix(ENTITY_RESOURCE_PREFIX) {
+        let query = OpenNodesQuery {
+            names: vec![resource_variable(name)?],
+        };
+        (
+            "/graph/open",
+            serde_json::to_value(query).map_err(ToolError::from)?,
+        )
+    } else if let Some(query) = uri.strip_prefix(SEARCH_RESOURCE_PREFIX) {
+        let query = SearchNodesQuery {
+            query: Some(resource_variable(query)?),
+            queries: Vec::new(),
+            data_filter: None,
+            anchor: None,
+        };
+        (
+            "/graph/search",
+            serde_json::to_value(query).map_err(ToolError::from)?,
+        )
+    } else {
+        return Err(ToolError::InvalidParams(format!("unknown resource URI: {}", uri)).into());
+    };
+    // Redacted keys read resources masked, as they do read_graph
+    let path = if caller.is_redacted() {
+        format!("{}?redact=true", path)
+    } else {
+        path.to_string()
+    };
+    let mut do_resp = call_do_read(stub, replica, &path, Some(body))
+        .await
+        .map_err(ToolError::from)?;
+    ensure_do_success(&mut do_resp).await?;
+    let graph: KnowledgeGraphDataResponse = do_resp.json().await.map_err(ToolError::from)?;
+    // A search may match nothing; an entity that doesn't exist is not a resource
+    if uri.starts_with(ENTITY_RESOURCE_PREFIX) && graph.entities.is_empty() {
+        return Err(JsonRpcError {
+            code: error_codes::RESOURCE_NOT_FOUND,
+            message: format!("Resource not found: {}", uri),
+            data: Some(serde_json::json!({ "uri": uri })),
+        });
+    }
+    let text = serde_json::to_string_pretty(&graph).map_err(ToolError::from)?;
+    Ok(serde_json::json!({
+        "contents": [{ "uri": uri, "mimeType": "application/json", "text": text }]
+    }))
+}
+
+// --- Logging (notifications/message) ---
+
+// Syslog severities used by MCP logging, least severe first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Notice,
+    Warning,
+    Error,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+// Level used until a session calls logging/setLevel
+pub const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::Info;
+pub const SESSION_ID_HEADER: &str = "Mcp-Session-Id";
+
+#[derive(Deserialize, Debug)]
+struct SetLevelParams {
+    level: LogLevel,
+}
+
+// State for handling one JSON-RPC request. Log entries are sent back as notifications/message
+// events ahead of the response when the client accepts an SSE stream.
+struct RpcContext<'a> {
+    stub: &'a GraphStub,
+    replica: Option<&'a GraphStub>, // Nearest read replica, for read_graph and search_nodes
+    caller: &'a Caller,
+    env: &'a Env,
+    session_id: Option<String>,
+    log: Vec<(LogLevel, Value)>,
+}
+
+impl RpcContext<'_> {
+    fn log(&mut self, level: LogLevel, data: Value) {
+        self.log.push((level, data));
+    }
+
+    fn notifications(&self, min_level: LogLevel) -> Vec<Value> {
+        self.log
+            .iter()
+            .filter(|(level, _)| *level >= min_level)
+            .map(|(level, data)| {
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "method": "notifications/message",
+                    "params": { "level": level, "logger": env!("CARGO_PKG_NAME"), "data": data }
+                })
+            })
+            .collect()
+    }
+}
+
+fn session_log_level_path(session_id: &str) -> String {
+    format!("/mcp/sessions/{}/log_level", encode_component(session_id))
+}
+
+// The level a session asked for, or the default when it never set one.
+async fn session_log_level(stub: &GraphStub, session_id: Option<&str>) -> LogLevel {
+    let Some(session_id) = session_id else {
+        return DEFAULT_LOG_LEVEL;
+    };
+    let Ok(mut do_resp) = call_do_get(stub, &session_log_level_path(session_id)).await else {
+        return DEFAULT_LOG_LEVEL;
+    };
+    if do_resp.status_code() != 200 {
+        return DEFAULT_LOG_LEVEL;
+    }
+    do_resp
+        .json::<SessionLogLevel>()
+        .await
+        .ok()
+        .and_then(|stored| serde_json::from_value(Value::String(stored.level)).ok())
+        .unwrap_or(DEFAULT_LOG_LEVEL)
+}
+
+// Runs a tool call, logging the invocation, its duration and any failed batch items.
+async fn call_tool_logged(
+    call: CallToolRequestParams,
+    ctx: &mut RpcContext<'_>,
+) -> std::result::Result<CallToolResponse, ToolError> {
+    ctx.log(
+        LogLevel::Debug,
+        serde_json::json!({ "event": "tool_invoked", "tool": call.name }),
+    );
+    let started_ms = worker::Date::now().as_millis();
+    let bytes_written = tool_bytes_written(&call.name, &call.arguments);
+    let result = match authorize_tool(&call.name, ctx.caller) {
+        Ok(()) => {
+            execute_tool(
+                &call.name,
+                call.arguments,
+                ctx.stub,
+                ctx.replica,
+                ctx.caller,
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    let duration_ms = worker::Date::now().as_millis().saturating_sub(started_ms);
+    metering::record(
+        ctx.env,
+        ctx.caller,
+        &call.name,
+        tool_entities_created(&call.name, &result),
+        bytes_written,
+    )
+    .await;
+
+    match &result {
+        Ok(call_response) => {
+            ctx.log(
+                LogLevel::Debug,
+                serde_json::json!({ "event": "tool_completed", "tool": call.name, "duration_ms": duration_ms }),
+            );
+            let summary = call_response
+                .structured_content
+                .as_ref()
+                .and_then(|content| content.get("summary"));
+            let failed = summary
+                .and_then(|s| s.get("failed"))
+                .and_then(Value::as_u64);
+            if let Some(failed) = failed.filter(|f| *f > 0) {
+                ctx.log(
+                    LogLevel::Warning,
+                    serde_json::json!({
+                        "event": "batch_items_failed",
+                        "tool": call.name,
+                        "failed": failed,
+                        "total": summary.and_then(|s| s.get("total")),
+                    }),
+                );
+            }
+        }
+        Err(e) => {
+            // Bad arguments are the caller's validation problem rather than a server error
+            let level = match e {
+                ToolError::InvalidParams(_) | ToolError::UnknownTool(_) => LogLevel::Warning,
+                _ => LogLevel::Error,
+            };
+            ctx.log(
+                level,
+                serde_json::json!({
+                    "event": "tool_failed",
+                    "tool": call.name,
+                    "duration_ms": duration_ms,
+                    "message": e.to_string(),
+                }),
+            );
+        }
+    }
+    result
+}
+
+// Dispatches one JSON-RPC method call to its result value.
+async fn dispatch_jsonrpc(
+    method: &str,
+    params: Value,
+    ctx: &mut RpcContext<'_>,
+) -> std::result::Result<Value, JsonRpcError> {
+    let (stub, caller) = (ctx.stub, ctx.caller);
+    match method {
+        // No "sampling": sampling/createMessage is a request to the client made in the middle of
+        // a call. The SSE stream here is written in one piece once the call is done (see
+        // jsonrpc_handler), and the client's reply would come in on another POST that nothing
+        // routes back to the waiting call, so AI features keep using the Workers AI binding.
+        "initialize" => Ok(serde_json::json!({
+            "protocolVersion": MCP_PROTOCOL_VERSION,
+            "capabilities": { "tools": {}, "resources": {}, "completions": {}, "logging": {} },
+            "serverInfo": {
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION")
+            }
+        })),
+        "ping" => Ok(serde_json::json!({})),
+        "tools/list" => Ok(serde_json::to_value(ListToolsResponse {
+            tools: tools_for(caller),
+        })
+        .map_err(|e| JsonRpcError::from(ToolError::from(e)))?),
+        "completion/complete" => {
+            let params: CompleteRequestParams = serde_json::from_value(params)
+                .map_err(|e| JsonRpcError::from(ToolError::InvalidParams(e.to_string())))?;
+            if !caller.has_scope(Scope::Read) {
+                return Err(ToolError::Forbidden(
+                    "completion requires the 'read' scope".to_string(),
+                )
+                .into());
+            }
+            let completion = complete_argument(&params.argument, stub).await?;
+            Ok(serde_json::json!({ "completion": completion }))
+        }
+        "resources/list" | "resources/templates/list" | "resources/read"
+            if !caller.has_scope(Scope::Read) =>
+        {
+            Err(ToolError::Forbidden("resources require the 'read' scope".to_string()).into())
+        }
+        // Entities are only reachable through the templates; listing them all could be huge
+        "resources/list" => Ok(serde_json::json!({ "resources": [] })),
+        "resources/templates/list" => Ok(resource_templates()),
+        "resources/read" => {
+            let params: ReadResourceParams = serde_json::from_value(params)
+                .map_err(|e| JsonRpcError::from(ToolError::InvalidParams(e.to_string())))?;
+            read_resource(&params.uri, stub, ctx.replica, caller).await
+        }
+        "logging/setLevel" => {
+            let params: SetLevelParams = serde_json::from_value(params)
+                .map_err(|e| JsonRpcError::from(ToolError::InvalidParams(e.to_string())))?;
+            let Some(session_id) = ctx.session_id.as_deref() else {
+                return Err(JsonRpcError {
+                    code: error_codes::INVALID_REQUEST,
+                    message: format!(
+                        "logging/setLevel requires the {} header returned by initialize",
+                        SESSION_ID_HEADER
+                    ),
+                    data: None,
+                });
+            };
+            let mut do_resp = call_do_post(
+                stub,
+                &session_log_level_path(session_id),
+                serde_json::json!({ "level": params.level }),
+            )
+            .await
+            .map_err(ToolError::from)?;
+            ensure_do_success(&mut do_resp).await?;
+            Ok(serde_json::json!({}))
+        }
+        "tools/call" => {
+            let call: CallToolRequestParams = serde_json::from_value(params)
+                .map_err(|e| JsonRpcError::from(ToolError::InvalidParams(e.to_string())))?;
+            let call_response = call_tool_logged(call, ctx).await?;
+            serde_json::to_value(call_response).map_err(|e| ToolError::from(e).into())
+        }
+        _ => Err(JsonRpcError {
+            code: error_codes::METHOD_NOT_FOUND,
+            message: format!("Method not found: {}", method),
+            data: None,
+        }),
+    }
+}
+
+// MCP over JSON-RPC 2.0: one request per POST, answered with a single JSON response.
+pub async fn jsonrpc_handler(
+    mut req: WorkerRequest,
+    stub: GraphStub,
+    caller: &Caller,
+    env: &Env,
+) -> Result<Response> {
+    let replica = replication::nearest_replica(env, &req, stub.graph());
+    let body = req.text().await?;
+    let rpc_req: JsonRpcRequest = match serde_json::from_str::<Value>(&body) {
+        Err(e) => {
+            return Response::from_json(&JsonRpcResponse::failure(
+                Value::Null,
+                error_codes::PARSE_ERROR,
+                format!("Parse error: {}", e),
+                None,
+            ))
+        }
+        Ok(value) => match serde_json::from_value(value) {
+            Ok(r) => r,
+            Err(e) => {
+                return Response::from_json(&JsonRpcResponse::failure(
+                    Value::Null,
+                    error_codes::INVALID_REQUEST,
+                    format!("Invalid request: {}", e),
+                    None,
+                ))
+            }
+        },
+    };
+
+    let id = match rpc_req.id {
+        Some(id) => id,
+        // Notifications (e.g. notifications/initialized) get no response body
+        None => return Response::empty().map(|r| r.with_status(202)),
+    };
+    if rpc_req.jsonrpc != "2.0" {
+        return Response::from_json(&JsonRpcResponse::failure(
+            id,
+            error_codes::INVALID_REQUEST,
+            "Invalid request: jsonrpc must be \"2.0\"",
+            None,
+        ));
+    }
+
+    let accepts_sse = req
+        .headers()
+        .get("accept")?
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+    let is_initialize = rpc_req.method == "initialize";
+    let mut ctx = RpcContext {
+        stub: &stub,
+        replica: replica.as_ref(),
+        caller,
+        env,
+        // initialize starts a new session; later requests echo its id
+        session_id: if is_initialize {
+            Some(uuid::Uuid::new_v4().to_string())
+        } else {
+            req.headers().get(SESSION_ID_HEADER)?
+        },
+        log: Vec::new(),
+    };
+
+    let rpc_resp = match dispatch_jsonrpc(&rpc_req.method, rpc_req.params, &mut ctx).await {
+        Ok(result) => JsonRpcResponse::success(id, result),
+        Err(e) => JsonRpcResponse::failure(id, e.code, e.message, e.data),
+    };
+
+    let notifications = if accepts_sse && !ctx.log.is_empty() {
+        let min_level = session_log_level(&stub, ctx.session_id.as_deref()).await;
+        ctx.notifications(min_level)
+    } else {
+        Vec::new()
+    };
+    let mut response = if notifications.is_empty() {
+        Response::from_json(&rpc_resp)?
+    } else {
+        // Streamable HTTP: log notifications first, then the response, as one SSE stream
+        let mut body = String::new();
+        for message in notifications
+            .iter()
+            .chain(std::iter::once(&serde_json::to_value(&rpc_resp)?))
+        {
+            body.push_str(&format!("event: message\ndata: {}\n\n", message));
+        }
+        let mut headers = Headers::new();
+        headers.set("content-type", "text/event-stream")?;
+        Response::ok(body)?.with_headers(headers)
+    };
+    if let (true, Some(session_id)) = (is_initialize, &ctx.session_id) {
+        response.headers_mut().set(SESSION_ID_HEADER, session_id)?;
     }
+    Ok(response)
 }
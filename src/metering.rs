@@ -0,0 +1,248 @@
+use crate::auth::Caller;
+use crate::types::{UsageBucket, UsageCounters, UsageReport};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use worker::*;
+
+// Per-caller usage counters, so multi-tenant deployments can bill or cap consumers. The
+// worker sends one event per call to a single MeteringDO, which adds it to an hourly bucket;
+// GET /admin/usage reads the buckets back, optionally merged per day. Metering never fails
+// a request: if the DO can't be reached the event is logged and dropped.
+
+pub const METERING_BINDING: &str = "METERING_DO";
+// Set by the knowledge graph DO on responses to writes that added entities
+pub const ENTITIES_CREATED_HEADER: &str = "X-Entities-Created";
+const METERING_INSTANCE: &str = "usage";
+const BUCKET_KEY_PREFIX: &str = "usage:";
+const HOUR_MS: u64 = 60 * 60 * 1000;
+const DAY_MS: u64 = 24 * HOUR_MS;
+const RETENTION_MS: u64 = 90 * DAY_MS; // Older buckets are dropped by the DO's alarm
+const MAX_DELETE_BATCH: usize = 128;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UsageEvent {
+    caller: String,
+    operation: String,
+    entities_created: u64,
+    bytes_written: u64,
+}
+
+impl UsageCounters {
+    fn merge(&mut self, other: UsageCounters) {
+        for (operation, count) in other.calls {
+            *self.calls.entry(operation).or_default() += count;
+        }
+        self.entities_created += other.entities_created;
+        self.bytes_written += other.bytes_written;
+    }
+}
+
+// Bucket starts are zero-padded so storage keys list in time order.
+fn bucket_key(start_ms: u64, caller: &str) -> String {
+    format!("{}{:015}:{}", BUCKET_KEY_PREFIX, start_ms, caller)
+}
+
+fn parse_bucket_key(key: &str) -> Option<(u64, String)> {
+    let (start, caller) = key.strip_prefix(BUCKET_KEY_PREFIX)?.split_once(':')?;
+    Some((start.parse().ok()?, caller.to_string()))
+}
+
+fn metering_stub(env: &Env) -> Result<Stub> {
+    env.durable_object(METERING_BINDING)?
+        .id_from_name(METERING_INSTANCE)?
+        .get_stub()
+}
+
+pub fn entities_created(response: &Response) -> u64 {
+    response
+        .headers()
+        .get(ENTITIES_CREATED_HEADER)
+        .ok()
+        .flatten()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+pub async fn record(
+    env: &Env,
+    caller: &Caller,
+    operation: &str,
+    entities_created: u64,
+    bytes_written: u64,
+) {
+    let event = UsageEvent {
+        caller: caller.id().to_string(),
+        operation: operation.to_string(),
+        entities_created,
+        bytes_written,
+    };
+    let result: Result<()> = async {
+        let mut req_init = RequestInit::new();
+        req_init.with_method(Method::Post);
+        req_init.with_body(Some(serde_json::to_string(&event)?.into()));
+        let req = Request::new_with_init("https://metering.internal-url/record", &req_init)?;
+        let response = metering_stub(env)?.fetch_with_request(req).await?;
+        match response.status_code() {
+            200..=299 => Ok(()),
+            status => Err(Error::RustError(format!("metering DO returned {}", status))),
+        }
+    }
+    .await;
+    if let Err(e) = result {
+        console_warn!("Failed to record usage of '{}': {}", operation, e);
+    }
+}
+
+// Forwards a usage query (from, to, granularity, caller) to the metering DO.
+pub async fn usage_report(env: &Env, query: Option<&str>) -> Result<Response> {
+    let query = query.map(|q| format!("?{}", q)).unwrap_or_default();
+    metering_stub(env)?
+        .fetch_with_str(&format!("https://metering.internal-url/usage{}", query))
+        .await
+}
+
+#[durable_object]
+pub struct MeteringDO {
+    state: State,
+}
+
+impl MeteringDO {
+    async fn record(&self, event: UsageEvent) -> Result<()> {
+        let now_ms = Date::now().as_millis();
+        let key = bucket_key(now_ms - now_ms % HOUR_MS, &event.caller);
+        let mut storage = self.state.storage();
+        let mut counters = storage.get::<UsageCounters>(&key).await.unwrap_or_default();
+        counters.merge(UsageCounters {
+            calls: BTreeMap::from([(event.operation, 1)]),
+            entities_created: event.entities_created,
+            bytes_written: event.bytes_written,
+        });
+        storage.put(&key, &counters).await?;
+        if storage.get_alarm().await?.is_none() {
+            storage.set_alarm((now_ms + DAY_MS) as i64).await?;
+        }
+        Ok(())
+    }
+
+    async fn report(
+        &self,
+        from_ms: u64,
+        to_ms: u64,
+        bucket_ms: u64,
+        caller: Option<&str>,
+    ) -> Result<UsageReport> {
+        // Hourly buckets overlapping the range, aligned to the requested granularity
+        let start_key = bucket_key(from_ms - from_ms % bucket_ms, "");
+        let end_key = bucket_key(to_ms, "");
+        let entries = self
+            .state
+            .storage()
+            .list_with_options(ListOptions::new().start(&start_key).end(&end_key))
+            .await?;
+        let mut merged: BTreeMap<(u64, String), UsageCounters> = BTreeMap::new();
+        entries.for_each(&mut |value, key| {
+            let Some((start_ms, bucket_caller)) =
+                key.as_string().as_deref().and_then(parse_bucket_key)
+            else {
+                return;
+            };
+            if caller.is_some_and(|c| c != bucket_caller) {
+                return;
+            }
+            if let Ok(counters) = serde_wasm_bindgen::from_value::<UsageCounters>(value) {
+                merged
+                    .entry((start_ms - start_ms % bucket_ms, bucket_caller))
+                    .or_default()
+                    .merge(counters);
+            }
+        });
+        Ok(UsageReport {
+            bucket_ms,
+            from_ms,
+            to_ms,
+            buckets: merged
+                .into_iter()
+                .map(|((start_ms, caller), counters)| UsageBucket {
+                    start_ms,
+                    caller,
+                    counters,
+                })
+                .collect(),
+        })
+    }
+}
+
+#[durable_object]
+impl DurableObject for MeteringDO {
+    fn new(state: State, _env: Env) -> Self {
+        Self { state }
+    }
+
+    async fn fetch(&mut self, mut req: Request) -> Result<Response> {
+        match (req.method(), req.path().as_str()) {
+            (Method::Post, "/record") => {
+                let event: UsageEvent = match req.json().await {
+                    Ok(e) => e,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                self.record(event).await?;
+                Response::empty().map(|r| r.with_status(204))
+            }
+            (Method::Get, "/usage") => {
+                let url = req.url()?;
+                let query_params: HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let now_ms = Date::now().as_millis();
+                let parse_ms = |name: &str, default: u64| match query_params.get(name) {
+                    Some(v) => v
+                        .parse::<u64>()
+                        .map_err(|_| format!("Bad request: '{}' must be epoch milliseconds", name)),
+                    None => Ok(default),
+                };
+                let (from_ms, to_ms) =
+                    match (parse_ms("from", now_ms - DAY_MS), parse_ms("to", now_ms)) {
+                        (Ok(from), Ok(to)) if from <= to => (from, to),
+                        (Err(e), _) | (_, Err(e)) => return Response::error(e, 400),
+                        _ => return Response::error("Bad request: 'from' is after 'to'", 400),
+                    };
+                let bucket_ms = match query_params.get("granularity").map(String::as_str) {
+                    None | Some("hour") => HOUR_MS,
+                    Some("day") => DAY_MS,
+                    Some(other) => {
+                        return Response::error(
+                            format!(
+                                "Bad request: unknown granularity '{}' (expected hour or day)",
+                                other
+                            ),
+                            400,
+                        )
+                    }
+                };
+                let caller = query_params.get("caller").map(String::as_str);
+                Response::from_json(&self.report(from_ms, to_ms, bucket_ms, caller).await?)
+            }
+            _ => Response::error("Not Found", 404),
+        }
+    }
+
+    // Drops buckets past the retention period, then checks again a day later.
+    async fn alarm(&mut self) -> Result<Response> {
+        let now_ms = Date::now().as_millis();
+        let cutoff_key = bucket_key(now_ms.saturating_sub(RETENTION_MS), "");
+        let mut storage = self.state.storage();
+        let expired = storage
+            .list_with_options(ListOptions::new().start(BUCKET_KEY_PREFIX).end(&cutoff_key))
+            .await?;
+        let mut expired_keys: Vec<String> = Vec::new();
+        expired.for_each(&mut |_, key| {
+            if let Some(key) = key.as_string() {
+                expired_keys.push(key);
+            }
+        });
+        for chunk in expired_keys.chunks(MAX_DELETE_BATCH) {
+            storage.delete_multiple(chunk.to_vec()).await?;
+        }
+        storage.set_alarm((now_ms + DAY_MS) as i64).await?;
+        Response::empty()
+    }
+}
@@ -0,0 +1,156 @@
+use crate::kg::KnowledgeGraphState;
+use crate::maintenance::normalized_node_data;
+use crate::names::{canonical_name, canonicalize_aliases};
+use serde_json::{json, Value as JsonValue};
+use std::collections::HashMap;
+
+// Layout version written by this build. Bump it together with a new entry in `MIGRATIONS`.
+pub const CURRENT_SCHEMA_VERSION: u32 = 2;
+
+// Keys used by older builds. Data found under them is moved to the canonical key and the
+// stale copy deleted on the first load of each DO instance.
+// "generic_kg_state_v1" was written by the original `do_memory.rs` Durable Object.
+pub const LEGACY_STATE_KEYS: &[&str] = &["generic_kg_state_v1"];
+
+// Newest node or edge timestamp in raw persisted state of any layout, to tell which of two
+// copies was written last.
+pub fn last_written_ms(raw: &JsonValue) -> u64 {
+    let timestamps = |map: &str, fields: &'static [&'static str]| {
+        raw.get(map)
+            .and_then(|v| v.as_object())
+            .into_iter()
+            .flat_map(|entries| entries.values())
+            .flat_map(move |entry| fields.iter().filter_map(move |f| entry.get(*f)?.as_u64()))
+            .max()
+            .unwrap_or(0)
+    };
+    timestamps("nodes", &["created_at_ms", "updated_at_ms"])
+        .max(timestamps("edges", &["created_at_ms"]))
+}
+
+// Each migration upgrades the raw persisted JSON from version `from` to `from + 1`.
+type Migration = fn(&mut JsonValue) -> Result<(), String>;
+
+const MIGRATIONS: &[(u32, Migration)] = &[(0, migrate_v0_to_v1), (1, migrate_v1_to_v2)];
+
+fn schema_version_of(raw: &JsonValue) -> u32 {
+    raw.get("schema_version")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32)
+        .unwrap_or(0)
+}
+
+// v0: unversioned state from either legacy Durable Object. Guarantees the top-level maps exist,
+// turns `"data": null` on edges (written by do_memory.rs) into an absent value, and normalizes
+// node data to an object with a string `observations` array.
+fn migrate_v0_to_v1(raw: &mut JsonValue) -> Result<(), String> {
+    let obj = raw
+        .as_object_mut()
+        .ok_or_else(|| "Persisted graph state is not an object".to_string())?;
+    for key in ["nodes", "edges", "metadata"] {
+        if !obj.get(key).is_some_and(|v| v.is_object()) {
+            obj.insert(key.to_string(), json!({}));
+        }
+    }
+
+    if let Some(edges) = obj.get_mut("edges").and_then(|v| v.as_object_mut()) {
+        for edge in edges.values_mut() {
+            if let Some(edge_obj) = edge.as_object_mut() {
+                if edge_obj.get("data").is_some_and(|d| d.is_null()) {
+                    edge_obj.remove("data");
+                }
+            }
+        }
+    }
+
+    if let Some(nodes) = obj.get_mut("nodes").and_then(|v| v.as_object_mut()) {
+        for node in nodes.values_mut() {
+            if let Some(node_obj) = node.as_object_mut() {
+                let data = node_obj.get("data").cloned().unwrap_or(JsonValue::Null);
+                if let Some(normalized) = normalized_node_data(&data) {
+                    node_obj.insert("data".to_string(), normalized);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// v1: entity names as written. Canonicalizes node names and aliases (see names.rs) and points
+// edges at the renamed nodes. A node whose canonical name is already taken by another node
+// keeps its name. The search index is dropped so it gets rebuilt from the new names.
+fn migrate_v1_to_v2(raw: &mut JsonValue) -> Result<(), String> {
+    let obj = raw
+        .as_object_mut()
+        .ok_or_else(|| "Persisted graph state is not an object".to_string())?;
+    let mut renamed: HashMap<String, String> = HashMap::new();
+    if let Some(nodes) = obj.get_mut("nodes").and_then(|v| v.as_object_mut()) {
+        let mut names: Vec<String> = nodes.keys().cloned().collect();
+        names.sort(); // Deterministic winner when two names canonicalize alike
+        for name in names {
+            let canonical = canonical_name(&name);
+            if canonical == name || canonical.is_empty() || nodes.contains_key(&canonical) {
+                continue;
+            }
+            if let Some(mut node) = nodes.remove(&name) {
+                if let Some(node_obj) = node.as_object_mut() {
+                    node_obj.insert("id".to_string(), json!(canonical));
+                }
+                nodes.insert(canonical.clone(), node);
+                renamed.insert(name, canonical);
+            }
+        }
+        for node in nodes.values_mut() {
+            if let Some(data) = node.get_mut("data") {
+                canonicalize_aliases(data);
+            }
+        }
+    }
+
+    if !renamed.is_empty() {
+        if let Some(edges) = obj.get_mut("edges").and_then(|v| v.as_object_mut()) {
+            for edge in edges.values_mut().filter_map(|e| e.as_object_mut()) {
+                for key in ["source_node_id", "target_node_id"] {
+                    let new_name = edge
+                        .get(key)
+                        .and_then(|v| v.as_str())
+                        .and_then(|name| renamed.get(name));
+                    if let Some(new_name) = new_name.cloned() {
+                        edge.insert(key.to_string(), json!(new_name));
+                    }
+                }
+            }
+        }
+    }
+    obj.remove("search_index");
+    Ok(())
+}
+
+// Upgrades raw persisted state to `CURRENT_SCHEMA_VERSION` and deserializes it.
+// Returns the state and whether any migration ran (so the caller knows to persist it).
+pub fn migrate(mut raw: JsonValue) -> Result<(KnowledgeGraphState, bool), String> {
+    let original_version = schema_version_of(&raw);
+    if original_version > CURRENT_SCHEMA_VERSION {
+        return Err(format!(
+            "Persisted schema version {} is newer than supported version {}",
+            original_version, CURRENT_SCHEMA_VERSION
+        ));
+    }
+
+    let mut version = original_version;
+    while version < CURRENT_SCHEMA_VERSION {
+        let (_, migration) = MIGRATIONS
+            .iter()
+            .find(|(from, _)| *from == version)
+            .ok_or_else(|| format!("No migration registered from schema version {}", version))?;
+        migration(&mut raw)?;
+        version += 1;
+        if let Some(obj) = raw.as_object_mut() {
+            obj.insert("schema_version".to_string(), json!(version));
+        }
+    }
+
+    let state: KnowledgeGraphState = serde_json::from_value(raw)
+        .map_err(|e| format!("Failed to deserialize graph state: {}", e))?;
+    Ok((state, version != original_version))
+}
@@ -0,0 +1,219 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{
+    AddObservationsPayload, CreateEntitiesPayload, CreateRelationsPayload, DeleteEntitiesPayload,
+    DeleteObservationsPayload, DeleteRelationsPayload, EntityToCreate, ExtractedGraph,
+    OpenNodesQuery, PinEntitiesPayload, RelationToCreate, RememberPayload, SearchNodesQuery,
+};
+use serde_json::Value as JsonValue;
+use unicode_normalization::UnicodeNormalization;
+
+// Entity names and aliases are canonicalized before they are written or looked up: control
+// characters are dropped (tabs and line breaks become spaces), surrounding whitespace is trimmed and the rest is put in Unicode NFC,
+// so a name typed with a precomposed "é" finds the entity stored with "e" + combining accent.
+// Names longer than MAX_NAME_CHARS are refused. Names stored by older builds are converted by
+// the schema v2 migration (see migrations.rs).
+//
+// With the graph metadata setting `case_insensitive_names: true`, a name that matches no entity
+// exactly resolves to the one entity whose name matches it ignoring case, so "alice" finds (and
+// doesn't duplicate) "Alice". Stored names keep their casing. When several entities match
+// ignoring case, the name is left as given.
+
+pub const MAX_NAME_CHARS: usize = 256;
+// Graph metadata key turning on case-insensitive name resolution
+pub const CASE_INSENSITIVE_NAMES_KEY: &str = "case_insensitive_names";
+
+pub fn canonical_name(name: &str) -> String {
+    let normalized: String = name
+        .chars()
+        // Tabs and line breaks become spaces so the words they separated stay apart
+        .map(|c| {
+            if c.is_control() && c.is_whitespace() {
+                ' '
+            } else {
+                c
+            }
+        })
+        .filter(|c| !c.is_control())
+        .nfc()
+        .collect();
+    normalized.trim().to_string()
+}
+
+pub fn canonicalize(name: &mut String) {
+    *name = canonical_name(name);
+}
+
+pub fn check_length(name: &str, path: String) -> Result<(), String> {
+    if name.chars().count() > MAX_NAME_CHARS {
+        return Err(format!(
+            "{} is longer than {} characters",
+            path, MAX_NAME_CHARS
+        ));
+    }
+    Ok(())
+}
+
+// The string entries of a data object's "aliases" array.
+pub fn canonicalize_aliases(data: &mut JsonValue) {
+    if let Some(aliases) = data.get_mut("aliases").and_then(|v| v.as_array_mut()) {
+        for alias in aliases.iter_mut() {
+            if let Some(s) = alias.as_str() {
+                *alias = JsonValue::String(canonical_name(s));
+            }
+        }
+    }
+}
+
+pub fn canonicalize_entity(entity: &mut EntityToCreate) {
+    canonicalize(&mut entity.name);
+    if let Some(data) = &mut entity.data {
+        canonicalize_aliases(data);
+    }
+}
+
+pub fn canonicalize_relation(relation: &mut RelationToCreate) {
+    canonicalize(&mut relation.from);
+    canonicalize(&mut relation.to);
+}
+
+pub fn canonicalize_extracted(graph: &mut ExtractedGraph) {
+    graph.entities.iter_mut().for_each(canonicalize_entity);
+    graph.relations.iter_mut().for_each(canonicalize_relation);
+}
+
+impl KnowledgeGraphState {
+    pub fn case_insensitive_names(&self) -> bool {
+        self.metadata
+            .get(CASE_INSENSITIVE_NAMES_KEY)
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+    }
+
+    // Rewrites `name` to the stored name it refers to, if it differs only in case.
+    pub fn resolve_name(&self, name: &mut String) {
+        if !self.case_insensitive_names() || self.nodes.contains_key(name.as_str()) {
+            return;
+        }
+        let lowercase = name.to_lowercase();
+        let mut matches = self
+            .search_index
+            .ids_named(name)
+            .filter(|id| id.to_lowercase() == lowercase);
+        if let (Some(id), None) = (matches.next(), matches.next()) {
+            *name = id.to_string();
+        }
+    }
+}
+
+// Payloads whose entity names are resolved against the graph before they are applied.
+pub trait ResolveNames {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState);
+}
+
+impl ResolveNames for CreateEntitiesPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for entity in &mut self.entities {
+            graph.resolve_name(&mut entity.name);
+        }
+    }
+}
+
+impl ResolveNames for CreateRelationsPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for relation in &mut self.relations {
+            graph.resolve_name(&mut relation.from);
+            graph.resolve_name(&mut relation.to);
+        }
+    }
+}
+
+impl ResolveNames for AddObservationsPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for item in &mut self.observations {
+            graph.resolve_name(&mut item.entity_name);
+        }
+    }
+}
+
+impl ResolveNames for RememberPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for entity in &mut self.entities {
+            graph.resolve_name(&mut entity.name);
+        }
+        for relation in &mut self.relations {
+            graph.resolve_name(&mut relation.from);
+            graph.resolve_name(&mut relation.to);
+        }
+        for item in &mut self.observations {
+            graph.resolve_name(&mut item.entity_name);
+        }
+    }
+}
+
+impl ResolveNames for PinEntitiesPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for name in &mut self.entity_names {
+            graph.resolve_name(name);
+        }
+    }
+}
+
+impl ResolveNames for DeleteEntitiesPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for name in &mut self.entity_names {
+            graph.resolve_name(name);
+        }
+        self.expected_versions = std::mem::take(&mut self.expected_versions)
+            .into_iter()
+            .map(|(mut name, version)| {
+                graph.resolve_name(&mut name);
+                (name, version)
+            })
+            .collect();
+    }
+}
+
+impl ResolveNames for DeleteObservationsPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for item in &mut self.deletions {
+            graph.resolve_name(&mut item.entity_name);
+        }
+    }
+}
+
+impl ResolveNames for DeleteRelationsPayload {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for relation in &mut self.relations {
+            graph.resolve_name(&mut relation.from);
+            graph.resolve_name(&mut relation.to);
+        }
+    }
+}
+
+impl ResolveNames for OpenNodesQuery {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for name in &mut self.names {
+            graph.resolve_name(name);
+        }
+    }
+}
+
+impl ResolveNames for SearchNodesQuery {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        if let Some(anchor) = &mut self.anchor {
+            graph.resolve_name(anchor);
+        }
+    }
+}
+
+impl ResolveNames for ExtractedGraph {
+    fn resolve_names(&mut self, graph: &KnowledgeGraphState) {
+        for entity in &mut self.entities {
+            graph.resolve_name(&mut entity.name);
+        }
+        for relation in &mut self.relations {
+            graph.resolve_name(&mut relation.from);
+            graph.resolve_name(&mut relation.to);
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::{HashSet, VecDeque};
+
+// Agents often ask /graph/open for names that don't exist, and each such request would load
+// and deserialize the whole graph just to find nothing. The DO keeps the most recently missed
+// names in memory and answers a request made only of them straight away. Writes only happen
+// through the DO, and every save clears the list, so it never outlives the version it was
+// filled at.
+
+pub const MAX_MISSING_NAMES: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct MissingNames {
+    version: u64,            // Graph version the names were missing at
+    order: VecDeque<String>, // Least recently used first
+    names: HashSet<String>,
+}
+
+impl MissingNames {
+    pub fn clear(&mut self) {
+        self.order.clear();
+        self.names.clear();
+    }
+
+    fn touch(&mut self, name: &str) {
+        if let Some(position) = self.order.iter().position(|n| n == name) {
+            if let Some(entry) = self.order.remove(position) {
+                self.order.push_back(entry);
+            }
+        }
+    }
+
+    // The graph version at which every one of `names` was missing, if they all were.
+    pub fn all_missing(&mut self, names: &[String]) -> Option<u64> {
+        if names.is_empty() || !names.iter().all(|name| self.names.contains(name)) {
+            return None;
+        }
+        for name in names {
+            self.touch(name);
+        }
+        Some(self.version)
+    }
+
+    // Remembers names found missing at `version`, evicting the least recently used.
+    pub fn record<'a>(&mut self, version: u64, missing: impl IntoIterator<Item = &'a String>) {
+        if version != self.version {
+            self.clear();
+            self.version = version;
+        }
+        for name in missing {
+            if self.names.contains(name) {
+                self.touch(name);
+                continue;
+            }
+            self.names.insert(name.clone());
+            self.order.push_back(name.clone());
+            if self.order.len() > MAX_MISSING_NAMES {
+                if let Some(evicted) = self.order.pop_front() {
+                    self.names.remove(&evicted);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{BatchResult, BatchStatus};
+
+// Pinned entities hold foundational facts that cleanup shouldn't touch. clear_graph keeps them
+// (and the relations between them), forget leaves them and their observations alone, and
+// pruning faded relations skips relations with a pinned end, unless the caller passes `force`. Explicit deletes and erasure requests still remove
+// them: pinning guards against bulk jobs, not against deliberately deleting one entity.
+
+impl KnowledgeGraphState {
+    pub fn is_pinned(&self, node_id: &str) -> bool {
+        self.nodes.get(node_id).is_some_and(|node| node.pinned)
+    }
+
+    // Pins (or unpins) each named entity; already in that state is Unchanged.
+    pub fn set_pinned(
+        &mut self,
+        entity_names: Vec<String>,
+        pinned: bool,
+        now_ms: u64,
+    ) -> Vec<BatchResult> {
+        let mut results = Vec::with_capacity(entity_names.len());
+        for (index, name) in entity_names.into_iter().enumerate() {
+            let Some(node) = self.nodes.get_mut(&name) else {
+                results.push(BatchResult::failed(
+                    index,
+                    Some(name.clone()),
+                    BatchStatus::NotFound,
+                    format!("Entity with name {} not found", name),
+                ));
+                continue;
+            };
+            if node.pinned == pinned {
+                results.push(BatchResult::ok(index, name, BatchStatus::Unchanged));
+                continue;
+            }
+            node.pinned = pinned;
+            node.updated_at_ms = now_ms;
+            node.updated_by = self.actor.clone();
+            node.version += 1;
+            results.push(BatchResult::ok(index, name, BatchStatus::Updated));
+        }
+        results
+    }
+}
@@ -0,0 +1,175 @@
+use crate::auth::Caller;
+use crate::types::RateLimitStatus;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use worker::*;
+
+// Fixed-window request limits per caller. Each caller id (see auth::Caller) gets its own
+// RateLimiterDO, so counting never touches the knowledge graph DO. Limiting is skipped
+// (with a warning) when the binding is missing or the limiter can't be reached.
+
+pub const RATE_LIMITER_BINDING: &str = "RATE_LIMITER_DO";
+// Overridable with the RATE_LIMIT_REQUESTS (0 disables) and RATE_LIMIT_WINDOW_SECONDS vars
+const DEFAULT_REQUESTS_PER_WINDOW: u32 = 600;
+const DEFAULT_WINDOW_SECONDS: u64 = 60;
+const WINDOW_KEY: &str = "window";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+struct LimiterParams {
+    limit: u32,
+    window_seconds: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WindowCounter {
+    started_at_ms: u64,
+    count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RateLimitDecision {
+    allowed: bool, // Whether this (or, for a peek, the next) request is within the limit
+    #[serde(flatten)]
+    status: RateLimitStatus,
+}
+
+fn var_or<T: std::str::FromStr>(env: &Env, name: &str, default: T) -> T {
+    env.var(name)
+        .ok()
+        .and_then(|v| v.to_string().parse().ok())
+        .unwrap_or(default)
+}
+
+fn limiter_params(env: &Env) -> Option<LimiterParams> {
+    let limit = var_or(env, "RATE_LIMIT_REQUESTS", DEFAULT_REQUESTS_PER_WINDOW);
+    let window_seconds = var_or(env, "RATE_LIMIT_WINDOW_SECONDS", DEFAULT_WINDOW_SECONDS);
+    (limit > 0 && window_seconds > 0).then_some(LimiterParams {
+        limit,
+        window_seconds,
+    })
+}
+
+async fn call_limiter(env: &Env, caller: &Caller, action: &str) -> Option<RateLimitDecision> {
+    let params = limiter_params(env)?;
+    let result: Result<RateLimitDecision> = async {
+        let stub = env
+            .durable_object(RATE_LIMITER_BINDING)?
+            .id_from_name(caller.id())?
+            .get_stub()?;
+        let mut req_init = RequestInit::new();
+        req_init.with_method(Method::Post);
+        req_init.with_body(Some(serde_json::to_string(&params)?.into()));
+        let req = Request::new_with_init(
+            &format!("https://rate-limiter.internal-url/{}", action),
+            &req_init,
+        )?;
+        stub.fetch_with_request(req).await?.json().await
+    }
+    .await;
+    match result {
+        Ok(decision) => Some(decision),
+        Err(e) => {
+            console_warn!("Rate limiter unavailable, not limiting: {}", e);
+            None
+        }
+    }
+}
+
+// The caller's current window without counting a request, for GET /quota.
+pub async fn status(env: &Env, caller: &Caller) -> Option<RateLimitStatus> {
+    call_limiter(env, caller, "peek")
+        .await
+        .map(|decision| decision.status)
+}
+
+fn with_rate_limit_headers(response: Response, status: &RateLimitStatus) -> Result<Response> {
+    // Headers of a response fetched from a DO are immutable, so set them on a copy
+    let mut headers = response.headers().clone();
+    headers.set("X-RateLimit-Limit", &status.limit.to_string())?;
+    headers.set("X-RateLimit-Remaining", &status.remaining.to_string())?;
+    headers.set("X-RateLimit-Reset", &status.reset_at.to_string())?;
+    Ok(response.with_headers(headers))
+}
+
+// Counts the request against the caller's limit and runs `handler` if it is within it.
+// Over the limit, `rejected` builds the transport's error body, which is sent as a 429.
+pub async fn enforce(
+    env: &Env,
+    caller: &Caller,
+    rejected: impl FnOnce(&str) -> Result<Response>,
+    handler: impl Future<Output = Result<Response>>,
+) -> Result<Response> {
+    let Some(decision) = call_limiter(env, caller, "consume").await else {
+        return handler.await;
+    };
+    let status = decision.status;
+    if !decision.allowed {
+        let retry_after = status
+            .reset_at
+            .saturating_sub(Date::now().as_millis() / 1000)
+            .max(1);
+        let message = format!(
+            "Rate limit of {} requests per {}s exceeded; retry in {}s",
+            status.limit, status.window_seconds, retry_after
+        );
+        let response = with_rate_limit_headers(rejected(&message)?.with_status(429), &status)?;
+        let mut headers = response.headers().clone();
+        headers.set("Retry-After", &retry_after.to_string())?;
+        return Ok(response.with_headers(headers));
+    }
+    with_rate_limit_headers(handler.await?, &status)
+}
+
+#[durable_object]
+pub struct RateLimiterDO {
+    state: State,
+}
+
+#[durable_object]
+impl DurableObject for RateLimiterDO {
+    fn new(state: State, _env: Env) -> Self {
+        Self { state }
+    }
+
+    async fn fetch(&mut self, mut req: Request) -> Result<Response> {
+        let consume = match (req.method(), req.path().as_str()) {
+            (Method::Post, "/consume") => true,
+            (Method::Post, "/peek") => false,
+            _ => return Response::error("Not Found", 404),
+        };
+        let params: LimiterParams = match req.json().await {
+            Ok(p) => p,
+            Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+        };
+
+        let now_ms = Date::now().as_millis();
+        let window_ms = params.window_seconds * 1000;
+        let mut storage = self.state.storage();
+        // An expired window restarts with the first request after it
+        let mut window = storage
+            .get::<WindowCounter>(WINDOW_KEY)
+            .await
+            .ok()
+            .filter(|w| now_ms < w.started_at_ms + window_ms)
+            .unwrap_or(WindowCounter {
+                started_at_ms: now_ms,
+                count: 0,
+            });
+        let allowed = window.count < params.limit;
+        if consume && allowed {
+            window.count += 1;
+            storage.put(WINDOW_KEY, &window).await?;
+        }
+
+        Response::from_json(&RateLimitDecision {
+            allowed,
+            status: RateLimitStatus {
+                limit: params.limit,
+                remaining: params.limit.saturating_sub(window.count),
+                used: window.count,
+                window_seconds: params.window_seconds,
+                reset_at: (window.started_at_ms + window_ms).div_ceil(1000),
+            },
+        })
+    }
+}
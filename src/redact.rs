@@ -0,0 +1,215 @@
+use crate::types::{
+    AnchorPath, ApiEntity, ApiRelation, ContextPack, Edge, Node, NodeEdge, ObservationMatch,
+    ReachabilityResult, RecentEntity,
+};
+use regex::Regex;
+use serde_json::Value as JsonValue;
+use std::sync::OnceLock;
+use worker::{Env, Method};
+
+// Masks personal data in what the DO reads back (graph dumps, node and edge reads, searches
+// and path queries; see redacts_route) when the request asks for `redact=true` or the
+// caller's key has the "redacted" scope.
+// Every string in names, observations and data is scanned for emails and phone-like numbers;
+// values of the data fields listed in REDACT_FIELDS are masked whole. Entity and relation
+// types are left alone. Redaction is deterministic, so a redacted name still matches the
+// `from`/`to` of its relations.
+
+// Comma-separated data keys (case-insensitive) whose values are always masked
+pub const REDACT_FIELDS_VAR: &str = "REDACT_FIELDS";
+const DEFAULT_REDACT_FIELDS: &[&str] = &["email", "phone", "address", "ssn", "password"];
+// Digit runs shorter than this (dates, years, small counts) are not treated as phone numbers
+const MIN_PHONE_DIGITS: usize = 9;
+
+fn email_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap())
+}
+
+fn number_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(r"\+?\d[\d\s().-]*\d").unwrap())
+}
+
+pub struct Redactor {
+    fields: Vec<String>, // Lowercased
+}
+
+impl Redactor {
+    pub fn from_env(env: &Env) -> Self {
+        let fields = match env.var(REDACT_FIELDS_VAR) {
+            Ok(v) => v
+                .to_string()
+                .split(',')
+                .map(|f| f.trim().to_lowercase())
+                .filter(|f| !f.is_empty())
+                .collect(),
+            Err(_) => DEFAULT_REDACT_FIELDS
+                .iter()
+                .map(|f| f.to_string())
+                .collect(),
+        };
+        Redactor { fields }
+    }
+
+    pub fn text(&self, text: &str) -> String {
+        let text = email_pattern().replace_all(text, "[email]");
+        number_pattern()
+            .replace_all(&text, |caps: &regex::Captures| {
+                let digits = caps[0].chars().filter(char::is_ascii_digit).count();
+                if digits >= MIN_PHONE_DIGITS {
+                    "[number]".to_string()
+                } else {
+                    caps[0].to_string()
+                }
+            })
+            .into_owned()
+    }
+
+    pub fn value(&self, value: &mut JsonValue) {
+        match value {
+            JsonValue::String(s) => *s = self.text(s),
+            JsonValue::Array(items) => items.iter_mut().for_each(|item| self.value(item)),
+            JsonValue::Object(map) => {
+                for (key, item) in map.iter_mut() {
+                    if self.fields.contains(&key.to_lowercase()) {
+                        *item = JsonValue::String("[redacted]".to_string());
+                    } else {
+                        self.value(item);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn entities(&self, entities: &mut [ApiEntity]) {
+        for entity in entities {
+            entity.name = self.text(&entity.name);
+            for observation in &mut entity.observations {
+                *observation = self.text(observation);
+            }
+            if let Some(data) = &mut entity.data {
+                self.value(data);
+            }
+        }
+    }
+
+    // Raw nodes keep their observations in data, so masking the data covers them.
+    pub fn nodes(&self, nodes: &mut [Node]) {
+        for node in nodes {
+            node.id = self.text(&node.id);
+            self.value(&mut node.data);
+            node.observation_authors = std::mem::take(&mut node.observation_authors)
+                .into_iter()
+                .map(|(observation, actor)| (self.text(&observation), actor))
+                .collect();
+            node.observation_added_ms = std::mem::take(&mut node.observation_added_ms)
+                .into_iter()
+                .map(|(observation, at_ms)| (self.text(&observation), at_ms))
+                .collect();
+        }
+    }
+
+    // Edge endpoints are node ids, i.e. entity names.
+    pub fn edges(&self, edges: &mut [Edge]) {
+        for edge in edges {
+            edge.source_node_id = self.text(&edge.source_node_id);
+            edge.target_node_id = self.text(&edge.target_node_id);
+            if let Some(data) = &mut edge.data {
+                self.value(data);
+            }
+        }
+    }
+
+    pub fn node_edges(&self, node_edges: &mut [NodeEdge]) {
+        for item in node_edges {
+            item.neighbor_id = self.text(&item.neighbor_id);
+            self.edges(std::slice::from_mut(&mut item.edge));
+        }
+    }
+
+    pub fn names(&self, names: &mut [String]) {
+        for name in names {
+            *name = self.text(name);
+        }
+    }
+
+    pub fn anchor_paths(&self, paths: &mut [AnchorPath]) {
+        for item in paths {
+            item.entity_name = self.text(&item.entity_name);
+            self.names(&mut item.path);
+            self.relations(&mut item.relations);
+        }
+    }
+
+    pub fn reachability(&self, result: &mut ReachabilityResult) {
+        self.names(&mut result.path);
+        self.relations(&mut result.relations);
+    }
+
+    pub fn observation_matches(&self, matches: &mut [ObservationMatch]) {
+        for item in matches {
+            item.entity_name = self.text(&item.entity_name);
+            item.observation = self.text(&item.observation);
+        }
+    }
+
+    pub fn context(&self, pack: &mut ContextPack) {
+        pack.markdown = self.text(&pack.markdown);
+        self.names(&mut pack.entities);
+    }
+
+    pub fn recent(&self, recent: &mut [RecentEntity]) {
+        for item in recent {
+            self.entities(std::slice::from_mut(&mut item.entity));
+        }
+    }
+
+    pub fn relations(&self, relations: &mut [ApiRelation]) {
+        for relation in relations {
+            relation.from = self.text(&relation.from);
+            relation.to = self.text(&relation.to);
+            if let Some(data) = &mut relation.data {
+                self.value(data);
+            }
+        }
+    }
+}
+
+// Read routes of the DO whose responses are redacted, or carry no names or data at all (path
+// without the /v1 prefix). Keys with the "redacted" scope can't call the other read routes.
+pub fn redacts_route(method: &Method, path: &str) -> bool {
+    let segments: Vec<&str> = path.split('/').collect();
+    match (method, segments.as_slice()) {
+        (Method::Get | Method::Head, ["", "edges", "find"]) => false,
+        (
+            Method::Get | Method::Head,
+            ["", "graph", "state"]
+            | ["", "state"]
+            | ["", "graph", "export"]
+            | ["", "graph", "recent"]
+            | ["", "graph", "stats"]
+            | ["", "graph", "entity-templates"]
+            | ["", "graph", "relation-types"]
+            | ["", "nodes"]
+            | ["", "nodes", _]
+            | ["", "nodes", _, "related"]
+            | ["", "nodes", _, "edges"]
+            | ["", "edges"]
+            | ["", "edges", _],
+        ) => true,
+        (
+            Method::Post,
+            ["", "nodes", "batch-get"]
+            | ["", "graph", "search"]
+            | ["", "graph", "open"]
+            | ["", "graph", "relations", "search"]
+            | ["", "graph", "observations", "search"]
+            | ["", "graph", "context"]
+            | ["", "graph", "subgraph"]
+            | ["", "graph", "reachable"],
+        ) => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,184 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{Edge, RelationTypeSpec};
+use uuid::Uuid;
+
+// Relation type catalog: per-type semantics that create_relations and delete_relations honor.
+// A symmetric type (FRIENDS_WITH) or a type with an inverse (MANAGES <-> REPORTS_TO) gets its
+// mirrored edge stored alongside the original, so every traversal sees both directions
+// without knowing about the catalog. Deleting either edge of a pair removes the other, and
+// patching the data of one patches the other's as well.
+
+impl KnowledgeGraphState {
+    // Type of the edge that mirrors an edge of `edge_type`, if the catalog defines one.
+    pub fn mirror_type(&self, edge_type: &str) -> Option<&str> {
+        let (name, spec) = self.relation_types.get_key_value(edge_type)?;
+        if spec.symmetric {
+            Some(name)
+        } else {
+            spec.inverse.as_deref()
+        }
+    }
+
+    pub fn find_edge_id(&self, source: &str, target: &str, edge_type: &str) -> Option<String> {
+        self.edges
+            .values()
+            .find(|e| {
+                e.source_node_id == source && e.target_node_id == target && e.edge_type == edge_type
+            })
+            .map(|e| e.id.clone())
+    }
+
+    // Creates the mirror of `edge_id` when its type calls for one and it doesn't exist yet;
+    // returns the new edge's id. A symmetric self-loop is its own mirror.
+    pub fn ensure_mirror_edge(&mut self, edge_id: &str, current_time_ms: u64) -> Option<String> {
+        let edge = self.edges.get(edge_id)?;
+        let mirror_type = self.mirror_type(&edge.edge_type)?.to_string();
+        if edge.source_node_id == edge.target_node_id && mirror_type == edge.edge_type {
+            return None;
+        }
+        if self
+            .find_edge_id(&edge.target_node_id, &edge.source_node_id, &mirror_type)
+            .is_some()
+        {
+            return None;
+        }
+        let mirror = Edge {
+            id: Uuid::new_v4().to_string(),
+            edge_type: mirror_type,
+            source_node_id: edge.target_node_id.clone(),
+            target_node_id: edge.source_node_id.clone(),
+            data: edge.data.clone(),
+            created_at_ms: current_time_ms,
+            created_by: self.actor.clone(),
+            updated_by: None,
+            confidence: edge.confidence,
+            confidence_at_ms: edge.confidence_at_ms,
+        };
+        let mirror_id = mirror.id.clone();
+        self.edges.insert(mirror_id.clone(), mirror);
+        Some(mirror_id)
+    }
+
+    // Id of the stored mirror of `edge`, if it has one. A symmetric self-loop finds itself.
+    pub fn mirror_edge_id(&self, edge: &Edge) -> Option<String> {
+        let mirror_type = self.mirror_type(&edge.edge_type)?;
+        self.find_edge_id(&edge.target_node_id, &edge.source_node_id, mirror_type)
+    }
+
+    // Removes the mirror of an edge that was just deleted, if there is one.
+    pub fn remove_mirror_edge(&mut self, deleted: &Edge) -> Option<String> {
+        let mirror_id = self.mirror_edge_id(deleted)?;
+        self.edges.remove(&mirror_id);
+        Some(mirror_id)
+    }
+
+    // Registers the semantics of a relation type. An inverse is registered in both directions,
+    // replacing whatever either type was paired with before. Existing edges of both types get
+    // their mirrors; returns the ids of the edges created for that.
+    pub fn set_relation_type(
+        &mut self,
+        name: &str,
+        spec: RelationTypeSpec,
+        current_time_ms: u64,
+    ) -> Result<Vec<String>, String> {
+        if spec.symmetric && spec.inverse.is_some() {
+            return Err("a relation type can't be both symmetric and have an inverse".to_string());
+        }
+        if let Some(inverse) = &spec.inverse {
+            if inverse.trim().is_empty() {
+                return Err("'inverse' must not be empty".to_string());
+            }
+            if inverse == name {
+                return Err(format!(
+                    "'{}' can't be its own inverse; mark it symmetric instead",
+                    name
+                ));
+            }
+            if self
+                .relation_types
+                .get(inverse)
+                .is_some_and(|other| other.symmetric)
+            {
+                return Err(format!(
+                    "'{}' is symmetric and can't be an inverse",
+                    inverse
+                ));
+            }
+        }
+
+        let inverse = spec.inverse.clone();
+        self.unlink_inverse(name);
+        if let Some(inverse) = &inverse {
+            self.unlink_inverse(inverse);
+            self.relation_types.insert(
+                inverse.clone(),
+                RelationTypeSpec {
+                    symmetric: false,
+                    inverse: Some(name.to_string()),
+                },
+            );
+        }
+        self.relation_types.insert(name.to_string(), spec);
+
+        let mut edge_ids: Vec<String> = self
+            .edges
+            .values()
+            .filter(|e| e.edge_type == name || inverse.as_ref() == Some(&e.edge_type))
+            .map(|e| e.id.clone())
+            .collect();
+        edge_ids.sort();
+        Ok(edge_ids
+            .iter()
+            .filter_map(|id| self.ensure_mirror_edge(id, current_time_ms))
+            .collect())
+    }
+
+    // Drops a type from the catalog (and its inverse's link back to it); edges are kept.
+    pub fn remove_relation_type(&mut self, name: &str) -> Option<RelationTypeSpec> {
+        self.unlink_inverse(name);
+        self.relation_types.remove(name)
+    }
+
+    // Moves the catalog entry of a renamed relation type to its new name, with its inverse's
+    // link back to it. When the new name has an entry of its own, that one is kept and the old
+    // entry's inverse loses its link. Returns whether the catalog changed.
+    pub fn rename_relation_type_spec(&mut self, from: &str, to: &str) -> bool {
+        let Some(spec) = self.relation_types.remove(from) else {
+            return false;
+        };
+        let keep_existing = self.relation_types.contains_key(to);
+        if let Some(inverse) = &spec.inverse {
+            if let Some(other) = self.relation_types.get_mut(inverse) {
+                if other.inverse.as_deref() == Some(from) {
+                    other.inverse = (!keep_existing).then(|| to.to_string());
+                }
+            }
+        }
+        if !keep_existing {
+            self.relation_types.insert(to.to_string(), spec);
+        }
+        true
+    }
+
+    // Clears the back-reference held by the current inverse of `name`.
+    fn unlink_inverse(&mut self, name: &str) {
+        let Some(old_inverse) = self
+            .relation_types
+            .get(name)
+            .and_then(|spec| spec.inverse.clone())
+        else {
+            return;
+        };
+        // An entry that only existed as the other half of the pair goes away with it
+        if self
+            .relation_types
+            .get(&old_inverse)
+            .is_some_and(|other| other.inverse.as_deref() == Some(name))
+        {
+            self.relation_types.remove(&old_inverse);
+        }
+        if let Some(spec) = self.relation_types.get_mut(name) {
+            spec.inverse = None;
+        }
+    }
+}
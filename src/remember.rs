@@ -0,0 +1,70 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::{
+    AddObservationItem, BatchResponse, BatchResult, BatchStatus, MissingNodePolicy,
+    RememberPayload, RememberResponse,
+};
+
+// POST /graph/remember: create_entities, create_relations and add_observations in one call,
+// all or nothing. Entities come first, so the relations and observations can refer to them;
+// an entity that already exists gets its observations added instead. If any item fails
+// (a relation to a missing entity unless on_missing_node says otherwise, observations for a
+// missing entity, ...) the response reports every item with `error` set and the route
+// doesn't save, so nothing is written.
+
+// Statuses that make the whole call fail. Existing and skipped items don't: remembering the
+// same facts twice is fine.
+fn failed(result: &BatchResult) -> bool {
+    matches!(
+        result.status,
+        BatchStatus::NotFound | BatchStatus::Conflict | BatchStatus::Error
+    )
+}
+
+impl KnowledgeGraphState {
+    pub fn remember(&mut self, payload: RememberPayload) -> RememberResponse {
+        let policy = self.near_duplicate_policy(payload.on_near_duplicate);
+        let on_missing_node = payload.on_missing_node.unwrap_or(MissingNodePolicy::Error);
+
+        let mut entity_results = self.create_entities_batch(payload.entities.clone(), policy);
+        let existing: Vec<(usize, AddObservationItem)> = entity_results
+            .iter()
+            .zip(payload.entities)
+            .filter(|(result, _)| result.status == BatchStatus::AlreadyExists)
+            .map(|(result, entity)| {
+                let item = AddObservationItem {
+                    entity_name: entity.name,
+                    contents: entity.observations,
+                    expected_version: None,
+                };
+                (result.index, item)
+            })
+            .collect();
+        let (indexes, items): (Vec<usize>, Vec<AddObservationItem>) = existing.into_iter().unzip();
+        for (index, mut result) in indexes.into_iter().zip(self.add_observations_batch(items)) {
+            result.index = index;
+            entity_results[index] = result;
+        }
+
+        let (relation_results, relations_rejected) =
+            match self.create_relations_batch(payload.relations, on_missing_node) {
+                Ok(results) => (results, false),
+                Err(results) => (results, true),
+            };
+        let observation_results = self.add_observations_batch(payload.observations);
+
+        let failures = entity_results
+            .iter()
+            .chain(&relation_results)
+            .chain(&observation_results)
+            .filter(|result| failed(result))
+            .count();
+        let error = (failures > 0 || relations_rejected)
+            .then(|| format!("Nothing was remembered: {} item(s) failed", failures.max(1)));
+        RememberResponse {
+            entities: BatchResponse::new(entity_results),
+            relations: BatchResponse::new(relation_results).with_on_missing_node(on_missing_node),
+            observations: BatchResponse::new(observation_results),
+            error,
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::GraphStub;
+use futures_util::future::join_all;
+use worker::*;
+
+// Read replicas: extra instances of the graph DO placed near clients with location hints.
+// There is no change log to stream, so the primary ships a versioned snapshot of its state
+// after every write (and on each maintenance run, to seed new replicas); a replica only
+// applies snapshots newer than the one it holds. The worker sends read-only requests to the
+// replica nearest the client and everything else to the primary, so replica reads are
+// eventually consistent. A replica that has not received a snapshot yet answers 503 and the
+// worker falls back to the primary. Only the shared graph is replicated; graphs of individual
+// Access identities (see access.rs) are always served by their own DO.
+
+// Comma-separated location hints of the replicas, e.g. "weur,apac"; unset disables replicas
+pub const READ_REPLICA_REGIONS_VAR: &str = "READ_REPLICA_REGIONS";
+// Inserted after the API version prefix on requests addressed to a replica
+pub const REPLICA_PATH_PREFIX: &str = "/replica";
+// Snapshot delivery from the primary; never forwarded from /do/*path
+pub const REPLICA_SYNC_PATH: &str = "/internal/replica/sync";
+const PRIMARY_NAME: &str = crate::KNOWLEDGE_GRAPH_NAME;
+
+pub fn replica_regions(env: &Env) -> Vec<String> {
+    env.var(READ_REPLICA_REGIONS_VAR)
+        .map(|v| {
+            v.to_string()
+                .split(',')
+                .map(|region| region.trim().to_string())
+                .filter(|region| !region.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn replica_stub(env: &Env, region: &str) -> Result<Stub> {
+    env.durable_object("KNOWLEDGE_GRAPH_DO")?
+        .id_from_name(&format!("{}:replica:{}", PRIMARY_NAME, region))?
+        .get_stub_with_location_hint(region)
+}
+
+pub fn replica_stubs(env: &Env) -> Vec<Stub> {
+    replica_regions(env)
+        .iter()
+        .filter_map(|region| match replica_stub(env, region) {
+            Ok(stub) => Some(stub),
+            Err(e) => {
+                console_warn!("Skipping read replica '{}': {}", region, e);
+                None
+            }
+        })
+        .collect()
+}
+
+// Location hints closest to a client continent, most preferred first.
+fn regions_for_continent(continent: &str) -> &'static [&'static str] {
+    match continent {
+        "NA" => &["enam", "wnam"],
+        "SA" => &["sam", "enam"],
+        "EU" => &["weur", "eeur"],
+        "AF" => &["afr", "weur"],
+        "AS" => &["apac", "me"],
+        "OC" => &["oc", "apac"],
+        _ => &[],
+    }
+}
+
+// Whether a DO instance is the primary of the shared graph, the only one that replicates.
+pub fn is_primary(env: &Env, id: &ObjectId) -> bool {
+    env.durable_object("KNOWLEDGE_GRAPH_DO")
+        .and_then(|namespace| Ok(namespace.id_from_name(PRIMARY_NAME)?.to_string()))
+        .is_ok_and(|primary| primary == id.to_string())
+}
+
+// Whether a DO instance is one of the configured replicas, the only ones that accept snapshots.
+pub fn is_replica_instance(env: &Env, id: &ObjectId) -> bool {
+    let Ok(namespace) = env.durable_object("KNOWLEDGE_GRAPH_DO") else {
+        return false;
+    };
+    let id = id.to_string();
+    replica_regions(env).iter().any(|region| {
+        namespace
+            .id_from_name(&format!("{}:replica:{}", PRIMARY_NAME, region))
+            .is_ok_and(|replica| replica.to_string() == id)
+    })
+}
+
+// The configured replica of `graph` nearest to the client, if any is close enough to be
+// worth it.
+pub fn nearest_replica(env: &Env, req: &Request, graph: &str) -> Option<GraphStub> {
+    let regions = replica_regions(env);
+    if regions.is_empty() || graph != PRIMARY_NAME {
+        return None;
+    }
+    let continent = req.cf()?.continent()?;
+    let region = regions_for_continent(&continent)
+        .iter()
+        .find(|hint| regions.iter().any(|r| r == *hint))?;
+    replica_stub(env, region)
+        .ok()
+        .map(|stub| GraphStub::new(stub, PRIMARY_NAME))
+}
+
+// Delivers a serialized KnowledgeGraphState to every replica at once; failures are only
+// logged since the next write or maintenance run sends a newer snapshot anyway.
+pub async fn push_snapshot(replicas: Vec<Stub>, snapshot: String) {
+    join_all(
+        replicas
+            .iter()
+            .map(|replica| send_snapshot(replica, &snapshot)),
+    )
+    .await;
+}
+
+async fn send_snapshot(replica: &Stub, snapshot: &str) {
+    let result: Result<Response> = async {
+        let mut req_init = RequestInit::new();
+        req_init.with_method(Method::Post);
+        req_init.with_body(Some(snapshot.into()));
+        let req = Request::new_with_init(
+            &format!("https://durable-object.internal-url{}", REPLICA_SYNC_PATH),
+            &req_init,
+        )?;
+        replica.fetch_with_request(req).await
+    }
+    .await;
+    match result {
+        Ok(response) if (200..300).contains(&response.status_code()) => {}
+        Ok(response) => console_warn!(
+            "Read replica rejected snapshot with status {}",
+            response.status_code()
+        ),
+        Err(e) => console_warn!("Failed to send snapshot to read replica: {}", e),
+    }
+}
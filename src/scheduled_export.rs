@@ -0,0 +1,100 @@
+use crate::{graph_stub, KNOWLEDGE_GRAPH_NAME};
+use worker::*;
+
+// Periodic full dumps for consumers outside the worker. A cron trigger in wrangler.toml runs
+// `run`, which reads the graph state of every known graph and delivers it to the configured
+// destinations: an R2 bucket bound as EXPORT_BUCKET (one object per run under
+// exports/<graph>/<timestamp>.json) and/or an EXPORT_WEBHOOK_URL that receives it as a JSON
+// POST. With neither configured the trigger does nothing. Failures are logged; the next
+// run exports again.
+
+pub const EXPORT_BUCKET_BINDING: &str = "EXPORT_BUCKET";
+pub const EXPORT_WEBHOOK_URL_VAR: &str = "EXPORT_WEBHOOK_URL";
+
+// Graphs the worker serves; there is one DO instance per name. Graphs of individual Access
+// identities (see access.rs) are created on first use and can't be listed, so they're not exported
+const KNOWN_GRAPHS: &[&str] = &[KNOWLEDGE_GRAPH_NAME];
+
+async fn read_state(env: &Env, graph: &str) -> Result<Vec<u8>> {
+    let mut req_init = RequestInit::new();
+    req_init.with_method(Method::Get);
+    let req = Request::new_with_init(
+        &format!(
+            "https://durable-object.internal-url{}/graph/state",
+            crate::API_V1_PREFIX
+        ),
+        &req_init,
+    )?;
+    let mut response = graph_stub(env, graph)?.fetch_with_request(req).await?;
+    if response.status_code() != 200 {
+        return Err(Error::RustError(format!(
+            "graph state read failed with status {}",
+            response.status_code()
+        )));
+    }
+    response.bytes().await
+}
+
+async fn post_to_webhook(url: &str, graph: &str, body: Vec<u8>) -> Result<()> {
+    let mut headers = Headers::new();
+    headers.set("content-type", "application/json")?;
+    headers.set("x-graph-name", graph)?;
+    let mut req_init = RequestInit::new();
+    req_init
+        .with_method(Method::Post)
+        .with_headers(headers)
+        .with_body(Some(body.into()));
+    let response = Fetch::Request(Request::new_with_init(url, &req_init)?)
+        .send()
+        .await?;
+    if !(200..300).contains(&response.status_code()) {
+        return Err(Error::RustError(format!(
+            "export webhook answered {}",
+            response.status_code()
+        )));
+    }
+    Ok(())
+}
+
+pub async fn run(env: &Env, scheduled_at_ms: u64) {
+    let bucket = env.bucket(EXPORT_BUCKET_BINDING).ok();
+    let webhook_url = env
+        .var(EXPORT_WEBHOOK_URL_VAR)
+        .ok()
+        .map(|v| v.to_string())
+        .filter(|v| !v.is_empty());
+    if bucket.is_none() && webhook_url.is_none() {
+        console_log!("Scheduled export skipped: no export bucket or webhook configured");
+        return;
+    }
+    for graph in KNOWN_GRAPHS {
+        let body = match read_state(env, graph).await {
+            Ok(body) => body,
+            Err(e) => {
+                console_error!("Scheduled export of '{}' failed: {}", graph, e);
+                continue;
+            }
+        };
+        if let Some(bucket) = &bucket {
+            let key = format!("exports/{}/{}.json", graph, scheduled_at_ms);
+            let stored = bucket
+                .put(&key, body.clone())
+                .http_metadata(HttpMetadata {
+                    content_type: Some("application/json".to_string()),
+                    ..Default::default()
+                })
+                .execute()
+                .await;
+            match stored {
+                Ok(_) => console_log!("Exported '{}' to R2 as {}", graph, key),
+                Err(e) => console_error!("Export of '{}' to R2 failed: {}", graph, e),
+            }
+        }
+        if let Some(url) = &webhook_url {
+            match post_to_webhook(url, graph, body).await {
+                Ok(()) => console_log!("Exported '{}' to the export webhook", graph),
+                Err(e) => console_error!("Export of '{}' to the webhook failed: {}", graph, e),
+            }
+        }
+    }
+}
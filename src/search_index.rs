@@ -0,0 +1,202 @@
+use crate::kg::KnowledgeGraphState;
+use crate::types::Node;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// Inverted index from lowercased tokens to the ids of the nodes containing them. It covers the
+// same text `search_nodes` matches against: the node id, its type and its observations.
+// Alongside it, a sorted map of lowercased entity names and aliases answers prefix lookups
+// for autocompletion; it is cheap to derive, so it is rebuilt on load instead of persisted.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SearchIndex {
+    postings: BTreeMap<String, BTreeSet<String>>, // Token -> node ids
+    #[serde(skip)]
+    node_tokens: HashMap<String, BTreeSet<String>>, // Node id -> tokens, derived from postings
+    #[serde(skip)]
+    names: BTreeMap<String, BTreeSet<String>>, // Lowercased name or alias -> node ids
+    #[serde(skip)]
+    node_names: HashMap<String, BTreeSet<String>>, // Node id -> its keys in `names`
+}
+
+// Splits text into lowercased alphanumeric runs.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
+
+// The strings of a node that are searchable: id, type and string observations.
+pub fn searchable_strings(node: &Node) -> impl Iterator<Item = &str> {
+    let observations = node
+        .data
+        .get("observations")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str());
+    [node.id.as_str(), node.node_type.as_str()]
+        .into_iter()
+        .chain(observations)
+}
+
+// Alternative names of an entity: the strings in its data's "aliases" array.
+pub fn aliases(node: &Node) -> impl Iterator<Item = &str> {
+    node.data
+        .get("aliases")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str())
+}
+
+impl SearchIndex {
+    pub fn is_empty(&self) -> bool {
+        self.postings.is_empty()
+    }
+
+    // Rebuilds the id -> tokens map after deserialization (it isn't persisted).
+    fn restore_node_tokens(&mut self) {
+        self.node_tokens.clear();
+        for (token, ids) in &self.postings {
+            for id in ids {
+                self.node_tokens
+                    .entry(id.clone())
+                    .or_default()
+                    .insert(token.clone());
+            }
+        }
+    }
+
+    pub fn remove_node(&mut self, node_id: &str) {
+        for key in self.node_names.remove(node_id).unwrap_or_default() {
+            if let Some(ids) = self.names.get_mut(&key) {
+                ids.remove(node_id);
+                if ids.is_empty() {
+                    self.names.remove(&key);
+                }
+            }
+        }
+        let Some(tokens) = self.node_tokens.remove(node_id) else {
+            return;
+        };
+        for token in tokens {
+            if let Some(ids) = self.postings.get_mut(&token) {
+                ids.remove(node_id);
+                if ids.is_empty() {
+                    self.postings.remove(&token);
+                }
+            }
+        }
+    }
+
+    // (Re)indexes a node, replacing whatever was indexed for its id before.
+    pub fn index_node(&mut self, node: &Node) {
+        self.remove_node(&node.id);
+        let tokens: BTreeSet<String> = searchable_strings(node).flat_map(tokenize).collect();
+        for token in &tokens {
+            self.postings
+                .entry(token.clone())
+                .or_default()
+                .insert(node.id.clone());
+        }
+        self.node_tokens.insert(node.id.clone(), tokens);
+        self.index_names(node);
+    }
+
+    fn index_names(&mut self, node: &Node) {
+        let keys: BTreeSet<String> = std::iter::once(node.id.as_str())
+            .chain(aliases(node))
+            .map(str::to_lowercase)
+            .collect();
+        for key in &keys {
+            self.names
+                .entry(key.clone())
+                .or_default()
+                .insert(node.id.clone());
+        }
+        self.node_names.insert(node.id.clone(), keys);
+    }
+
+    pub fn clear(&mut self) {
+        self.postings.clear();
+        self.node_tokens.clear();
+        self.names.clear();
+        self.node_names.clear();
+    }
+
+    // (lowercased name or alias, node id) pairs whose key starts with `prefix`, in key order.
+    pub fn names_with_prefix<'a>(
+        &'a self,
+        prefix: &str,
+    ) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        let prefix = prefix.to_lowercase();
+        self.names
+            .range(prefix.clone()..)
+            .take_while(move |(key, _)| key.starts_with(&prefix))
+            .flat_map(|(key, ids)| ids.iter().map(move |id| (key.as_str(), id.as_str())))
+    }
+
+    // Ids of the nodes whose name or an alias equals `name`, ignoring case.
+    pub fn ids_named<'a>(&'a self, name: &str) -> impl Iterator<Item = &'a str> + 'a {
+        self.names
+            .get(&name.to_lowercase())
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    pub fn rebuild<'a>(&mut self, nodes: impl IntoIterator<Item = &'a Node>) {
+        self.clear();
+        for node in nodes {
+            self.index_node(node);
+        }
+    }
+
+    // Ids of nodes that may contain `query` as a substring, or `None` if the query has no
+    // tokens to look up. Every query token must occur inside some token of a matching node,
+    // so the candidates are a superset of the real matches and callers still verify them.
+    pub fn candidates(&self, query: &str) -> Option<BTreeSet<String>> {
+        let mut result: Option<BTreeSet<String>> = None;
+        for query_token in tokenize(query).collect::<BTreeSet<_>>() {
+            let ids: BTreeSet<String> = self
+                .postings
+                .iter()
+                .filter(|(token, _)| token.contains(&query_token))
+                .flat_map(|(_, ids)| ids.iter().cloned())
+                .collect();
+            result = Some(match result {
+                Some(acc) => acc.intersection(&ids).cloned().collect(),
+                None => ids,
+            });
+        }
+        result
+    }
+}
+
+impl KnowledgeGraphState {
+    // Reindexes a node after it was inserted or modified, or drops it from the index if it no
+    // longer exists.
+    pub fn reindex_node(&mut self, node_id: &str) {
+        match self.nodes.get(node_id) {
+            Some(node) => self.search_index.index_node(node),
+            None => self.search_index.remove_node(node_id),
+        }
+    }
+
+    pub fn rebuild_search_index(&mut self) {
+        self.search_index.rebuild(self.nodes.values());
+    }
+
+    // Prepares the index of a freshly loaded state: states saved before the index existed get
+    // one built, otherwise only the in-memory maps are restored.
+    pub fn ensure_search_index(&mut self) {
+        if self.search_index.is_empty() && !self.nodes.is_empty() {
+            self.rebuild_search_index();
+        } else {
+            self.search_index.restore_node_tokens();
+            for node in self.nodes.values() {
+                self.search_index.index_names(node);
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use crate::types::{ApiEntity, ApiRelation};
+use futures_util::stream;
+use serde::Serialize;
+use std::vec::IntoIter;
+use worker::{Headers, Response, Result};
+
+// Full-graph reads (GET /graph/state and /state) are written as a stream of chunks of
+// STREAM_PAGE_SIZE entities or relations instead of one serialized string, so the largest
+// allocation is a page of JSON rather than the whole document, and the worker passes the body
+// through to the client as it arrives. The bytes are the same as serializing a
+// KnowledgeGraphDataResponse in one go.
+
+pub const STREAM_PAGE_SIZE: usize = 500;
+
+enum Section {
+    Entities,
+    Relations,
+    Done,
+}
+
+struct GraphChunks {
+    entities: IntoIter<ApiEntity>,
+    relations: IntoIter<ApiRelation>,
+    section: Section,
+    first: bool, // No comma before the first item of a section
+}
+
+// Appends up to a page of items to `out`; returns whether the section ran out.
+fn write_page<T: Serialize>(items: &mut IntoIter<T>, first: &mut bool, out: &mut Vec<u8>) -> bool {
+    for _ in 0..STREAM_PAGE_SIZE {
+        let Some(item) = items.next() else {
+            return true;
+        };
+        if !std::mem::take(first) {
+            out.push(b',');
+        }
+        // Serializing plain data structs into a Vec can't fail
+        serde_json::to_writer(&mut *out, &item).unwrap_or_default();
+    }
+    items.len() == 0
+}
+
+impl Iterator for GraphChunks {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        let mut out = Vec::new();
+        match self.section {
+            Section::Entities => {
+                if self.first {
+                    out.extend_from_slice(b"{\"entities\":[");
+                }
+                if write_page(&mut self.entities, &mut self.first, &mut out) {
+                    out.extend_from_slice(b"],\"relations\":[");
+                    self.section = Section::Relations;
+                    self.first = true;
+                }
+            }
+            Section::Relations => {
+                if write_page(&mut self.relations, &mut self.first, &mut out) {
+                    out.extend_from_slice(b"]}");
+                    self.section = Section::Done;
+                }
+            }
+            Section::Done => return None,
+        }
+        Some(out)
+    }
+}
+
+pub fn graph_response(entities: Vec<ApiEntity>, relations: Vec<ApiRelation>) -> Result<Response> {
+    let chunks = GraphChunks {
+        entities: entities.into_iter(),
+        relations: relations.into_iter(),
+        section: Section::Entities,
+        first: true,
+    };
+    let mut headers = Headers::new();
+    headers.set("content-type", "application/json")?;
+    Ok(
+        Response::from_stream(stream::iter(chunks.map(Ok::<_, worker::Error>)))?
+            .with_headers(headers),
+    )
+}
@@ -0,0 +1,131 @@
+use crate::kg::KnowledgeGraphState;
+use crate::names;
+use crate::types::{
+    BatchResult, BatchStatus, EntityTemplate, EntityToCreate, MissingNodePolicy,
+    NearDuplicatePolicy, RelationToCreate,
+};
+
+// Entity templates: per entity type, the observations every new entity of the type starts
+// with, the data fields it must set and the relations it gets, so agents creating structured
+// records (Tasks, Meetings, ...) don't repeat the boilerplate. create_entities only applies
+// them when asked to with `use_template`. A template relation whose target doesn't exist is
+// skipped, as create_relations does by default.
+
+impl KnowledgeGraphState {
+    pub fn set_entity_template(
+        &mut self,
+        entity_type: &str,
+        mut template: EntityTemplate,
+    ) -> Result<(), String> {
+        for (i, field) in template.required_fields.iter().enumerate() {
+            if field.trim().is_empty() {
+                return Err(format!("required_fields[{}] must not be empty", i));
+            }
+        }
+        for (i, relation) in template.relations.iter_mut().enumerate() {
+            if relation.relation_type.trim().is_empty() {
+                return Err(format!("relations[{}].relationType must not be empty", i));
+            }
+            names::canonicalize(&mut relation.to);
+            if relation.to.is_empty() {
+                return Err(format!("relations[{}].to must not be empty", i));
+            }
+        }
+        self.entity_templates
+            .insert(entity_type.to_string(), template);
+        Ok(())
+    }
+
+    pub fn remove_entity_template(&mut self, entity_type: &str) -> Option<EntityTemplate> {
+        self.entity_templates.remove(entity_type)
+    }
+
+    // The first required field of the entity's template that its data doesn't set.
+    fn missing_required_field(&self, entity: &EntityToCreate) -> Option<String> {
+        let template = self.entity_templates.get(&entity.entity_type)?;
+        template
+            .required_fields
+            .iter()
+            .find(|field| {
+                entity
+                    .data
+                    .as_ref()
+                    .and_then(|data| data.get(field.as_str()))
+                    .is_none_or(|value| value.is_null())
+            })
+            .cloned()
+    }
+
+    // create_entities_batch with each entity's type template applied: entities missing a
+    // required field are refused, the others get the template's observations and, once
+    // created, its relations.
+    pub fn create_entities_from_templates(
+        &mut self,
+        entities: Vec<EntityToCreate>,
+        policy: NearDuplicatePolicy,
+    ) -> Vec<BatchResult> {
+        let mut refused = Vec::new();
+        let mut to_create = Vec::new();
+        let mut indexes = Vec::new();
+        for (index, mut entity) in entities.into_iter().enumerate() {
+            if let Some(field) = self.missing_required_field(&entity) {
+                refused.push(BatchResult::failed(
+                    index,
+                    Some(entity.name),
+                    BatchStatus::Error,
+                    format!(
+                        "The {} template requires data field '{}'",
+                        entity.entity_type, field
+                    ),
+                ));
+                continue;
+            }
+            if let Some(template) = self.entity_templates.get(&entity.entity_type) {
+                for observation in &template.observations {
+                    if !entity.observations.contains(observation) {
+                        entity.observations.push(observation.clone());
+                    }
+                }
+            }
+            indexes.push((index, entity.entity_type.clone()));
+            to_create.push(entity);
+        }
+
+        let mut results = self.create_entities_batch(to_create, policy);
+        for (result, (index, entity_type)) in results.iter_mut().zip(indexes) {
+            result.index = index;
+            let (BatchStatus::Created, Some(name)) = (result.status, &result.id) else {
+                continue;
+            };
+            let Some(template) = self.entity_templates.get(&entity_type) else {
+                continue;
+            };
+            let relations: Vec<RelationToCreate> = template
+                .relations
+                .iter()
+                .map(|relation| {
+                    let mut to = relation.to.clone();
+                    self.resolve_name(&mut to);
+                    RelationToCreate {
+                        from: name.clone(),
+                        to,
+                        relation_type: relation.relation_type.clone(),
+                        data: None,
+                        confidence: None,
+                    }
+                })
+                .collect();
+            // Skip never rejects the batch
+            if let Ok(created) = self.create_relations_batch(relations, MissingNodePolicy::Skip) {
+                result.relations_created = created
+                    .into_iter()
+                    .filter(|r| r.status == BatchStatus::Created)
+                    .filter_map(|r| r.id)
+                    .collect();
+            }
+        }
+        results.extend(refused);
+        results.sort_by_key(|r| r.index);
+        results
+    }
+}
@@ -0,0 +1,54 @@
+use crate::types::TimeRange;
+
+// Time windows of the created_between and updated_between filters of GET /nodes and GET /edges.
+// They are matched by filtering: the DO loads a fresh state for every request, so an index
+// would have to be rebuilt (or deserialized) each time, which costs more than the scan.
+
+// One bound: epoch milliseconds, an RFC 3339 timestamp, or a date (midnight UTC).
+fn parse_bound(raw: &str) -> Result<Option<u64>, String> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(ms) = raw.parse::<u64>() {
+        return Ok(Some(ms));
+    }
+    let ms = if let Ok(time) = chrono::DateTime::parse_from_rfc3339(raw) {
+        time.timestamp_millis()
+    } else if let Ok(date) = chrono::NaiveDate::parse_from_str(raw, "%Y-%m-%d") {
+        date.and_hms_opt(0, 0, 0)
+            .map(|time| time.and_utc().timestamp_millis())
+            .unwrap_or_default()
+    } else {
+        return Err(format!(
+            "'{}' is not epoch milliseconds, an RFC 3339 timestamp or a YYYY-MM-DD date",
+            raw
+        ));
+    };
+    u64::try_from(ms)
+        .map(Some)
+        .map_err(|_| format!("'{}' is before 1970", raw))
+}
+
+impl TimeRange {
+    // Parses "start,end"; either side may be left empty for an open range.
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        let Some((start, end)) = raw.split_once(',') else {
+            return Err("expected 'start,end' (either may be empty)".to_string());
+        };
+        let range = TimeRange {
+            start: parse_bound(start)?,
+            end: parse_bound(end)?,
+        };
+        if let (Some(start), Some(end)) = (range.start, range.end) {
+            if start > end {
+                return Err("start is after end".to_string());
+            }
+        }
+        Ok(range)
+    }
+
+    pub fn contains(&self, at_ms: u64) -> bool {
+        self.start.is_none_or(|start| at_ms >= start) && self.end.is_none_or(|end| at_ms < end)
+    }
+}
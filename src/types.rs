@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Node {
@@ -10,6 +10,18 @@ pub struct Node {
     pub data: JsonValue,
     pub created_at_ms: u64,
     pub updated_at_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>, // Actor ids, see KnowledgeGraphState::actor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub observation_authors: BTreeMap<String, String>, // Observation -> actor that added it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub observation_added_ms: BTreeMap<String, u64>, // Observation -> when it was added
+    #[serde(default)]
+    pub version: u64, // Bumped on every change to the node, for expected_version checks
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool, // Kept by clears and cleanup jobs unless they're forced, see pinning.rs
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -22,13 +34,15 @@ pub struct Edge {
     pub data: Option<JsonValue>,
     pub created_at_ms: u64,
     // As per context, Edge doesn't have updated_at_ms
-}
-
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
-pub struct KnowledgeGraphState {
-    pub nodes: HashMap<String, Node>,
-    pub edges: HashMap<String, Edge>,
-    pub metadata: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+    // In [0, 1] as of confidence_at_ms; may fade, see confidence.rs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence_at_ms: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -43,6 +57,8 @@ pub struct UpdateNodePayload {
     #[serde(rename = "type")]
     pub node_type: Option<String>,
     pub data: Option<JsonValue>,
+    #[serde(default)]
+    pub expected_version: Option<u64>, // Rejected with 409 when the node has moved on
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -72,6 +88,30 @@ pub struct EntityToCreate {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CreateEntitiesPayload {
     pub entities: Vec<EntityToCreate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_near_duplicate: Option<NearDuplicatePolicy>, // Defaults to the graph's, see dedup.rs
+    #[serde(default)]
+    pub use_template: bool, // Apply the entity type's template, see templates.rs
+    #[serde(default)]
+    pub merge_existing: bool, // Merge into matching existing entities, see import.rs
+}
+
+// What create_entities does with a name very close to an existing entity's
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NearDuplicatePolicy {
+    // Create the entity, with a warning naming the existing one
+    Warn,
+    // Skip the entity
+    Reject,
+    // Don't check
+    Ignore,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NearDuplicate {
+    pub name: String,  // The existing entity
+    pub distance: f64, // Normalized edit distance, in [0, 1]
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -81,11 +121,29 @@ pub struct RelationToCreate {
     #[serde(rename = "relationType")]
     pub relation_type: String,
     pub data: Option<JsonValue>,
+    // In [0, 1]; setting it on an existing relation refreshes that relation's confidence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+}
+
+// What create_relations does with a relation whose `from` or `to` entity doesn't exist
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingNodePolicy {
+    // Leave the relation out and report it as skipped
+    #[default]
+    Skip,
+    // Reject the whole batch without writing anything
+    Error,
+    // Create a minimal "Unknown" entity for each missing endpoint
+    CreatePlaceholder,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CreateRelationsPayload {
     pub relations: Vec<RelationToCreate>,
+    #[serde(default)]
+    pub on_missing_node: MissingNodePolicy,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -93,6 +151,8 @@ pub struct AddObservationItem {
     #[serde(rename = "entityName")]
     pub entity_name: String,
     pub contents: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -100,10 +160,49 @@ pub struct AddObservationsPayload {
     pub observations: Vec<AddObservationItem>,
 }
 
+// POST /graph/remember: the three writes in one atomic call, see remember.rs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RememberPayload {
+    #[serde(default)]
+    pub entities: Vec<EntityToCreate>,
+    #[serde(default)]
+    pub relations: Vec<RelationToCreate>,
+    #[serde(default)]
+    pub observations: Vec<AddObservationItem>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_near_duplicate: Option<NearDuplicatePolicy>,
+    // Defaults to error, so a relation to a missing entity fails the whole call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_missing_node: Option<MissingNodePolicy>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RememberResponse {
+    pub entities: BatchResponse, // Existing entities report their observations being added
+    pub relations: BatchResponse,
+    pub observations: BatchResponse,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // Set when nothing was written
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DeleteEntitiesPayload {
     #[serde(rename = "entityNames")]
     pub entity_names: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub expected_versions: BTreeMap<String, u64>, // Entity name -> expected version
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PinEntitiesPayload {
+    #[serde(rename = "entityNames")]
+    pub entity_names: Vec<String>,
+    #[serde(default = "default_true")]
+    pub pinned: bool, // false unpins
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -111,6 +210,8 @@ pub struct DeleteObservationItem {
     #[serde(rename = "entityName")]
     pub entity_name: String,
     pub observations: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -133,9 +234,123 @@ pub struct DeleteRelationsPayload {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchNodesQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+    // Several queries in one call instead of `query`, answered grouped per query
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queries: Vec<String>,
+    // Optional expression over node data, e.g. `data.status == "draft" && data.priority > 3`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_filter: Option<String>,
+    // Entity (e.g. the current user) to report each match's shortest relation path from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+}
+
+// POST /graph/search with `anchor`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnchoredSearchResponse {
+    pub entities: Vec<ApiEntity>,
+    pub relations: Vec<ApiRelation>,
+    pub anchor_paths: Vec<AnchorPath>,
+}
+
+// How a matched entity connects to the search anchor. Relations are followed in either
+// direction; matches the anchor can't reach have no path.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AnchorPath {
+    #[serde(rename = "entityName")]
+    pub entity_name: String,
+    pub path: Vec<String>, // Entity names from the anchor to the match
+    pub relations: Vec<ApiRelation>, // The relations along `path`, in order
+}
+
+// POST /graph/search with `queries`: one group per query, in request order
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GroupedSearchResponse {
+    pub results: Vec<QuerySearchResults>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuerySearchResults {
+    pub query: String,
+    pub entities: Vec<ApiEntity>,
+    pub relations: Vec<ApiRelation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anchor_paths: Vec<AnchorPath>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchRelationsQuery {
     pub query: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchObservationsQuery {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BuildContextQuery {
+    pub query: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hops: Option<usize>, // Relations to follow out from the matching entities
+}
+
+// POST /graph/context, see context.rs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContextPack {
+    pub query: String,
+    pub markdown: String,
+    pub estimated_tokens: usize,
+    pub entities: Vec<String>, // Entities included, most relevant first
+    pub truncated: bool,       // The budget ran out before everything relevant was listed
+}
+
+// POST /graph/observations/search, newest first
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchObservationsResponse {
+    pub observations: Vec<ObservationMatch>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ObservationMatch {
+    #[serde(rename = "entityName")]
+    pub entity_name: String,
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+    pub observation: String,
+    // Unknown for observations stored before their timestamps and authors were kept
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_at_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_by: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubgraphQuery {
+    #[serde(default, alias = "node_types")]
+    pub entity_types: Option<Vec<String>>, // All entity types when omitted
+    #[serde(default)]
+    pub relation_types: Option<Vec<String>>, // All relation types when omitted
+}
+
+// POST /nodes/batch-get: raw nodes for sync and backup tooling
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchGetNodesPayload {
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchGetNodesResponse {
+    pub nodes: Vec<Node>,
+    pub missing: Vec<String>, // Requested ids with no node
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct OpenNodesQuery {
     pub names: Vec<String>,
@@ -149,6 +364,14 @@ pub struct ApiEntity {
     pub entity_type: String,
     pub observations: Vec<String>,
     pub data: Option<JsonValue>, // To match node_to_api_entity logic
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -158,6 +381,13 @@ pub struct ApiRelation {
     #[serde(rename = "relationType")]
     pub relation_type: String,
     pub data: Option<JsonValue>, // To match edge_to_api_relation logic
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_by: Option<String>,
+    // Current confidence, after any decay
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -165,3 +395,930 @@ pub struct KnowledgeGraphDataResponse {
     pub entities: Vec<ApiEntity>,
     pub relations: Vec<ApiRelation>,
 }
+
+// Batch Operation Results
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    Created,
+    Updated,
+    Unchanged,
+    Deleted,
+    AlreadyExists,
+    Skipped,
+    NotFound,
+    Conflict, // expected_version didn't match
+    Error,
+}
+
+// Outcome of one item of a batch request; `index` is the item's position in the request.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchResult {
+    pub index: usize,
+    pub id: Option<String>, // Entity name, or edge ID for relations
+    pub status: BatchStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    // Placeholder entities created to satisfy this item (create_relations with create_placeholder)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub placeholders_created: Vec<String>,
+    // Edge created to mirror this relation (symmetric or inverse type), or removed with it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirrored_edge_id: Option<String>,
+    // Existing entity the new one's name is very close to (create_entities)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub near_duplicate: Option<NearDuplicate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    // Edge IDs of the template's default relations created with this entity
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relations_created: Vec<String>,
+    // Every edge removed for this relation (delete_relations); `id` is the first of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edges_deleted: Vec<String>,
+    // Every mirror edge removed along with them; `mirrored_edge_id` is the first of them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mirrors_deleted: Vec<String>,
+}
+
+impl BatchResult {
+    pub fn ok(index: usize, id: impl Into<String>, status: BatchStatus) -> Self {
+        BatchResult {
+            index,
+            id: Some(id.into()),
+            status,
+            error: None,
+            placeholders_created: Vec::new(),
+            mirrored_edge_id: None,
+            near_duplicate: None,
+            warning: None,
+            relations_created: Vec::new(),
+            edges_deleted: Vec::new(),
+            mirrors_deleted: Vec::new(),
+        }
+    }
+
+    pub fn failed(
+        index: usize,
+        id: Option<String>,
+        status: BatchStatus,
+        error: impl Into<String>,
+    ) -> Self {
+        BatchResult {
+            index,
+            id,
+            status,
+            error: Some(error.into()),
+            placeholders_created: Vec::new(),
+            mirrored_edge_id: None,
+            near_duplicate: None,
+            warning: None,
+            relations_created: Vec::new(),
+            edges_deleted: Vec::new(),
+            mirrors_deleted: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize, // Items that were not applied (already existed, not found, errored)
+    pub by_status: BTreeMap<BatchStatus, usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchResponse {
+    pub results: Vec<BatchResult>,
+    pub summary: BatchSummary,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_missing_node: Option<MissingNodePolicy>, // Echoed back for create_relations
+    // Requested name -> entity it was created as or merged into (create_entities with
+    // merge_existing)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_names: Option<BTreeMap<String, String>>,
+}
+
+impl BatchResponse {
+    pub fn new(results: Vec<BatchResult>) -> Self {
+        let mut summary = BatchSummary {
+            total: results.len(),
+            ..Default::default()
+        };
+        for result in &results {
+            if result.error.is_some() {
+                summary.failed += 1;
+            } else {
+                summary.succeeded += 1;
+            }
+            *summary.by_status.entry(result.status).or_insert(0) += 1;
+        }
+        BatchResponse {
+            results,
+            summary,
+            on_missing_node: None,
+            resolved_names: None,
+        }
+    }
+
+    // 201 when the batch created anything, like the single-resource creation routes.
+    pub fn creation_status(&self) -> u16 {
+        if self.summary.by_status.contains_key(&BatchStatus::Created) {
+            201
+        } else {
+            200
+        }
+    }
+
+    pub fn with_on_missing_node(mut self, policy: MissingNodePolicy) -> Self {
+        self.on_missing_node = Some(policy);
+        self
+    }
+
+    pub fn with_resolved_names(mut self, resolved_names: BTreeMap<String, String>) -> Self {
+        self.resolved_names = Some(resolved_names);
+        self
+    }
+}
+
+// Graph Reset
+
+// One-time token that must be echoed back to confirm a destructive operation.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ConfirmationToken {
+    pub token: String,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ClearGraphPayload {
+    #[serde(default)]
+    pub confirm_token: Option<String>, // Omit to request a token
+    #[serde(default)]
+    pub force: bool, // Also delete pinned entities
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ClearGraphResponse {
+    ConfirmationRequired {
+        confirm_token: String,
+        expires_at_ms: u64,
+    },
+    Cleared {
+        deleted_entities: usize,
+        deleted_relations: usize,
+        #[serde(default)]
+        kept_pinned: usize, // Pinned entities left in place (always 0 when forced)
+    },
+}
+
+// POST /graph/forget, see forget.rs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct ForgetPayload {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>, // Case-insensitive substring
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>, // Regex
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entity_types: Vec<String>,
+    // Epoch milliseconds, until exclusive: when observations were added, or entities updated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until_ms: Option<u64>,
+    #[serde(default)]
+    pub scope: ForgetScope,
+    #[serde(default)]
+    pub force: bool, // Also forget pinned entities and their observations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confirm_token: Option<String>, // Omit to preview and get a token
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ForgetScope {
+    #[default]
+    Observations,
+    Entities,
+}
+
+// A forget request waiting for its confirmation token
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PendingForget {
+    pub confirmation: ConfirmationToken,
+    pub request: ForgetPayload,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ForgetPlan {
+    pub entities: Vec<String>, // Deleted with their relations
+    pub relations: usize,
+    pub observations: BTreeMap<String, Vec<String>>, // Entity name -> observations
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ForgetResponse {
+    ConfirmationRequired {
+        confirm_token: String,
+        expires_at_ms: u64,
+        plan: ForgetPlan,
+    },
+    Forgotten {
+        plan: ForgetPlan,
+    },
+}
+
+// Admin / Maintenance
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CompactionReport {
+    pub orphaned_edges_removed: Vec<String>, // Edge IDs whose source or target node was missing
+    pub nodes_normalized: Vec<String>,       // Node IDs whose data/observations were rewritten
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GraphStats {
+    pub entity_count: usize,
+    pub relation_count: usize,
+    pub observation_count: usize,
+    pub entity_type_count: usize,
+    pub relation_type_count: usize,
+    pub schema_version: u32,
+}
+
+// Graph shape (GET /graph/analytics/degrees)
+
+// Entities whose degree (incoming plus outgoing relations) lies in min..=max
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DegreeBucket {
+    pub min: usize,
+    pub max: usize,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DegreeHub {
+    pub name: String,
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+    pub in_degree: usize,
+    pub out_degree: usize,
+    pub degree: usize,
+}
+
+// GET /graph/top: the entities ranked highest by one measure, with all measures shown
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TopMetric {
+    Degree,
+    Observations,
+    Recency,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopEntity {
+    pub name: String,
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+    pub degree: usize,
+    pub observation_count: usize,
+    pub updated_at_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopEntitiesResponse {
+    pub by: TopMetric,
+    pub entities: Vec<TopEntity>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelationTypeDegrees {
+    #[serde(rename = "relationType")]
+    pub relation_type: String,
+    pub relation_count: usize,
+    pub source_count: usize, // Distinct entities with an outgoing relation of this type
+    pub target_count: usize, // Distinct entities with an incoming one
+    pub avg_out_degree: f64, // Relations per source
+    pub avg_in_degree: f64,  // Relations per target
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DegreeReport {
+    pub entity_count: usize,
+    pub relation_count: usize,
+    pub average_degree: f64,
+    pub max_degree: usize,
+    pub isolated_count: usize, // Entities without any relation
+    pub histogram: Vec<DegreeBucket>,
+    pub top_hubs: Vec<DegreeHub>,
+    pub relation_types: Vec<RelationTypeDegrees>,
+}
+
+// Effective schema (GET /graph/analytics/type-pairs)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TypePairReport {
+    pub relation_count: usize,
+    pub pairs: Vec<TypePairCount>, // Most frequent first
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TypePairCount {
+    pub source_type: String,
+    #[serde(rename = "relationType")]
+    pub relation_type: String,
+    pub target_type: String,
+    pub count: usize,
+    pub share: f64,      // Of the relations of this relation type
+    pub anomalous: bool, // A rare combination for a well-used relation type
+}
+
+// Graph size at the end of a UTC day (GET /graph/analytics/growth)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GrowthPoint {
+    pub day: String, // YYYY-MM-DD
+    pub entity_count: usize,
+    pub relation_count: usize,
+    pub observation_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GrowthHistory {
+    pub points: Vec<GrowthPoint>, // Oldest first
+}
+
+// Outcome of one scheduled (alarm) or manual maintenance run.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MaintenanceReport {
+    pub ran_at_ms: u64,
+    pub compaction: CompactionReport,
+    pub clear_token_expired: bool, // A stale clear_graph confirmation token was dropped
+    pub sessions_purged: usize,    // Idle MCP session log levels removed from storage
+    #[serde(default)]
+    pub idempotency_keys_purged: usize, // Expired Idempotency-Key responses, see idempotency.rs
+    #[serde(default)]
+    pub faded_relations_removed: Vec<String>, // Edge IDs whose confidence fell below the floor
+    #[serde(default)]
+    pub entities_summarized: Vec<String>, // Old observations replaced by a summary, see auto_summary.rs
+    pub stats: GraphStats,
+    pub next_run_at_ms: u64,
+}
+
+// POST /graph/admin/prune-faded-relations
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PruneFadedRelationsResponse {
+    pub floor: f64,
+    pub removed: Vec<String>, // Edge IDs
+}
+
+// Maintenance lock, see lock.rs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GraphLock {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub locked_at_ms: u64,
+    pub expires_at_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LockPayload {
+    #[serde(default)]
+    pub reason: Option<String>,
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>, // Defaults to DEFAULT_LOCK_TTL_SECONDS
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LockStatus {
+    pub locked: bool,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    pub lock: Option<GraphLock>,
+}
+
+// Integrity Validation
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DanglingEdge {
+    pub edge_id: String,
+    pub missing_source: bool,
+    pub missing_target: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateEdgeGroup {
+    pub source_node_id: String,
+    pub target_node_id: String,
+    #[serde(rename = "type")]
+    pub edge_type: String,
+    pub edge_ids: Vec<String>, // Sorted; every edge sharing the tuple, including the first
+}
+
+// Result of GET /graph/validate; the graph is not modified. Ids are sorted.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ValidationReport {
+    pub valid: bool, // No violations of any kind
+    pub nodes_checked: usize,
+    pub edges_checked: usize,
+    pub dangling_edges: Vec<DanglingEdge>,
+    pub non_object_data: Vec<String>, // Node IDs whose data is not a JSON object
+    pub malformed_observations: Vec<String>, // Node IDs whose observations aren't an array of strings
+    pub duplicate_edges: Vec<DuplicateEdgeGroup>,
+}
+
+// Fixes applied by POST /graph/repair; a fix that wasn't requested is omitted.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RepairReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orphan_edges_removed: Option<Vec<String>>, // Edge IDs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nodes_normalized: Option<Vec<String>>, // Node IDs whose data/observations were rewritten
+}
+
+// Import Preflight
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportIssueKind {
+    Schema,
+    DuplicateName,
+    MissingEndpoint,
+    SizeLimit,
+    Erased,            // Matches a tombstone of POST /graph/erase
+    AlreadyExists,     // Warning: the entity is already in the graph
+    DuplicateRelation, // Warning: the same from/to/relationType tuple appears twice
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportIssue {
+    pub kind: ImportIssueKind,
+    pub path: String, // e.g. "entities[3].entityType"; empty for the payload as a whole
+    pub message: String,
+}
+
+// Result of POST /graph/import/validate; nothing is written. The issue lists are capped,
+// the counts are not.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportValidationReport {
+    pub valid: bool, // No errors; warnings don't block an import
+    pub payload_bytes: usize,
+    pub entity_count: usize,
+    pub relation_count: usize,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub errors: Vec<ImportIssue>,
+    pub warnings: Vec<ImportIssue>,
+}
+
+// Entity Templates
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct EntityTemplate {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub observations: Vec<String>, // Added to each new entity of the type
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required_fields: Vec<String>, // Keys its data must set (non-null)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relations: Vec<TemplateRelation>, // Created from each new entity of the type
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TemplateRelation {
+    #[serde(rename = "relationType")]
+    pub relation_type: String,
+    pub to: String,
+}
+
+// PUT /graph/entity-templates/{entityType}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntityTemplateUpdateResponse {
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+    pub template: EntityTemplate,
+}
+
+// Relation Type Catalog
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RelationTypeSpec {
+    #[serde(default)]
+    pub symmetric: bool, // A -[T]-> B implies B -[T]-> A
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inverse: Option<String>, // A -[T]-> B implies B -[inverse]-> A
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelationTypeUpdateResponse {
+    #[serde(rename = "relationType")]
+    pub relation_type: String,
+    pub spec: RelationTypeSpec,
+    pub mirrored_edges_created: Vec<String>, // Mirrors added for edges that already existed
+}
+
+// Erasure
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ErasePayload {
+    #[serde(default)]
+    pub name: Option<String>, // Entity name, matched case-insensitively as a whole word
+    #[serde(default)]
+    pub pattern: Option<String>, // Regex; give either this or `name`
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+// Keeps an erased subject from being written again (see erasure.rs)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Tombstone {
+    pub subject: String,
+    pub pattern: String, // Regex matched against names and observations
+    pub erased_at_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ErasureReport {
+    pub dry_run: bool,
+    pub tombstone: Tombstone,
+    pub entities_removed: Vec<String>,
+    pub relations_removed: Vec<String>,                // Edge IDs
+    pub observations_removed: BTreeMap<String, usize>, // Entity name -> observations removed
+    pub data_scrubbed: Vec<String>, // Entities whose other data values mentioned the subject
+}
+
+// Type Renames
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RenameTypePayload {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RenameTypeResponse {
+    pub from: String,
+    pub to: String,
+    pub renamed: usize, // Nodes or edges whose type was rewritten
+}
+
+// Graph Export
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct D3Node {
+    pub id: String,
+    pub group: usize, // Index of the entity type in D3Graph::groups
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct D3Link {
+    pub source: String,
+    pub target: String,
+    pub value: usize, // Relations from source to target
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct D3Graph {
+    pub nodes: Vec<D3Node>,
+    pub links: Vec<D3Link>,
+    pub groups: Vec<String>, // Entity types, sorted
+}
+
+// GET /graph/recent, most recently touched first
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecentEntitiesResponse {
+    pub entities: Vec<RecentEntity>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RecentEntity {
+    #[serde(flatten)]
+    pub entity: ApiEntity, // Observations cut down to the latest ones
+    pub observation_count: usize,
+    pub created_at_ms: u64,
+    pub updated_at_ms: u64,
+}
+
+// Time window of the created_between/updated_between filters; see time_range.rs
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TimeRange {
+    pub start: Option<u64>, // Inclusive, epoch milliseconds
+    pub end: Option<u64>,   // Exclusive
+}
+
+// Edge Listing
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct EdgeListQuery {
+    pub edge_type: Option<String>,
+    pub source_node_id: Option<String>,
+    pub target_node_id: Option<String>,
+    pub created_after: Option<u64>, // Exclusive, epoch milliseconds
+    pub created_between: Option<TimeRange>,
+    pub offset: usize,
+    pub limit: Option<usize>, // Defaults to DEFAULT_EDGE_PAGE_SIZE, capped at MAX_EDGE_PAGE_SIZE
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EdgeListResponse {
+    pub edges: Vec<Edge>,
+    pub total: usize, // Edges matching the filters across all pages
+    pub offset: usize,
+    pub next_offset: Option<usize>, // None on the last page
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeDirection {
+    Outgoing,
+    Incoming,
+}
+
+// An edge seen from one of its endpoints (GET /nodes/{id}/edges).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NodeEdge {
+    pub direction: EdgeDirection,
+    pub neighbor_id: String, // The endpoint at the other end; equals the node for self-loops
+    #[serde(flatten)]
+    pub edge: Edge,
+}
+
+// Graph Algorithms
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReachabilityQuery {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub relation_types: Option<Vec<String>>, // Only follow relations of these types
+    #[serde(default)]
+    pub max_depth: Option<usize>, // Maximum number of hops; unlimited when omitted
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReachabilityResult {
+    pub reachable: bool,
+    pub path: Vec<String>, // Entity names from `from` to `to`, empty when unreachable
+    pub relations: Vec<ApiRelation>, // The relations along `path`, in order
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Cycle {
+    pub entities: Vec<String>, // All entities of the strongly connected component, sorted
+    pub path: Vec<String>,     // A witness cycle; starts and ends with the same entity
+    pub relations: Vec<ApiRelation>, // The relations along `path`, in order
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CycleReport {
+    pub relation_type: Option<String>, // None when all relation types were considered
+    pub has_cycles: bool,
+    pub cycles: Vec<Cycle>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TopoOrder {
+    // Relation sources come before their targets (e.g. PARENT_OF: parents first)
+    #[default]
+    SourceFirst,
+    // Relation targets come first (e.g. DEPENDS_ON: dependencies first)
+    TargetFirst,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopoSortQuery {
+    #[serde(default)]
+    pub relation_types: Option<Vec<String>>, // All relation types when omitted
+    #[serde(default)]
+    pub order: TopoOrder,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopoSortResult {
+    pub order: Vec<String>,
+}
+
+// Body of every DO error response (status >= 400), see errors.rs
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DoErrorBody {
+    pub error: DoErrorDetail,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DoErrorDetail {
+    pub code: String, // Derived from the status, e.g. "not_found", "invalid_request"
+    pub message: String,
+    // Failed items of a rejected batch (create_relations with on_missing_node=error)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub failures: Vec<BatchResult>,
+    // Any other JSON the route answered with, e.g. the cycle of a toposort conflict
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub details: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TopoSortConflict {
+    pub error: String,
+    pub cycle: Cycle,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SimilarEntity {
+    pub name: String,
+    pub score: f64,                  // Mean of the two similarities below, in [0, 1]
+    pub neighbor_similarity: f64,    // Jaccard similarity of neighbor sets
+    pub observation_similarity: f64, // Jaccard similarity of observation tokens
+    pub shared_neighbors: Vec<String>,
+}
+
+// AI Features
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SummarizePayload {
+    #[serde(default)]
+    pub replace_observations: bool, // Swap the summarized observations for the summary
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntitySummary {
+    pub name: String,
+    pub summary: String,
+    pub model: String,
+    pub summarized_observations: usize,
+    pub replaced_observations: bool,
+}
+
+// Observations a summary replaced, kept in DO storage (GET /nodes/{id}/archived-observations)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ArchivedObservations {
+    pub summarized_at_ms: u64,
+    pub model: String,
+    pub summary: String,
+    pub observations: Vec<String>,
+}
+
+// Entities and relations extracted from text by the model (POST /graph/ingest)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ExtractedGraph {
+    #[serde(default)]
+    pub entities: Vec<EntityToCreate>,
+    #[serde(default)]
+    pub relations: Vec<RelationToCreate>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IngestPayload {
+    pub text: String,
+    #[serde(default)]
+    pub dry_run: bool, // Only return what was extracted
+    #[serde(default)]
+    pub on_missing_node: MissingNodePolicy, // As in create_relations
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IngestResponse {
+    pub model: String,
+    pub extracted: ExtractedGraph,
+    // Write results, absent on dry runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entities: Option<BatchResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observations: Option<BatchResponse>, // Observations added to entities that already existed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relations: Option<BatchResponse>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SuggestRelationsPayload {
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub max_suggestions: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RelationSuggestion {
+    pub from: String,
+    pub to: String,
+    #[serde(rename = "relationType")]
+    pub relation_type: String,
+    #[serde(default)]
+    pub confidence: f64, // 0.0 to 1.0, as judged by the model
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct RelationSuggestions {
+    #[serde(default)]
+    pub suggestions: Vec<RelationSuggestion>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SuggestRelationsResponse {
+    pub model: String,
+    pub suggestions: Vec<RelationSuggestion>, // Highest confidence first
+}
+
+// Completion
+
+// What a completion request looks up (GET /graph/complete?kind=...)
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CompletionKind {
+    Entity,
+    EntityType,
+    RelationType,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CompletionResult {
+    pub values: Vec<String>,
+    pub total: usize,
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
+}
+
+// An entity whose name or alias starts with the prefix of GET /graph/suggest
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntitySuggestion {
+    pub name: String,
+    #[serde(rename = "entityType")]
+    pub entity_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alias: Option<String>, // The matching alias, when the name itself didn't match
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SuggestResult {
+    pub suggestions: Vec<EntitySuggestion>,
+    #[serde(rename = "hasMore")]
+    pub has_more: bool,
+}
+
+// MCP Sessions
+
+// Log level chosen by an MCP session via logging/setLevel (validated by the MCP layer)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SessionLogLevel {
+    pub level: String,
+    #[serde(default)]
+    pub updated_at_ms: u64,
+}
+
+// Response to a write with an Idempotency-Key, kept to answer its retries (see idempotency.rs)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IdempotentResponse {
+    pub request: String, // "POST /graph/entities": a key is only good for one route
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub stored_at_ms: u64,
+}
+
+// Rate Limiting
+
+// A caller's position in the current fixed window (see rate_limit.rs)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RateLimitStatus {
+    pub limit: u32,
+    pub remaining: u32,
+    pub used: u32,
+    pub window_seconds: u64,
+    pub reset_at: u64, // Epoch seconds at which the window restarts
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct QuotaResponse {
+    pub caller: String, // Key fingerprint, or client IP when authentication is disabled
+    pub scopes: Vec<String>,
+    pub rate_limit: Option<RateLimitStatus>, // None when rate limiting is disabled
+}
+
+// Usage Metering
+
+// What one caller did within one time bucket (see metering.rs)
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UsageCounters {
+    pub calls: BTreeMap<String, u64>, // MCP tool name, or "<METHOD> /<resource>" for REST
+    pub entities_created: u64,
+    pub bytes_written: u64, // Request payload bytes of write operations
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageBucket {
+    pub start_ms: u64,
+    pub caller: String,
+    #[serde(flatten)]
+    pub counters: UsageCounters,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UsageReport {
+    pub bucket_ms: u64,
+    pub from_ms: u64,
+    pub to_ms: u64,
+    pub buckets: Vec<UsageBucket>, // Ordered by start_ms, then caller
+}
@@ -0,0 +1,218 @@
+use crate::confidence;
+use crate::names;
+use crate::types::{
+    AddObservationsPayload, CreateEntitiesPayload, CreateRelationsPayload, DeleteEntitiesPayload,
+    DeleteObservationsPayload, DeleteRelationsPayload, PinEntitiesPayload, RememberPayload,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+
+// Parsing and validation of the batch write payloads, shared by the DO routes and the MCP
+// tools so both entry points accept exactly the same requests with the same defaults
+// (`observations` optional, `on_missing_node` defaulting to skip, `data` kept as given).
+// A payload that fails here is rejected as a whole; per-item outcomes such as "already
+// exists" or "not found" stay in the batch results. Entity names are canonicalized first
+// (see names.rs).
+
+pub trait Validate {
+    fn canonicalize(&mut self) {}
+    fn validate(&self) -> Result<(), String>;
+}
+
+pub fn from_value<T: DeserializeOwned + Validate>(value: JsonValue) -> Result<T, String> {
+    let mut payload: T = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    payload.canonicalize();
+    payload.validate()?;
+    Ok(payload)
+}
+
+pub fn from_str<T: DeserializeOwned + Validate>(body: &str) -> Result<T, String> {
+    let mut payload: T = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    payload.canonicalize();
+    payload.validate()?;
+    Ok(payload)
+}
+
+fn require(value: &str, path: String) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("{} must not be empty", path));
+    }
+    Ok(())
+}
+
+fn require_name(value: &str, path: String) -> Result<(), String> {
+    require(value, path.clone())?;
+    names::check_length(value, path)
+}
+
+fn require_object(value: Option<&JsonValue>, path: String) -> Result<(), String> {
+    match value {
+        None | Some(JsonValue::Object(_)) => Ok(()),
+        Some(_) => Err(format!("{} must be an object", path)),
+    }
+}
+
+impl Validate for CreateEntitiesPayload {
+    fn canonicalize(&mut self) {
+        self.entities
+            .iter_mut()
+            .for_each(names::canonicalize_entity);
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, entity) in self.entities.iter().enumerate() {
+            require_name(&entity.name, format!("entities[{}].name", i))?;
+            require(&entity.entity_type, format!("entities[{}].entityType", i))?;
+            require_object(entity.data.as_ref(), format!("entities[{}].data", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for CreateRelationsPayload {
+    fn canonicalize(&mut self) {
+        self.relations
+            .iter_mut()
+            .for_each(names::canonicalize_relation);
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, relation) in self.relations.iter().enumerate() {
+            require_name(&relation.from, format!("relations[{}].from", i))?;
+            require_name(&relation.to, format!("relations[{}].to", i))?;
+            require(
+                &relation.relation_type,
+                format!("relations[{}].relationType", i),
+            )?;
+            confidence::check_confidence(
+                relation.confidence,
+                format!("relations[{}].confidence", i),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for AddObservationsPayload {
+    fn canonicalize(&mut self) {
+        for item in &mut self.observations {
+            names::canonicalize(&mut item.entity_name);
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, item) in self.observations.iter().enumerate() {
+            require(&item.entity_name, format!("observations[{}].entityName", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for RememberPayload {
+    fn canonicalize(&mut self) {
+        self.entities
+            .iter_mut()
+            .for_each(names::canonicalize_entity);
+        self.relations
+            .iter_mut()
+            .for_each(names::canonicalize_relation);
+        for item in &mut self.observations {
+            names::canonicalize(&mut item.entity_name);
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.entities.is_empty() && self.relations.is_empty() && self.observations.is_empty() {
+            return Err(
+                "nothing to remember: entities, relations and observations are empty".to_string(),
+            );
+        }
+        for (i, entity) in self.entities.iter().enumerate() {
+            require_name(&entity.name, format!("entities[{}].name", i))?;
+            require(&entity.entity_type, format!("entities[{}].entityType", i))?;
+            require_object(entity.data.as_ref(), format!("entities[{}].data", i))?;
+        }
+        for (i, relation) in self.relations.iter().enumerate() {
+            require_name(&relation.from, format!("relations[{}].from", i))?;
+            require_name(&relation.to, format!("relations[{}].to", i))?;
+            require(
+                &relation.relation_type,
+                format!("relations[{}].relationType", i),
+            )?;
+            confidence::check_confidence(
+                relation.confidence,
+                format!("relations[{}].confidence", i),
+            )?;
+        }
+        for (i, item) in self.observations.iter().enumerate() {
+            require(&item.entity_name, format!("observations[{}].entityName", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for PinEntitiesPayload {
+    fn canonicalize(&mut self) {
+        self.entity_names.iter_mut().for_each(names::canonicalize);
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, name) in self.entity_names.iter().enumerate() {
+            require(name, format!("entityNames[{}]", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for DeleteEntitiesPayload {
+    fn canonicalize(&mut self) {
+        self.entity_names.iter_mut().for_each(names::canonicalize);
+        self.expected_versions = std::mem::take(&mut self.expected_versions)
+            .into_iter()
+            .map(|(name, version)| (names::canonical_name(&name), version))
+            .collect();
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, name) in self.entity_names.iter().enumerate() {
+            require(name, format!("entityNames[{}]", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for DeleteObservationsPayload {
+    fn canonicalize(&mut self) {
+        for item in &mut self.deletions {
+            names::canonicalize(&mut item.entity_name);
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, item) in self.deletions.iter().enumerate() {
+            require(&item.entity_name, format!("deletions[{}].entityName", i))?;
+        }
+        Ok(())
+    }
+}
+
+impl Validate for DeleteRelationsPayload {
+    fn canonicalize(&mut self) {
+        for relation in &mut self.relations {
+            names::canonicalize(&mut relation.from);
+            names::canonicalize(&mut relation.to);
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (i, relation) in self.relations.iter().enumerate() {
+            require(&relation.from, format!("relations[{}].from", i))?;
+            require(&relation.to, format!("relations[{}].to", i))?;
+            require(
+                &relation.relation_type,
+                format!("relations[{}].relationType", i),
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,64 @@
-use crate::kg::KnowledgeGraphState;
+use crate::ai;
+use crate::algorithms::DEFAULT_SIMILAR_LIMIT;
+use crate::analytics::{record_growth, DEFAULT_TOP_HUBS, MAX_TOP_HUBS};
+use crate::auth::{self, Scope};
+use crate::auto_summary::MAX_ARCHIVES_PER_ENTITY;
+use crate::cache::{self, GRAPH_VERSION_HEADER};
+use crate::confidence;
+use crate::context::{
+    DEFAULT_CONTEXT_HOPS, DEFAULT_CONTEXT_TOKENS, MAX_CONTEXT_HOPS, MAX_CONTEXT_TOKENS,
+};
+use crate::errors;
+use crate::export::{ExportFormat, ExportOptions, StateOptions};
+use crate::filter::DataFilter;
+use crate::idempotency;
+use crate::kg::{
+    KnowledgeGraphState, DEFAULT_OBSERVATION_SEARCH_LIMIT, DEFAULT_RECENT_LIMIT,
+    DEFAULT_RECENT_OBSERVATIONS, DEFAULT_SUGGEST_LIMIT, MAX_BATCH_GET_IDS, MAX_COMPLETION_VALUES,
+    MAX_OBSERVATION_SEARCH_LIMIT, MAX_RECENT_LIMIT, MAX_SEARCH_QUERIES,
+};
+use crate::metering::ENTITIES_CREATED_HEADER;
+use crate::migrations::{self, LEGACY_STATE_KEYS};
+use crate::names::{self, ResolveNames};
+use crate::negative_cache::MissingNames;
+use crate::redact::Redactor;
+use crate::replication::{self, REPLICA_PATH_PREFIX, REPLICA_SYNC_PATH};
+use crate::streaming;
 use crate::types::*;
+use crate::validation;
+use crate::API_V1_PREFIX;
+use percent_encoding::percent_decode_str;
+use serde_json::Value as JsonValue;
 use worker::*;
 
-const KG_STATE_KEY: &str = "knowledgeGraphState_v1"; // Added a version suffix
+// Storage key of the graph state; the suffix is its version
+const KG_STATE_KEY: &str = "knowledgeGraphState_v1";
+// Storage key prefix for per-session MCP log levels, kept apart from the graph state
+const MCP_SESSION_LOG_LEVEL_PREFIX: &str = "mcp_session_log_level:";
+// Session log levels not updated for this long are purged by maintenance
+const MCP_SESSION_TTL_MS: u64 = 24 * 60 * 60 * 1000;
+// Report of the most recent maintenance run
+const MAINTENANCE_REPORT_KEY: &str = "maintenance_last_report";
+// Daily graph sizes recorded by maintenance, see analytics.rs
+const GROWTH_HISTORY_KEY: &str = "growth_history";
+// Overridable with the MAINTENANCE_INTERVAL_MINUTES var
+const DEFAULT_MAINTENANCE_INTERVAL_MINUTES: u64 = 60;
+// Maintenance lock set by POST /graph/admin/lock, see lock.rs
+const GRAPH_LOCK_KEY: &str = "graph_lock";
+// Set on instances that serve as read replicas
+const REPLICA_ROLE_KEY: &str = "replica_role";
+// Durable Object storage deletes at most this many keys per call
+const MAX_DELETE_BATCH: usize = 128;
+// Storage key prefix for the observations each entity's summaries replaced, see auto_summary.rs
+const OBSERVATION_ARCHIVE_PREFIX: &str = "observation_archive:";
 
 #[durable_object]
 pub struct KnowledgeGraphDO {
     state: State,
+    env: Env,                  // For bindings used by AI features
+    legacy_keys_unified: bool, // Set once no copy of the state is left under a legacy key
+    // Recent /graph/open misses, see negative_cache.rs
+    missing_names: MissingNames,
     // We don't store the graph directly in the struct to ensure it's always loaded
     // from storage at the beginning of a request and saved at the end,
     // or managed carefully across multiple await points if optimized.
@@ -19,7 +71,11 @@ impl KnowledgeGraphDO {
     }
 
     // Helper method to construct a Node for the simple POST /nodes endpoint
-    fn construct_node_from_payload(id: String, payload: CreateNodePayload) -> Node {
+    fn construct_node_from_payload(
+        id: String,
+        payload: CreateNodePayload,
+        actor: Option<String>,
+    ) -> Node {
         let current_time_ms = Date::now().as_millis();
         Node {
             id,
@@ -27,11 +83,70 @@ impl KnowledgeGraphDO {
             data: payload.data,
             created_at_ms: current_time_ms,
             updated_at_ms: current_time_ms,
+            created_by: actor.clone(),
+            updated_by: actor,
+            observation_authors: Default::default(),
+            observation_added_ms: Default::default(),
+            version: 1,
+            pinned: false,
         }
     }
 
+    // Graph dumps are redacted when the worker (or the client) passed `redact=true`.
+    fn redactor_for(&self, req: &Request) -> Result<Option<Redactor>> {
+        let redact = req
+            .url()?
+            .query_pairs()
+            .filter(|(name, _)| name == "redact")
+            .last()
+            .is_some_and(|(_, value)| value == "true");
+        Ok(redact.then(|| Redactor::from_env(&self.env)))
+    }
+
+    // Shared by the entity and relation type renames.
+    fn validate_type_rename(payload: &RenameTypePayload) -> std::result::Result<(), String> {
+        if payload.from.trim().is_empty() || payload.to.trim().is_empty() {
+            return Err("'from' and 'to' must not be empty".to_string());
+        }
+        if payload.from == payload.to {
+            return Err("'from' and 'to' are the same type".to_string());
+        }
+        Ok(())
+    }
+
+    // Parses the query string of GET /edges.
+    fn parse_edge_list_query(
+        params: &std::collections::HashMap<String, String>,
+    ) -> std::result::Result<EdgeListQuery, String> {
+        let parse_number = |key: &str| -> std::result::Result<Option<u64>, String> {
+            params
+                .get(key)
+                .map(|v| {
+                    v.parse::<u64>()
+                        .map_err(|_| format!("'{}' must be a non-negative integer", key))
+                })
+                .transpose()
+        };
+        Ok(EdgeListQuery {
+            edge_type: params.get("edge_type").cloned(),
+            source_node_id: params.get("source_node_id").cloned(),
+            target_node_id: params.get("target_node_id").cloned(),
+            created_after: parse_number("created_after")?,
+            created_between: params
+                .get("created_between")
+                .map(|v| TimeRange::parse(v).map_err(|e| format!("invalid created_between: {}", e)))
+                .transpose()?,
+            offset: parse_number("offset")?.unwrap_or(0) as usize,
+            limit: parse_number("limit")?.map(|l| l as usize),
+        })
+    }
+
     // Helper method to construct an Edge for the simple POST /edges endpoint
-    fn construct_edge_from_payload(id: String, payload: CreateEdgePayload) -> Edge {
+    fn construct_edge_from_payload(
+        id: String,
+        payload: CreateEdgePayload,
+        actor: Option<String>,
+    ) -> Edge {
         let current_time_ms = Date::now().as_millis();
         Edge {
             id,
@@ -41,30 +156,450 @@ impl KnowledgeGraphDO {
             data: payload.data,
             created_at_ms: current_time_ms,
             // updated_at_ms is not in Edge struct in types.rs
+            created_by: actor,
+            updated_by: None,
+            confidence: None,
+            confidence_at_ms: None,
+        }
+    }
+
+    // Loads every persisted copy of the state. Keys written by older builds are only looked
+    // at until the instance has unified them under KG_STATE_KEY.
+    async fn load_raw_graph_states(&self) -> Vec<(&'static str, JsonValue)> {
+        let storage = self.state.storage();
+        let mut copies = Vec::new();
+        if let Ok(raw) = storage.get::<JsonValue>(KG_STATE_KEY).await {
+            copies.push((KG_STATE_KEY, raw));
+        }
+        if !self.legacy_keys_unified {
+            for legacy_key in LEGACY_STATE_KEYS {
+                if let Ok(raw) = storage.get::<JsonValue>(legacy_key).await {
+                    copies.push((*legacy_key, raw));
+                }
+            }
         }
+        copies
     }
 
     async fn load_or_initialize_graph_state(&mut self) -> Result<KnowledgeGraphState> {
-        match self.state.storage().get(KG_STATE_KEY).await {
-            Ok(state) => Ok(state),
-            Err(_) => Ok(KnowledgeGraphState::new()), // Initialize if not found or error
+        let copies = self.load_raw_graph_states().await;
+        let stale_keys: Vec<String> = copies
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| key != KG_STATE_KEY)
+            .collect();
+        let highest_version = copies
+            .iter()
+            .filter_map(|(_, raw)| raw.get("version").and_then(|v| v.as_u64()))
+            .max()
+            .unwrap_or(0);
+        // Different builds may each have written a copy; the most recently written one wins
+        // (the canonical key on a tie) and the others are deleted once it has been saved
+        let Some((key, raw)) = copies
+            .into_iter()
+            .max_by_key(|(key, raw)| (migrations::last_written_ms(raw), *key == KG_STATE_KEY))
+        else {
+            self.legacy_keys_unified = true;
+            return Ok(KnowledgeGraphState::new()); // Initialize if not found
+        };
+        let (mut graph_state, migrated) = migrations::migrate(raw).map_err(|e| {
+            console_error!("Failed to load graph state from '{}': {}", key, e);
+            Error::RustError(e)
+        })?;
+        graph_state.ensure_search_index();
+        if migrated || !stale_keys.is_empty() {
+            console_log!(
+                "Migrated graph state from '{}' to schema version {}",
+                key,
+                graph_state.schema_version
+            );
+            // Cached reads are keyed by version, so the kept copy must not go back in versions
+            graph_state.version = graph_state.version.max(highest_version);
+            self.save_graph_state(&mut graph_state).await?;
+        }
+        if !stale_keys.is_empty() {
+            self.state
+                .storage()
+                .delete_multiple(stale_keys.clone())
+                .await?;
+            console_log!("Deleted stale graph state copies under {:?}", stale_keys);
         }
+        self.legacy_keys_unified = true;
+        Ok(graph_state)
     }
 
-    async fn save_graph_state(&mut self, graph_state: &KnowledgeGraphState) -> Result<()> {
-        self.state.storage().put(KG_STATE_KEY, graph_state).await
+    // Every save is a new graph version; read-only routes must not save.
+    async fn save_graph_state(&mut self, graph_state: &mut KnowledgeGraphState) -> Result<()> {
+        graph_state.version += 1;
+        self.missing_names.clear();
+        self.state.storage().put(KG_STATE_KEY, &*graph_state).await
     }
-}
 
-#[durable_object]
-impl DurableObject for KnowledgeGraphDO {
-    fn new(state: State, _env: Env) -> Self {
-        Self { state }
+    async fn is_replica(&self) -> bool {
+        self.state
+            .storage()
+            .get::<bool>(REPLICA_ROLE_KEY)
+            .await
+            .unwrap_or(false)
+    }
+
+    async fn active_lock(&self, now_ms: u64) -> Option<GraphLock> {
+        self.state
+            .storage()
+            .get::<GraphLock>(GRAPH_LOCK_KEY)
+            .await
+            .ok()
+            .filter(|lock| lock.is_active(now_ms))
+    }
+
+    // Ships the state to the read replicas after the response has been sent.
+    fn replicate(&self, graph_state: &KnowledgeGraphState) -> Result<()> {
+        if !replication::is_primary(&self.env, &self.state.id()) {
+            return Ok(());
+        }
+        let replicas = replication::replica_stubs(&self.env);
+        if !replicas.is_empty() {
+            let snapshot = serde_json::to_string(graph_state)?;
+            self.state
+                .wait_until(replication::push_snapshot(replicas, snapshot));
+        }
+        Ok(())
+    }
+
+    // Replica side of replication: keeps the newest snapshot sent by the primary.
+    async fn apply_replica_snapshot(&mut self, req: &mut Request) -> Result<Response> {
+        let snapshot: KnowledgeGraphState = match req.json().await {
+            Ok(s) => s,
+            Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+        };
+        let current_version = if self.is_replica().await {
+            self.load_or_initialize_graph_state().await?.version
+        } else {
+            0
+        };
+        if snapshot.version > current_version {
+            let mut storage = self.state.storage();
+            storage.put(KG_STATE_KEY, &snapshot).await?;
+            self.missing_names.clear();
+            storage.put(REPLICA_ROLE_KEY, true).await?;
+        }
+        Response::empty().map(|r| r.with_status(204))
+    }
+
+    fn maintenance_interval_ms(&self) -> u64 {
+        let minutes = self
+            .env
+            .var("MAINTENANCE_INTERVAL_MINUTES")
+            .ok()
+            .and_then(|v| v.to_string().parse::<u64>().ok())
+            .filter(|m| *m > 0)
+            .unwrap_or(DEFAULT_MAINTENANCE_INTERVAL_MINUTES);
+        minutes * 60 * 1000
+    }
+
+    // Schedules the first maintenance run; afterwards each run schedules the next one.
+    async fn ensure_maintenance_alarm(&self) -> Result<()> {
+        let storage = self.state.storage();
+        if storage.get_alarm().await?.is_none() {
+            let next_run_at_ms = Date::now().as_millis() + self.maintenance_interval_ms();
+            storage.set_alarm(next_run_at_ms as i64).await?;
+        }
+        Ok(())
+    }
+
+    // Removes MCP session log levels that have not been set within MCP_SESSION_TTL_MS.
+    async fn purge_stale_sessions(&self, now_ms: u64) -> Result<usize> {
+        let mut storage = self.state.storage();
+        let entries = storage
+            .list_with_options(ListOptions::new().prefix(MCP_SESSION_LOG_LEVEL_PREFIX))
+            .await?;
+        let mut stale_keys: Vec<String> = Vec::new();
+        entries.for_each(&mut |value, key| {
+            let stale = match serde_wasm_bindgen::from_value::<SessionLogLevel>(value) {
+                Ok(stored) => stored.updated_at_ms + MCP_SESSION_TTL_MS < now_ms,
+                Err(_) => true, // Unreadable entries are of no use to anyone
+            };
+            if let (true, Some(key)) = (stale, key.as_string()) {
+                stale_keys.push(key);
+            }
+        });
+        for chunk in stale_keys.chunks(MAX_DELETE_BATCH) {
+            storage.delete_multiple(chunk.to_vec()).await?;
+        }
+        Ok(stale_keys.len())
+    }
+
+    // Removes stored Idempotency-Key responses past their expiry, see idempotency.rs.
+    async fn purge_expired_idempotency_keys(&self, now_ms: u64) -> Result<usize> {
+        let mut storage = self.state.storage();
+        let entries = storage
+            .list_with_options(ListOptions::new().prefix(idempotency::IDEMPOTENCY_KEY_PREFIX))
+            .await?;
+        let mut expired_keys: Vec<String> = Vec::new();
+        entries.for_each(&mut |value, key| {
+            let expired = match serde_wasm_bindgen::from_value::<IdempotentResponse>(value) {
+                Ok(stored) => stored.is_expired(now_ms),
+                Err(_) => true,
+            };
+            if let (true, Some(key)) = (expired, key.as_string()) {
+                expired_keys.push(key);
+            }
+        });
+        for chunk in expired_keys.chunks(MAX_DELETE_BATCH) {
+            storage.delete_multiple(chunk.to_vec()).await?;
+        }
+        Ok(expired_keys.len())
+    }
+
+    // Adds to the archive of observations replaced by summaries of `name`.
+    async fn archive_observations(&self, name: &str, archived: ArchivedObservations) -> Result<()> {
+        let key = format!("{}{}", OBSERVATION_ARCHIVE_PREFIX, name);
+        let mut storage = self.state.storage();
+        let mut archive = storage
+            .get::<Vec<ArchivedObservations>>(&key)
+            .await
+            .unwrap_or_default();
+        archive.push(archived);
+        if archive.len() > MAX_ARCHIVES_PER_ENTITY {
+            archive.drain(..archive.len() - MAX_ARCHIVES_PER_ENTITY);
+        }
+        storage.put(&key, &archive).await
+    }
+
+    // Drops archived observations of entities that no longer exist or that match an erased
+    // subject, so erasure reaches the archive too.
+    async fn purge_archived_observations(&self, graph_state: &KnowledgeGraphState) -> Result<()> {
+        let mut storage = self.state.storage();
+        let entries = storage
+            .list_with_options(ListOptions::new().prefix(OBSERVATION_ARCHIVE_PREFIX))
+            .await?;
+        let erased = graph_state.erased_subjects();
+        let mut stale_keys: Vec<String> = Vec::new();
+        let mut rewritten: Vec<(String, Vec<ArchivedObservations>)> = Vec::new();
+        entries.for_each(&mut |value, key| {
+            let Some(key) = key.as_string() else {
+                return;
+            };
+            let name = &key[OBSERVATION_ARCHIVE_PREFIX.len()..];
+            let archive = match serde_wasm_bindgen::from_value::<Vec<ArchivedObservations>>(value) {
+                Ok(archive) if graph_state.nodes.contains_key(name) => archive,
+                _ => {
+                    stale_keys.push(key);
+                    return;
+                }
+            };
+            let kept: Vec<ArchivedObservations> = archive
+                .iter()
+                .filter(|entry| !erased.matches(&entry.summary))
+                .map(|entry| ArchivedObservations {
+                    observations: entry
+                        .observations
+                        .iter()
+                        .filter(|observation| !erased.matches(observation))
+                        .cloned()
+                        .collect(),
+                    ..entry.clone()
+                })
+                .collect();
+            let unchanged = kept.len() == archive.len()
+                && kept
+                    .iter()
+                    .zip(&archive)
+                    .all(|(a, b)| a.observations.len() == b.observations.len());
+            if !unchanged {
+                rewritten.push((key, kept));
+            }
+        });
+        for chunk in stale_keys.chunks(MAX_DELETE_BATCH) {
+            storage.delete_multiple(chunk.to_vec()).await?;
+        }
+        for (key, archive) in rewritten {
+            storage.put(&key, &archive).await?;
+        }
+        Ok(())
+    }
+
+    // Replaces the old observations of the entities due for it with a Workers AI summary, see
+    // auto_summary.rs; returns their names. `graph_state` is reloaded after the model calls.
+    async fn auto_summarize(
+        &mut self,
+        graph_state: &mut KnowledgeGraphState,
+        now_ms: u64,
+    ) -> Result<Vec<String>> {
+        let due = graph_state.stale_entities(now_ms);
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut summaries = Vec::new();
+        for (name, observations) in due {
+            let Some(node) = graph_state.get_node(&name) else {
+                continue;
+            };
+            let prompt = ai::summary_prompt(&node.id, &node.node_type, &observations);
+            match ai::complete(&self.env, ai::SUMMARIZE_SYSTEM_PROMPT, &prompt, 256).await {
+                Ok(summary) => summaries.push((name, observations, summary)),
+                Err(e) => {
+                    console_warn!("Auto-summary of '{}' failed: {}", name, e);
+                    if matches!(e, ai::AiError::Unavailable(_)) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Other requests may have run while waiting on the model
+        *graph_state = self.load_or_initialize_graph_state().await?;
+        let model = ai::text_model(&self.env);
+        let mut summarized = Vec::new();
+        for (name, observations, summary) in summaries {
+            // Only what is still there (and still not pinned) is replaced
+            let Some(node) = graph_state.get_node(&name).filter(|node| !node.pinned) else {
+                continue;
+            };
+            let current = KnowledgeGraphState::observations_of(node);
+            let observations: Vec<String> = observations
+                .into_iter()
+                .filter(|observation| current.contains(observation))
+                .collect();
+            if observations.is_empty() {
+                continue;
+            }
+            if graph_state
+                .apply_summary(&name, &summary, &observations, &model, true)
+                .is_none()
+            {
+                continue;
+            }
+            self.archive_observations(
+                &name,
+                ArchivedObservations {
+                    summarized_at_ms: now_ms,
+                    model: model.clone(),
+                    summary,
+                    observations,
+                },
+            )
+            .await?;
+            summarized.push(name);
+        }
+        if !summarized.is_empty() {
+            self.save_graph_state(graph_state).await?;
+            console_log!(
+                "auto_summary {}",
+                serde_json::json!({ "model": model, "entities": summarized })
+            );
+        }
+        Ok(summarized)
     }
 
-    async fn fetch(&mut self, mut req: Request) -> Result<Response> {
-        let path = req.path();
+    // Shared scheduler entry point for housekeeping; runs from the alarm or on demand.
+    async fn run_maintenance(&mut self) -> Result<MaintenanceReport> {
+        let now_ms = Date::now().as_millis();
+        let mut graph_state = self.load_or_initialize_graph_state().await?;
+        let mut report = graph_state.housekeeping(now_ms);
+        let changed = report.clear_token_expired
+            || !report.compaction.orphaned_edges_removed.is_empty()
+            || !report.compaction.nodes_normalized.is_empty()
+            || !report.faded_relations_removed.is_empty();
+        if changed {
+            self.save_graph_state(&mut graph_state).await?;
+        }
+        report.entities_summarized = self.auto_summarize(&mut graph_state, now_ms).await?;
+        if !report.entities_summarized.is_empty() {
+            report.stats = graph_state.stats();
+        }
+        // Also seeds replicas added since the last write
+        self.replicate(&graph_state)?;
+        report.sessions_purged = self.purge_stale_sessions(now_ms).await?;
+        report.idempotency_keys_purged = self.purge_expired_idempotency_keys(now_ms).await?;
+
+        report.next_run_at_ms = now_ms + self.maintenance_interval_ms();
+        let mut storage = self.state.storage();
+        storage.set_alarm(report.next_run_at_ms as i64).await?;
+        storage.put(MAINTENANCE_REPORT_KEY, &report).await?;
+        let mut history = storage
+            .get::<Vec<GrowthPoint>>(GROWTH_HISTORY_KEY)
+            .await
+            .unwrap_or_default();
+        record_growth(&mut history, &report.stats, now_ms);
+        storage.put(GROWTH_HISTORY_KEY, &history).await?;
+        // One structured line per run, for log-based metrics
+        console_log!(
+            "maintenance_stats {}",
+            serde_json::to_string(&report.stats)?
+        );
+        Ok(report)
+    }
+
+    async fn route(&mut self, mut req: Request) -> Result<Response> {
+        // Routes are matched without the `/v1` prefix; unprefixed paths are aliases of v1.
+        let full_path = req.path();
+        let path = match full_path.strip_prefix(API_V1_PREFIX) {
+            Some(rest) if rest.starts_with('/') => rest.to_string(),
+            _ => full_path,
+        };
+        // Reads routed to this instance as a read replica, see replication.rs
+        let (replica_read, path) = match path.strip_prefix(REPLICA_PATH_PREFIX) {
+            Some(rest) if rest.starts_with('/') => (true, rest.to_string()),
+            _ => (false, path),
+        };
+        if req.method() == Method::Post && path == REPLICA_SYNC_PATH {
+            // Second line of defence behind the worker's /do guard: the primary and the
+            // per-identity graphs never take snapshots
+            if !replication::is_replica_instance(&self.env, &self.state.id()) {
+                return Response::error("Not Found", 404);
+            }
+            return self.apply_replica_snapshot(&mut req).await;
+        }
+        let is_replica = self.is_replica().await;
+        // Routes are matched on decoded segments, so their scope is worked out the same way
+        let route_scope = auth::scope_for_do_route(&req.method(), &auth::decoded_do_route(&path));
+        if replica_read {
+            if !is_replica {
+                return Response::error("Read replica has not been synchronized yet", 503);
+            }
+            if route_scope != Scope::Read {
+                return Response::error("Read replicas only serve read-only routes", 405);
+            }
+        } else if !is_replica {
+            // Replicas only mirror the primary, so they run no maintenance of their own
+            self.ensure_maintenance_alarm().await?;
+        }
+        if route_scope == Scope::Write {
+            let now_ms = Date::now().as_millis();
+            if let Some(lock) = self.active_lock(now_ms).await {
+                let message = match &lock.reason {
+                    Some(reason) => format!("Graph is locked for maintenance: {}", reason),
+                    None => "Graph is locked for maintenance".to_string(),
+                };
+                let mut response = Response::error(message, 503)?;
+                response
+                    .headers_mut()
+                    .set("retry-after", &lock.retry_after_seconds(now_ms).to_string())?;
+                return Ok(response);
+            }
+        }
+        // Opens of names recently found missing skip loading the graph, see negative_cache.rs
+        let mut open_query = None;
+        if req.method() == Method::Post && path == "/graph/open" {
+            let mut payload: OpenNodesQuery = match req.json().await {
+                Ok(p) => p,
+                Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+            };
+            payload.names.iter_mut().for_each(names::canonicalize);
+            if let Some(version) = self.missing_names.all_missing(&payload.names) {
+                let mut response = streaming::graph_response(Vec::new(), Vec::new())?;
+                response
+                    .headers_mut()
+                    .set(GRAPH_VERSION_HEADER, &version.to_string())?;
+                return Ok(response);
+            }
+            open_query = Some(payload);
+        }
         let mut graph_state = self.load_or_initialize_graph_state().await?;
+        graph_state.actor = req.headers().get(auth::ACTOR_HEADER)?;
+        let entities_before = graph_state.nodes.len();
+        let version_before = graph_state.version;
 
         // Helper macro for handling results and saving state
         macro_rules! handle_result {
@@ -83,10 +618,10 @@ impl DurableObject for KnowledgeGraphDO {
 
                 // Assuming successful completion of the operation at a top level
                 // and save the graph state.
-                self.save_graph_state(&graph_state).await?;
+                self.save_graph_state(&mut graph_state).await?;
 
                 // Return the result as JSON with a default 200 OK status.
-                // This handles types like Vec<Result<String, String>> or Ok(SerializableType)
+                // This handles types like Vec<BatchResult> or Ok(SerializableType)
                 // which Response::from_json can serialize.
                 Response::from_json(&value)
             }};
@@ -96,7 +631,7 @@ impl DurableObject for KnowledgeGraphDO {
             ($op:expr, success_status_code: $status:expr) => {
                 match $op {
                     Ok(val) => {
-                        self.save_graph_state(&graph_state).await?;
+                        self.save_graph_state(&mut graph_state).await?;
                         Response::from_json(&val).map(|r| r.with_status($status))
                     }
                     Err(e) => {
@@ -109,7 +644,7 @@ impl DurableObject for KnowledgeGraphDO {
             ($op:expr, no_content_success: true) => {
                 match $op {
                     Ok(_) => {
-                        self.save_graph_state(&graph_state).await?;
+                        self.save_graph_state(&mut graph_state).await?;
                         Response::empty().map(|r| r.with_status(204)) // No Content
                     }
                     Err(e) => {
@@ -120,10 +655,31 @@ impl DurableObject for KnowledgeGraphDO {
             };
         }
 
+        // Segments are percent-decoded so ids like "Alice Smith" (sent as `Alice%20Smith`) match.
+        let segments: Vec<String> = path
+            .split('/')
+            .map(|segment| percent_decode_str(segment).decode_utf8_lossy().into_owned())
+            .collect();
+
+        let conditional_read = is_conditional_read(&req.method(), &segments);
+        let etag = cache::graph_etag(graph_state.version);
+        if conditional_read
+            && req
+                .headers()
+                .get("if-none-match")?
+                .is_some_and(|condition| cache::etag_matches(&condition, &etag))
+        {
+            return cache::not_modified(&etag);
+        }
+
         // Using a simple path matching for now. A router could be used for more complex scenarios.
-        match (
+        let response = match (
             req.method(),
-            path.split('/').collect::<Vec<&str>>().as_slice(),
+            segments
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<&str>>()
+                .as_slice(),
         ) {
             // === Node Operations (Original Simple API) ===
             (Method::Post, ["", "nodes"]) => {
@@ -133,34 +689,97 @@ impl DurableObject for KnowledgeGraphDO {
                 };
                 let node_id = Self::new_id();
                 // Construct the Node object
-                let node_to_add = Self::construct_node_from_payload(node_id.clone(), payload);
+                let node_to_add = Self::construct_node_from_payload(
+                    node_id.clone(),
+                    payload,
+                    graph_state.actor.clone(),
+                );
                 // Call the kg.rs add_node method
                 graph_state.add_node(node_to_add.clone()); // add_node in kg.rs returns the ID, but we already have it.
                                                            // Let's assume the returned Node is what we want.
                                                            // Explicitly specify the error type for the Result passed to handle_result!
-                handle_result!(Ok::<Node, worker::Error>(node_to_add), success_status_code: 201)
+                with_location(
+                    handle_result!(Ok::<Node, worker::Error>(node_to_add), success_status_code: 201),
+                    &format!("/nodes/{}", node_id),
+                )
             }
             (Method::Get, ["", "nodes"]) => {
                 let url = req.url()?;
                 let query_params: std::collections::HashMap<String, String> =
                     url.query_pairs().into_owned().collect();
 
-                if let Some(type_filter) = query_params.get("type") {
-                    let nodes = graph_state.find_nodes_by_type(type_filter);
-                    // find_nodes_by_type returns Vec<&Node>, which is serializable
-                    Response::from_json(&nodes)
-                } else {
-                    // Return all nodes if no type filter
-                    let all_nodes: Vec<&Node> = graph_state.nodes.values().collect();
-                    Response::from_json(&all_nodes)
+                let data_filter = match query_params
+                    .get("data_filter")
+                    .map(|f| DataFilter::parse(f))
+                {
+                    Some(Ok(filter)) => Some(filter),
+                    Some(Err(e)) => {
+                        return Response::error(
+                            format!("Bad request: invalid data_filter: {}", e),
+                            400,
+                        )
+                    }
+                    None => None,
+                };
+                // "start,end" windows; see TimeRange::parse
+                let parse_range = |key: &str| {
+                    query_params
+                        .get(key)
+                        .map(|v| TimeRange::parse(v).map_err(|e| format!("invalid {}: {}", key, e)))
+                        .transpose()
+                };
+                let (created, updated) = match (
+                    parse_range("created_between"),
+                    parse_range("updated_between"),
+                ) {
+                    (Ok(created), Ok(updated)) => (created, updated),
+                    (Err(e), _) | (_, Err(e)) => {
+                        return Response::error(format!("Bad request: {}", e), 400)
+                    }
+                };
+                let nodes = graph_state.filter_nodes(
+                    query_params.get("type").map(String::as_str),
+                    data_filter.as_ref(),
+                    created.as_ref(),
+                    updated.as_ref(),
+                );
+                match self.redactor_for(&req)? {
+                    Some(redactor) => {
+                        let mut nodes: Vec<Node> = nodes.into_iter().cloned().collect();
+                        redactor.nodes(&mut nodes);
+                        Response::from_json(&nodes)
+                    }
+                    None => Response::from_json(&nodes),
                 }
             }
+            (Method::Post, ["", "nodes", "batch-get"]) => {
+                let payload: BatchGetNodesPayload = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                if payload.ids.len() > MAX_BATCH_GET_IDS {
+                    return Response::error(
+                        format!("Bad request: at most {} ids per request", MAX_BATCH_GET_IDS),
+                        400,
+                    );
+                }
+                let (mut nodes, missing) = graph_state.nodes_by_ids(&payload.ids);
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.nodes(&mut nodes);
+                }
+                Response::from_json(&BatchGetNodesResponse { nodes, missing })
+            }
             (Method::Get, ["", "nodes", node_id]) => {
                 match graph_state.get_node(node_id) {
-                    Some(node) => {
-                        self.save_graph_state(&graph_state).await?; // Save not strictly needed for GET, but good practice if there were reads that modify state (e.g. access counts)
-                        Response::from_json(node)
-                    }
+                    // Reads don't save: every save bumps the graph version
+                    Some(node) => match self.redactor_for(&req)? {
+                        Some(redactor) => {
+                            let mut node = node.clone();
+                            redactor.nodes(std::slice::from_mut(&mut node));
+                            Response::from_json(&node)
+                        }
+                        None => Response::from_json(node),
+                    },
                     None => Response::error("Node not found", 404),
                 }
             }
@@ -169,19 +788,41 @@ impl DurableObject for KnowledgeGraphDO {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
+                if let Some(e) = graph_state.version_conflict(node_id, payload.expected_version) {
+                    return Response::error(format!("Conflict: {}", e), 409);
+                }
                 match graph_state.update_node(node_id, payload.node_type, payload.data) {
                     Some(updated_node) => {
-                        self.save_graph_state(&graph_state).await?;
+                        self.save_graph_state(&mut graph_state).await?;
                         Response::from_json(&updated_node)
                     }
                     None => Response::error("Node not found", 404),
                 }
             }
             (Method::Delete, ["", "nodes", node_id_str]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let expected_version = match query_params
+                    .get("expected_version")
+                    .map(|v| v.parse::<u64>())
+                {
+                    Some(Ok(version)) => Some(version),
+                    Some(Err(_)) => {
+                        return Response::error(
+                            "Bad request: 'expected_version' must be a non-negative integer",
+                            400,
+                        )
+                    }
+                    None => None,
+                };
+                if let Some(e) = graph_state.version_conflict(node_id_str, expected_version) {
+                    return Response::error(format!("Conflict: {}", e), 409);
+                }
                 match graph_state.delete_node_and_connected_edges(node_id_str) {
                     Some(deleted_node) => {
                         // Returns Option<Node>
-                        self.save_graph_state(&graph_state).await?;
+                        self.save_graph_state(&mut graph_state).await?;
                         Response::from_json(
                             &serde_json::json!({ "deleted_id": deleted_node.id, "status": "deleted" }),
                         )
@@ -189,6 +830,133 @@ impl DurableObject for KnowledgeGraphDO {
                     None => Response::error("Node not found", 404),
                 }
             }
+            (Method::Get, ["", "nodes", node_id_str, "edges"]) => {
+                if graph_state.get_node(node_id_str).is_none() {
+                    return Response::error("Node not found", 404);
+                }
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let mut edges = graph_state.node_edges(
+                    node_id_str,
+                    query_params.get("direction").map(String::as_str),
+                    query_params.get("edge_type").map(String::as_str),
+                );
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.node_edges(&mut edges);
+                }
+                Response::from_json(&edges)
+            }
+            (Method::Get, ["", "nodes", node_id_str, "similar"]) => {
+                if graph_state.get_node(node_id_str).is_none() {
+                    return Response::error("Node not found", 404);
+                }
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let limit = match query_params.get("limit").map(|l| l.parse::<usize>()) {
+                    Some(Ok(limit)) => limit,
+                    Some(Err(_)) => {
+                        return Response::error(
+                            "Bad request: 'limit' must be a non-negative integer",
+                            400,
+                        )
+                    }
+                    None => DEFAULT_SIMILAR_LIMIT,
+                };
+                Response::from_json(&graph_state.similar_entities(node_id_str, limit))
+            }
+            (Method::Post, ["", "nodes", node_id_str, "summarize"]) => {
+                // An empty body means default options
+                let payload: SummarizePayload = match req.text().await {
+                    Ok(body) if body.trim().is_empty() => SummarizePayload::default(),
+                    Ok(body) => match serde_json::from_str(&body) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                    },
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                let Some(node) = graph_state.get_node(node_id_str) else {
+                    return Response::error("Node not found", 404);
+                };
+                let observations = KnowledgeGraphState::observations_of(node);
+                if observations.is_empty() {
+                    return Response::error("Node has no observations to summarize", 400);
+                }
+                let prompt = ai::summary_prompt(&node.id, &node.node_type, &observations);
+                let summary = match ai::complete(
+                    &self.env,
+                    ai::SUMMARIZE_SYSTEM_PROMPT,
+                    &prompt,
+                    256,
+                )
+                .await
+                {
+                    Ok(summary) => summary,
+                    Err(e) => return Response::error(e.to_string(), e.status()),
+                };
+
+                // Other requests may have run while waiting on the model, so apply the
+                // summary to freshly loaded state.
+                graph_state = self.load_or_initialize_graph_state().await?;
+                graph_state.actor = req.headers().get(auth::ACTOR_HEADER)?;
+                let model = ai::text_model(&self.env);
+                match graph_state.apply_summary(
+                    node_id_str,
+                    &summary,
+                    &observations,
+                    &model,
+                    payload.replace_observations,
+                ) {
+                    Some(_) if payload.replace_observations => {
+                        self.archive_observations(
+                            node_id_str,
+                            ArchivedObservations {
+                                summarized_at_ms: Date::now().as_millis(),
+                                model: model.clone(),
+                                summary: summary.clone(),
+                                observations: observations.clone(),
+                            },
+                        )
+                        .await?;
+                        handle_result!(EntitySummary {
+                            name: node_id_str.to_string(),
+                            summary,
+                            model,
+                            summarized_observations: observations.len(),
+                            replaced_observations: true,
+                        })
+                    }
+                    Some(_) => handle_result!(EntitySummary {
+                        name: node_id_str.to_string(),
+                        summary,
+                        model,
+                        summarized_observations: observations.len(),
+                        replaced_observations: payload.replace_observations,
+                    }),
+                    None => Response::error("Node not found", 404),
+                }
+            }
+            (Method::Get, ["", "nodes", node_id_str, "archived-observations"]) => {
+                // Archives aren't part of the snapshot; a replica's 503 sends the worker to the
+                // primary
+                if is_replica {
+                    return Response::error("Archived observations are kept by the primary", 503);
+                }
+                if graph_state.get_node(node_id_str).is_none() {
+                    return Response::error("Node not found", 404);
+                }
+                let archive = self
+                    .state
+                    .storage()
+                    .get::<Vec<ArchivedObservations>>(&format!(
+                        "{}{}",
+                        OBSERVATION_ARCHIVE_PREFIX, node_id_str
+                    ))
+                    .await
+                    .unwrap_or_default();
+                Response::from_json(&archive)
+            }
             (Method::Get, ["", "nodes", node_id_str, "related"]) => {
                 if graph_state.get_node(node_id_str).is_none() {
                     return Response::error("Start node not found", 404);
@@ -242,8 +1010,11 @@ impl DurableObject for KnowledgeGraphDO {
 
                 related_nodes.sort_by_key(|n| n.id.clone());
                 related_nodes.dedup_by_key(|n| n.id.clone());
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.nodes(&mut related_nodes);
+                }
 
-                // self.save_graph_state(&graph_state).await?; // Not strictly needed for GET but good practice
+                // self.save_graph_state(&mut graph_state).await?; // Not strictly needed for GET but good practice
                 Response::from_json(&related_nodes)
             }
 
@@ -255,18 +1026,57 @@ impl DurableObject for KnowledgeGraphDO {
                 };
                 let edge_id = Self::new_id();
                 // Construct the Edge object
-                let edge_to_add = Self::construct_edge_from_payload(edge_id.clone(), payload);
+                let edge_to_add = Self::construct_edge_from_payload(
+                    edge_id.clone(),
+                    payload,
+                    graph_state.actor.clone(),
+                );
                 // Call the kg.rs add_edge method
                 graph_state.add_edge(edge_to_add.clone()); // add_edge in kg.rs returns the ID.
                                                            // Let's assume the returned Edge is what we want.
                                                            // Explicitly specify the error type for the Result passed to handle_result!
-                handle_result!(Ok::<Edge, worker::Error>(edge_to_add), success_status_code: 201)
+                with_location(
+                    handle_result!(Ok::<Edge, worker::Error>(edge_to_add), success_status_code: 201),
+                    &format!("/edges/{}", edge_id),
+                )
             }
-            (Method::Get, ["", "edges", edge_id]) => match graph_state.get_edge(edge_id) {
-                Some(edge) => {
-                    self.save_graph_state(&graph_state).await?;
-                    Response::from_json(edge)
+            (Method::Get, ["", "edges"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                match Self::parse_edge_list_query(&query_params) {
+                    Ok(query) => {
+                        let mut page = graph_state.list_edges(&query);
+                        if let Some(redactor) = self.redactor_for(&req)? {
+                            redactor.edges(&mut page.edges);
+                        }
+                        Response::from_json(&page)
+                    }
+                    Err(e) => Response::error(format!("Bad request: {}", e), 400),
                 }
+            }
+            (Method::Get, ["", "edges", "find"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                match (query_params.get("from"), query_params.get("to")) {
+                    (Some(from), Some(to)) => Response::from_json(&graph_state.find_edges(
+                        from,
+                        to,
+                        query_params.get("type").map(String::as_str),
+                    )),
+                    _ => Response::error("Bad request: 'from' and 'to' are required", 400),
+                }
+            }
+            (Method::Get, ["", "edges", edge_id]) => match graph_state.get_edge(edge_id) {
+                Some(edge) => match self.redactor_for(&req)? {
+                    Some(redactor) => {
+                        let mut edge = edge.clone();
+                        redactor.edges(std::slice::from_mut(&mut edge));
+                        Response::from_json(&edge)
+                    }
+                    None => Response::from_json(edge),
+                },
                 None => Response::error("Edge not found", 404),
             },
             (Method::Put, ["", "edges", _edge_id]) => {
@@ -280,140 +1090,1061 @@ impl DurableObject for KnowledgeGraphDO {
                 // based on the previous context. Commenting out for now.
                 // match graph_state.update_edge_data(edge_id, payload.data) {
                 //     Some(updated_edge) => {
-                //         self.save_graph_state(&graph_state).await?;
+                //         self.save_graph_state(&mut graph_state).await?;
                 //         Response::from_json(&updated_edge)
                 //     }
                 //     None => Response::error("Edge not found", 404),
                 // }
                 Response::error("Route /edges/:id PUT not implemented yet", 501)
             }
+            (Method::Patch, ["", "edges", edge_id]) => {
+                // The body is a JSON merge patch for the edge's data (null clears it)
+                let patch: JsonValue = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                match graph_state.patch_edge_data(edge_id, &patch) {
+                    Some(updated_edge) => {
+                        self.save_graph_state(&mut graph_state).await?;
+                        Response::from_json(&updated_edge)
+                    }
+                    None => Response::error("Edge not found", 404),
+                }
+            }
             (Method::Delete, ["", "edges", edge_id]) => {
                 match graph_state.remove_edge(edge_id) {
                     Some(deleted_edge) => {
-                        // Returns Option<Edge>
-                        self.save_graph_state(&graph_state).await?;
-                        Response::from_json(
-                            &serde_json::json!({ "deleted_id": deleted_edge.id, "status": "deleted" }),
-                        )
+                        // Like delete_relations, the mirror goes with it (see relation_types.rs)
+                        let mirrored_edge_id = graph_state.remove_mirror_edge(&deleted_edge);
+                        self.save_graph_state(&mut graph_state).await?;
+                        let mut body = serde_json::json!({ "deleted_id": deleted_edge.id, "status": "deleted" });
+                        if let Some(mirrored_edge_id) = mirrored_edge_id {
+                            body["mirrored_edge_id"] = JsonValue::String(mirrored_edge_id);
+                        }
+                        Response::from_json(&body)
                     }
                     None => Response::error("Edge not found", 404),
                 }
             }
 
             // === Batch Graph Operations (Newer API) ===
-            // These operations return Vec<Result<String, String>> or a struct, not a single top-level Result<T, E>.
+            // These operations return a BatchResponse (one result per requested item plus a summary) or a struct, not a single top-level Result<T, E>.
             // They should use the first arm of handle_result!
             (Method::Post, ["", "graph", "entities"]) => {
-                let payload: CreateEntitiesPayload = match req.json().await {
+                let mut payload: CreateEntitiesPayload =
+                    match validation::from_str(&req.text().await?) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                    };
+                payload.resolve_names(&graph_state);
+                let policy = graph_state.near_duplicate_policy(payload.on_near_duplicate);
+                let batch = if payload.merge_existing {
+                    let (results, resolved_names) = graph_state.create_or_merge_entities(
+                        payload.entities,
+                        policy,
+                        payload.use_template,
+                        Date::now().as_millis(),
+                    );
+                    BatchResponse::new(results).with_resolved_names(resolved_names)
+                } else if payload.use_template {
+                    BatchResponse::new(
+                        graph_state.create_entities_from_templates(payload.entities, policy),
+                    )
+                } else {
+                    BatchResponse::new(graph_state.create_entities_batch(payload.entities, policy))
+                };
+                let status = batch.creation_status();
+                handle_result!(batch).map(|r| r.with_status(status))
+            }
+            (Method::Post, ["", "graph", "relations"]) => {
+                let mut payload: CreateRelationsPayload =
+                    match validation::from_str(&req.text().await?) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                    };
+                payload.resolve_names(&graph_state);
+                let policy = payload.on_missing_node;
+                match graph_state.create_relations_batch(payload.relations, policy) {
+                    Ok(results) => {
+                        let batch = BatchResponse::new(results).with_on_missing_node(policy);
+                        let status = batch.creation_status();
+                        handle_result!(batch).map(|r| r.with_status(status))
+                    }
+                    Err(results) => {
+                        // Nothing was written, so the state is not saved
+                        Response::from_json(
+                            &BatchResponse::new(results).with_on_missing_node(policy),
+                        )
+                        .map(|r| r.with_status(400))
+                    }
+                }
+            }
+            (Method::Post, ["", "graph", "remember"]) => {
+                let mut payload: RememberPayload = match validation::from_str(&req.text().await?) {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                payload.resolve_names(&graph_state);
+                if let Err(results) = graph_state.check_versions(
+                    payload
+                        .observations
+                        .iter()
+                        .map(|item| (item.entity_name.as_str(), item.expected_version)),
+                ) {
+                    return Response::from_json(&BatchResponse::new(results))
+                        .map(|r| r.with_status(409));
+                }
+                let remembered = graph_state.remember(payload);
+                if remembered.error.is_some() {
+                    // Nothing is saved, so none of it was written
+                    return Response::from_json(&remembered).map(|r| r.with_status(400));
+                }
+                let created = [&remembered.entities, &remembered.relations]
+                    .iter()
+                    .any(|batch| batch.creation_status() == 201);
+                let status = if created { 201 } else { 200 };
+                handle_result!(remembered).map(|r| r.with_status(status))
+            }
+            (Method::Post, ["", "graph", "observations", "add"]) => {
+                let mut payload: AddObservationsPayload =
+                    match validation::from_str(&req.text().await?) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                    };
+                payload.resolve_names(&graph_state);
+                if let Err(results) = graph_state.check_versions(
+                    payload
+                        .observations
+                        .iter()
+                        .map(|item| (item.entity_name.as_str(), item.expected_version)),
+                ) {
+                    return Response::from_json(&BatchResponse::new(results))
+                        .map(|r| r.with_status(409));
+                }
+                let results = graph_state.add_observations_batch(payload.observations);
+                handle_result!(BatchResponse::new(results))
+            }
+            (Method::Post, ["", "graph", "entities", "delete"]) => {
+                let mut payload: DeleteEntitiesPayload =
+                    match validation::from_str(&req.text().await?) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                    };
+                payload.resolve_names(&graph_state);
+                if let Err(results) = graph_state.check_versions(
+                    payload
+                        .entity_names
+                        .iter()
+                        .map(|name| (name.as_str(), payload.expected_versions.get(name).copied())),
+                ) {
+                    return Response::from_json(&BatchResponse::new(results))
+                        .map(|r| r.with_status(409));
+                }
+                let results = graph_state.delete_entities_batch(payload.entity_names);
+                handle_result!(BatchResponse::new(results))
+            }
+            (Method::Post, ["", "graph", "entities", "pin"]) => {
+                // {"entityNames": [...], "pinned": false} unpins
+                let mut payload: PinEntitiesPayload = match validation::from_str(&req.text().await?)
+                {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                match graph_state.create_entities_batch(payload.entities) {
-                    Ok(nodes) => {
-                        self.save_graph_state(&graph_state).await?;
-                        Response::from_json(&nodes) // HTTP 200 by default
+                payload.resolve_names(&graph_state);
+                let results = graph_state.set_pinned(
+                    payload.entity_names,
+                    payload.pinned,
+                    Date::now().as_millis(),
+                );
+                handle_result!(BatchResponse::new(results))
+            }
+            (Method::Post, ["", "graph", "observations", "delete"]) => {
+                let mut payload: DeleteObservationsPayload =
+                    match validation::from_str(&req.text().await?) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                    };
+                payload.resolve_names(&graph_state);
+                if let Err(results) = graph_state.check_versions(
+                    payload
+                        .deletions
+                        .iter()
+                        .map(|item| (item.entity_name.as_str(), item.expected_version)),
+                ) {
+                    return Response::from_json(&BatchResponse::new(results))
+                        .map(|r| r.with_status(409));
+                }
+                let results = graph_state.delete_observations_batch(payload.deletions);
+                handle_result!(BatchResponse::new(results))
+            }
+            (Method::Post, ["", "graph", "relations", "delete"]) => {
+                let mut payload: DeleteRelationsPayload =
+                    match validation::from_str(&req.text().await?) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                    };
+                payload.resolve_names(&graph_state);
+                let results = graph_state.delete_relations_batch(payload.relations);
+                handle_result!(BatchResponse::new(results))
+            }
+            (Method::Post, ["", "graph", "clear"]) => {
+                // An empty body requests a confirmation token
+                let body = req.text().await?;
+                let payload: ClearGraphPayload = if body.trim().is_empty() {
+                    ClearGraphPayload::default()
+                } else {
+                    match serde_json::from_str(&body) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                     }
+                };
+                match graph_state.clear_graph(payload.confirm_token.as_deref(), payload.force) {
+                    Ok(response_data) => handle_result!(response_data),
                     Err(e_str) => {
-                        console_error!("Error in create_entities_batch: {}", e_str);
-                        Response::error(format!("Failed to create entities: {}", e_str), 500)
+                        // An expired token is consumed, so persist that
+                        self.save_graph_state(&mut graph_state).await?;
+                        Response::error(e_str, 400)
                     }
                 }
             }
-            (Method::Post, ["", "graph", "relations"]) => {
-                let payload: CreateRelationsPayload = match req.json().await {
+            (Method::Post, ["", "graph", "forget"]) => {
+                let payload: ForgetPayload = match req.json().await {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                match graph_state.create_relations_batch(payload.relations) {
-                    Ok(edges) => {
-                        self.save_graph_state(&graph_state).await?;
-                        Response::from_json(&edges) // HTTP 200 by default
+                let confirmed = payload.confirm_token.is_some();
+                let scope = payload.scope;
+                match graph_state.forget(payload, Date::now().as_millis()) {
+                    Ok(response_data) => {
+                        if let ForgetResponse::Forgotten { plan } = &response_data {
+                            console_log!(
+                                "Forgot {} entities, {} relations and the observations of {} entities",
+                                plan.entities.len(),
+                                plan.relations,
+                                plan.observations.len()
+                            );
+                        }
+                        let response = handle_result!(response_data);
+                        if confirmed && scope == ForgetScope::Entities {
+                            self.purge_archived_observations(&graph_state).await?;
+                        }
+                        response
                     }
-                    Err(e_str) => {
-                        console_error!("Error in create_relations_batch: {}", e_str);
-                        Response::error(format!("Failed to create relations: {}", e_str), 500)
+                    Err(e_str) if confirmed => {
+                        // An expired token is consumed, so persist that
+                        self.save_graph_state(&mut graph_state).await?;
+                        Response::error(format!("Bad request: {}", e_str), 400)
                     }
+                    Err(e_str) => Response::error(format!("Bad request: {}", e_str), 400),
                 }
             }
-            (Method::Post, ["", "graph", "observations", "add"]) => {
-                let payload: AddObservationsPayload = match req.json().await {
+            (Method::Post, ["", "graph", "types", "rename"]) => {
+                let payload: RenameTypePayload = match req.json().await {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                let result = graph_state.add_observations_batch(payload.observations);
-                handle_result!(result)
+                if let Err(e) = Self::validate_type_rename(&payload) {
+                    return Response::error(format!("Bad request: {}", e), 400);
+                }
+                let renamed = graph_state.rename_node_type(&payload.from, &payload.to);
+                if renamed > 0 {
+                    console_log!(
+                        "Renamed entity type '{}' to '{}' on {} node(s)",
+                        payload.from,
+                        payload.to,
+                        renamed
+                    );
+                    self.save_graph_state(&mut graph_state).await?;
+                }
+                Response::from_json(&RenameTypeResponse {
+                    from: payload.from,
+                    to: payload.to,
+                    renamed,
+                })
             }
-            (Method::Post, ["", "graph", "entities", "delete"]) => {
-                let payload: DeleteEntitiesPayload = match req.json().await {
+            (Method::Get, ["", "graph", "metadata"]) => Response::from_json(&graph_state.metadata),
+            (Method::Put | Method::Patch, ["", "graph", "metadata"]) => {
+                // PUT replaces the metadata; PATCH merges into it (null removes a key)
+                let update: JsonValue = match req.json().await {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                match graph_state.delete_entities_batch(payload.entity_names) {
-                    Ok(deleted_ids) => {
-                        self.save_graph_state(&graph_state).await?;
-                        Response::from_json(&deleted_ids)
+                let merge = req.method() == Method::Patch;
+                match graph_state.update_metadata(&update, merge) {
+                    Ok(changed) => {
+                        if changed {
+                            self.save_graph_state(&mut graph_state).await?;
+                        }
+                        Response::from_json(&graph_state.metadata)
                     }
-                    Err(e_str) => {
-                        console_error!("Error in delete_entities_batch: {}", e_str);
-                        Response::error(format!("Failed to delete entities: {}", e_str), 500)
+                    Err(e) => Response::error(format!("Bad request: {}", e), 400),
+                }
+            }
+            (Method::Get, ["", "graph", "entity-templates"]) => {
+                Response::from_json(&graph_state.entity_templates)
+            }
+            (Method::Put, ["", "graph", "entity-templates", entity_type]) => {
+                let template: EntityTemplate = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                match graph_state.set_entity_template(entity_type, template) {
+                    Ok(()) => {
+                        let template = graph_state.entity_templates[*entity_type].clone();
+                        self.save_graph_state(&mut graph_state).await?;
+                        Response::from_json(&EntityTemplateUpdateResponse {
+                            entity_type: entity_type.to_string(),
+                            template,
+                        })
                     }
+                    Err(e) => Response::error(format!("Bad request: {}", e), 400),
                 }
             }
-            (Method::Post, ["", "graph", "observations", "delete"]) => {
-                let payload: DeleteObservationsPayload = match req.json().await {
+            (Method::Delete, ["", "graph", "entity-templates", entity_type]) => {
+                match graph_state.remove_entity_template(entity_type) {
+                    Some(_) => {
+                        self.save_graph_state(&mut graph_state).await?;
+                        Response::empty().map(|r| r.with_status(204))
+                    }
+                    None => Response::error("No template for this entity type", 404),
+                }
+            }
+            (Method::Get, ["", "graph", "relation-types"]) => {
+                Response::from_json(&graph_state.relation_types)
+            }
+            (Method::Put, ["", "graph", "relation-types", relation_type]) => {
+                let spec: RelationTypeSpec = match req.json().await {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                let result = graph_state.delete_observations_batch(payload.deletions);
-                handle_result!(result)
+                match graph_state.set_relation_type(
+                    relation_type,
+                    spec.clone(),
+                    Date::now().as_millis(),
+                ) {
+                    Ok(mirrored_edges_created) => {
+                        self.save_graph_state(&mut graph_state).await?;
+                        Response::from_json(&RelationTypeUpdateResponse {
+                            relation_type: relation_type.to_string(),
+                            spec,
+                            mirrored_edges_created,
+                        })
+                    }
+                    Err(e) => Response::error(format!("Bad request: {}", e), 400),
+                }
             }
-            (Method::Post, ["", "graph", "relations", "delete"]) => {
-                let payload: DeleteRelationsPayload = match req.json().await {
+            (Method::Delete, ["", "graph", "relation-types", relation_type]) => {
+                match graph_state.remove_relation_type(relation_type) {
+                    Some(_) => {
+                        self.save_graph_state(&mut graph_state).await?;
+                        Response::empty().map(|r| r.with_status(204))
+                    }
+                    None => Response::error("Relation type not in catalog", 404),
+                }
+            }
+            (Method::Post, ["", "graph", "relation-types", "rename"]) => {
+                let payload: RenameTypePayload = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                if let Err(e) = Self::validate_type_rename(&payload) {
+                    return Response::error(format!("Bad request: {}", e), 400);
+                }
+                let renamed = graph_state.rename_edge_type(&payload.from, &payload.to);
+                // The type's catalog entry follows it, see relation_types.rs
+                let catalog_changed =
+                    graph_state.rename_relation_type_spec(&payload.from, &payload.to);
+                if renamed > 0 || catalog_changed {
+                    console_log!(
+                        "Renamed relation type '{}' to '{}' on {} edge(s)",
+                        payload.from,
+                        payload.to,
+                        renamed
+                    );
+                    self.save_graph_state(&mut graph_state).await?;
+                }
+                Response::from_json(&RenameTypeResponse {
+                    from: payload.from,
+                    to: payload.to,
+                    renamed,
+                })
+            }
+            (Method::Get, ["", "graph", "stats"]) => Response::from_json(&graph_state.stats()),
+            (Method::Get, ["", "graph", "top"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let by = match query_params.get("by").map(|v| TopMetric::parse(v)) {
+                    Some(Ok(by)) => by,
+                    Some(Err(e)) => return Response::error(format!("Bad request: {}", e), 400),
+                    None => TopMetric::Degree,
+                };
+                let limit = match query_params.get("limit").map(|v| v.parse::<usize>()) {
+                    Some(Ok(limit)) => limit.min(MAX_TOP_HUBS),
+                    Some(Err(_)) => return Response::error("Bad request: invalid limit", 400),
+                    None => DEFAULT_TOP_HUBS,
+                };
+                let entities = graph_state.top_entities(by, limit);
+                Response::from_json(&TopEntitiesResponse { by, entities })
+            }
+            (Method::Get, ["", "graph", "recent"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let limit = match query_params.get("limit").map(|v| v.parse::<usize>()) {
+                    Some(Ok(limit)) => limit.min(MAX_RECENT_LIMIT),
+                    Some(Err(_)) => return Response::error("Bad request: invalid limit", 400),
+                    None => DEFAULT_RECENT_LIMIT,
+                };
+                let observations = match query_params.get("observations").map(|v| v.parse()) {
+                    Some(Ok(observations)) => observations,
+                    Some(Err(_)) => {
+                        return Response::error("Bad request: invalid observations", 400)
+                    }
+                    None => DEFAULT_RECENT_OBSERVATIONS,
+                };
+                let mut entities = graph_state.recent_entities(limit, observations);
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.recent(&mut entities);
+                }
+                Response::from_json(&RecentEntitiesResponse { entities })
+            }
+            (Method::Get, ["", "graph", "analytics", "growth"]) => {
+                // Only the primary runs maintenance; a replica's 503 sends the worker there
+                if is_replica {
+                    return Response::error("Growth history is kept by the primary", 503);
+                }
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let days = match query_params.get("days").map(|v| v.parse::<usize>()) {
+                    Some(Ok(days)) => Some(days),
+                    Some(Err(_)) => return Response::error("Bad request: invalid days", 400),
+                    None => None,
+                };
+                let mut points = self
+                    .state
+                    .storage()
+                    .get::<Vec<GrowthPoint>>(GROWTH_HISTORY_KEY)
+                    .await
+                    .unwrap_or_default();
+                if let Some(days) = days {
+                    points.drain(..points.len().saturating_sub(days));
+                }
+                Response::from_json(&GrowthHistory { points })
+            }
+            (Method::Get, ["", "graph", "analytics", "type-pairs"]) => {
+                Response::from_json(&graph_state.type_pair_report())
+            }
+            (Method::Get, ["", "graph", "analytics", "degrees"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let top = match query_params.get("top").map(|v| v.parse::<usize>()) {
+                    Some(Ok(top)) => top.min(MAX_TOP_HUBS),
+                    Some(Err(_)) => return Response::error("Bad request: invalid top", 400),
+                    None => DEFAULT_TOP_HUBS,
+                };
+                Response::from_json(&graph_state.degree_report(top))
+            }
+            (Method::Get, ["", "graph", "validate"]) => {
+                Response::from_json(&graph_state.validate())
+            }
+            (Method::Post, ["", "graph", "erase"]) => {
+                let payload: ErasePayload = match req.json().await {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                match graph_state.delete_relations_batch(payload.relations) {
-                    Ok(deleted_ids) => {
-                        self.save_graph_state(&graph_state).await?;
-                        Response::from_json(&deleted_ids)
+                match graph_state.erase(&payload, Date::now().as_millis()) {
+                    Ok(report) => {
+                        if !report.dry_run {
+                            // Only counts: the subject is what was asked to be forgotten
+                            console_log!(
+                                "Erased a subject: {} entities, {} relations",
+                                report.entities_removed.len(),
+                                report.relations_removed.len()
+                            );
+                            self.save_graph_state(&mut graph_state).await?;
+                            self.purge_archived_observations(&graph_state).await?;
+                        }
+                        Response::from_json(&report)
                     }
-                    Err(e_str) => {
-                        console_error!("Error in delete_relations_batch: {}", e_str);
-                        Response::error(format!("Failed to delete relations: {}", e_str), 500)
+                    Err(e) => Response::error(format!("Bad request: {}", e), 400),
+                }
+            }
+            (Method::Post, ["", "graph", "import", "validate"]) => {
+                let body = req.text().await?;
+                Response::from_json(&graph_state.validate_import(&body))
+            }
+            (Method::Post, ["", "graph", "repair"]) => {
+                let url = req.url()?;
+                let fix_param = url
+                    .query_pairs()
+                    .find(|(name, _)| name == "fix")
+                    .map(|(_, value)| value.into_owned());
+                let fixes: Option<Vec<&str>> = fix_param.as_deref().map(|fixes| {
+                    fixes
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|fix| !fix.is_empty())
+                        .collect()
+                });
+                match graph_state.repair(fixes.as_deref()) {
+                    Ok(report) => {
+                        // Nothing to fix means nothing to save, so the graph version stays put
+                        let changed = report
+                            .orphan_edges_removed
+                            .iter()
+                            .chain(&report.nodes_normalized)
+                            .any(|ids| !ids.is_empty());
+                        if changed {
+                            console_log!("Repair applied: {:?}", report);
+                            self.save_graph_state(&mut graph_state).await?;
+                        }
+                        Response::from_json(&report)
+                    }
+                    Err(e) => Response::error(format!("Bad request: {}", e), 400),
+                }
+            }
+
+            // === Admin Operations ===
+            (Method::Post, ["", "graph", "admin", "compact"]) => {
+                let report = graph_state.compact();
+                console_log!(
+                    "Compaction removed {} orphaned edge(s) and normalized {} node(s)",
+                    report.orphaned_edges_removed.len(),
+                    report.nodes_normalized.len()
+                );
+                handle_result!(report)
+            }
+            (Method::Post, ["", "graph", "admin", "prune-faded-relations"]) => {
+                // ?floor= overrides the graph's relation_confidence_floor; ?force=true also
+                // prunes relations of pinned entities
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let floor = match query_params.get("floor") {
+                    Some(raw) => match raw.parse::<f64>() {
+                        Ok(floor) if (0.0..=1.0).contains(&floor) => floor,
+                        _ => {
+                            return Response::error(
+                                "Bad request: floor must be a number between 0 and 1",
+                                400,
+                            )
+                        }
+                    },
+                    None => match graph_state.confidence_floor() {
+                        Some(floor) => floor,
+                        None => {
+                            return Response::error(
+                                format!(
+                                    "Bad request: pass ?floor= or set {} in the graph metadata",
+                                    confidence::CONFIDENCE_FLOOR_KEY
+                                ),
+                                400,
+                            )
+                        }
+                    },
+                };
+                let force = query_params.get("force").is_some_and(|v| v == "true");
+                let removed =
+                    graph_state.prune_faded_relations(Date::now().as_millis(), floor, force);
+                console_log!("Pruned {} faded relation(s)", removed.len());
+                handle_result!(PruneFadedRelationsResponse { floor, removed })
+            }
+            (Method::Post, ["", "graph", "admin", "maintenance"]) => {
+                // Runs now and restarts the schedule; the loaded graph_state is not reused
+                let report = self.run_maintenance().await?;
+                Response::from_json(&report)
+            }
+            (Method::Get, ["", "graph", "admin", "lock"]) => {
+                let now_ms = Date::now().as_millis();
+                Response::from_json(&LockStatus::of(self.active_lock(now_ms).await, now_ms))
+            }
+            (Method::Post, ["", "graph", "admin", "lock"]) => {
+                // An empty body takes the defaults; locking again replaces the lock
+                let body = req.text().await?;
+                let payload: LockPayload = if body.trim().is_empty() {
+                    LockPayload::default()
+                } else {
+                    match serde_json::from_str(&body) {
+                        Ok(p) => p,
+                        Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                     }
+                };
+                let now_ms = Date::now().as_millis();
+                match GraphLock::new(payload, now_ms) {
+                    Ok(lock) => {
+                        self.state.storage().put(GRAPH_LOCK_KEY, &lock).await?;
+                        console_log!("Graph locked until {}", lock.expires_at_ms);
+                        Response::from_json(&LockStatus::of(Some(lock), now_ms))
+                    }
+                    Err(e) => Response::error(format!("Bad request: {}", e), 400),
+                }
+            }
+            (Method::Post, ["", "graph", "admin", "unlock"]) => {
+                self.state.storage().delete(GRAPH_LOCK_KEY).await?;
+                console_log!("Graph unlocked");
+                Response::from_json(&LockStatus::of(None, Date::now().as_millis()))
+            }
+            (Method::Get, ["", "graph", "admin", "maintenance"]) => {
+                match self
+                    .state
+                    .storage()
+                    .get::<MaintenanceReport>(MAINTENANCE_REPORT_KEY)
+                    .await
+                {
+                    Ok(report) => Response::from_json(&report),
+                    Err(_) => Response::error("Maintenance has not run yet", 404),
                 }
             }
             (Method::Post, ["", "graph", "search"]) => {
-                let payload: SearchNodesQuery = match req.json().await {
+                let mut payload: SearchNodesQuery = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                payload.anchor.iter_mut().for_each(names::canonicalize);
+                payload.resolve_names(&graph_state);
+                if let Some(anchor) = &payload.anchor {
+                    if graph_state.get_node(anchor).is_none() {
+                        return Response::error(format!("Node '{}' not found", anchor), 404);
+                    }
+                }
+                let anchor = payload.anchor.as_deref();
+                let redactor = self.redactor_for(&req)?;
+                let data_filter = match payload.data_filter.as_deref().map(DataFilter::parse) {
+                    Some(Ok(filter)) => Some(filter),
+                    Some(Err(e)) => {
+                        return Response::error(
+                            format!("Bad request: invalid data_filter: {}", e),
+                            400,
+                        )
+                    }
+                    None => None,
+                };
+                match (payload.query, payload.queries) {
+                    (Some(query), queries) if queries.is_empty() => {
+                        let (mut entities, mut relations) =
+                            graph_state.search_nodes(&query, data_filter.as_ref());
+                        // Paths are found before the names are masked
+                        let mut anchor_paths =
+                            anchor.map(|anchor| graph_state.anchor_paths(anchor, &entities));
+                        if let Some(redactor) = &redactor {
+                            redactor.entities(&mut entities);
+                            redactor.relations(&mut relations);
+                            if let Some(anchor_paths) = &mut anchor_paths {
+                                redactor.anchor_paths(anchor_paths);
+                            }
+                        }
+                        match anchor_paths {
+                            Some(anchor_paths) => Response::from_json(&AnchoredSearchResponse {
+                                anchor_paths,
+                                entities,
+                                relations,
+                            }),
+                            None => streaming::graph_response(entities, relations),
+                        }
+                    }
+                    (None, queries) if !queries.is_empty() => {
+                        if queries.len() > MAX_SEARCH_QUERIES {
+                            return Response::error(
+                                format!(
+                                    "Bad request: at most {} queries per search",
+                                    MAX_SEARCH_QUERIES
+                                ),
+                                400,
+                            );
+                        }
+                        let results = queries
+                            .into_iter()
+                            .map(|query| {
+                                let (entities, relations) =
+                                    graph_state.search_nodes(&query, data_filter.as_ref());
+                                let mut results = QuerySearchResults {
+                                    query,
+                                    anchor_paths: anchor.map_or_else(Vec::new, |anchor| {
+                                        graph_state.anchor_paths(anchor, &entities)
+                                    }),
+                                    entities,
+                                    relations,
+                                };
+                                if let Some(redactor) = &redactor {
+                                    redactor.entities(&mut results.entities);
+                                    redactor.relations(&mut results.relations);
+                                    redactor.anchor_paths(&mut results.anchor_paths);
+                                }
+                                results
+                            })
+                            .collect();
+                        Response::from_json(&GroupedSearchResponse { results })
+                    }
+                    _ => Response::error("Bad request: pass either query or queries", 400),
+                }
+            }
+            (Method::Post, ["", "graph", "relations", "search"]) => {
+                let payload: SearchRelationsQuery = match req.json().await {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                let (entities, relations) = graph_state.search_nodes(&payload.query);
-                let response_data = KnowledgeGraphDataResponse {
+                let (mut entities, mut relations) = graph_state.search_relations(&payload.query);
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.entities(&mut entities);
+                    redactor.relations(&mut relations);
+                }
+                Response::from_json(&KnowledgeGraphDataResponse {
                     entities,
                     relations,
+                })
+            }
+            (Method::Post, ["", "graph", "observations", "search"]) => {
+                let payload: SearchObservationsQuery = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                handle_result!(response_data) // Use the first arm for direct value response
+                let limit = payload
+                    .limit
+                    .map_or(DEFAULT_OBSERVATION_SEARCH_LIMIT, |limit| {
+                        limit.min(MAX_OBSERVATION_SEARCH_LIMIT)
+                    });
+                let mut observations = graph_state.search_observations(&payload.query, limit);
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.observation_matches(&mut observations);
+                }
+                Response::from_json(&SearchObservationsResponse { observations })
             }
-            (Method::Post, ["", "graph", "open"]) => {
-                let payload: OpenNodesQuery = match req.json().await {
+            (Method::Post, ["", "graph", "context"]) => {
+                let payload: BuildContextQuery = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                if payload.query.trim().is_empty() {
+                    return Response::error("Bad request: query must not be empty", 400);
+                }
+                let max_tokens = payload
+                    .max_tokens
+                    .unwrap_or(DEFAULT_CONTEXT_TOKENS)
+                    .min(MAX_CONTEXT_TOKENS);
+                let hops = payload
+                    .hops
+                    .unwrap_or(DEFAULT_CONTEXT_HOPS)
+                    .min(MAX_CONTEXT_HOPS);
+                let mut pack = graph_state.build_context(&payload.query, max_tokens, hops);
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.context(&mut pack);
+                }
+                Response::from_json(&pack)
+            }
+            (Method::Post, ["", "graph", "reachable"]) => {
+                let payload: ReachabilityQuery = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                for name in [&payload.from, &payload.to] {
+                    if graph_state.get_node(name).is_none() {
+                        return Response::error(format!("Node '{}' not found", name), 404);
+                    }
+                }
+                let mut result = graph_state.reachable(
+                    &payload.from,
+                    &payload.to,
+                    payload.relation_types.as_deref(),
+                    payload.max_depth,
+                );
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.reachability(&mut result);
+                }
+                Response::from_json(&result)
+            }
+            (Method::Get, ["", "graph", "cycles"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let relation_type = query_params.get("relation_type").map(String::as_str);
+                Response::from_json(&graph_state.find_cycles(relation_type))
+            }
+            (Method::Post, ["", "graph", "toposort"]) => {
+                let payload: TopoSortQuery = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                match graph_state.toposort(payload.relation_types.as_deref(), payload.order) {
+                    Ok(order) => Response::from_json(&TopoSortResult { order }),
+                    Err(cycle) => Response::from_json(&TopoSortConflict {
+                        error: format!("Relations form a cycle: {}", cycle.path.join(" -> ")),
+                        cycle,
+                    })
+                    .map(|resp| resp.with_status(409)),
+                }
+            }
+            (Method::Get, ["", "graph", "export"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let options = match ExportOptions::from_query(&query_params) {
+                    Ok(options) => options,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                let mut slice = match graph_state.export_slice(&options) {
+                    Ok(slice) => slice,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.entities(&mut slice.entities);
+                    redactor.relations(&mut slice.relations);
+                }
+                match options.format {
+                    ExportFormat::Json => Response::from_json(&slice.into_json()),
+                    ExportFormat::D3 => Response::from_json(&slice.to_d3()),
+                    ExportFormat::Mermaid => {
+                        let mut headers = Headers::new();
+                        headers.set("content-type", "text/plain; charset=utf-8")?;
+                        Ok(
+                            Response::ok(slice.to_mermaid(options.direction))?
+                                .with_headers(headers),
+                        )
+                    }
+                }
+            }
+            (Method::Post, ["", "graph", "subgraph"]) => {
+                let payload: SubgraphQuery = match req.json().await {
                     Ok(p) => p,
                     Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                let (entities, relations) = graph_state.open_nodes(&payload.names);
-                let response_data = KnowledgeGraphDataResponse {
+                let (mut entities, mut relations) = graph_state.subgraph(
+                    payload.entity_types.as_deref(),
+                    payload.relation_types.as_deref(),
+                );
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.entities(&mut entities);
+                    redactor.relations(&mut relations);
+                }
+                Response::from_json(&KnowledgeGraphDataResponse {
                     entities,
                     relations,
+                })
+            }
+            (Method::Post, ["", "graph", "ingest"]) => {
+                let payload: IngestPayload = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                if payload.text.trim().is_empty() {
+                    return Response::error("Bad request: text is empty", 400);
+                }
+                if payload.text.chars().count() > ai::MAX_INGEST_TEXT_CHARS {
+                    return Response::error(
+                        format!(
+                            "Payload too large: text exceeds {} characters",
+                            ai::MAX_INGEST_TEXT_CHARS
+                        ),
+                        413,
+                    );
+                }
+                let mut extracted: ExtractedGraph =
+                    match ai::complete(&self.env, ai::EXTRACT_SYSTEM_PROMPT, &payload.text, 2048)
+                        .await
+                        .and_then(|reply| ai::parse_json_reply(&reply))
+                    {
+                        Ok(extracted) => extracted,
+                        Err(e) => return Response::error(e.to_string(), e.status()),
+                    };
+                names::canonicalize_extracted(&mut extracted);
+                // A confidence the model made up out of range is dropped
+                for relation in &mut extracted.relations {
+                    if relation
+                        .confidence
+                        .is_some_and(|c| !(0.0..=1.0).contains(&c))
+                    {
+                        relation.confidence = None;
+                    }
+                }
+                let model = ai::text_model(&self.env);
+                if payload.dry_run {
+                    return Response::from_json(&IngestResponse {
+                        model,
+                        extracted,
+                        entities: None,
+                        observations: None,
+                        relations: None,
+                    });
+                }
+
+                // Reload: other requests may have run while waiting on the model
+                graph_state = self.load_or_initialize_graph_state().await?;
+                graph_state.actor = req.headers().get(auth::ACTOR_HEADER)?;
+                extracted.resolve_names(&graph_state);
+                let (entities, observations, relations) =
+                    graph_state.apply_extracted_graph(&extracted, payload.on_missing_node);
+                handle_result!(IngestResponse {
+                    model,
+                    extracted,
+                    entities: Some(entities),
+                    observations: Some(observations),
+                    relations: Some(relations),
+                })
+            }
+            (Method::Post, ["", "graph", "relations", "suggest"]) => {
+                let payload: SuggestRelationsPayload = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
+                };
+                if payload.names.is_empty() {
+                    return Response::error("Bad request: names is empty", 400);
+                }
+                let missing: Vec<&str> = payload
+                    .names
+                    .iter()
+                    .filter(|name| graph_state.get_node(name).is_none())
+                    .map(String::as_str)
+                    .collect();
+                if !missing.is_empty() {
+                    return Response::error(
+                        format!("Nodes not found: {}", missing.join(", ")),
+                        404,
+                    );
+                }
+                let limit = payload
+                    .max_suggestions
+                    .unwrap_or(ai::DEFAULT_RELATION_SUGGESTIONS)
+                    .clamp(1, ai::MAX_RELATION_SUGGESTIONS);
+
+                let candidates = graph_state.suggestion_candidates(&payload.names);
+                let (entities, relations) = graph_state.open_nodes(&candidates);
+                let prompt = format!(
+                    "Entities to find relations for: {}\n\nEntities:\n{}\n\nExisting relations:\n{}\n\nSuggest at most {} relations.",
+                    payload.names.join(", "),
+                    serde_json::to_string(&entities)?,
+                    serde_json::to_string(&relations)?,
+                    limit
+                );
+                let suggested: RelationSuggestions = match ai::complete(
+                    &self.env,
+                    ai::SUGGEST_RELATIONS_SYSTEM_PROMPT,
+                    &prompt,
+                    1024,
+                )
+                .await
+                .and_then(|reply| ai::parse_json_reply(&reply))
+                {
+                    Ok(suggested) => suggested,
+                    Err(e) => return Response::error(e.to_string(), e.status()),
+                };
+                Response::from_json(&SuggestRelationsResponse {
+                    model: ai::text_model(&self.env),
+                    suggestions: graph_state
+                        .filter_relation_suggestions(suggested.suggestions, limit),
+                })
+            }
+            (Method::Get, ["", "graph", "suggest"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let prefix = query_params.get("q").map(String::as_str).unwrap_or("");
+                let limit = match query_params.get("limit").map(|l| l.parse::<usize>()) {
+                    Some(Ok(limit)) => limit.min(MAX_COMPLETION_VALUES),
+                    Some(Err(_)) => {
+                        return Response::error(
+                            "Bad request: 'limit' must be a non-negative integer",
+                            400,
+                        )
+                    }
+                    None => DEFAULT_SUGGEST_LIMIT,
+                };
+                Response::from_json(&graph_state.suggest(prefix, limit))
+            }
+            (Method::Get, ["", "graph", "complete"]) => {
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let kind: CompletionKind =
+                    match query_params
+                        .get("kind")
+                        .map(|k| serde_json::from_value(JsonValue::String(k.clone())))
+                    {
+                        Some(Ok(kind)) => kind,
+                        _ => return Response::error(
+                            "Bad request: 'kind' must be one of entity, entity_type, relation_type",
+                            400,
+                        ),
+                    };
+                let prefix = query_params.get("prefix").map(String::as_str).unwrap_or("");
+                let limit = match query_params.get("limit").map(|l| l.parse::<usize>()) {
+                    Some(Ok(limit)) => limit.min(MAX_COMPLETION_VALUES),
+                    Some(Err(_)) => {
+                        return Response::error(
+                            "Bad request: 'limit' must be a non-negative integer",
+                            400,
+                        )
+                    }
+                    None => MAX_COMPLETION_VALUES,
+                };
+                Response::from_json(&graph_state.complete(kind, prefix, limit))
+            }
+            // === MCP Session State ===
+            (Method::Post, ["", "mcp", "sessions", session_id, "log_level"]) => {
+                let mut payload: SessionLogLevel = match req.json().await {
+                    Ok(p) => p,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                handle_result!(response_data) // Use the first arm for direct value response
+                payload.updated_at_ms = Date::now().as_millis();
+                self.state
+                    .storage()
+                    .put(
+                        &format!("{}{}", MCP_SESSION_LOG_LEVEL_PREFIX, session_id),
+                        &payload,
+                    )
+                    .await?;
+                Response::from_json(&payload)
+            }
+            (Method::Get, ["", "mcp", "sessions", session_id, "log_level"]) => {
+                // Log levels are stored by the primary only; a replica's 503 sends the worker there
+                if is_replica {
+                    return Response::error("Session log levels are kept by the primary", 503);
+                }
+                let key = format!("{}{}", MCP_SESSION_LOG_LEVEL_PREFIX, session_id);
+                match self.state.storage().get::<SessionLogLevel>(&key).await {
+                    Ok(stored) => Response::from_json(&stored),
+                    Err(_) => Response::error("Session has no log level", 404),
+                }
+            }
+            (Method::Post, ["", "graph", "open"]) => {
+                // Read and canonicalized before the graph was loaded
+                let Some(mut payload) = open_query.take() else {
+                    return Response::error("Bad request: missing body", 400);
+                };
+                let requested = payload.names.clone();
+                payload.resolve_names(&graph_state);
+                let (mut entities, mut relations) = graph_state.open_nodes(&payload.names);
+                let found: std::collections::HashSet<&str> =
+                    entities.iter().map(|e| e.name.as_str()).collect();
+                self.missing_names.record(
+                    graph_state.version,
+                    requested
+                        .iter()
+                        .zip(&payload.names)
+                        .filter(|(_, resolved)| !found.contains(resolved.as_str()))
+                        .map(|(name, _)| name),
+                );
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.entities(&mut entities);
+                    redactor.relations(&mut relations);
+                }
+                streaming::graph_response(entities, relations)
             }
             (Method::Get, ["", "graph", "state"]) => {
-                let (entities, relations) = graph_state.get_full_graph_data();
-                let response_data = KnowledgeGraphDataResponse {
-                    entities,
-                    relations,
+                let url = req.url()?;
+                let query_params: std::collections::HashMap<String, String> =
+                    url.query_pairs().into_owned().collect();
+                let options = match StateOptions::from_query(&query_params) {
+                    Ok(options) => options,
+                    Err(e) => return Response::error(format!("Bad request: {}", e), 400),
                 };
-                handle_result!(response_data) // Use the first arm for direct value response
+                let (mut entities, mut relations) = graph_state.state_view(&options);
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.entities(&mut entities);
+                    redactor.relations(&mut relations);
+                }
+                streaming::graph_response(entities, relations)
             }
 
             // === Original State Endpoint (for debugging/compatibility if needed) ===
@@ -422,16 +2153,121 @@ impl DurableObject for KnowledgeGraphDO {
             // If the original `/state` was returning the raw `KnowledgeGraphState` struct (with HashMaps),
             // that would be different.
             (Method::Get, ["", "state"]) => {
-                let (entities, relations) = graph_state.get_full_graph_data();
-                let response_data = KnowledgeGraphDataResponse {
-                    entities,
-                    relations,
-                }; // Using ApiEntity/ApiRelation
-                   // If raw state is needed: Response::from_json(&graph_state) after saving.
-                handle_result!(response_data) // Use the first arm for direct value response
+                let (mut entities, mut relations) = graph_state.get_full_graph_data();
+                if let Some(redactor) = self.redactor_for(&req)? {
+                    redactor.entities(&mut entities);
+                    redactor.relations(&mut relations);
+                }
+                // Same body as /graph/state, see streaming.rs
+                streaming::graph_response(entities, relations)
             }
 
             _ => Response::error("Not Found", 404),
+        };
+        // Lets the worker key its edge cache by graph version (see cache.rs)
+        let mut response = response?;
+        response
+            .headers_mut()
+            .set(GRAPH_VERSION_HEADER, &graph_state.version.to_string())?;
+        if conditional_read && response.status_code() == 200 {
+            response.headers_mut().set("etag", &etag)?;
+        }
+        if graph_state.version > version_before && !is_replica {
+            self.replicate(&graph_state)?;
+        }
+        // Counted per caller by the worker's usage metering
+        if graph_state.nodes.len() > entities_before {
+            response.headers_mut().set(
+                ENTITIES_CREATED_HEADER,
+                &(graph_state.nodes.len() - entities_before).to_string(),
+            )?;
+        }
+        Ok(response)
+    }
+}
+
+// Creation responses (201) point at the new resource. The path is the DO's own; the worker
+// rewrites it to the route the client called.
+fn with_location(response: Result<Response>, path: &str) -> Result<Response> {
+    let mut response = response?;
+    if response.status_code() == 201 {
+        response.headers_mut().set("location", path)?;
+    }
+    Ok(response)
+}
+
+// Graph reads that carry an ETag (the graph version) and answer 304 on a matching
+// If-None-Match. Searches are POSTs but read-only, so polling them benefits as well.
+fn is_conditional_read(method: &Method, segments: &[String]) -> bool {
+    let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+    matches!(
+        (method, segments.as_slice()),
+        (Method::Get, ["", "graph", "state"])
+            | (Method::Get, ["", "state"])
+            | (Method::Get, ["", "nodes"])
+            | (Method::Get, ["", "nodes", _])
+            | (Method::Post, ["", "graph", "search"])
+            | (Method::Post, ["", "graph", "relations", "search"])
+            | (Method::Post, ["", "graph", "observations", "search"])
+            | (Method::Post, ["", "graph", "context"])
+    )
+}
+
+#[durable_object]
+impl DurableObject for KnowledgeGraphDO {
+    fn new(state: State, env: Env) -> Self {
+        Self {
+            state,
+            env,
+            legacy_keys_unified: false,
+            missing_names: MissingNames::default(),
         }
     }
+
+    async fn fetch(&mut self, req: Request) -> Result<Response> {
+        let Some((key, request)) = idempotency::key_for(&req)? else {
+            let response = self.route(req).await?;
+            return errors::into_structured(response).await;
+        };
+        let mut storage = self.state.storage();
+        let now_ms = Date::now().as_millis();
+        if let Ok(stored) = storage.get::<IdempotentResponse>(&key).await {
+            if !stored.is_expired(now_ms) {
+                return stored.replay(&request);
+            }
+        }
+        storage
+            .put(
+                &key,
+                IdempotentResponse::in_progress(request.clone(), now_ms),
+            )
+            .await?;
+        let response = match self.route(req).await {
+            Ok(response) => errors::into_structured(response).await,
+            Err(e) => Err(e),
+        };
+        match response {
+            Ok(response) if (200..300).contains(&response.status_code()) => {
+                let (stored, response) =
+                    IdempotentResponse::capture(request, response, Date::now().as_millis()).await?;
+                storage.put(&key, stored).await?;
+                Ok(response)
+            }
+            // Failed writes can be retried for real
+            response => {
+                storage.delete(&key).await?;
+                response
+            }
+        }
+    }
+
+    async fn alarm(&mut self) -> Result<Response> {
+        let report = self.run_maintenance().await?;
+        console_log!(
+            "Scheduled maintenance removed {} orphaned edge(s), purged {} session(s)",
+            report.compaction.orphaned_edges_removed.len(),
+            report.sessions_purged
+        );
+        Response::from_json(&report)
+    }
 }